@@ -0,0 +1,47 @@
+use crate::compress::{Compression, ParallelCompressor};
+use crate::split;
+use crate::DumpSummary;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+//
+// Tar output, e.g. --output-file backup.tar.zst. The dump is written to a
+//   staging file next to the archive as usual, then split into a file per
+//   object (see split.rs) and those become the archive's entries. Each entry
+//   restores on its own while the backup stays a single file. The archive
+//   is compressed as a whole, following its extension like any other output.
+//
+pub fn is_archive(path: &str) -> bool {
+    let path = path.to_ascii_lowercase();
+    path.ends_with(".tar") || path.ends_with(".tgz") || path.contains(".tar.")
+}
+
+// Where the dump is written before it goes into the archive
+pub fn staging_file(path: &str) -> String {
+    format!("{}.partial.sql", path)
+}
+
+pub fn write_archive(path: &str, compression: Compression, threads: usize) -> io::Result<DumpSummary> {
+    let staging = staging_file(path);
+    let staging_dir = format!("{}.partial", path);
+    let summary = split::split(&staging, &staging_dir)?;
+
+    let file = File::create(path)?;
+    let output: Box<dyn Write> = match compression {
+        Compression::None => Box::new(BufWriter::new(file)),
+        _ => Box::new(ParallelCompressor::new(file, compression, threads)),
+    };
+    let mut builder = tar::Builder::new(output);
+    // Sorted so each table's DDL comes before its data
+    let mut entries: Vec<PathBuf> = fs::read_dir(&staging_dir)?.map(|e| e.map(|e| e.path())).collect::<io::Result<_>>()?;
+    entries.sort();
+    for entry in &entries {
+        builder.append_path_with_name(entry, entry.file_name().unwrap())?;
+    }
+    builder.into_inner()?.flush()?;
+
+    fs::remove_dir_all(&staging_dir)?;
+    fs::remove_file(&staging)?;
+    Ok(summary)
+}
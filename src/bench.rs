@@ -0,0 +1,256 @@
+use crate::logger::Logger;
+use crate::plan::CHUNK_ROWS;
+use crate::sql::{qualified_name, quote_identifier};
+use crate::std_writer::StdWriter;
+use async_std::stream::StreamExt;
+use async_std::task::block_on;
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use std::thread;
+use std::time::Instant;
+
+// Times the catalog query is run, the median is reported
+const METADATA_RUNS: usize = 10;
+
+// A level with at least this share of the best throughput is good enough, more
+//   connections only add load on the server
+const GOOD_ENOUGH: f64 = 0.9;
+
+//
+// One timed scan of the benchmark table
+//
+#[derive(Clone, Debug)]
+pub struct ScanResult {
+    pub connections: usize,
+    pub rows: u64,
+    pub seconds: f64,
+}
+
+impl ScanResult {
+    pub fn rows_per_second(&self) -> f64 {
+        self.rows as f64 / self.seconds.max(0.001)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BenchReport {
+    pub schema: String,
+    pub table: String,
+    pub metadata_ms: f64,
+    pub scans: Vec<ScanResult>,
+    // Whether each connection read its own primary key range, or all of them
+    //   the same rows
+    pub key_ranges: bool,
+}
+
+//
+// Measure how fast this server can be dumped from here: the latency of the
+//   catalog queries every dump starts with, and how many rows a second one
+//   connection and then 2, 4, ... up to `max_connections` connections read
+//   from `table`. Each scan reads the first `rows` rows, split into primary
+//   key ranges when the table has a single integer key. The single
+//   connection scan is run twice and the first run thrown away, it pulls the
+//   rows into the buffer pool for the others.
+//
+pub async fn run_bench(
+    pool: &Pool<MySql>,
+    schema: &str,
+    table: &str,
+    max_connections: usize,
+    rows: u64,
+) -> Result<BenchReport, sqlx::Error> {
+    let metadata_ms = metadata_latency(pool, schema).await?;
+    let ranges = key_ranges(pool, schema, table, rows).await?;
+
+    let mut levels = vec![];
+    let mut connections = 1;
+    while connections < max_connections {
+        levels.push(connections);
+        connections *= 2;
+    }
+    levels.push(max_connections.max(1));
+
+    Logger::info(format!("Warming up with a scan of {}", table));
+    scan(pool, schema, table, 1, rows, ranges.as_ref())?;
+    let mut scans = vec![];
+    for connections in levels {
+        Logger::info(format!("Scanning {} with {} connections", table, connections));
+        scans.push(scan(pool, schema, table, connections, rows, ranges.as_ref())?);
+    }
+    Ok(BenchReport {
+        schema: schema.to_string(),
+        table: table.to_string(),
+        metadata_ms,
+        scans,
+        key_ranges: ranges.is_some(),
+    })
+}
+
+// The base table with the most data, the default table to benchmark
+pub async fn largest_table(pool: &Pool<MySql>, schema: &str) -> Result<Option<String>, sqlx::Error> {
+    let table: Option<(String,)> = sqlx::query_as(
+        "select table_name from information_schema.tables where table_schema=? and table_type='BASE TABLE' order by data_length desc limit 1",
+    )
+    .bind(schema)
+    .fetch_optional(pool)
+    .await?;
+    Ok(table.map(|t| t.0))
+}
+
+async fn metadata_latency(pool: &Pool<MySql>, schema: &str) -> Result<f64, sqlx::Error> {
+    let mut timings = vec![];
+    for _ in 0..METADATA_RUNS {
+        let started = Instant::now();
+        let _: Vec<(String, String)> =
+            sqlx::query_as("select table_name, column_name from information_schema.columns where table_schema=?")
+                .bind(schema)
+                .fetch_all(pool)
+                .await?;
+        timings.push(started.elapsed().as_secs_f64() * 1000.0);
+    }
+    timings.sort_by(|a, b| a.total_cmp(b));
+    Ok(timings[timings.len() / 2])
+}
+
+// The primary key column and the range of it the first `rows` rows cover,
+//   when the key is a single integer column
+async fn key_ranges(pool: &Pool<MySql>, schema: &str, table: &str, rows: u64) -> Result<Option<(String, i64, i64)>, sqlx::Error> {
+    let key: Vec<(String, String)> = sqlx::query_as(
+        "select c.column_name, c.data_type from information_schema.key_column_usage k join information_schema.columns c on c.table_schema=k.table_schema and c.table_name=k.table_name and c.column_name=k.column_name where k.table_schema=? and k.table_name=? and k.constraint_name='PRIMARY'",
+    )
+    .bind(schema)
+    .bind(table)
+    .fetch_all(pool)
+    .await?;
+    if key.len() != 1 || !["tinyint", "smallint", "mediumint", "int", "bigint"].contains(&key[0].1.as_str()) {
+        return Ok(None);
+    }
+    let column = quote_identifier(&key[0].0);
+    let name = qualified_name(schema, table);
+    let (min,): (Option<i64>,) = sqlx::query_as(&format!("select cast(min({}) as signed) from {}", column, name))
+        .fetch_one(pool)
+        .await?;
+    let Some(min) = min else {
+        return Ok(None);
+    };
+    // The key of the first row past the sample, or just past the last key
+    let end: Option<(i64,)> = sqlx::query_as(&format!(
+        "select cast({0} as signed) from {1} order by {0} limit 1 offset {2}",
+        column, name, rows
+    ))
+    .fetch_optional(pool)
+    .await?;
+    let end = match end {
+        Some((end,)) => end,
+        None => {
+            let (max,): (i64,) = sqlx::query_as(&format!("select cast(max({}) as signed) from {}", column, name))
+                .fetch_one(pool)
+                .await?;
+            max.saturating_add(1)
+        }
+    };
+    Ok(Some((column, min, end)))
+}
+
+// Read the sample with several connections at once, each on its own thread
+fn scan(
+    pool: &Pool<MySql>,
+    schema: &str,
+    table: &str,
+    connections: usize,
+    rows: u64,
+    ranges: Option<&(String, i64, i64)>,
+) -> Result<ScanResult, sqlx::Error> {
+    let name = qualified_name(schema, table);
+    let queries: Vec<String> = (0..connections)
+        .map(|i| match ranges {
+            Some((column, min, end)) => {
+                let step = (end.saturating_sub(*min) / connections as i64).max(1);
+                let from = min + step * i as i64;
+                let to = if i + 1 == connections { *end } else { from + step };
+                format!("select * from {} where {} >= {} and {} < {}", name, column, from, column, to)
+            }
+            None => format!("select * from {} limit {}", name, rows / connections as u64),
+        })
+        .collect();
+
+    let started = Instant::now();
+    let handles: Vec<_> = queries
+        .into_iter()
+        .map(|query| {
+            let pool = pool.clone();
+            thread::spawn(move || {
+                block_on(async {
+                    let mut count = 0u64;
+                    let mut stream = sqlx::query(&query).fetch(&pool);
+                    while let Some(row) = stream.next().await {
+                        row?;
+                        count += 1;
+                    }
+                    Ok::<u64, sqlx::Error>(count)
+                })
+            })
+        })
+        .collect();
+    let mut total = 0;
+    for handle in handles {
+        total += handle.join().expect("A benchmark thread panicked")?;
+    }
+    Ok(ScanResult {
+        connections,
+        rows: total,
+        seconds: started.elapsed().as_secs_f64(),
+    })
+}
+
+pub fn write_report(writer: &mut StdWriter, report: &BenchReport) {
+    writer.println(format!("Benchmark of {}.{}", report.schema, report.table).as_str());
+    writer.println("");
+    writer.println(format!("Catalog query latency: {:.1} ms (median of {})", report.metadata_ms, METADATA_RUNS).as_str());
+    writer.println(
+        if report.key_ranges {
+            "Each connection read its own primary key range"
+        } else {
+            "No single integer primary key, every connection read the same rows"
+        },
+    );
+    writer.println("");
+    writer.println("Connections       Rows    Seconds     Rows/s   Speed-up");
+    let single = report.scans.first().map(|s| s.rows_per_second()).unwrap_or(1.0).max(1.0);
+    for scan in &report.scans {
+        writer.println(
+            format!(
+                "{:>11} {:>10} {:>10.2} {:>10.0} {:>9.1}x",
+                scan.connections,
+                scan.rows,
+                scan.seconds,
+                scan.rows_per_second(),
+                scan.rows_per_second() / single
+            )
+            .as_str(),
+        );
+    }
+
+    let best = report.scans.iter().map(|s| s.rows_per_second()).fold(0.0, f64::max);
+    let Some(suggested) = report.scans.iter().find(|s| s.rows_per_second() >= best * GOOD_ENOUGH) else {
+        return;
+    };
+    let per_connection = (suggested.rows_per_second() / suggested.connections as f64).max(1.0);
+    writer.println("");
+    writer.println(
+        format!(
+            "Suggested: --thread-count {}, more connections add less than {:.0}%",
+            suggested.connections,
+            (1.0 - GOOD_ENOUGH) * 100.0
+        )
+        .as_str(),
+    );
+    writer.println(
+        format!(
+            "Tables over {} rows are split into chunks of about that many, one takes a connection about {:.0} s",
+            CHUNK_ROWS,
+            CHUNK_ROWS as f64 / per_connection
+        )
+        .as_str(),
+    );
+}
@@ -0,0 +1,246 @@
+use crate::logger::Logger;
+use crate::shell::BINARY_TYPES;
+use crate::sql::{qualified_name, quote_identifier};
+use crate::{DumpSummary, ExportOptions};
+use async_std::stream::StreamExt;
+use serde_json::{json, Value};
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use sqlx::Row;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+// name, data type, column type, is nullable, numeric precision and scale,
+//   comment
+type ColumnRow = (String, String, String, String, Option<u64>, Option<u64>, String);
+
+// The widest decimals NUMERIC holds, wider ones need BIGNUMERIC
+const NUMERIC_DIGITS: u64 = 29;
+const NUMERIC_SCALE: u64 = 9;
+
+//
+// A bundle for bq load: for each table the rows as newline delimited JSON in
+//   <table>.json and the table's BigQuery schema in <table>.schema.json, and
+//   a load.sh running bq load for every table into the dataset named by
+//   $DATASET (the schema name by default). Dates that BigQuery can't hold,
+//   such as zero dates, become NULL so temporal columns are always NULLABLE.
+//   Binary values are base64 as BYTES expects and geometry is WKT in a
+//   STRING.
+//
+pub async fn write_bigquery_dir(
+    pool: &Pool<MySql>,
+    dir: &str,
+    schema: &str,
+    options: &ExportOptions,
+) -> Result<DumpSummary, sqlx::Error> {
+    fs::create_dir_all(dir)?;
+    let tables: Vec<String> = sqlx::query_as::<_, (String,)>(
+        "select table_name from information_schema.tables where table_schema=? and table_type='BASE TABLE' order by table_name",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|t| t.0)
+    .filter(|t| options.includes_data(t))
+    .collect();
+
+    let mut summary = DumpSummary::default();
+    let mut script = format!("#!/bin/sh\n# Load the tables dumped from {} with bq load\nset -e\nDATASET=${{DATASET:-{}}}\n", schema, schema);
+    for table in &tables {
+        let name = options.renames.table(table);
+        let base = name.replace(['/', '\\'], "_");
+        let columns: Vec<ColumnRow> = sqlx::query_as(
+            "select column_name, data_type, column_type, is_nullable, numeric_precision, numeric_scale, column_comment from information_schema.columns where table_schema=? and table_name=? order by ordinal_position",
+        )
+        .bind(schema)
+        .bind(table)
+        .fetch_all(pool)
+        .await?;
+        let kinds: Vec<Kind> = columns.iter().map(|c| Kind::of(&c.1, &c.2, c.4, c.5)).collect();
+
+        let fields: Vec<Value> = columns
+            .iter()
+            .zip(&kinds)
+            .map(|(c, kind)| {
+                let nullable = c.3 == "YES" || matches!(kind, Kind::Date | Kind::Datetime | Kind::Timestamp);
+                let mut field = json!({
+                    "name": options.renames.column(table, &c.0),
+                    "type": kind.type_name(),
+                    "mode": if nullable { "NULLABLE" } else { "REQUIRED" },
+                });
+                if !c.6.is_empty() {
+                    field["description"] = json!(c.6);
+                }
+                field
+            })
+            .collect();
+        let schema_json = serde_json::to_string_pretty(&fields).expect("Unable to serialize the BigQuery schema");
+        fs::write(Path::new(dir).join(format!("{}.schema.json", base)), format!("{}\n", schema_json))?;
+
+        let path = Path::new(dir).join(format!("{}.json", base));
+        let rows = write_table_rows(pool, &path, schema, table, &columns, &kinds, options).await?;
+        Logger::info(format!("Wrote {} rows of {} to {}", rows, table, path.display()));
+        script.push_str(&format!(
+            "bq load --source_format=NEWLINE_DELIMITED_JSON \"$DATASET.{}\" '{}.json' '{}.schema.json'\n",
+            name,
+            base.replace('\'', "'\\''"),
+            base.replace('\'', "'\\''")
+        ));
+        summary.tables += 1;
+        summary.rows += rows;
+    }
+    fs::write(Path::new(dir).join("load.sh"), script)?;
+    Ok(summary)
+}
+
+//
+// The BigQuery type of a column and how its values are fetched and written
+//   as JSON, the Kafka sink writes its messages the same way
+//
+#[derive(Clone, Copy, Debug)]
+pub enum Kind {
+    Int64,
+    Float64,
+    Bool,
+    // NUMERIC or BIGNUMERIC, written as strings so no digits are lost
+    Numeric(bool),
+    Date,
+    Datetime,
+    Timestamp,
+    Time,
+    Json,
+    Bytes,
+    Geography,
+    String,
+}
+
+impl Kind {
+    pub fn of(data_type: &str, column_type: &str, precision: Option<u64>, scale: Option<u64>) -> Kind {
+        let column_type = column_type.to_lowercase();
+        match data_type.to_lowercase().as_str() {
+            "tinyint" if column_type.starts_with("tinyint(1)") => Kind::Bool,
+            "bit" if column_type == "bit(1)" => Kind::Bool,
+            // Unsigned BIGINT goes past INT64
+            "bigint" if column_type.contains("unsigned") => Kind::Numeric(false),
+            "tinyint" | "smallint" | "mediumint" | "int" | "integer" | "bigint" | "year" | "bit" => Kind::Int64,
+            "float" | "double" | "real" => Kind::Float64,
+            "decimal" | "numeric" => {
+                let scale = scale.unwrap_or(0);
+                let digits = precision.unwrap_or(10).saturating_sub(scale);
+                Kind::Numeric(digits > NUMERIC_DIGITS || scale > NUMERIC_SCALE)
+            }
+            "date" => Kind::Date,
+            "datetime" => Kind::Datetime,
+            "timestamp" => Kind::Timestamp,
+            "time" => Kind::Time,
+            "json" => Kind::Json,
+            "geometry" | "point" | "linestring" | "polygon" | "multipoint" | "multilinestring" | "multipolygon"
+            | "geometrycollection" | "geomcollection" => Kind::Geography,
+            t if BINARY_TYPES.contains(&t) || t == "vector" => Kind::Bytes,
+            _ => Kind::String,
+        }
+    }
+
+    fn type_name(self) -> &'static str {
+        match self {
+            Kind::Int64 => "INT64",
+            Kind::Float64 => "FLOAT64",
+            Kind::Bool => "BOOL",
+            Kind::Numeric(false) => "NUMERIC",
+            Kind::Numeric(true) => "BIGNUMERIC",
+            Kind::Date => "DATE",
+            Kind::Datetime => "DATETIME",
+            Kind::Timestamp => "TIMESTAMP",
+            Kind::Time => "TIME",
+            Kind::Json => "JSON",
+            Kind::Bytes => "BYTES",
+            // WKT in a string, MySQL geometry is often planar and wouldn't
+            //   load as GEOGRAPHY
+            Kind::Geography | Kind::String => "STRING",
+        }
+    }
+
+    // Everything is fetched as text
+    pub fn select_expression(self, name: &str) -> String {
+        let name = quote_identifier(name);
+        match self {
+            // bit(1) as text is a raw byte
+            Kind::Bool | Kind::Int64 => format!("cast({} + 0 as char)", name),
+            // Seconds since the epoch, free of the session time zone
+            Kind::Timestamp => format!("cast(unix_timestamp({}) as char)", name),
+            Kind::Bytes => format!("to_base64({})", name),
+            Kind::Geography => format!("ST_AsText({})", name),
+            _ => format!("cast({} as char)", name),
+        }
+    }
+
+    pub fn json_value(self, value: String) -> Value {
+        match self {
+            Kind::Int64 => value.parse::<i64>().map(Value::from).unwrap_or(Value::String(value)),
+            Kind::Float64 => value.parse::<f64>().ok().and_then(|f| serde_json::Number::from_f64(f).map(Value::Number)).unwrap_or(Value::String(value)),
+            Kind::Bool => Value::Bool(value != "0"),
+            Kind::Date | Kind::Datetime if value.starts_with("0000") => Value::Null,
+            Kind::Timestamp => timestamp_value(&value),
+            Kind::Json => serde_json::from_str(&value).unwrap_or(Value::String(value)),
+            // base64 output is wrapped every 76 characters
+            Kind::Bytes => Value::String(value.replace('\n', "")),
+            _ => Value::String(value),
+        }
+    }
+}
+
+async fn write_table_rows(
+    pool: &Pool<MySql>,
+    path: &Path,
+    schema: &str,
+    table: &str,
+    columns: &[ColumnRow],
+    kinds: &[Kind],
+    options: &ExportOptions,
+) -> Result<u64, sqlx::Error> {
+    let select_list = columns
+        .iter()
+        .zip(kinds)
+        .map(|(c, kind)| kind.select_expression(&c.0))
+        .collect::<Vec<String>>()
+        .join(",");
+    let names: Vec<String> = columns.iter().map(|c| options.renames.column(table, &c.0)).collect();
+    let row_filter = options.row_clause(table);
+    let query = format!("select {} from {}{}", select_list, qualified_name(schema, table), row_filter);
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    let mut rows = 0;
+    let mut data_rows = sqlx::query(&query).fetch(pool);
+    while let Some(row) = data_rows.next().await {
+        let row = row?;
+        let mut object = serde_json::Map::new();
+        for (i, kind) in kinds.iter().enumerate() {
+            // NULLs are left out, BigQuery reads a missing field as NULL
+            if let Some(value) = row.try_get_unchecked::<Option<String>, usize>(i)? {
+                let value = kind.json_value(value);
+                if !value.is_null() {
+                    object.insert(names[i].clone(), value);
+                }
+            }
+        }
+        writeln!(writer, "{}", Value::Object(object))?;
+        rows += 1;
+    }
+    writer.flush()?;
+    Ok(rows)
+}
+
+// UNIX_TIMESTAMP() output, such as 1577872800.250000, as a UTC timestamp.
+//   Zero timestamps come back as 0 and are NULL.
+fn timestamp_value(value: &str) -> Value {
+    let (seconds, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let micros: String = fraction.chars().chain(std::iter::repeat('0')).take(6).collect();
+    match (seconds.parse::<i64>(), micros.parse::<u32>()) {
+        (Ok(seconds), Ok(micros)) if seconds > 0 || micros > 0 => chrono::DateTime::from_timestamp(seconds, micros * 1000)
+            .map(|t| Value::String(t.format("%Y-%m-%d %H:%M:%S%.6f UTC").to_string()))
+            .unwrap_or(Value::Null),
+        _ => Value::Null,
+    }
+}
@@ -0,0 +1,113 @@
+use crate::logger::Logger;
+use crate::shard::ShardOutput;
+use crate::std_writer::StdWriter;
+use crate::{base_tables, export_data, export_tables, ExportOptions};
+use serde::Serialize;
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use std::fs;
+use std::path::Path;
+
+//
+// The loader index written next to the bucket files. Load the bucket files
+//   in any order or all at once, then the output file with the views,
+//   routines and triggers.
+//
+#[derive(Debug, Serialize)]
+pub struct BucketIndex {
+    pub buckets: Vec<BucketFile>,
+    pub after: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BucketFile {
+    // Relative to the index
+    pub file: String,
+    pub tables: Vec<String>,
+    pub rows: u64,
+}
+
+//
+// The bucket a table goes into, from its name alone so it stays in the same
+//   bucket run after run whatever else is in the schema. Jump consistent hash
+//   of a 64 bit FNV-1a of the name: going from K to K+1 buckets moves only
+//   about a (K+1)th of the tables, all into the new bucket.
+//
+pub fn table_bucket(table: &str, buckets: usize) -> usize {
+    let mut key = table.bytes().fold(0xcbf29ce484222325u64, |hash, b| (hash ^ b as u64).wrapping_mul(0x100000001b3));
+    let mut bucket = 0i64;
+    let mut next = 0i64;
+    while next < buckets as i64 {
+        bucket = next;
+        key = key.wrapping_mul(2862933555777941757).wrapping_add(1);
+        next = ((bucket + 1) as f64 * ((1i64 << 31) as f64 / ((key >> 33) + 1) as f64)) as i64;
+    }
+    bucket as usize
+}
+
+//
+// The tables of a schema, structure and rows, spread over `buckets` files by
+//   table_bucket, so CI jobs can each restore one bucket in parallel. Every
+//   file loads on its own, empty buckets included so the set of files is the
+//   same every run. Returns the tables and rows written.
+//
+#[allow(clippy::too_many_arguments)]
+pub async fn export_table_buckets(
+    pool: &Pool<MySql>,
+    output: &ShardOutput,
+    schema: &str,
+    after_file: &str,
+    buckets: usize,
+    options: &ExportOptions,
+    include_ddl: bool,
+    include_data: bool,
+) -> Result<(usize, u64), sqlx::Error> {
+    fs::create_dir_all(&output.dir)?;
+    let mut assigned: Vec<Vec<String>> = vec![vec![]; buckets];
+    for (table,) in base_tables(pool, schema, options).await?.into_iter().filter(|t| options.includes(&t.0)) {
+        assigned[table_bucket(&table, buckets)].push(table);
+    }
+
+    let mut index = BucketIndex {
+        buckets: vec![],
+        after: after_file.to_string(),
+    };
+    let mut tables = 0;
+    for (bucket, bucket_tables) in assigned.into_iter().enumerate() {
+        let file = format!("bucket-{}.sql{}", bucket + 1, output.compression.extension());
+        let mut writer = StdWriter::open(
+            Some(Path::new(&output.dir).join(&file).to_string_lossy().to_string()),
+            output.compression,
+            1,
+            false,
+        );
+        for line in &output.prefix {
+            writer.println(line);
+        }
+        let mut options = options.clone();
+        options.objects = Some(bucket_tables.clone());
+        let mut rows = 0;
+        if !bucket_tables.is_empty() {
+            if include_ddl {
+                tables += export_tables(pool, &mut writer, schema, &options).await?;
+            }
+            if include_data {
+                rows = export_data(pool, &mut writer, 1, schema, &options).await?;
+            }
+        }
+        for line in &output.postfix {
+            writer.println(line);
+        }
+        writer.flush();
+        Logger::info(format!("Wrote {} tables and {} rows to {}", bucket_tables.len(), rows, file));
+        index.buckets.push(BucketFile {
+            file,
+            tables: bucket_tables.iter().map(|t| options.renames.table(t)).collect(),
+            rows,
+        });
+    }
+
+    let index_json = serde_json::to_string_pretty(&index).expect("Unable to serialize the bucket index");
+    fs::write(Path::new(&output.dir).join("index.json"), format!("{}\n", index_json))?;
+    Ok((tables, index.buckets.iter().map(|b| b.rows).sum()))
+}
@@ -0,0 +1,39 @@
+use std::sync::{Condvar, Mutex};
+
+//
+// A limit on the bytes of fetched table data waiting to be written. Export
+//   threads reserve space for each batch before handing it over and block
+//   while the writer is behind, so memory use stays near the budget whatever
+//   the shape of the tables.
+//
+pub struct MemoryBudget {
+    limit: usize,
+    used: Mutex<usize>,
+    released: Condvar,
+}
+
+impl MemoryBudget {
+    pub fn new(limit: usize) -> Self {
+        MemoryBudget {
+            limit,
+            used: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    // Wait until `bytes` fit in the budget. A batch bigger than the whole
+    //   budget is let through on its own rather than waiting forever.
+    pub fn acquire(&self, bytes: usize) {
+        let mut used = self.used.lock().unwrap();
+        while *used > 0 && *used + bytes > self.limit {
+            used = self.released.wait(used).unwrap();
+        }
+        *used += bytes;
+    }
+
+    pub fn release(&self, bytes: usize) {
+        let mut used = self.used.lock().unwrap();
+        *used = used.saturating_sub(bytes);
+        self.released.notify_all();
+    }
+}
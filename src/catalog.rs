@@ -0,0 +1,298 @@
+use crate::logger::Logger;
+use serde::{Deserialize, Serialize};
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+//
+// A structured description of a schema read from information_schema. This is
+//   what the non-SQL output formats (documentation, diagrams, catalogs) are
+//   generated from, and it serializes directly to the schema-json format.
+//
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Catalog {
+    pub schema: String,
+    pub tables: Vec<Table>,
+    pub views: Vec<View>,
+    pub routines: Vec<Routine>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Table {
+    pub name: String,
+    pub engine: Option<String>,
+    pub comment: String,
+    pub columns: Vec<ColumnInfo>,
+    pub indexes: Vec<IndexInfo>,
+    pub foreign_keys: Vec<ForeignKey>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnInfo {
+    pub name: String,
+    pub column_type: String,
+    pub data_type: String,
+    pub character_set: Option<String>,
+    pub nullable: bool,
+    pub default: Option<String>,
+    pub key: String,
+    pub extra: String,
+    pub comment: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexInfo {
+    pub name: String,
+    pub unique: bool,
+    pub columns: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForeignKey {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub referenced_table: String,
+    pub referenced_columns: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct View {
+    pub name: String,
+    pub columns: Vec<ColumnInfo>,
+    pub depends_on: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Routine {
+    pub name: String,
+    pub routine_type: String,
+    pub returns: Option<String>,
+    pub comment: String,
+    pub definition: Option<String>,
+}
+
+// table_name, column_name, column_type, data_type, character_set_name,
+//   is_nullable, column_default, column_key, extra, column_comment
+type ColumnRow = (String, String, String, String, Option<String>, String, Option<String>, String, String, String);
+
+// routine_name, routine_type, dtd_identifier, routine_comment, routine_definition
+type RoutineRow = (String, String, Option<String>, String, Option<String>);
+
+impl Table {
+    pub fn primary_key(&self) -> Option<&IndexInfo> {
+        self.indexes.iter().find(|i| i.name == "PRIMARY")
+    }
+}
+
+// What a cached catalog was read from, so it is only used while that holds
+#[derive(Serialize, Deserialize)]
+struct CachedCatalog {
+    version: String,
+    fingerprint: String,
+    catalog: Catalog,
+}
+
+//
+// Everything in a schema's metadata that changes when its structure does,
+//   boiled down to one row: object counts, when tables were last created or
+//   rebuilt and routines altered, and checksums over the column and view
+//   definitions, which catch the ALTERs that don't rebuild the table.
+//
+const FINGERPRINT_QUERY: &str = "select cast(concat_ws('/', \
+    (select count(*) from information_schema.tables where table_schema=?), \
+    (select coalesce(max(create_time), '') from information_schema.tables where table_schema=?), \
+    (select count(*) from information_schema.statistics where table_schema=?), \
+    (select count(*) from information_schema.referential_constraints where constraint_schema=?), \
+    (select coalesce(sum(crc32(concat_ws(',', table_name, column_name, ordinal_position, column_type, is_nullable, column_default, extra, column_comment))), 0) from information_schema.columns where table_schema=?), \
+    (select coalesce(sum(crc32(view_definition)), 0) from information_schema.views where table_schema=?), \
+    (select count(*) from information_schema.routines where routine_schema=?), \
+    (select coalesce(max(last_altered), '') from information_schema.routines where routine_schema=?)) as char)";
+
+//
+// Load a schema's catalog through a cache directory shared between runs,
+//   one file per server and schema. The cached catalog is used while the
+//   schema's fingerprint still matches, which takes one query instead of
+//   reading every table's metadata, otherwise it is read again and the file
+//   rewritten. A cache that can't be read or written only costs the time.
+//
+pub async fn load_cached_catalog(pool: &Pool<MySql>, schema: &str, cache_dir: &Path, server: &str) -> Result<Catalog, sqlx::Error> {
+    let mut query = sqlx::query_as::<_, (String,)>(FINGERPRINT_QUERY);
+    for _ in 0..FINGERPRINT_QUERY.matches('?').count() {
+        query = query.bind(schema);
+    }
+    let (fingerprint,): (String,) = query.fetch_one(pool).await?;
+
+    let path = cache_file(cache_dir, server, schema);
+    let cached = fs::read_to_string(&path)
+        .ok()
+        .and_then(|json| serde_json::from_str::<CachedCatalog>(&json).ok())
+        .filter(|c| c.version == env!("CARGO_PKG_VERSION") && c.fingerprint == fingerprint && c.catalog.schema == schema);
+    if let Some(cached) = cached {
+        Logger::info(format!("Using the cached metadata of {} from {}", schema, path.display()));
+        return Ok(cached.catalog);
+    }
+
+    let catalog = load_catalog(pool, schema).await?;
+    let cached = CachedCatalog {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        fingerprint,
+        catalog,
+    };
+    let written = fs::create_dir_all(cache_dir).and_then(|_| {
+        fs::write(&path, serde_json::to_string(&cached).expect("Unable to serialize the schema catalog"))
+    });
+    if let Err(e) = written {
+        Logger::warn(format!("Unable to cache the metadata of {} in {}: {}", schema, path.display(), e));
+    }
+    Ok(cached.catalog)
+}
+
+// host-port-schema.json, with anything that isn't safe in a file name replaced
+fn cache_file(cache_dir: &Path, server: &str, schema: &str) -> PathBuf {
+    let name: String = format!("{}-{}", server, schema)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    cache_dir.join(format!("{}.json", name))
+}
+
+pub async fn load_catalog(pool: &Pool<MySql>, schema: &str) -> Result<Catalog, sqlx::Error> {
+    let objects: Vec<(String, String, Option<String>, String)> = sqlx::query_as(
+        "select table_name, table_type, engine, table_comment from information_schema.tables where table_schema=? order by table_name",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await?;
+
+    let columns: Vec<ColumnRow> = sqlx::query_as(
+        "select table_name, column_name, column_type, data_type, character_set_name, is_nullable, column_default, column_key, extra, column_comment from information_schema.columns where table_schema=? order by table_name, ordinal_position",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await?;
+
+    let statistics: Vec<(String, String, i64, Option<String>)> = sqlx::query_as(
+        "select table_name, index_name, cast(non_unique as signed), column_name from information_schema.statistics where table_schema=? order by table_name, index_name, seq_in_index",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await?;
+
+    let key_columns: Vec<(String, String, String, String, String)> = sqlx::query_as(
+        "select table_name, constraint_name, column_name, referenced_table_name, referenced_column_name from information_schema.key_column_usage where table_schema=? and referenced_table_name is not null order by table_name, constraint_name, ordinal_position",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await?;
+
+    let routines: Vec<RoutineRow> = sqlx::query_as(
+        "select routine_name, routine_type, dtd_identifier, routine_comment, routine_definition from information_schema.routines where routine_schema=? order by routine_type, routine_name",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await?;
+
+    // VIEW_TABLE_USAGE only exists from MySQL 8.0.13, older servers simply
+    //   don't get view dependencies
+    let view_usage: Vec<(String, String)> = sqlx::query_as(
+        "select view_name, table_name from information_schema.view_table_usage where view_schema=? and table_schema=?",
+    )
+    .bind(schema)
+    .bind(schema)
+    .fetch_all(pool)
+    .await
+    .unwrap_or_default();
+
+    let columns_for = |table_name: &str| -> Vec<ColumnInfo> {
+        columns
+            .iter()
+            .filter(|c| c.0 == table_name)
+            .map(|c| ColumnInfo {
+                name: c.1.clone(),
+                column_type: c.2.clone(),
+                data_type: c.3.clone(),
+                character_set: c.4.clone(),
+                nullable: c.5 == "YES",
+                default: c.6.clone(),
+                key: c.7.clone(),
+                extra: c.8.clone(),
+                comment: c.9.clone(),
+            })
+            .collect()
+    };
+
+    let mut tables = vec![];
+    let mut views = vec![];
+    for (name, table_type, engine, comment) in &objects {
+        if table_type == "VIEW" {
+            views.push(View {
+                name: name.clone(),
+                columns: columns_for(name),
+                depends_on: view_usage
+                    .iter()
+                    .filter(|u| &u.0 == name)
+                    .map(|u| u.1.clone())
+                    .collect(),
+            });
+            continue;
+        }
+
+        let mut indexes: Vec<IndexInfo> = vec![];
+        for (_, index_name, non_unique, column_name) in statistics.iter().filter(|s| &s.0 == name) {
+            // Functional index parts have no column name
+            let column_name = column_name.clone().unwrap_or_else(|| "(expression)".to_string());
+            match indexes.iter_mut().find(|i| &i.name == index_name) {
+                Some(index) => index.columns.push(column_name),
+                None => indexes.push(IndexInfo {
+                    name: index_name.clone(),
+                    unique: *non_unique == 0,
+                    columns: vec![column_name],
+                }),
+            }
+        }
+
+        let mut foreign_keys: Vec<ForeignKey> = vec![];
+        for (_, constraint_name, column_name, referenced_table, referenced_column) in
+            key_columns.iter().filter(|k| &k.0 == name)
+        {
+            match foreign_keys.iter_mut().find(|f| &f.name == constraint_name) {
+                Some(fk) => {
+                    fk.columns.push(column_name.clone());
+                    fk.referenced_columns.push(referenced_column.clone());
+                }
+                None => foreign_keys.push(ForeignKey {
+                    name: constraint_name.clone(),
+                    columns: vec![column_name.clone()],
+                    referenced_table: referenced_table.clone(),
+                    referenced_columns: vec![referenced_column.clone()],
+                }),
+            }
+        }
+
+        tables.push(Table {
+            name: name.clone(),
+            engine: engine.clone(),
+            comment: comment.clone(),
+            columns: columns_for(name),
+            indexes,
+            foreign_keys,
+        });
+    }
+
+    Ok(Catalog {
+        schema: schema.to_string(),
+        tables,
+        views,
+        routines: routines
+            .into_iter()
+            .map(|(name, routine_type, returns, comment, definition)| Routine {
+                name,
+                routine_type,
+                returns,
+                comment,
+                definition,
+            })
+            .collect(),
+    })
+}
@@ -0,0 +1,113 @@
+use crate::escape::{quote_string, EscapeMode};
+use crate::sql::quote_identifier;
+use crate::ExportOptions;
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+
+// name, data type, column type, is nullable, datetime precision, numeric
+//   precision, numeric scale, comment
+type ColumnRow = (String, String, String, String, Option<u64>, Option<u64>, Option<u64>, String);
+
+//
+// CREATE TABLE for ClickHouse, built from the catalog rather than rewriting
+//   MySQL's DDL. Columns get the closest ClickHouse type, wrapped in
+//   Nullable() where MySQL allows NULL, and the table a MergeTree engine
+//   ordered by the primary key. Defaults, indexes and constraints have no
+//   ClickHouse equivalent and are left out.
+//
+pub async fn create_table(
+    pool: &Pool<MySql>,
+    schema: &str,
+    table: &str,
+    options: &ExportOptions,
+) -> Result<String, sqlx::Error> {
+    let columns: Vec<ColumnRow> = sqlx::query_as(
+        "select column_name, data_type, column_type, is_nullable, datetime_precision, numeric_precision, numeric_scale, column_comment from information_schema.columns where table_schema=? and table_name=? order by ordinal_position",
+    )
+    .bind(schema)
+    .bind(table)
+    .fetch_all(pool)
+    .await?;
+    let primary_key: Vec<(String,)> = sqlx::query_as(
+        "select column_name from information_schema.statistics where table_schema=? and table_name=? and index_name='PRIMARY' order by seq_in_index",
+    )
+    .bind(schema)
+    .bind(table)
+    .fetch_all(pool)
+    .await?;
+    let (comment,): (String,) =
+        sqlx::query_as("select table_comment from information_schema.tables where table_schema=? and table_name=?")
+            .bind(schema)
+            .bind(table)
+            .fetch_one(pool)
+            .await?;
+
+    let definitions: Vec<String> = columns
+        .iter()
+        .map(|(name, data_type, column_type, nullable, datetime_precision, precision, scale, comment)| {
+            let mut column_type = column_type_for(data_type, column_type, *datetime_precision, *precision, *scale);
+            if nullable == "YES" {
+                column_type = format!("Nullable({})", column_type);
+            }
+            let mut definition = format!("    {} {}", quote_identifier(&options.renames.column(table, name)), column_type);
+            if !comment.is_empty() {
+                definition.push_str(&format!(" COMMENT {}", quote_string(comment, EscapeMode::Backslash)));
+            }
+            definition
+        })
+        .collect();
+    // Without a primary key there is nothing to sort on
+    let order_by = if primary_key.is_empty() {
+        "tuple()".to_string()
+    } else {
+        let key: Vec<String> = primary_key.iter().map(|c| quote_identifier(&options.renames.column(table, &c.0))).collect();
+        format!("({})", key.join(", "))
+    };
+
+    let mut ddl = format!(
+        "CREATE TABLE IF NOT EXISTS {}\n(\n{}\n)\nENGINE = MergeTree\nORDER BY {}",
+        options.table_name(table),
+        definitions.join(",\n"),
+        order_by
+    );
+    if !comment.is_empty() {
+        ddl.push_str(&format!("\nCOMMENT {}", quote_string(&comment, EscapeMode::Backslash)));
+    }
+    Ok(ddl)
+}
+
+// The ClickHouse type for a MySQL column, String for anything without a
+//   closer match
+fn column_type_for(
+    data_type: &str,
+    column_type: &str,
+    datetime_precision: Option<u64>,
+    precision: Option<u64>,
+    scale: Option<u64>,
+) -> String {
+    let column_type = column_type.to_lowercase();
+    let unsigned = column_type.contains("unsigned");
+    let name = match (data_type.to_lowercase().as_str(), unsigned) {
+        // tinyint(1) and bit(1) are how MySQL spells a boolean
+        ("tinyint", _) if column_type.starts_with("tinyint(1)") => "Bool",
+        ("bit", _) if column_type == "bit(1)" => "Bool",
+        ("bit", _) => "UInt64",
+        ("tinyint", false) => "Int8",
+        ("tinyint", true) => "UInt8",
+        ("smallint", false) => "Int16",
+        ("smallint", true) | ("year", _) => "UInt16",
+        ("mediumint" | "int" | "integer", false) => "Int32",
+        ("mediumint" | "int" | "integer", true) => "UInt32",
+        ("bigint", false) => "Int64",
+        ("bigint", true) => "UInt64",
+        ("float", _) => "Float32",
+        ("double" | "real", _) => "Float64",
+        ("decimal" | "numeric", _) => {
+            return format!("Decimal({}, {})", precision.unwrap_or(10), scale.unwrap_or(0));
+        }
+        ("date", _) => "Date32",
+        ("datetime" | "timestamp", _) => return format!("DateTime64({})", datetime_precision.unwrap_or(0)),
+        _ => "String",
+    };
+    name.to_string()
+}
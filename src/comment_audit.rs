@@ -0,0 +1,81 @@
+use crate::logger::Logger;
+use regex::Regex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Comments dropped or changed by the DDL rewrites in this run
+static PROBLEMS: AtomicUsize = AtomicUsize::new(0);
+
+//
+// The table and column comments of a CREATE TABLE statement, as the quoted
+//   literals SHOW CREATE TABLE writes. Columns are kept in order, with None
+//   for those without a comment.
+//
+#[derive(Debug, Default, PartialEq)]
+pub struct TableComments {
+    pub table: Option<String>,
+    pub columns: Vec<(String, Option<String>)>,
+}
+
+impl TableComments {
+    pub fn parse(ddl: &str) -> TableComments {
+        // String literals and identifiers are matched whole, so COMMENT inside
+        //   a default value or a column name isn't taken for the clause
+        let tokens = Regex::new(r"'(?:[^'\\]|\\.|'')*'|`(?:[^`]|``)*`|\bCOMMENT[ =]('(?:[^'\\]|\\.|'')*')").unwrap();
+        let comment = |line: &str| tokens.captures_iter(line).find_map(|c| c.get(1).map(|m| m.as_str().to_string()));
+
+        let mut comments = TableComments::default();
+        for line in ddl.lines().map(str::trim_start) {
+            if let Some(name) = line.strip_prefix('`') {
+                let name = name.split('`').next().unwrap_or_default().to_string();
+                comments.columns.push((name, comment(line)));
+            } else if line.starts_with(')') {
+                comments.table = comment(line);
+            }
+        }
+        comments
+    }
+}
+
+//
+// A check for --audit-comments. Comments are the schema's documentation, so
+//   the statement read from the server is compared with the one written after
+//   renaming, charset conversion and option stripping, and every comment
+//   that didn't come through unchanged is reported. Columns are matched by
+//   position, as renames change their names but never their order.
+//
+pub fn audit_table(table: &str, source_ddl: &str, written_ddl: &str) -> bool {
+    let source = TableComments::parse(source_ddl);
+    let written = TableComments::parse(written_ddl);
+    let mut problems = vec![];
+    if let Some(comment) = &source.table {
+        problems.extend(compare(format!("table {}", table), comment, written.table.as_ref()));
+    }
+    for (index, (column, comment)) in source.columns.iter().enumerate() {
+        if let Some(comment) = comment {
+            let written = written.columns.get(index).and_then(|c| c.1.as_ref());
+            problems.extend(compare(format!("column {}.{}", table, column), comment, written));
+        }
+    }
+    for problem in &problems {
+        Logger::warn(problem);
+    }
+    PROBLEMS.fetch_add(problems.len(), Ordering::Relaxed);
+    problems.is_empty()
+}
+
+fn compare(object: String, comment: &str, written: Option<&String>) -> Option<String> {
+    match written {
+        None => Some(format!("The comment on {} was dropped, it was {}", object, comment)),
+        Some(written) if written != comment => Some(format!("The comment on {} was changed from {} to {}", object, comment, written)),
+        Some(_) => None,
+    }
+}
+
+// Start over for another dump in the same process, as watch runs them
+pub fn reset() {
+    PROBLEMS.store(0, Ordering::Relaxed);
+}
+
+pub fn problems() -> usize {
+    PROBLEMS.load(Ordering::Relaxed)
+}
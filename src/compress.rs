@@ -0,0 +1,241 @@
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::str::FromStr;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+// Uncompressed bytes handed to a worker at a time
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Compression::None),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            "xz" => Ok(Compression::Xz),
+            _ => Err(format!("Unknown compression {s}, expected one of none, gzip, zstd, xz")),
+        }
+    }
+}
+
+impl Compression {
+    // The compression a file name asks for, e.g. backup.sql.zst
+    pub fn from_path(path: &str) -> Compression {
+        let extension = path.rsplit_once('.').map(|(_, e)| e.to_ascii_lowercase()).unwrap_or_default();
+        match extension.as_str() {
+            "gz" | "gzip" | "tgz" => Compression::Gzip,
+            "zst" | "zstd" => Compression::Zstd,
+            "xz" => Compression::Xz,
+            _ => Compression::None,
+        }
+    }
+
+    // What a file written with this compression ends with, e.g. ".zst"
+    pub fn extension(self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+            Compression::Xz => ".xz",
+        }
+    }
+
+    // The compression of a stream from its first bytes
+    fn from_magic(bytes: &[u8]) -> Compression {
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else if bytes.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else {
+            Compression::None
+        }
+    }
+
+    // Compress one chunk into a complete gzip member or zstd frame. Both
+    //   formats allow members to be concatenated, so the chunks can be
+    //   compressed independently and still decompress as a single stream.
+    fn compress(self, chunk: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(chunk.to_vec()),
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::with_capacity(chunk.len() / 4), flate2::Compression::default());
+                encoder.write_all(chunk)?;
+                encoder.finish()
+            }
+            Compression::Zstd => zstd::bulk::compress(chunk, zstd::DEFAULT_COMPRESSION_LEVEL),
+            Compression::Xz => {
+                let mut encoder = xz2::write::XzEncoder::new(Vec::with_capacity(chunk.len() / 4), 6);
+                encoder.write_all(chunk)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+//
+// Open a dump file for reading, decompressing it when it is gzip, zstd or xz
+//   compressed. The format is recognised from the first bytes rather than
+//   the extension so a renamed file still reads. Streams made of several
+//   members or frames, as the parallel compressor writes, are read to the
+//   end.
+//
+pub fn open_input(path: &str) -> io::Result<Box<dyn BufRead>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let compression = Compression::from_magic(reader.fill_buf()?);
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::stream::read::Decoder::with_buffer(reader)?)),
+        Compression::Xz => Box::new(BufReader::new(xz2::read::XzDecoder::new_multi_decoder(reader))),
+    })
+}
+
+type Job = (u64, Vec<u8>);
+
+// Sequence number of the next chunk to be written and the first write error,
+//   signalled whenever a chunk is written
+type Progress = Arc<(Mutex<(u64, Option<io::Error>)>, Condvar)>;
+
+//
+// Compress on worker threads, like pigz. Output is cut into chunks which
+//   are compressed in parallel while the caller keeps producing, and a writer
+//   thread puts the compressed chunks back in order. The job channel is
+//   bounded so a slow disk holds up the producer rather than memory.
+//
+pub struct ParallelCompressor {
+    chunk: Vec<u8>,
+    sequence: u64,
+    jobs: Option<SyncSender<Job>>,
+    written: Progress,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl ParallelCompressor {
+    pub fn new<W: Write + Send + 'static>(output: W, compression: Compression, workers: usize) -> Self {
+        let workers = workers.max(1);
+        let (jobs, job_receiver) = sync_channel::<Job>(workers * 2);
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let (results, result_receiver) = sync_channel::<(u64, io::Result<Vec<u8>>)>(workers * 2);
+        let written = Arc::new((Mutex::new((0, None)), Condvar::new()));
+
+        let mut threads = vec![];
+        for _ in 0..workers {
+            let job_receiver = job_receiver.clone();
+            let results = results.clone();
+            threads.push(thread::spawn(move || loop {
+                let job = job_receiver.lock().unwrap().recv();
+                match job {
+                    Ok((sequence, chunk)) => {
+                        if results.send((sequence, compression.compress(&chunk))).is_err() {
+                            return;
+                        }
+                    }
+                    Err(_) => return,
+                }
+            }));
+        }
+        drop(results);
+
+        let writer_written = written.clone();
+        threads.push(thread::spawn(move || write_in_order(output, result_receiver, writer_written)));
+
+        ParallelCompressor {
+            chunk: Vec::with_capacity(CHUNK_SIZE),
+            sequence: 0,
+            jobs: Some(jobs),
+            written,
+            threads,
+        }
+    }
+
+    fn dispatch(&mut self) -> io::Result<()> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::replace(&mut self.chunk, Vec::with_capacity(CHUNK_SIZE));
+        self.jobs
+            .as_ref()
+            .unwrap()
+            .send((self.sequence, chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Compression threads have stopped"))?;
+        self.sequence += 1;
+        Ok(())
+    }
+}
+
+fn write_in_order<W: Write>(
+    mut output: W,
+    results: Receiver<(u64, io::Result<Vec<u8>>)>,
+    written: Progress,
+) {
+    let mut pending: BTreeMap<u64, io::Result<Vec<u8>>> = BTreeMap::new();
+    let mut next = 0;
+    for (sequence, result) in results {
+        pending.insert(sequence, result);
+        while let Some(result) = pending.remove(&next) {
+            let result = result.and_then(|bytes| output.write_all(&bytes)).and_then(|_| output.flush());
+            let (lock, condvar) = &*written;
+            let mut state = lock.lock().unwrap();
+            if let Err(e) = result {
+                state.1.get_or_insert(e);
+            }
+            next += 1;
+            state.0 = next;
+            condvar.notify_all();
+        }
+    }
+}
+
+impl Write for ParallelCompressor {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.chunk.extend_from_slice(buf);
+        if self.chunk.len() >= CHUNK_SIZE {
+            self.dispatch()?;
+        }
+        Ok(buf.len())
+    }
+
+    // Waits until everything written so far is compressed and on disk
+    fn flush(&mut self) -> io::Result<()> {
+        self.dispatch()?;
+        let (lock, condvar) = &*self.written;
+        let mut state = lock.lock().unwrap();
+        while state.0 < self.sequence && state.1.is_none() {
+            state = condvar.wait(state).unwrap();
+        }
+        match state.1.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ParallelCompressor {
+    fn drop(&mut self) {
+        let _ = self.dispatch();
+        // Closing the job channel stops the workers, which closes the result
+        //   channel and stops the writer once everything is written
+        self.jobs = None;
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use crate::hooks::TableHooks;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+//
+// The dump config file, TOML with a section per table:
+//
+//   [tables.users]
+//   filter = "row.deleted_at == ()"
+//
+//   [tables.users.transform]
+//   email = "`user${row.id}@example.com`"
+//
+//   [tables.orders]
+//   before = ["create or replace view recent_orders as select * from orders where created > now() - interval 30 day"]
+//   source = "recent_orders"
+//   after = ["drop view recent_orders", "sh:echo orders done"]
+//
+//   [tables.order_lines]
+//   order_by = "order_no, line_no"
+//
+// Filters and transforms are scripts, see script.rs. They need dbdump to
+//   be built with the scripting feature. Hooks are described in hooks.rs.
+//   order_by is the ORDER BY the table's rows are read with, so dumps of the
+//   same data from two systems list the rows in the same order.
+//
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub tables: BTreeMap<String, TableConfig>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TableConfig {
+    // Evaluated per row, rows are only exported when it returns true
+    pub filter: Option<String>,
+    // Column name to a script evaluated per value, returning the new value
+    pub transform: BTreeMap<String, String>,
+    // Run before and after the table's data is exported
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+    // Table or view the rows are read from instead
+    pub source: Option<String>,
+    // SQL expression list the rows are exported in the order of
+    pub order_by: Option<String>,
+}
+
+impl Config {
+    pub fn load(path: &str) -> Result<Config, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Unable to read config file {}: {}", path, e))?;
+        toml::from_str(&text).map_err(|e| format!("Invalid config file {}: {}", path, e))
+    }
+
+    pub fn table_hooks(&self) -> HashMap<String, TableHooks> {
+        self.tables
+            .iter()
+            .filter(|(_, t)| !t.before.is_empty() || !t.after.is_empty() || t.source.is_some())
+            .map(|(name, t)| {
+                let hooks = TableHooks {
+                    before: t.before.clone(),
+                    after: t.after.clone(),
+                    source: t.source.clone(),
+                };
+                (name.clone(), hooks)
+            })
+            .collect()
+    }
+
+    pub fn row_orders(&self) -> HashMap<String, String> {
+        self.tables
+            .iter()
+            .filter_map(|(name, t)| t.order_by.as_ref().map(|o| (name.clone(), o.clone())))
+            .collect()
+    }
+
+    pub fn has_scripts(&self) -> bool {
+        self.tables.values().any(|t| t.filter.is_some() || !t.transform.is_empty())
+    }
+}
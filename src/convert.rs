@@ -0,0 +1,227 @@
+use crate::compress::open_input;
+use crate::escape::{unquote_string, EscapeMode};
+use crate::metadata::Metadata;
+use crate::normalize::{create_table_columns, insert_pattern, object_name, split_rows};
+use crate::restore::StatementReader;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+//
+// The formats a dump file can be converted to, one file per table
+//   Csv   - RFC 4180 with a header row, NULL is an empty field and an empty
+//           string is ""
+//   Jsonl - one JSON object per row, keyed by column name
+//
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConvertFormat {
+    #[default]
+    Csv,
+    Jsonl,
+}
+
+impl FromStr for ConvertFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(ConvertFormat::Csv),
+            "jsonl" => Ok(ConvertFormat::Jsonl),
+            _ => Err(format!("Unknown convert format {s}, expected one of csv, jsonl")),
+        }
+    }
+}
+
+impl ConvertFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ConvertFormat::Csv => "csv",
+            ConvertFormat::Jsonl => "jsonl",
+        }
+    }
+}
+
+// One value of an insert
+enum Field {
+    Null,
+    Number(String),
+    Text(String),
+}
+
+#[derive(Debug, Default)]
+pub struct ConvertSummary {
+    pub tables: usize,
+    pub rows: u64,
+}
+
+struct TableFile {
+    writer: BufWriter<File>,
+    columns: Vec<String>,
+}
+
+//
+// Turn the rows of a SQL dump back into data files without a database, for
+//   when the dump is all that is left. Only the inserts are read, values are
+//   written as the dump has them: numbers stay numbers, strings are
+//   unescaped and anything else (hex literals, function calls) is written as
+//   its SQL text. Column names come from the insert or else the CREATE TABLE
+//   earlier in the file, when neither has them JSONL rows are arrays and the
+//   CSV has no header.
+//
+pub fn convert(input_file: &str, output_dir: &str, format: ConvertFormat) -> io::Result<ConvertSummary> {
+    std::fs::create_dir_all(output_dir)?;
+    let insert = insert_pattern();
+    let create = Regex::new(r"(?is)^create\s+table\s+(?:if\s+not\s+exists\s+)?((?:(?:`(?:[^`]|``)+`|\w+)\.)?(?:`(?:[^`]|``)+`|\w+))").unwrap();
+    let identifier = Regex::new(r"`((?:[^`]|``)+)`|(\w+)").unwrap();
+
+    let mut summary = ConvertSummary::default();
+    let mut table_columns: HashMap<String, Vec<String>> = HashMap::new();
+    let mut files: HashMap<String, TableFile> = HashMap::new();
+    let mut mode = EscapeMode::Backslash;
+    let mut statements = StatementReader::new(open_input(input_file)?).keep_comments();
+    while let Some(statement) = statements.next() {
+        let mut metadata = Metadata::default();
+        for comment in statements.take_comments() {
+            metadata.read_line(&comment);
+        }
+        if !metadata.entries().is_empty() {
+            mode = metadata.escape_mode();
+            statements.set_backslash_escapes(mode == EscapeMode::Backslash);
+        }
+        let statement = statement?;
+
+        if let Some(captures) = create.captures(&statement) {
+            table_columns.insert(object_name(&captures[1]), create_table_columns(&statement));
+            continue;
+        }
+        let Some(captures) = insert.captures(&statement) else {
+            continue;
+        };
+        let table = object_name(&captures[2]);
+        let values = &statement[captures.get(0).unwrap().end()..];
+        let Some(rows) = split_rows(values, mode) else {
+            return Err(io::Error::other(format!("Unable to read the rows of an insert into {}", table)));
+        };
+
+        if !files.contains_key(&table) {
+            let columns = match captures.get(3) {
+                Some(list) => identifier
+                    .captures_iter(list.as_str())
+                    .map(|c| c.get(1).map(|q| q.as_str().replace("``", "`")).unwrap_or_else(|| c[2].to_string()))
+                    .collect(),
+                None => table_columns.get(&table).cloned().unwrap_or_default(),
+            };
+            let file_name = table.replace(['/', '\\'], "_");
+            let path = Path::new(output_dir).join(format!("{}.{}", file_name, format.extension()));
+            let mut writer = BufWriter::new(File::create(path)?);
+            if format == ConvertFormat::Csv && !columns.is_empty() {
+                let header: Vec<String> = columns.iter().map(|c| csv_field(c)).collect();
+                writeln!(writer, "{}", header.join(","))?;
+            }
+            files.insert(table.clone(), TableFile { writer, columns });
+            summary.tables += 1;
+        }
+        let file = files.get_mut(&table).unwrap();
+
+        for row in rows {
+            let fields: Vec<Field> = split_values(row, mode).into_iter().map(|v| parse_field(v, mode)).collect();
+            let line = match format {
+                ConvertFormat::Csv => csv_row(&fields),
+                ConvertFormat::Jsonl => json_row(&fields, &file.columns),
+            };
+            writeln!(file.writer, "{}", line)?;
+            summary.rows += 1;
+        }
+    }
+    for file in files.values_mut() {
+        file.writer.flush()?;
+    }
+    Ok(summary)
+}
+
+// The values of one row, split on the commas outside strings and calls
+fn split_values(row: &str, mode: EscapeMode) -> Vec<&str> {
+    let mut values = vec![];
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+    let mut chars = row.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if let Some(q) = quote {
+            if c == '\\' && mode == EscapeMode::Backslash {
+                chars.next();
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '\'' | '"' => quote = Some(c),
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                values.push(row[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    values.push(row[start..].trim());
+    values
+}
+
+fn parse_field(value: &str, mode: EscapeMode) -> Field {
+    if value.eq_ignore_ascii_case("NULL") {
+        return Field::Null;
+    }
+    // A character set introducer such as _binary or _utf8mb4
+    let literal = match value.strip_prefix('_') {
+        Some(rest) => rest.trim_start_matches(|c: char| c.is_alphanumeric() || c == '_').trim_start(),
+        None => value,
+    };
+    if literal.len() >= 2 && (literal.starts_with('\'') || literal.starts_with('"')) && literal.ends_with(&literal[..1]) {
+        return Field::Text(unquote_string(literal, mode));
+    }
+    let numeric = value.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.');
+    if numeric && value.parse::<f64>().is_ok() {
+        return Field::Number(value.to_string());
+    }
+    Field::Text(value.to_string())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.is_empty() || value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_row(fields: &[Field]) -> String {
+    fields
+        .iter()
+        .map(|f| match f {
+            Field::Null => String::new(),
+            Field::Number(n) => n.clone(),
+            Field::Text(t) => csv_field(t),
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn json_row(fields: &[Field], columns: &[String]) -> String {
+    let values = fields.iter().map(|f| match f {
+        Field::Null => serde_json::Value::Null,
+        Field::Number(n) => serde_json::from_str(n).unwrap_or_else(|_| serde_json::Value::String(n.clone())),
+        Field::Text(t) => serde_json::Value::String(t.clone()),
+    });
+    let value = if columns.len() == fields.len() {
+        serde_json::Value::Object(columns.iter().cloned().zip(values).collect())
+    } else {
+        serde_json::Value::Array(values.collect())
+    };
+    value.to_string()
+}
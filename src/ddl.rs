@@ -0,0 +1,139 @@
+use regex::Regex;
+
+//
+// Rewrites of the CREATE TABLE statements SHOW CREATE TABLE returns, for
+//   targets that can't take them as they are.
+//
+
+//
+// Drop the tablespace and DATA/INDEX DIRECTORY placements from a table and
+//   its partitions. Creating them needs the tablespace to exist or the FILE
+//   privilege and the same paths on the target, the table is created in the
+//   target's default place instead.
+//
+pub fn strip_tablespaces(ddl: &str) -> String {
+    // Only the table options and partitions after the column list, so
+    //   column comments are left alone
+    let (columns, options) = ddl.split_at(ddl.rfind("\n)").unwrap_or(0));
+    let comment = Regex::new(r"\s*/\*!\d+ TABLESPACE (?:`(?:[^`]|``)+`|\w+)(?: STORAGE \w+)? \*/").unwrap();
+    let tablespace = Regex::new(r"(?i)\s+(?:STORAGE\s+\w+\s+)?TABLESPACE\s*=?\s*(?:`(?:[^`]|``)+`|\w+)(?:\s+STORAGE\s+\w+)?").unwrap();
+    let directory = Regex::new(r"(?i)\s+(?:DATA|INDEX)\s+DIRECTORY\s*=?\s*'(?:[^'\\]|\\.|'')*'").unwrap();
+    let options = comment.replace_all(options, "");
+    let options = tablespace.replace_all(&options, "");
+    format!("{}{}", columns, directory.replace_all(&options, ""))
+}
+
+//
+// Take the plain secondary indexes out of a CREATE TABLE so they can be
+//   built once the rows are in, one sort instead of an update per row.
+//   Returns the statement without them and the index definitions for an
+//   ALTER TABLE ... ADD. Primary and unique keys stay, they are constraints
+//   the load relies on, and so do the indexes foreign keys need.
+//
+pub fn defer_indexes(ddl: &str) -> (String, Vec<String>) {
+    let mut lines: Vec<&str> = ddl.lines().collect();
+    // The column list runs from the line opening CREATE TABLE to the line
+    //   closing it
+    let Some(end) = lines.iter().rposition(|l| l.starts_with(')')) else {
+        return (ddl.to_string(), vec![]);
+    };
+    if end < 2 {
+        return (ddl.to_string(), vec![]);
+    }
+    let definitions: Vec<&str> = lines[1..end].iter().map(|l| l.trim_end().trim_end_matches(',')).collect();
+
+    let secondary = Regex::new(r"^\s*(?:FULLTEXT |SPATIAL )?KEY ").unwrap();
+    let foreign_key = Regex::new(r"FOREIGN KEY \(([^)]*)\)").unwrap();
+    let foreign_keys: Vec<Vec<&str>> = definitions
+        .iter()
+        .filter_map(|d| foreign_key.captures(d))
+        .map(|c| c.get(1).unwrap().as_str().split(',').map(str::trim).collect())
+        .collect();
+
+    let mut kept = vec![];
+    let mut deferred = vec![];
+    for definition in definitions {
+        if secondary.is_match(definition) && !foreign_keys.iter().any(|fk| index_starts_with(definition, fk)) {
+            deferred.push(definition.trim().to_string());
+        } else {
+            kept.push(definition);
+        }
+    }
+    if deferred.is_empty() {
+        return (ddl.to_string(), vec![]);
+    }
+    let kept = kept.join(",\n");
+    let tail = lines.split_off(end);
+    (format!("{}\n{}\n{}", lines[0], kept, tail.join("\n")), deferred)
+}
+
+// Whether an index definition's leading columns are the quoted names of a
+//   foreign key's columns, prefix lengths and ordering aside
+fn index_starts_with(definition: &str, columns: &[&str]) -> bool {
+    let Some(open) = definition.find(" (") else {
+        return false;
+    };
+    let mut depth = 0;
+    let mut parts = vec![];
+    let mut start = open + 2;
+    for (i, c) in definition.char_indices().skip(open + 2) {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => {
+                parts.push(definition[start..i].trim());
+                break;
+            }
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(definition[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.len() >= columns.len()
+        && parts.iter().zip(columns).all(|(part, column)| {
+            part == column || part.starts_with(&format!("{}(", column)) || part.starts_with(&format!("{} ", column))
+        })
+}
+
+//
+// The parts of a table written on their own, for a target whose tables
+//   already exist
+//   Indexes     - secondary, unique, FULLTEXT and SPATIAL keys, not the
+//                 primary key the table was created with
+//   Constraints - foreign keys and CHECK constraints
+//
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TablePart {
+    Indexes,
+    Constraints,
+}
+
+impl TablePart {
+    pub fn name(self) -> &'static str {
+        match self {
+            TablePart::Indexes => "indexes",
+            TablePart::Constraints => "constraints",
+        }
+    }
+}
+
+// The definitions of one part of a CREATE TABLE, each ready to follow ADD
+//   in an ALTER TABLE
+pub fn table_parts(ddl: &str, part: TablePart) -> Vec<String> {
+    let lines: Vec<&str> = ddl.lines().collect();
+    let Some(end) = lines.iter().rposition(|l| l.starts_with(')')) else {
+        return vec![];
+    };
+    let pattern = match part {
+        TablePart::Indexes => Regex::new(r"^(?:UNIQUE |FULLTEXT |SPATIAL )?KEY ").unwrap(),
+        TablePart::Constraints => Regex::new(r"^CONSTRAINT ").unwrap(),
+    };
+    lines[1..end.max(1)]
+        .iter()
+        .map(|l| l.trim().trim_end_matches(','))
+        .filter(|d| pattern.is_match(d))
+        .map(str::to_string)
+        .collect()
+}
@@ -0,0 +1,90 @@
+use crate::logger::Logger;
+use crate::sql::qualified_name;
+use regex::Regex;
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+//
+// Detection of DDL run against a table while it is dumped. The definition is
+//   remembered when the dump writes the table's CREATE TABLE, or when it
+//   starts on its rows, and read again once they are exported. A table
+//   altered in between was dumped partly under one definition and partly
+//   under another, or its rows don't match the CREATE TABLE written, so the
+//   dump may not restore as the source was.
+//
+// The AUTO_INCREMENT counter is left out, inserts move it on. One is made
+//   per dump and shared by the clones of its options, so dumps run in the
+//   same process, by watch or federate, don't see each other's tables.
+//
+#[derive(Clone, Debug, Default)]
+pub struct TableDefinitions(Arc<Mutex<Definitions>>);
+
+#[derive(Debug, Default)]
+struct Definitions {
+    // The definition each table had when the dump first read it, by schema
+    //   and table
+    tables: BTreeMap<(String, String), String>,
+    // Tables whose definition changed during the dump
+    changes: usize,
+}
+
+impl TableDefinitions {
+    pub fn record(&self, schema: &str, table: &str, ddl: &str) {
+        let mut definitions = self.0.lock().unwrap();
+        definitions
+            .tables
+            .entry((schema.to_string(), table.to_string()))
+            .or_insert_with(|| definition(ddl));
+    }
+
+    pub async fn remember(&self, pool: &Pool<MySql>, schema: &str, table: &str) -> Result<(), sqlx::Error> {
+        if self.0.lock().unwrap().tables.contains_key(&(schema.to_string(), table.to_string())) {
+            return Ok(());
+        }
+        let ddl = show_create_table(pool, schema, table).await?;
+        self.record(schema, table, &ddl);
+        Ok(())
+    }
+
+    // Read the table's definition again and report it when it changed since
+    //   it was remembered. Each change is reported once.
+    pub async fn check(&self, pool: &Pool<MySql>, schema: &str, table: &str) -> Result<bool, sqlx::Error> {
+        let current = definition(&show_create_table(pool, schema, table).await?);
+        let mut definitions = self.0.lock().unwrap();
+        let key = (schema.to_string(), table.to_string());
+        match definitions.tables.get(&key) {
+            Some(remembered) if *remembered != current => {
+                definitions.tables.insert(key, current);
+                definitions.changes += 1;
+                Logger::error(format!(
+                    "The definition of {} changed while it was being dumped, the dump may be inconsistent for it and should be taken again",
+                    qualified_name(schema, table)
+                ));
+                Ok(false)
+            }
+            Some(_) => Ok(true),
+            None => {
+                definitions.tables.insert(key, current);
+                Ok(true)
+            }
+        }
+    }
+
+    pub fn changes(&self) -> usize {
+        self.0.lock().unwrap().changes
+    }
+}
+
+fn definition(ddl: &str) -> String {
+    let auto_increment = Regex::new(r" AUTO_INCREMENT=\d+").unwrap();
+    auto_increment.replace(ddl, "").into_owned()
+}
+
+async fn show_create_table(pool: &Pool<MySql>, schema: &str, table: &str) -> Result<String, sqlx::Error> {
+    let ddl: (String, String) = sqlx::query_as(&format!("SHOW CREATE TABLE {}", qualified_name(schema, table)))
+        .fetch_one(pool)
+        .await?;
+    Ok(ddl.1)
+}
@@ -0,0 +1,123 @@
+use crate::catalog::{Catalog, Table};
+
+//
+// Structural comparison of two schemas. Differences are reported from the
+//   point of view of turning the target into the source:
+//   + only in the source, - only in the target, ~ present in both but different
+//
+pub fn diff_catalogs(source: &Catalog, target: &Catalog) -> Vec<String> {
+    let mut differences = vec![];
+
+    for table in &source.tables {
+        match target.tables.iter().find(|t| t.name == table.name) {
+            Some(other) => diff_tables(table, other, &mut differences),
+            None => differences.push(format!("+ table {}", table.name)),
+        }
+    }
+    for table in &target.tables {
+        if !source.tables.iter().any(|t| t.name == table.name) {
+            differences.push(format!("- table {}", table.name));
+        }
+    }
+
+    for view in &source.views {
+        if !target.views.iter().any(|v| v.name == view.name) {
+            differences.push(format!("+ view {}", view.name));
+        }
+    }
+    for view in &target.views {
+        if !source.views.iter().any(|v| v.name == view.name) {
+            differences.push(format!("- view {}", view.name));
+        }
+    }
+
+    for routine in &source.routines {
+        match target
+            .routines
+            .iter()
+            .find(|r| r.name == routine.name && r.routine_type == routine.routine_type)
+        {
+            Some(other) if other.definition != routine.definition => differences.push(format!(
+                "~ {} {} definition differs",
+                routine.routine_type.to_lowercase(),
+                routine.name
+            )),
+            Some(_) => {}
+            None => differences.push(format!("+ {} {}", routine.routine_type.to_lowercase(), routine.name)),
+        }
+    }
+    for routine in &target.routines {
+        if !source
+            .routines
+            .iter()
+            .any(|r| r.name == routine.name && r.routine_type == routine.routine_type)
+        {
+            differences.push(format!("- {} {}", routine.routine_type.to_lowercase(), routine.name));
+        }
+    }
+
+    differences
+}
+
+fn diff_tables(source: &Table, target: &Table, differences: &mut Vec<String>) {
+    for column in &source.columns {
+        match target.columns.iter().find(|c| c.name == column.name) {
+            Some(other) => {
+                if other.column_type != column.column_type
+                    || other.nullable != column.nullable
+                    || other.default != column.default
+                    || other.extra != column.extra
+                {
+                    differences.push(format!(
+                        "~ column {}.{}: {}{} -> {}{}",
+                        source.name,
+                        column.name,
+                        other.column_type,
+                        if other.nullable { " null" } else { " not null" },
+                        column.column_type,
+                        if column.nullable { " null" } else { " not null" }
+                    ));
+                }
+            }
+            None => differences.push(format!("+ column {}.{} {}", source.name, column.name, column.column_type)),
+        }
+    }
+    for column in &target.columns {
+        if !source.columns.iter().any(|c| c.name == column.name) {
+            differences.push(format!("- column {}.{}", source.name, column.name));
+        }
+    }
+
+    for index in &source.indexes {
+        match target.indexes.iter().find(|i| i.name == index.name) {
+            Some(other) if other.columns != index.columns || other.unique != index.unique => differences.push(format!(
+                "~ index {}.{}: ({}) -> ({})",
+                source.name,
+                index.name,
+                other.columns.join(","),
+                index.columns.join(",")
+            )),
+            Some(_) => {}
+            None => differences.push(format!("+ index {}.{} ({})", source.name, index.name, index.columns.join(","))),
+        }
+    }
+    for index in &target.indexes {
+        if !source.indexes.iter().any(|i| i.name == index.name) {
+            differences.push(format!("- index {}.{}", source.name, index.name));
+        }
+    }
+
+    for fk in &source.foreign_keys {
+        if !target.foreign_keys.iter().any(|f| f.name == fk.name) {
+            differences.push(format!(
+                "+ foreign key {}.{} -> {}",
+                source.name, fk.name, fk.referenced_table
+            ));
+        }
+    }
+    for fk in &target.foreign_keys {
+        if !source.foreign_keys.iter().any(|f| f.name == fk.name) {
+            differences.push(format!("- foreign key {}.{}", source.name, fk.name));
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use crate::catalog::{Catalog, ColumnInfo};
+use crate::std_writer::StdWriter;
+use sqlx::types::chrono::Local;
+
+//
+// Markdown data dictionary - one document per schema listing every table
+//   with its columns, indexes and foreign keys, followed by views and routines.
+//
+pub fn write_markdown(writer: &mut StdWriter, catalog: &Catalog) {
+    writer.println(format!("# Schema `{}`", catalog.schema).as_str());
+    writer.println("");
+    writer.println(format!("Generated by dbdump v{} at {}", env!("CARGO_PKG_VERSION"), Local::now()).as_str());
+    writer.println("");
+
+    writer.println("## Tables");
+    writer.println("");
+    for table in &catalog.tables {
+        writer.println(format!("- [{}](#{})", table.name, anchor(&table.name)).as_str());
+    }
+    writer.println("");
+
+    for table in &catalog.tables {
+        writer.println(format!("## {}", table.name).as_str());
+        writer.println("");
+        if !table.comment.is_empty() {
+            writer.println(cell(&table.comment).as_str());
+            writer.println("");
+        }
+        write_columns(writer, &table.columns);
+
+        if !table.indexes.is_empty() {
+            writer.println("### Indexes");
+            writer.println("");
+            writer.println("| Name | Columns | Unique |");
+            writer.println("|------|---------|--------|");
+            for index in &table.indexes {
+                writer.println(
+                    format!(
+                        "| {} | {} | {} |",
+                        cell(&index.name),
+                        cell(&index.columns.join(", ")),
+                        if index.unique { "yes" } else { "no" }
+                    )
+                    .as_str(),
+                );
+            }
+            writer.println("");
+        }
+
+        if !table.foreign_keys.is_empty() {
+            writer.println("### Foreign keys");
+            writer.println("");
+            writer.println("| Name | Columns | References |");
+            writer.println("|------|---------|------------|");
+            for fk in &table.foreign_keys {
+                writer.println(
+                    format!(
+                        "| {} | {} | [{}](#{}) ({}) |",
+                        cell(&fk.name),
+                        cell(&fk.columns.join(", ")),
+                        fk.referenced_table,
+                        anchor(&fk.referenced_table),
+                        cell(&fk.referenced_columns.join(", "))
+                    )
+                    .as_str(),
+                );
+            }
+            writer.println("");
+        }
+    }
+
+    if !catalog.views.is_empty() {
+        writer.println("## Views");
+        writer.println("");
+        for view in &catalog.views {
+            writer.println(format!("### {}", view.name).as_str());
+            writer.println("");
+            write_columns(writer, &view.columns);
+        }
+    }
+
+    if !catalog.routines.is_empty() {
+        writer.println("## Routines");
+        writer.println("");
+        writer.println("| Name | Type | Returns | Comment |");
+        writer.println("|------|------|---------|---------|");
+        for routine in &catalog.routines {
+            writer.println(
+                format!(
+                    "| {} | {} | {} | {} |",
+                    cell(&routine.name),
+                    routine.routine_type,
+                    cell(routine.returns.as_deref().unwrap_or("")),
+                    cell(&routine.comment)
+                )
+                .as_str(),
+            );
+        }
+        writer.println("");
+    }
+}
+
+fn write_columns(writer: &mut StdWriter, columns: &[ColumnInfo]) {
+    writer.println("| Column | Type | Nullable | Default | Key | Extra | Comment |");
+    writer.println("|--------|------|----------|---------|-----|-------|---------|");
+    for column in columns {
+        writer.println(
+            format!(
+                "| {} | {} | {} | {} | {} | {} | {} |",
+                cell(&column.name),
+                cell(&column.column_type),
+                if column.nullable { "yes" } else { "no" },
+                cell(column.default.as_deref().unwrap_or("")),
+                cell(&column.key),
+                cell(&column.extra),
+                cell(&column.comment)
+            )
+            .as_str(),
+        );
+    }
+    writer.println("");
+}
+
+// Table cells can't contain pipes or line breaks
+pub fn cell(value: &str) -> String {
+    value.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+// GitHub style heading anchors
+fn anchor(name: &str) -> String {
+    name.to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_' || *c == ' ')
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect()
+}
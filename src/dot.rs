@@ -0,0 +1,106 @@
+use crate::catalog::{Catalog, ColumnInfo};
+use crate::std_writer::StdWriter;
+
+//
+// GraphViz ER diagram - one node per table listing its columns, and an edge
+//   per foreign key from the referencing column to the referenced one. Views
+//   are optional and drawn dashed, with dashed edges to what they select from.
+//   Render with e.g. `dot -Tsvg schema.dot -o schema.svg`.
+//
+pub fn write_dot(writer: &mut StdWriter, catalog: &Catalog, include_views: bool) {
+    writer.println(format!("digraph {} {{", quote(&catalog.schema)).as_str());
+    writer.println("  rankdir=LR;");
+    writer.println("  node [shape=plaintext, fontname=\"Helvetica\"];");
+    writer.println("  edge [fontname=\"Helvetica\", fontsize=10];");
+
+    for table in &catalog.tables {
+        let pk = table.primary_key();
+        writer.println(
+            format!(
+                "  {} [label={}];",
+                quote(&table.name),
+                node_label(&table.name, &table.columns, |c| pk.is_some_and(|pk| pk.columns.contains(&c.name)), "solid")
+            )
+            .as_str(),
+        );
+    }
+
+    for table in &catalog.tables {
+        for fk in &table.foreign_keys {
+            let from = column_port(&table.columns, &fk.columns[0]);
+            let to = catalog
+                .tables
+                .iter()
+                .find(|t| t.name == fk.referenced_table)
+                .map(|t| column_port(&t.columns, &fk.referenced_columns[0]))
+                .unwrap_or_default();
+            writer.println(
+                format!(
+                    "  {}{} -> {}{} [label={}];",
+                    quote(&table.name),
+                    from,
+                    quote(&fk.referenced_table),
+                    to,
+                    quote(&fk.name)
+                )
+                .as_str(),
+            );
+        }
+    }
+
+    if include_views {
+        for view in &catalog.views {
+            writer.println(
+                format!(
+                    "  {} [label={}];",
+                    quote(&view.name),
+                    node_label(&view.name, &view.columns, |_| false, "dashed")
+                )
+                .as_str(),
+            );
+            for dependency in &view.depends_on {
+                writer.println(
+                    format!("  {} -> {} [style=dashed];", quote(&view.name), quote(dependency)).as_str(),
+                );
+            }
+        }
+    }
+
+    writer.println("}");
+}
+
+// An HTML-like label: the name as a header row and one row per column, each
+//   with a port so edges attach to the column rather than the table.
+fn node_label<F: Fn(&ColumnInfo) -> bool>(name: &str, columns: &[ColumnInfo], is_key: F, style: &str) -> String {
+    let mut label = format!(
+        "<<table border=\"1\" cellborder=\"0\" cellspacing=\"0\" style=\"{}\"><tr><td bgcolor=\"lightgrey\"><b>{}</b></td></tr>",
+        style,
+        html(name)
+    );
+    for (i, column) in columns.iter().enumerate() {
+        let text = format!("{} {}", html(&column.name), html(&column.column_type));
+        let text = if is_key(column) { format!("<u>{}</u>", text) } else { text };
+        label.push_str(format!("<tr><td port=\"c{}\" align=\"left\">{}</td></tr>", i, text).as_str());
+    }
+    label.push_str("</table>>");
+    label
+}
+
+fn column_port(columns: &[ColumnInfo], name: &str) -> String {
+    columns
+        .iter()
+        .position(|c| c.name == name)
+        .map(|i| format!(":c{}", i))
+        .unwrap_or_default()
+}
+
+fn quote(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
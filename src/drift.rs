@@ -0,0 +1,69 @@
+use crate::logger::Logger;
+use crate::ExportOptions;
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use std::collections::HashMap;
+
+// A table is flagged when its rows and the server's estimate are further
+//   apart than this factor, InnoDB's estimates are often a third out
+const DRIFT_FACTOR: f64 = 2.0;
+
+// Tables with fewer rows than this, read or estimated, are left out
+const MIN_ROWS: u64 = 1000;
+
+#[derive(Clone, Debug)]
+pub struct RowDrift {
+    pub table: String,
+    pub estimate: u64,
+    pub rows: u64,
+}
+
+//
+// Compare the rows read from each table with the estimate in
+//   information_schema.TABLES, once the data is written. A count far from
+//   the estimate means the table was busy while it was dumped, or its
+//   statistics are stale, and the dump may be worth taking again with the
+//   writes stopped. Tables read through a filter, a hook's source table or a
+//   transformer aren't compared, their row counts aren't the table's.
+//
+pub async fn check_row_drift(
+    pool: &Pool<MySql>,
+    schema: &str,
+    rows: &HashMap<String, u64>,
+    options: &ExportOptions,
+) -> Result<Vec<RowDrift>, sqlx::Error> {
+    if options.transformer.is_some() {
+        return Ok(vec![]);
+    }
+    let estimates: Vec<(String, Option<u64>)> = sqlx::query_as(
+        "select table_name, cast(table_rows as unsigned) from information_schema.tables where table_schema=? and table_type='BASE TABLE' order by table_name",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await?;
+
+    let mut drifts = vec![];
+    for (table, estimate) in estimates {
+        let (Some(read), Some(estimate)) = (rows.get(&table).copied(), estimate) else {
+            continue;
+        };
+        let filtered = options.row_filters.contains_key(&table) || options.hooks(&table).is_some_and(|h| h.source.is_some());
+        if filtered || read.max(estimate) < MIN_ROWS {
+            continue;
+        }
+        let ratio = read.max(estimate) as f64 / read.min(estimate).max(1) as f64;
+        if ratio > DRIFT_FACTOR {
+            drifts.push(RowDrift { table, estimate, rows: read });
+        }
+    }
+    for drift in &drifts {
+        Logger::warn(format!(
+            "{} rows were read from {}, the server estimated {}. It changed a lot while it was dumped or its statistics are stale (ANALYZE TABLE refreshes them), the dump may need taking again",
+            drift.rows, drift.table, drift.estimate
+        ));
+    }
+    if !drifts.is_empty() {
+        Logger::warn(format!("{} tables of {} differ widely from their estimated row counts", drifts.len(), schema));
+    }
+    Ok(drifts)
+}
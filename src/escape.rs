@@ -0,0 +1,204 @@
+use std::str::FromStr;
+
+//
+// String literal escaping for the values written into INSERT statements.
+//
+// MySQL understands two dialects of string literals:
+//   Backslash - the server default, where \ introduces an escape sequence
+//   Standard  - servers running with NO_BACKSLASH_ESCAPES, where the only
+//               special character is the quote itself, which is doubled
+//
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EscapeMode {
+    #[default]
+    Backslash,
+    Standard,
+}
+
+impl FromStr for EscapeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "backslash" => Ok(EscapeMode::Backslash),
+            "standard" => Ok(EscapeMode::Standard),
+            _ => Err(format!(
+                "Unknown escape mode {s}, expected one of backslash, standard"
+            )),
+        }
+    }
+}
+
+// Escape and quote a string so it can be used as a literal value.
+pub fn quote_string(value: &str, mode: EscapeMode) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('\'');
+    match mode {
+        EscapeMode::Backslash => escape_backslash(value, &mut out),
+        EscapeMode::Standard => escape_standard(value, &mut out),
+    }
+    out.push('\'');
+    out
+}
+
+// Same escapes as mysql_real_escape_string - every character is examined
+//   exactly once so a backslash is never escaped twice.
+fn escape_backslash(value: &str, out: &mut String) {
+    for c in value.chars() {
+        match c {
+            '\0' => out.push_str("\\0"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\\' => out.push_str("\\\\"),
+            '\'' => out.push_str("\\'"),
+            '"' => out.push_str("\\\""),
+            '\x1a' => out.push_str("\\Z"),
+            _ => out.push(c),
+        }
+    }
+}
+
+// With NO_BACKSLASH_ESCAPES everything but the quote is taken literally.
+fn escape_standard(value: &str, out: &mut String) {
+    for c in value.chars() {
+        if c == '\'' {
+            out.push_str("''");
+        } else {
+            out.push(c);
+        }
+    }
+}
+
+// The value of a quoted string literal, the reverse of quote_string. A
+//   doubled quote stands for one in both modes.
+pub fn unquote_string(literal: &str, mode: EscapeMode) -> String {
+    let quote = literal.chars().next().unwrap_or('\'');
+    let inner = literal.get(1..literal.len().saturating_sub(1)).unwrap_or("");
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' && mode == EscapeMode::Backslash {
+            match chars.next() {
+                Some('0') => out.push('\0'),
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some('t') => out.push('\t'),
+                Some('b') => out.push('\x08'),
+                Some('Z') => out.push('\x1a'),
+                // Kept as they are so LIKE patterns survive
+                Some(c @ ('%' | '_')) => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                Some(c) => out.push(c),
+                None => out.push('\\'),
+            }
+        } else if c == quote && chars.peek() == Some(&quote) {
+            chars.next();
+            out.push(c);
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::{quickcheck, Arbitrary, Gen};
+
+    const MODES: [EscapeMode; 2] = [EscapeMode::Backslash, EscapeMode::Standard];
+
+    // Text heavy in the characters that need escaping, mixed with any
+    //   Unicode, and with escapes that look already applied
+    #[derive(Clone, Debug)]
+    struct SqlText(String);
+
+    impl Arbitrary for SqlText {
+        fn arbitrary(g: &mut Gen) -> Self {
+            let pieces = ["\0", "\x1a", "\\", "'", "\"", "\n", "\r", "\t", "''", "\\'", "\\0", "\\Z", "%", "_", "é", "😀"];
+            let len = usize::arbitrary(g) % (g.size() + 1);
+            let text = (0..len)
+                .map(|_| match bool::arbitrary(g) {
+                    true => g.choose(&pieces).unwrap().to_string(),
+                    false => char::arbitrary(g).to_string(),
+                })
+                .collect();
+            SqlText(text)
+        }
+
+        fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+            Box::new(self.0.shrink().map(SqlText))
+        }
+    }
+
+    fn inner(literal: &str) -> &str {
+        &literal[1..literal.len() - 1]
+    }
+
+    #[test]
+    fn backslash_escapes() {
+        let quoted = quote_string("a\0b\x1ac\\d'e\"f\ng\rh", EscapeMode::Backslash);
+        assert_eq!(quoted, r#"'a\0b\Zc\\d\'e\"f\ng\rh'"#);
+    }
+
+    #[test]
+    fn standard_escapes() {
+        let quoted = quote_string("a\0b\x1ac\\d'e\n", EscapeMode::Standard);
+        assert_eq!(quoted, "'a\0b\x1ac\\d''e\n'");
+    }
+
+    #[test]
+    fn escaped_text_is_escaped_again_not_skipped() {
+        assert_eq!(quote_string(r"\'", EscapeMode::Backslash), r"'\\\''");
+        assert_eq!(quote_string("''", EscapeMode::Standard), "''''''");
+    }
+
+    quickcheck! {
+        fn round_trip(text: SqlText) -> bool {
+            MODES.iter().all(|mode| unquote_string(&quote_string(&text.0, *mode), *mode) == text.0)
+        }
+
+        fn round_trip_any_unicode(text: String) -> bool {
+            MODES.iter().all(|mode| unquote_string(&quote_string(&text, *mode), *mode) == text)
+        }
+
+        // Every character is escaped on its own, so nothing in the input,
+        //   escapes included, is escaped twice or left out
+        fn escapes_each_character_once(text: SqlText) -> bool {
+            MODES.iter().all(|mode| {
+                let by_character: String = text.0.chars().map(|c| inner(&quote_string(&c.to_string(), *mode)).to_string()).collect();
+                inner(&quote_string(&text.0, *mode)) == by_character
+            })
+        }
+
+        // Quoting a literal again gives back that literal, not its value
+        fn quoting_twice_unquotes_once(text: SqlText) -> bool {
+            MODES.iter().all(|mode| {
+                let once = quote_string(&text.0, *mode);
+                unquote_string(&quote_string(&once, *mode), *mode) == once
+            })
+        }
+
+        // The literal ends at its closing quote, none inside ends it early
+        fn quotes_inside_are_escaped(text: SqlText) -> bool {
+            let backslash = quote_string(&text.0, EscapeMode::Backslash);
+            let mut escaped = false;
+            let backslash_ok = inner(&backslash).chars().all(|c| {
+                let ok = c != '\'' || escaped;
+                escaped = c == '\\' && !escaped;
+                ok
+            });
+            let standard = quote_string(&text.0, EscapeMode::Standard);
+            let standard_ok = inner(&standard).split("''").all(|part| !part.contains('\''));
+            backslash_ok && standard_ok
+        }
+
+        // Characters the client library would choke on never appear raw
+        fn no_raw_control_characters_with_backslashes(text: SqlText) -> bool {
+            let quoted = quote_string(&text.0, EscapeMode::Backslash);
+            !quoted.contains(['\0', '\x1a', '\n', '\r'])
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use crate::plan::{self, WorkItem};
+use crate::std_writer::StdWriter;
+use crate::{base_tables, data_query, ExportOptions};
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use sqlx::{Column, Executor, Row};
+
+//
+// Write the statements the data export would run against a schema, one per
+//   table or chunk of a table, each followed by the server's EXPLAIN of it as
+//   SQL comments. Nothing is exported, the output is for vetting the load a
+//   dump puts on the server before running it.
+//
+pub async fn explain_data(
+    pool: &Pool<MySql>,
+    writer: &mut StdWriter,
+    thread_count: usize,
+    schema: &str,
+    options: &ExportOptions,
+) -> Result<usize, sqlx::Error> {
+    // A single thread reads whole tables, several share chunks of the big ones
+    let work = if thread_count == 1 {
+        base_tables(pool, schema, options)
+            .await?
+            .into_iter()
+            .filter(|t| options.includes_data(&t.0))
+            .map(|(table,)| WorkItem {
+                table,
+                chunk: None,
+                part: 1,
+                parts: 1,
+                rows: 0,
+                bytes: 0,
+            })
+            .collect()
+    } else {
+        plan::plan_export(pool, schema, options).await?
+    };
+
+    for item in &work {
+        let mut options = options.clone();
+        if let Some(chunk) = &item.chunk {
+            options.row_filters.insert(item.table.clone(), chunk.clone());
+        }
+        let query = data_query(pool, schema, &item.table, &options).await?;
+        writer.println(format!("-- {}.{}", schema, item.describe()).as_str());
+        writer.println(format!("{};", query).as_str());
+
+        // A plain string runs as a text query, so every value reads as text
+        let rows = pool.fetch_all(format!("explain {}", query).as_str()).await?;
+        if let Some(first) = rows.first() {
+            let names: Vec<&str> = first.columns().iter().map(|c| c.name()).collect();
+            writer.println(format!("-- {}", names.join(" | ")).as_str());
+        }
+        for row in &rows {
+            let values: Vec<String> = (0..row.len())
+                .map(|i| {
+                    row.try_get_unchecked::<Option<String>, usize>(i)
+                        .ok()
+                        .flatten()
+                        .unwrap_or_else(|| "NULL".to_string())
+                })
+                .collect();
+            writer.println(format!("-- {}", values.join(" | ")).as_str());
+        }
+        writer.println("");
+    }
+    Ok(work.len())
+}
@@ -0,0 +1,275 @@
+use crate::logger::Logger;
+use crate::shell::BINARY_TYPES;
+use crate::sql::{qualified_name, quote_identifier};
+use crate::{DumpSummary, ExportOptions};
+use arrow_array::{
+    ArrayRef, BinaryArray, Date32Array, Decimal128Array, Float32Array, Float64Array, Int16Array, Int32Array,
+    Int64Array, Int8Array, RecordBatch, StringArray, TimestampMicrosecondArray, UInt16Array, UInt32Array, UInt64Array,
+    UInt8Array,
+};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{ArrowError, DataType, Field, Schema, TimeUnit};
+use async_std::stream::StreamExt;
+use chrono::{NaiveDate, NaiveDateTime};
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use sqlx::Row;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+// Rows per record batch
+const BATCH_ROWS: usize = 65_536;
+
+// Decimals wider than this don't fit an Arrow decimal128 and are strings
+const MAX_DECIMAL_PRECISION: u64 = 38;
+
+// Name, data type, column type, is nullable, numeric precision and scale
+type ColumnInfo = (String, String, String, String, Option<u64>, Option<u64>);
+
+//
+// The Arrow type a MySQL column is written as. Temporal values the server
+//   can hold but Arrow can't (zero dates, TIME over 24 hours) are kept as
+//   NULL or a string, so dates are always nullable and TIME is a string.
+//
+#[derive(Clone, Copy, Debug)]
+enum Kind {
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    Float32,
+    Float64,
+    Decimal(u8, i8),
+    Date,
+    Timestamp,
+    Binary,
+    Utf8,
+}
+
+impl Kind {
+    fn of(data_type: &str, column_type: &str, precision: Option<u64>, scale: Option<u64>) -> Kind {
+        let data_type = data_type.to_lowercase();
+        let unsigned = column_type.to_lowercase().contains("unsigned");
+        match (data_type.as_str(), unsigned) {
+            ("tinyint", false) => Kind::Int8,
+            ("tinyint", true) => Kind::UInt8,
+            ("smallint", false) | ("year", _) => Kind::Int16,
+            ("smallint", true) => Kind::UInt16,
+            ("mediumint" | "int" | "integer", false) => Kind::Int32,
+            ("mediumint" | "int" | "integer", true) => Kind::UInt32,
+            ("bigint", false) => Kind::Int64,
+            ("bigint", true) => Kind::UInt64,
+            ("float", _) => Kind::Float32,
+            ("double" | "real", _) => Kind::Float64,
+            ("decimal" | "numeric", _) => match (precision, scale) {
+                (Some(precision), Some(scale)) if precision <= MAX_DECIMAL_PRECISION => {
+                    Kind::Decimal(precision as u8, scale as i8)
+                }
+                _ => Kind::Utf8,
+            },
+            ("date", _) => Kind::Date,
+            ("datetime" | "timestamp", _) => Kind::Timestamp,
+            (t, _) if BINARY_TYPES.contains(&t) || t == "vector" => Kind::Binary,
+            _ => Kind::Utf8,
+        }
+    }
+
+    fn data_type(self) -> DataType {
+        match self {
+            Kind::Int8 => DataType::Int8,
+            Kind::Int16 => DataType::Int16,
+            Kind::Int32 => DataType::Int32,
+            Kind::Int64 => DataType::Int64,
+            Kind::UInt8 => DataType::UInt8,
+            Kind::UInt16 => DataType::UInt16,
+            Kind::UInt32 => DataType::UInt32,
+            Kind::UInt64 => DataType::UInt64,
+            Kind::Float32 => DataType::Float32,
+            Kind::Float64 => DataType::Float64,
+            Kind::Decimal(precision, scale) => DataType::Decimal128(precision, scale),
+            Kind::Date => DataType::Date32,
+            Kind::Timestamp => DataType::Timestamp(TimeUnit::Microsecond, None),
+            Kind::Binary => DataType::Binary,
+            Kind::Utf8 => DataType::Utf8,
+        }
+    }
+}
+
+//
+// Write the rows of each table to <table>.arrow in the directory, an Arrow
+//   IPC file (Feather v2) that pandas.read_feather() and polars.read_ipc()
+//   load with the column types intact. Integers keep their width and sign,
+//   decimals their precision and scale, DATETIME and TIMESTAMP are
+//   microsecond timestamps without a time zone, binary columns are binary
+//   and everything else is a string. Only data is written, views are left
+//   out.
+//
+pub async fn write_arrow_dir(
+    pool: &Pool<MySql>,
+    dir: &str,
+    schema: &str,
+    options: &ExportOptions,
+) -> Result<DumpSummary, sqlx::Error> {
+    fs::create_dir_all(dir)?;
+    let tables: Vec<String> = sqlx::query_as::<_, (String,)>(
+        "select table_name from information_schema.tables where table_schema=? and table_type='BASE TABLE' order by table_name",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|t| t.0)
+    .filter(|t| options.includes_data(t))
+    .collect();
+
+    let mut summary = DumpSummary::default();
+    for table in &tables {
+        let name = options.renames.table(table).replace(['/', '\\'], "_");
+        let path = Path::new(dir).join(format!("{}.arrow", name));
+        let rows = write_table(pool, &path, schema, table, options).await?;
+        Logger::info(format!("Wrote {} rows of {} to {}", rows, table, path.display()));
+        summary.tables += 1;
+        summary.rows += rows;
+    }
+    Ok(summary)
+}
+
+async fn write_table(
+    pool: &Pool<MySql>,
+    path: &Path,
+    schema: &str,
+    table: &str,
+    options: &ExportOptions,
+) -> Result<u64, sqlx::Error> {
+    let columns: Vec<ColumnInfo> = sqlx::query_as(
+        "select column_name, data_type, column_type, is_nullable, numeric_precision, numeric_scale from information_schema.columns where table_schema=? and table_name=? order by ordinal_position",
+    )
+    .bind(schema)
+    .bind(table)
+    .fetch_all(pool)
+    .await?;
+    let kinds: Vec<Kind> = columns.iter().map(|c| Kind::of(&c.1, &c.2, c.4, c.5)).collect();
+    let fields: Vec<Field> = columns
+        .iter()
+        .zip(&kinds)
+        .map(|(c, kind)| {
+            let nullable = c.3 == "YES" || matches!(kind, Kind::Date | Kind::Timestamp);
+            Field::new(options.renames.column(table, &c.0), kind.data_type(), nullable)
+        })
+        .collect();
+    let arrow_schema = Arc::new(Schema::new(fields));
+
+    // Binary values are read as they are, everything else as text
+    let select_list = columns
+        .iter()
+        .zip(&kinds)
+        .map(|(c, kind)| match kind {
+            Kind::Binary => quote_identifier(&c.0),
+            _ => format!("cast({} as char)", quote_identifier(&c.0)),
+        })
+        .collect::<Vec<String>>()
+        .join(",");
+    let row_filter = options.row_clause(table);
+    let query = format!("select {} from {}{}", select_list, qualified_name(schema, table), row_filter);
+
+    let file = BufWriter::new(File::create(path)?);
+    let mut writer = FileWriter::try_new(file, &arrow_schema).map_err(arrow_error)?;
+    let mut batch: Vec<Vec<Option<Vec<u8>>>> = vec![Vec::with_capacity(BATCH_ROWS); columns.len()];
+    let mut rows = 0;
+    let mut data_rows = sqlx::query(&query).fetch(pool);
+    while let Some(row) = data_rows.next().await {
+        let row = row?;
+        for (i, values) in batch.iter_mut().enumerate() {
+            values.push(row.try_get_unchecked::<Option<Vec<u8>>, usize>(i)?);
+        }
+        rows += 1;
+        if batch[0].len() >= BATCH_ROWS {
+            write_batch(&mut writer, &arrow_schema, &kinds, &mut batch)?;
+        }
+    }
+    if batch.first().is_some_and(|values| !values.is_empty()) {
+        write_batch(&mut writer, &arrow_schema, &kinds, &mut batch)?;
+    }
+    writer.finish().map_err(arrow_error)?;
+    Ok(rows)
+}
+
+fn write_batch(
+    writer: &mut FileWriter<BufWriter<File>>,
+    schema: &Arc<Schema>,
+    kinds: &[Kind],
+    batch: &mut [Vec<Option<Vec<u8>>>],
+) -> Result<(), sqlx::Error> {
+    let arrays: Vec<ArrayRef> = kinds.iter().zip(batch.iter()).map(|(kind, values)| build_array(*kind, values)).collect();
+    let record_batch = RecordBatch::try_new(schema.clone(), arrays).map_err(arrow_error)?;
+    writer.write(&record_batch).map_err(arrow_error)?;
+    for values in batch.iter_mut() {
+        values.clear();
+    }
+    Ok(())
+}
+
+fn build_array(kind: Kind, values: &[Option<Vec<u8>>]) -> ArrayRef {
+    let text = values.iter().map(|v| v.as_deref().and_then(|v| std::str::from_utf8(v).ok()));
+    match kind {
+        Kind::Int8 => Arc::new(text.map(parse::<i8>).collect::<Int8Array>()),
+        Kind::Int16 => Arc::new(text.map(parse::<i16>).collect::<Int16Array>()),
+        Kind::Int32 => Arc::new(text.map(parse::<i32>).collect::<Int32Array>()),
+        Kind::Int64 => Arc::new(text.map(parse::<i64>).collect::<Int64Array>()),
+        Kind::UInt8 => Arc::new(text.map(parse::<u8>).collect::<UInt8Array>()),
+        Kind::UInt16 => Arc::new(text.map(parse::<u16>).collect::<UInt16Array>()),
+        Kind::UInt32 => Arc::new(text.map(parse::<u32>).collect::<UInt32Array>()),
+        Kind::UInt64 => Arc::new(text.map(parse::<u64>).collect::<UInt64Array>()),
+        Kind::Float32 => Arc::new(text.map(parse::<f32>).collect::<Float32Array>()),
+        Kind::Float64 => Arc::new(text.map(parse::<f64>).collect::<Float64Array>()),
+        Kind::Decimal(precision, scale) => Arc::new(
+            text.map(|v| v.and_then(|v| decimal_value(v, scale)))
+                .collect::<Decimal128Array>()
+                .with_precision_and_scale(precision, scale)
+                .expect("Decimal precision and scale come from the column"),
+        ),
+        Kind::Date => {
+            let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+            Arc::new(
+                text.map(|v| v.and_then(|v| NaiveDate::parse_from_str(v, "%Y-%m-%d").ok()))
+                    .map(|d| d.map(|d| (d - epoch).num_days() as i32))
+                    .collect::<Date32Array>(),
+            )
+        }
+        Kind::Timestamp => Arc::new(
+            text.map(|v| v.and_then(|v| NaiveDateTime::parse_from_str(v, "%Y-%m-%d %H:%M:%S%.f").ok()))
+                .map(|t| t.map(|t| t.and_utc().timestamp_micros()))
+                .collect::<TimestampMicrosecondArray>(),
+        ),
+        Kind::Binary => Arc::new(values.iter().map(|v| v.as_deref()).collect::<BinaryArray>()),
+        Kind::Utf8 => Arc::new(
+            values
+                .iter()
+                .map(|v| v.as_ref().map(|v| String::from_utf8_lossy(v).into_owned()))
+                .collect::<StringArray>(),
+        ),
+    }
+}
+
+fn parse<T: FromStr>(value: Option<&str>) -> Option<T> {
+    value.and_then(|v| v.parse().ok())
+}
+
+// A decimal as text, such as -12.50, as an integer of the column's scale
+fn decimal_value(value: &str, scale: i8) -> Option<i128> {
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let scale = scale.max(0) as usize;
+    let fraction: String = fraction.chars().chain(std::iter::repeat('0')).take(scale).collect();
+    format!("{}{}", whole, fraction).parse().ok()
+}
+
+fn arrow_error(e: ArrowError) -> sqlx::Error {
+    io::Error::other(e).into()
+}
@@ -0,0 +1,74 @@
+use std::str::FromStr;
+
+//
+// One server of a federated dump, from --shard name or --shard name=url.
+//   Without a url the shard's name is put in place of {shard} in the dump's
+//   url, e.g. mysql://user@{shard}.db.internal/app
+//
+#[derive(Clone, Debug)]
+pub struct Shard {
+    pub name: String,
+    pub url: Option<String>,
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, url) = match s.split_once('=') {
+            Some((name, url)) => (name.trim(), Some(url.trim().to_string())),
+            None => (s.trim(), None),
+        };
+        // The name ends up in schema names and section markers
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(format!("Invalid shard {s}, expected a name of letters, digits, _ and - optionally followed by =url"));
+        }
+        Ok(Shard {
+            name: name.to_string(),
+            url,
+        })
+    }
+}
+
+impl Shard {
+    pub fn url(&self, template: &str) -> Result<String, String> {
+        match &self.url {
+            Some(url) => Ok(url.clone()),
+            None if template.contains("{shard}") => Ok(template.replace("{shard}", &self.name)),
+            None => Err(format!("Shard {} has no url and the dump's url has no {{shard}} to put its name in", self.name)),
+        }
+    }
+
+    // The schema the shard is restored into with ShardLayout::Schemas,
+    //   {shard} in the suffix is replaced by its name
+    pub fn schema(&self, target: &str, suffix: &str) -> String {
+        format!("{}{}", target, suffix.replace("{shard}", &self.name.replace('-', "_")))
+    }
+}
+
+//
+// How the shards come together in the one dump. Every shard is written as a
+//   section named after it either way, so a single shard can be restored with
+//   restore --section.
+//   schemas  each shard is restored into its own schema, named with a suffix
+//   merged   the tables are created from the first shard and the rows of
+//            every shard loaded into them, the keys mustn't clash
+//
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ShardLayout {
+    #[default]
+    Schemas,
+    Merged,
+}
+
+impl FromStr for ShardLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "schemas" => Ok(ShardLayout::Schemas),
+            "merged" => Ok(ShardLayout::Merged),
+            _ => Err(format!("Unknown shard layout {s}, expected one of schemas, merged")),
+        }
+    }
+}
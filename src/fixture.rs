@@ -0,0 +1,170 @@
+use crate::catalog::{Catalog, Table};
+use crate::logger::Logger;
+use crate::sql::{qualified_name, quote_identifier};
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use std::collections::{BTreeSet, HashMap};
+
+//
+// Development fixtures - every table is cut down to a handful of rows, then
+//   rows are added to parent tables until every foreign key in the sample
+//   points at a row that is also in the sample.
+//
+// Rows are tracked by the SQL literal of their key, built server side with
+//   QUOTE() so no type handling is needed here, and the result is a WHERE
+//   clause per table for the data export.
+//
+#[derive(Default)]
+struct Sample {
+    // Row tuples selected, keyed by the column list they are values of
+    keys: HashMap<Vec<String>, BTreeSet<String>>,
+    // Tables without a primary or unique key can only be cut down by LIMIT
+    limit: Option<u64>,
+}
+
+impl Sample {
+    fn condition(&self) -> String {
+        let conditions: Vec<String> = self
+            .keys
+            .iter()
+            .filter(|(_, tuples)| !tuples.is_empty())
+            .map(|(columns, tuples)| {
+                format!(
+                    "({}) in ({})",
+                    column_list(columns),
+                    tuples.iter().cloned().collect::<Vec<String>>().join(",")
+                )
+            })
+            .collect();
+        if conditions.is_empty() {
+            "false".to_string()
+        } else {
+            conditions.join(" or ")
+        }
+    }
+
+    fn query_tail(&self) -> String {
+        match self.limit {
+            Some(limit) => format!("limit {}", limit),
+            None => format!("where {}", self.condition()),
+        }
+    }
+}
+
+pub fn column_list(columns: &[String]) -> String {
+    columns
+        .iter()
+        .map(|c| quote_identifier(c))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+// SQL literal for a row's values in `columns`, e.g. ('a',1)
+fn tuple_expression(columns: &[String]) -> String {
+    format!(
+        "concat('(', concat_ws(',', {}), ')')",
+        columns
+            .iter()
+            .map(|c| format!("quote({})", quote_identifier(c)))
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
+}
+
+// The columns that identify a row - the primary key or else the first
+//   unique index
+pub fn row_key(table: &Table) -> Option<Vec<String>> {
+    table
+        .primary_key()
+        .or_else(|| table.indexes.iter().find(|i| i.unique))
+        .map(|i| i.columns.clone())
+        .filter(|columns| !columns.iter().any(|c| c == "(expression)"))
+}
+
+//
+// Work out the rows to dump for each table. Returns the clause to append to
+//   each table's data query.
+//
+pub async fn select_rows(pool: &Pool<MySql>, catalog: &Catalog, rows: u64) -> Result<HashMap<String, String>, sqlx::Error> {
+    let schema = &catalog.schema;
+    let mut samples: HashMap<String, Sample> = HashMap::new();
+
+    //
+    // Seed every table with its first rows
+    //
+    for table in &catalog.tables {
+        let mut sample = Sample::default();
+        match row_key(table) {
+            Some(key) => {
+                let tuples: Vec<(String,)> = sqlx::query_as(&format!(
+                    "select {} from {} order by {} limit {}",
+                    tuple_expression(&key),
+                    qualified_name(schema, &table.name),
+                    column_list(&key),
+                    rows
+                ))
+                .fetch_all(pool)
+                .await?;
+                sample.keys.insert(key, tuples.into_iter().map(|t| t.0).collect());
+            }
+            None => sample.limit = Some(rows),
+        }
+        samples.insert(table.name.clone(), sample);
+    }
+
+    //
+    // Pull in parent rows until nothing changes. Each pass only adds rows so
+    //   this always finishes.
+    //
+    let mut pending: Vec<String> = catalog.tables.iter().map(|t| t.name.clone()).collect();
+    while let Some(table_name) = pending.pop() {
+        let table = match catalog.tables.iter().find(|t| t.name == table_name) {
+            Some(table) => table,
+            None => continue,
+        };
+        for fk in &table.foreign_keys {
+            if !samples.contains_key(&fk.referenced_table) {
+                continue;
+            }
+            let not_null = fk
+                .columns
+                .iter()
+                .map(|c| format!("{} is not null", quote_identifier(c)))
+                .collect::<Vec<String>>()
+                .join(" and ");
+            let tail = samples[&table_name].query_tail();
+            let tuples: Vec<(String,)> = sqlx::query_as(&format!(
+                "select distinct {} from (select * from {} {}) fixture where {}",
+                tuple_expression(&fk.columns),
+                qualified_name(schema, &table_name),
+                tail,
+                not_null
+            ))
+            .fetch_all(pool)
+            .await?;
+            if tuples.is_empty() {
+                continue;
+            }
+
+            let parent = samples.get_mut(&fk.referenced_table).unwrap();
+            if parent.keys.is_empty() {
+                Logger::warn(format!(
+                    "{} has no primary or unique key, rows referenced from {} may be missing from the fixture",
+                    fk.referenced_table, table_name
+                ));
+                continue;
+            }
+            let selected = parent.keys.entry(fk.referenced_columns.clone()).or_default();
+            let before = selected.len();
+            selected.extend(tuples.into_iter().map(|t| t.0));
+            if selected.len() > before && !pending.contains(&fk.referenced_table) {
+                pending.push(fk.referenced_table.clone());
+            }
+        }
+    }
+
+    Ok(samples
+        .into_iter()
+        .map(|(name, sample)| (name, sample.query_tail()))
+        .collect())
+}
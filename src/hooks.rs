@@ -0,0 +1,82 @@
+use crate::logger::Logger;
+use crate::restore::StatementReader;
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use sqlx::Executor;
+use std::io::{self, BufReader};
+use std::process::{Command, Stdio};
+
+//
+// Hooks run around one table's data export, from the config file. Each hook
+//   is SQL (inline or @file) or a shell command prefixed with sh:, which gets
+//   DBDUMP_SCHEMA and DBDUMP_TABLE in its environment. The rows can be read
+//   from another table or view instead, e.g. one the before hook builds. As
+//   hooks and the export use different connections a temporary table won't
+//   be visible to the export.
+//
+#[derive(Clone, Debug, Default)]
+pub struct TableHooks {
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+    pub source: Option<String>,
+}
+
+//
+// SQL run against the source around a dump, e.g. to refresh a reporting
+//   table first or write an audit row afterwards. A hook is either inline SQL
+//   or @path to a file of statements, which run in order on one connection.
+//   Failures are logged as hook failures so they aren't mistaken for a
+//   problem with the dump itself.
+//
+pub async fn run_sql_hook(pool: &Pool<MySql>, name: &str, hook: &str) -> Result<usize, sqlx::Error> {
+    let sql = match hook.strip_prefix('@') {
+        Some(path) => std::fs::read_to_string(path).map_err(|e| {
+            Logger::error(format!("The {} hook can't read {}: {}", name, path, e));
+            sqlx::Error::Io(e)
+        })?,
+        None => hook.to_string(),
+    };
+
+    let mut conn = pool.acquire().await?;
+    let mut count = 0;
+    for statement in StatementReader::new(BufReader::new(sql.as_bytes())) {
+        let statement = statement.map_err(sqlx::Error::Io)?;
+        if let Err(e) = conn.execute(statement.as_str()).await {
+            Logger::error(format!("The {} hook failed on statement {}: {}", name, count + 1, e));
+            return Err(e);
+        }
+        count += 1;
+    }
+    Logger::info(format!("Ran the {} hook, {} statements", name, count));
+    Ok(count)
+}
+
+pub async fn run_table_hook(pool: &Pool<MySql>, schema: &str, table: &str, when: &str, hook: &str) -> Result<(), sqlx::Error> {
+    let name = format!("{} {}", table, when);
+    match hook.strip_prefix("sh:") {
+        Some(command) => run_shell_hook(&name, command, schema, table),
+        None => run_sql_hook(pool, &name, hook).await.map(|_| ()),
+    }
+}
+
+// The command's output goes to stderr so it can't end up in a dump written
+//   to stdout
+fn run_shell_hook(name: &str, command: &str, schema: &str, table: &str) -> Result<(), sqlx::Error> {
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("DBDUMP_SCHEMA", schema)
+        .env("DBDUMP_TABLE", table)
+        .stdout(Stdio::from(io::stderr()))
+        .status()
+        .map_err(|e| {
+            Logger::error(format!("The {} hook can't run: {}", name, e));
+            sqlx::Error::Io(e)
+        })?;
+    if !status.success() {
+        let message = format!("The {} hook failed with {}", name, status);
+        Logger::error(&message);
+        return Err(sqlx::Error::Io(io::Error::other(message)));
+    }
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use std::str::FromStr;
+
+//
+// Formatting of JSON column values.
+//   Compact - the value as the server returns it, on a single line
+//   Pretty  - indented across lines inside the string literal, for dumps that
+//             are meant to be reviewed or diffed
+//
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum JsonFormat {
+    #[default]
+    Compact,
+    Pretty,
+}
+
+impl FromStr for JsonFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "compact" => Ok(JsonFormat::Compact),
+            "pretty" => Ok(JsonFormat::Pretty),
+            _ => Err(format!("Unknown json format {s}, expected one of compact, pretty")),
+        }
+    }
+}
+
+//
+// Check the value is well-formed and write it in the requested format. The
+//   value is never re-serialized, numbers would go through f64 and lose the
+//   digits of large integers and precise decimals. Compact keeps the
+//   server's text and pretty only changes the whitespace between tokens.
+//
+pub fn format_json(raw: &str, format: JsonFormat) -> Result<String, serde_json::Error> {
+    serde_json::from_str::<serde::de::IgnoredAny>(raw)?;
+    match format {
+        JsonFormat::Compact => Ok(raw.to_string()),
+        JsonFormat::Pretty => Ok(indent(raw)),
+    }
+}
+
+// Indent well-formed JSON by two spaces a level, as serde_json's pretty
+//   printer does, leaving strings and numbers as they are
+fn indent(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len() * 2);
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    let new_line = |out: &mut String, depth: usize| {
+        out.push('\n');
+        out.push_str(&"  ".repeat(depth));
+    };
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            '{' | '[' => {
+                out.push(c);
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                // Empty objects and arrays stay on one line
+                match chars.next_if(|c| *c == '}' || *c == ']') {
+                    Some(close) => out.push(close),
+                    None => {
+                        depth += 1;
+                        new_line(&mut out, depth);
+                    }
+                }
+            }
+            '}' | ']' => {
+                depth -= 1;
+                new_line(&mut out, depth);
+                out.push(c);
+            }
+            ',' => {
+                out.push(c);
+                new_line(&mut out, depth);
+            }
+            ':' => out.push_str(": "),
+            c if c.is_whitespace() => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,177 @@
+use crate::bigquery::Kind;
+use crate::logger::Logger;
+use crate::sql::qualified_name;
+use crate::{DumpSummary, ExportOptions};
+use async_std::stream::StreamExt;
+use rdkafka::config::ClientConfig;
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::producer::{BaseRecord, DeliveryResult, Producer, ProducerContext, ThreadedProducer};
+use rdkafka::ClientContext;
+use serde_json::{Map, Value};
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use sqlx::Row;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// How long the last messages have to reach the brokers
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(60);
+
+// name, data type, column type, numeric precision and scale
+type ColumnRow = (String, String, String, Option<u64>, Option<u64>);
+
+// Counts the messages the brokers didn't take, the producer reports them
+//   from its own thread
+#[derive(Default)]
+struct Deliveries {
+    failed: AtomicU64,
+}
+
+impl ClientContext for Deliveries {}
+
+impl ProducerContext for Deliveries {
+    type DeliveryOpaque = ();
+
+    fn delivery(&self, result: &DeliveryResult<'_>, _: ()) {
+        if let Err((e, _)) = result {
+            if self.failed.fetch_add(1, Ordering::Relaxed) == 0 {
+                Logger::error(format!("Unable to deliver a row to Kafka: {}", e));
+            }
+        }
+    }
+}
+
+//
+// Publish every row of the schema to Kafka, a topic per table, so a dump can
+//   bootstrap a streaming pipeline. Each message is the row as a JSON object
+//   (typed as in the BigQuery bundle) keyed by the JSON of its primary key
+//   columns, so a compacted topic keeps one message per row. Tables without
+//   a primary key are sent without keys. The topic is the template with
+//   {schema} and {table} filled in, settings are librdkafka properties such
+//   as security.protocol=SSL.
+//
+pub async fn publish_rows(
+    pool: &Pool<MySql>,
+    schema: &str,
+    options: &ExportOptions,
+    brokers: &str,
+    topic: &str,
+    settings: &[(String, String)],
+) -> Result<DumpSummary, sqlx::Error> {
+    let mut config = ClientConfig::new();
+    config.set("bootstrap.servers", brokers);
+    for (key, value) in settings {
+        config.set(key, value);
+    }
+    let producer: ThreadedProducer<Deliveries> =
+        config.create_with_context(Deliveries::default()).map_err(kafka_error)?;
+
+    let tables: Vec<String> = sqlx::query_as::<_, (String,)>(
+        "select table_name from information_schema.tables where table_schema=? and table_type='BASE TABLE' order by table_name",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|t| t.0)
+    .filter(|t| options.includes_data(t))
+    .collect();
+
+    let mut summary = DumpSummary::default();
+    for table in &tables {
+        let name = options.renames.table(table);
+        let table_topic = topic.replace("{schema}", schema).replace("{table}", &name);
+        let rows = publish_table(pool, &producer, &table_topic, schema, table, options).await?;
+        Logger::info(format!("Published {} rows of {} to {}", rows, table, table_topic));
+        summary.tables += 1;
+        summary.rows += rows;
+    }
+
+    producer.flush(FLUSH_TIMEOUT).map_err(kafka_error)?;
+    let failed = producer.context().failed.load(Ordering::Relaxed);
+    if failed > 0 {
+        return Err(io::Error::other(format!("{} rows couldn't be delivered to Kafka", failed)).into());
+    }
+    Ok(summary)
+}
+
+async fn publish_table(
+    pool: &Pool<MySql>,
+    producer: &ThreadedProducer<Deliveries>,
+    topic: &str,
+    schema: &str,
+    table: &str,
+    options: &ExportOptions,
+) -> Result<u64, sqlx::Error> {
+    let columns: Vec<ColumnRow> = sqlx::query_as(
+        "select column_name, data_type, column_type, numeric_precision, numeric_scale from information_schema.columns where table_schema=? and table_name=? order by ordinal_position",
+    )
+    .bind(schema)
+    .bind(table)
+    .fetch_all(pool)
+    .await?;
+    let primary_key: Vec<(String,)> = sqlx::query_as(
+        "select column_name from information_schema.statistics where table_schema=? and table_name=? and index_name='PRIMARY' order by seq_in_index",
+    )
+    .bind(schema)
+    .bind(table)
+    .fetch_all(pool)
+    .await?;
+    let kinds: Vec<Kind> = columns.iter().map(|c| Kind::of(&c.1, &c.2, c.3, c.4)).collect();
+    let names: Vec<String> = columns.iter().map(|c| options.renames.column(table, &c.0)).collect();
+    let key_names: Vec<String> = primary_key.iter().map(|c| options.renames.column(table, &c.0)).collect();
+
+    let select_list = columns
+        .iter()
+        .zip(&kinds)
+        .map(|(c, kind)| kind.select_expression(&c.0))
+        .collect::<Vec<String>>()
+        .join(",");
+    let row_filter = options.row_clause(table);
+    let query = format!("select {} from {}{}", select_list, qualified_name(schema, table), row_filter);
+
+    let mut rows = 0;
+    let mut data_rows = sqlx::query(&query).fetch(pool);
+    while let Some(row) = data_rows.next().await {
+        let row = row?;
+        let mut object = Map::new();
+        for (i, kind) in kinds.iter().enumerate() {
+            let value = match row.try_get_unchecked::<Option<String>, usize>(i)? {
+                Some(value) => kind.json_value(value),
+                None => Value::Null,
+            };
+            object.insert(names[i].clone(), value);
+        }
+        let key: Option<String> = (!key_names.is_empty()).then(|| {
+            let key: Map<String, Value> = key_names.iter().map(|k| (k.clone(), object[k].clone())).collect();
+            Value::Object(key).to_string()
+        });
+        let payload = Value::Object(object).to_string();
+        send(producer, topic, key.as_deref(), &payload).await?;
+        rows += 1;
+    }
+    Ok(rows)
+}
+
+// Send a message, waiting for room when the producer's queue is full
+async fn send(producer: &ThreadedProducer<Deliveries>, topic: &str, key: Option<&str>, payload: &str) -> Result<(), sqlx::Error> {
+    let mut record = BaseRecord::to(topic).payload(payload);
+    if let Some(key) = key {
+        record = record.key(key);
+    }
+    loop {
+        match producer.send(record) {
+            Ok(()) => return Ok(()),
+            Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), returned)) => {
+                record = returned;
+                async_std::task::sleep(Duration::from_millis(50)).await;
+            }
+            Err((e, _)) => return Err(kafka_error(e)),
+        }
+    }
+}
+
+fn kafka_error(e: KafkaError) -> sqlx::Error {
+    io::Error::other(e).into()
+}
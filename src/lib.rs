@@ -1,22 +1,554 @@
+pub mod archive;
+pub mod bench;
+pub mod bigquery;
+pub mod bucket;
+pub mod budget;
+pub mod catalog;
+pub mod clickhouse;
+pub mod comment_audit;
+pub mod compress;
+pub mod config;
+pub mod convert;
+pub mod ddl;
+pub mod ddl_change;
+pub mod diff;
+pub mod docs;
+pub mod dot;
+pub mod drift;
+pub mod escape;
+pub mod explain;
+#[cfg(feature = "arrow")]
+pub mod feather;
+pub mod federate;
+pub mod fixture;
+pub mod hooks;
+pub mod json;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+pub mod lint;
+pub mod list;
 pub mod logger;
+pub mod metadata;
+pub mod normalize;
+pub mod notify;
+pub mod picker;
+pub mod pii;
+pub mod plan;
+pub mod profile;
+pub mod query;
+pub mod rename;
+pub mod restore;
+pub mod routine;
+pub mod sample;
+pub mod schedule;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod seed;
+pub mod shape;
+pub mod shard;
+pub mod shell;
+pub mod slice;
+pub mod space;
+pub mod sql;
+pub mod split;
+pub mod sqlcheck;
+pub mod stats;
 pub mod std_writer;
-use async_std::stream;
+pub mod sync;
+pub mod tablespec;
+pub mod transcode;
+pub mod transform;
+pub mod types;
+pub mod uuid;
+pub mod validate;
+pub mod vector;
+pub mod verify;
+pub mod xml;
+use async_std::stream::StreamExt;
 use async_std::task::block_on;
+use catalog::Catalog;
+use escape::{quote_string, EscapeMode};
+use json::JsonFormat;
 use logger::Logger;
 use regex::Regex;
+use rename::Renames;
+use sql::{qualified_name, quote_identifier, Compat, NameCase};
 use sqlx::mysql::{MySql, MySqlColumn, MySqlRow};
-use sqlx::pool::{Pool, self};
+use sqlx::pool::Pool;
 use sqlx::types::chrono::Local;
 use sqlx::types::chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use sqlx::types::BigDecimal;
-use sqlx::{Column, Row};
+use sqlx::{Column, Row, ValueRef};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Display;
+use std::str::FromStr;
 use std_writer::StdWriter;
-use std::sync::{Arc, Mutex};
+use transcode::SourceEncoding;
+use transform::{Transformer, Value};
+use uuid::UuidFormat;
+use vector::VectorFormat;
+use budget::MemoryBudget;
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::Instant;
 
 const MAX_BUFFER_SIZE: usize = 20 * 1024 * 1024; // 20MB
+const DEFAULT_BUFFER_SIZE: usize = 256 * 1024 * 1024; // 256MB
+
+//
+// What dbdump produces
+//   Sql        - a restorable SQL dump (the default)
+//   Markdown   - a data dictionary documenting the schema
+//   Dot        - a GraphViz ER diagram of tables and foreign keys
+//   SchemaJson - the schema catalog as JSON for code generators and diff tools
+//   MysqlShell - a directory util.loadDump() in MySQL Shell can load
+//   Xml        - the schema and rows as mysqldump --xml writes them
+//   Arrow      - a directory of Arrow IPC files, one per table
+//   BigQuery   - a directory of JSON rows and schemas for bq load
+//   Kafka      - every row published to a Kafka topic per table
+//   AnonymizedSchema - the tables' structure with every name replaced
+//
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Sql,
+    Markdown,
+    Dot,
+    SchemaJson,
+    MysqlShell,
+    Xml,
+    Arrow,
+    BigQuery,
+    Kafka,
+    AnonymizedSchema,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sql" => Ok(OutputFormat::Sql),
+            "markdown" | "md" => Ok(OutputFormat::Markdown),
+            "dot" => Ok(OutputFormat::Dot),
+            "schema-json" => Ok(OutputFormat::SchemaJson),
+            "mysqlsh" => Ok(OutputFormat::MysqlShell),
+            "xml" => Ok(OutputFormat::Xml),
+            "arrow" | "feather" => Ok(OutputFormat::Arrow),
+            "bigquery" => Ok(OutputFormat::BigQuery),
+            "kafka" => Ok(OutputFormat::Kafka),
+            "anonymized-schema" => Ok(OutputFormat::AnonymizedSchema),
+            _ => Err(format!(
+                "Unknown format {s}, expected one of sql, markdown, dot, schema-json, mysqlsh, xml, arrow, bigquery, kafka, anonymized-schema"
+            )),
+        }
+    }
+}
+
+impl OutputFormat {
+    // Formats describing the schema rather than dumping it
+    pub fn is_documentation(self) -> bool {
+        matches!(
+            self,
+            OutputFormat::Markdown | OutputFormat::Dot | OutputFormat::SchemaJson | OutputFormat::AnonymizedSchema
+        )
+    }
+
+    // Formats written to a directory given as the output file, or not to a
+    //   file at all
+    pub fn is_directory(self) -> bool {
+        matches!(self, OutputFormat::MysqlShell | OutputFormat::Arrow | OutputFormat::BigQuery | OutputFormat::Kafka)
+    }
+}
+
+//
+// How much commentary goes into a SQL dump
+//   None    - no comments at all, for diffing dumps
+//   Minimal - the banner before each object
+//   Normal  - the header, banners and routine metadata (the default)
+//   Verbose - also table storage details and row counts
+// The completion trailer and section markers are always written.
+//
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Comments {
+    None,
+    Minimal,
+    #[default]
+    Normal,
+    Verbose,
+}
+
+impl FromStr for Comments {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Comments::None),
+            "minimal" => Ok(Comments::Minimal),
+            "normal" => Ok(Comments::Normal),
+            "verbose" => Ok(Comments::Verbose),
+            _ => Err(format!("Unknown comment level {s}, expected one of none, minimal, normal, verbose")),
+        }
+    }
+}
+
+//
+// Whether the data is loaded inside explicit transactions
+//   Off   - autocommit, every insert commits on its own (the default)
+//   Table - each table's rows in a transaction of their own
+//   Dump  - all of the data in one transaction
+// Either way a commit interval can break the transaction up every N rows.
+//
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TransactionalLoad {
+    #[default]
+    Off,
+    Table,
+    Dump,
+}
+
+impl FromStr for TransactionalLoad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(TransactionalLoad::Off),
+            "table" => Ok(TransactionalLoad::Table),
+            "dump" => Ok(TransactionalLoad::Dump),
+            _ => Err(format!("Unknown transactional load {s}, expected one of off, table, dump")),
+        }
+    }
+}
+
+//
+// What the data queries do about rows other sessions have locked
+//   None   - consistent reads that take no row locks (the default)
+//   Wait   - locking reads that wait for locked rows, up to the lock wait
+//            timeout
+//   Nowait - locking reads that fail the table at the first locked row
+//   Skip   - locking reads that leave locked rows out of the dump
+// Nowait needs MySQL 8.0 or MariaDB 10.3, Skip MySQL 8.0 or MariaDB 10.6.
+//
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LockedRows {
+    #[default]
+    None,
+    Wait,
+    Nowait,
+    Skip,
+}
+
+impl FromStr for LockedRows {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(LockedRows::None),
+            "wait" => Ok(LockedRows::Wait),
+            "nowait" => Ok(LockedRows::Nowait),
+            "skip" | "skip-locked" => Ok(LockedRows::Skip),
+            _ => Err(format!("Unknown locked rows policy {s}, expected one of none, wait, nowait, skip")),
+        }
+    }
+}
+
+impl LockedRows {
+    // The clause ending each data query on a server with this version
+    //   string, or an error when the server can't do it
+    pub fn clause(self, server_version: &str) -> Result<Option<String>, String> {
+        let mariadb = server_version.to_lowercase().contains("mariadb");
+        let version = major_minor(server_version);
+        // MariaDB has NOWAIT from 10.3 and SKIP LOCKED from 10.6
+        let needs = match (self, mariadb) {
+            (LockedRows::Nowait, false) if version < (8, 0) => Some(("nowait", "MySQL 8.0")),
+            (LockedRows::Skip, false) if version < (8, 0) => Some(("skip", "MySQL 8.0")),
+            (LockedRows::Nowait, true) if version < (10, 3) => Some(("nowait", "MariaDB 10.3")),
+            (LockedRows::Skip, true) if version < (10, 6) => Some(("skip", "MariaDB 10.6")),
+            _ => None,
+        };
+        if let Some((policy, needs)) = needs {
+            return Err(format!("--locked-rows {} needs {}, the server is {}", policy, needs, server_version));
+        }
+        let clause = match (self, mariadb) {
+            (LockedRows::None, _) => return Ok(None),
+            (LockedRows::Wait, _) => "LOCK IN SHARE MODE",
+            (LockedRows::Nowait, false) => "FOR SHARE NOWAIT",
+            (LockedRows::Skip, false) => "FOR SHARE SKIP LOCKED",
+            (LockedRows::Nowait, true) => "LOCK IN SHARE MODE NOWAIT",
+            (LockedRows::Skip, true) => "LOCK IN SHARE MODE SKIP LOCKED",
+        };
+        Ok(Some(clause.to_string()))
+    }
+}
+
+// The major and minor version in a VERSION() string, e.g. (10, 6) from
+//   10.6.12-MariaDB-log. Replication can prefix MariaDB's with 5.5.5-.
+fn major_minor(server_version: &str) -> (u32, u32) {
+    let version = server_version.strip_prefix("5.5.5-").unwrap_or(server_version);
+    let mut parts = version.split(|c: char| !c.is_ascii_digit()).map(|p| p.parse().unwrap_or(0));
+    (parts.next().unwrap_or(0), parts.next().unwrap_or(0))
+}
+
+//
+// How often a long table export logs its progress, every N rows ("500000")
+//   or every N seconds ("30s")
+//
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgressInterval {
+    Rows(u64),
+    Seconds(u64),
+}
+
+impl FromStr for ProgressInterval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid progress interval {s}, expected a number of rows or seconds like 30s");
+        match s.strip_suffix('s') {
+            Some(seconds) => seconds.parse().map(ProgressInterval::Seconds).map_err(|_| invalid()),
+            None => s.parse().map(ProgressInterval::Rows).map_err(|_| invalid()),
+        }
+        .and_then(|interval| match interval {
+            ProgressInterval::Rows(0) | ProgressInterval::Seconds(0) => Err(invalid()),
+            interval => Ok(interval),
+        })
+    }
+}
+
+//
+// Options controlling how table data is written
+//
+#[derive(Clone, Debug, Default)]
+pub struct ExportOptions {
+    pub single_row_inserts: bool,
+    pub skip_unknown_datatypes: bool,
+    pub escape_mode: EscapeMode,
+    pub compat: Compat,
+    pub transcode_latin1: Option<SourceEncoding>,
+    pub uuid_columns: Vec<String>,
+    pub uuid_format: UuidFormat,
+    pub json_format: JsonFormat,
+    pub vector_format: VectorFormat,
+    pub table_stats: bool,
+    pub checksum: bool,
+    // Only export these tables and views, None exports everything
+    pub objects: Option<Vec<String>>,
+    // Tables whose structure is exported but not their rows
+    pub schema_only: Vec<String>,
+    // Clause appended to a table's data query to restrict the rows exported
+    pub row_filters: HashMap<String, String>,
+    // ORDER BY for a table's data query, from the config file
+    pub row_orders: HashMap<String, String>,
+    // Comment starting every data query, so they can be told apart in the
+    //   processlist
+    pub query_tag: Option<String>,
+    // Locking clause ending every data query, from --locked-rows
+    pub locking_read: Option<String>,
+    // Hooks run around a table's data export, and where its rows come from
+    pub table_hooks: HashMap<String, hooks::TableHooks>,
+    // Bytes of fetched rows allowed to wait for the writer, 0 for the default
+    pub buffer_bytes: usize,
+    pub renames: Renames,
+    // Library hook to change or drop rows before they are written
+    pub transformer: Option<Transformer>,
+    pub comments: Comments,
+    // Qualify every object name with this schema instead of relying on the
+    //   use statement, references to the source schema are pointed at it too
+    pub qualify: Option<String>,
+    // Leave FOREIGN_KEY_CHECKS on while the dump loads
+    pub keep_foreign_key_checks: bool,
+    pub transactional_load: TransactionalLoad,
+    // Commit and start a new transaction every N rows, 0 to only commit at
+    //   the end of the table or dump
+    pub commit_rows: u64,
+    // Log progress through each table, None to only log finished tables
+    pub progress: Option<ProgressInterval>,
+    // Parse each insert statement before it is written, see sqlcheck.rs
+    pub check_sql: bool,
+    // Report table and column comments the DDL rewrites lose, see
+    //   comment_audit.rs
+    pub audit_comments: bool,
+    // Stop on values that don't decode as their type instead of writing
+    //   them raw
+    pub strict_data: bool,
+    // Consulted before the built in handling of each type, handlers first
+    pub type_handlers: types::TypeHandlers,
+    pub type_overrides: Vec<types::TypeOverrideRule>,
+    // How the server compares table names given by the user with its own
+    pub name_case: NameCase,
+    // Leave tablespaces and data directories out of CREATE TABLE
+    pub strip_tablespaces: bool,
+    // Create plain secondary indexes after a table's rows instead of in its
+    //   CREATE TABLE
+    pub defer_indexes: bool,
+    // Move each table's AUTO_INCREMENT past the largest value in the source
+    //   after its rows
+    pub auto_increment: bool,
+    // Queries dumped as tables, by the name of their table
+    pub queries: BTreeMap<String, query::QueryTable>,
+    // Views whose DDL is left out, a table of their rows takes their place
+    pub replaced_views: Vec<String>,
+    // Metadata from the cache, see catalog.rs, used instead of querying
+    //   information_schema for the table list, foreign keys and column types
+    pub catalog: Option<Arc<Catalog>>,
+    // The table definitions read during the dump, see ddl_change.rs
+    pub definitions: ddl_change::TableDefinitions,
+    // Fingerprints of the rows written, for --verify-script
+    pub fingerprints: Option<verify::Fingerprints>,
+}
+
+impl ExportOptions {
+    pub fn includes(&self, name: &str) -> bool {
+        self.objects
+            .as_ref()
+            .is_none_or(|objects| objects.iter().any(|o| self.name_case.same(o, name)))
+    }
+
+    // Whether a table's rows are exported
+    pub fn includes_data(&self, name: &str) -> bool {
+        self.includes(name) && !self.schema_only.iter().any(|t| self.name_case.same(t, name))
+    }
+
+    // The hooks configured for a table, whatever case the config names it in
+    pub fn hooks(&self, table: &str) -> Option<&hooks::TableHooks> {
+        self.configured(&self.table_hooks, table)
+    }
+
+    // The ORDER BY configured for a table
+    pub fn row_order(&self, table: &str) -> Option<&String> {
+        self.configured(&self.row_orders, table)
+    }
+
+    // The cached catalog, when it is the one of this schema
+    fn cached_catalog(&self, schema: &str) -> Option<&Catalog> {
+        self.catalog.as_deref().filter(|c| c.schema == schema)
+    }
+
+    fn configured<'a, T>(&self, by_table: &'a HashMap<String, T>, table: &str) -> Option<&'a T> {
+        by_table.get(table).or_else(|| {
+            by_table
+                .iter()
+                .find(|(name, _)| self.name_case.same(name, table))
+                .map(|(_, value)| value)
+        })
+    }
+
+    pub fn buffer_bytes(&self) -> usize {
+        if self.buffer_bytes == 0 {
+            DEFAULT_BUFFER_SIZE
+        } else {
+            self.buffer_bytes
+        }
+    }
+
+    // What follows the table in a data query: the table's row filter, its
+    //   order and the locking clause
+    pub fn row_clause(&self, table: &str) -> String {
+        let mut clause = self.row_selection(table);
+        if let Some(locking_read) = &self.locking_read {
+            clause.push_str(&format!(" {}", locking_read));
+        }
+        clause
+    }
+
+    // The rows of a table that are exported, the row clause without the
+    //   locking read
+    pub fn row_selection(&self, table: &str) -> String {
+        let filter = self.row_filters.get(table);
+        // Fixtures cut tables without a key down with a LIMIT, which has to
+        //   come after the ORDER BY
+        let (condition, limit) = match filter {
+            Some(f) if f.starts_with("limit ") => (None, Some(f)),
+            _ => (filter, None),
+        };
+        let mut clause = condition.map(|f| format!(" {}", f)).unwrap_or_default();
+        if let Some(order) = self.row_order(table) {
+            clause.push_str(&format!(" order by {}", order));
+        }
+        if let Some(limit) = limit {
+            clause.push_str(&format!(" {}", limit));
+        }
+        clause
+    }
+
+    // DDL as it is written to the dump, with renames and qualification applied
+    pub fn rewrite_ddl(&self, ddl: &str, schema: &str) -> String {
+        let ddl = self.renames.rewrite_ddl(ddl, schema);
+        match &self.qualify {
+            Some(target) => rename::qualify(&ddl, schema, &self.compat.quote_identifier(target)),
+            None => rename::strip_schema(&ddl, schema),
+        }
+    }
+
+    // A table's name as written in the statements that load it
+    pub fn table_name(&self, table: &str) -> String {
+        let name = self.compat.quote_identifier(&self.renames.table(table));
+        match &self.qualify {
+            Some(target) => format!("{}.{}", self.compat.quote_identifier(target), name),
+            None => name,
+        }
+    }
+
+    // Rows are handed to the writer in batches of about this many bytes,
+    //   small enough that every export thread can have a couple in flight
+    pub fn batch_bytes(&self, thread_count: usize) -> usize {
+        (self.buffer_bytes() / (2 * thread_count.max(1))).clamp(64 * 1024, MAX_BUFFER_SIZE)
+    }
+}
+
+//
+// How an individual column's values are turned into SQL literals when the
+//   default handling in cast_data isn't wanted
+//
+#[derive(Clone, Copy)]
+enum ColumnRender {
+    Default,
+    Transcode(SourceEncoding),
+    Uuid(UuidFormat),
+    Spatial,
+    Vector(VectorFormat),
+    Time,
+}
+
+const SPATIAL_TYPES: [&str; 9] = [
+    "geometry", "point", "linestring", "polygon", "multipoint", "multilinestring", "multipolygon", "geometrycollection",
+    "geomcollection",
+];
+
+impl ColumnRender {
+    fn for_column(table_name: &str, name: &str, data_type: &str, charset: Option<&str>, options: &ExportOptions) -> ColumnRender {
+        let data_type = data_type.to_ascii_lowercase();
+        if options.uuid_columns.iter().any(|p| uuid::matches_column(p, table_name, name, options.name_case)) {
+            ColumnRender::Uuid(options.uuid_format)
+        } else if SPATIAL_TYPES.contains(&data_type.as_str()) {
+            ColumnRender::Spatial
+        } else if data_type == "vector" {
+            ColumnRender::Vector(options.vector_format)
+        } else if data_type == "time" {
+            ColumnRender::Time
+        } else if let Some(encoding) = options.transcode_latin1.filter(|_| charset == Some("latin1")) {
+            ColumnRender::Transcode(encoding)
+        } else {
+            ColumnRender::Default
+        }
+    }
+
+    // The expression used in the select list to fetch the column
+    fn select_expression(&self, name: &str) -> String {
+        let name = quote_identifier(name);
+        match self {
+            // latin1 columns being transcoded are fetched as raw bytes
+            ColumnRender::Transcode(_) => format!("cast({name} as binary) as {name}"),
+            // Geometry is fetched as SRID:WKT so the SRID survives the round trip
+            ColumnRender::Spatial => format!("concat(ST_SRID({name}), ':', ST_AsText({name})) as {name}"),
+            ColumnRender::Vector(format) => format.select_expression(&name),
+            // TIME is a duration (-838:59:59 to 838:59:59) that the driver
+            //   decodes as a time of day, so it is fetched as text
+            ColumnRender::Time => format!("cast({name} as char) as {name}"),
+            _ => name,
+        }
+    }
+}
 
 //
 // Export the table DDL - tables are ordered so that we try and
@@ -25,27 +557,83 @@ const MAX_BUFFER_SIZE: usize = 20 * 1024 * 1024; // 20MB
 pub async fn export_tables(
     pool: &Pool<MySql>,
     writer: &mut StdWriter,
-    schema: &String,
-) -> Result<(), sqlx::Error> {
+    schema: &str,
+    options: &ExportOptions,
+) -> Result<usize, sqlx::Error> {
     //
     // Grab all of the tables from the selected schema
-    let table_names: Vec<(String,)> =
-        sqlx::query_as("select table_name from information_schema.tables where table_schema=? and table_type='BASE TABLE'")
-            .bind(&schema)
-            .fetch_all(pool)
-            .await?;
+    let table_names = base_tables(pool, schema, options).await?;
 
-    let table_names = order_tables(pool, schema, table_names).await?;
+    let table_names = order_tables(pool, schema, table_names, options).await?;
 
-    for table_name in &table_names {
-        writer.println(format!("-- Extract DDL for table {}", table_name).as_str());
+    let mut count = 0;
+    for table_name in table_names.iter().filter(|t| options.includes(t)) {
+        count += 1;
+        if options.comments >= Comments::Minimal {
+            writer.println(format!("-- Extract DDL for table {}", table_name).as_str());
+        }
+        if options.comments >= Comments::Verbose {
+            write_table_details(pool, writer, schema, table_name).await?;
+        }
+        if options.compat == Compat::ClickHouse {
+            writer.println(format!("{};", clickhouse::create_table(pool, schema, table_name, options).await?).as_str());
+            continue;
+        }
+        if options.table_stats {
+            stats::write_table_stats(pool, writer, schema, table_name).await?;
+        }
         let ddl: (String, String) =
-            sqlx::query_as(&format!("SHOW CREATE TABLE {}.{}", &schema, &table_name))
+            sqlx::query_as(&format!("SHOW CREATE TABLE {}", qualified_name(schema, table_name)))
                 .fetch_one(pool)
                 .await?;
-        writer.println(format!("{};", ddl.1).as_str());
+        options.definitions.record(schema, table_name, &ddl.1);
+        let source_ddl = ddl.1;
+        let ddl = if options.transcode_latin1.is_some() {
+            transcode::rewrite_ddl(&source_ddl)
+        } else {
+            source_ddl.clone()
+        };
+        let ddl = if options.strip_tablespaces { ddl::strip_tablespaces(&ddl) } else { ddl };
+        let ddl = options.rewrite_ddl(&ddl, schema);
+        let ddl = if options.defer_indexes { ddl::defer_indexes(&ddl).0 } else { ddl };
+        if options.audit_comments {
+            comment_audit::audit_table(table_name, &source_ddl, &ddl);
+        }
+        writer.println(format!("{};", ddl).as_str());
     }
-    Ok(())
+    Ok(count)
+}
+
+//
+// Export only the indexes or only the constraints of each table, as an
+//   ALTER TABLE ... ADD per table, to apply to tables created some other way.
+//   Returns the number of tables written.
+//
+pub async fn export_table_parts(
+    pool: &Pool<MySql>,
+    writer: &mut StdWriter,
+    schema: &str,
+    options: &ExportOptions,
+    part: ddl::TablePart,
+) -> Result<usize, sqlx::Error> {
+    let table_names = base_tables(pool, schema, options).await?;
+    let mut count = 0;
+    for (table_name,) in table_names.iter().filter(|t| options.includes(&t.0)) {
+        let ddl: (String, String) = sqlx::query_as(&format!("SHOW CREATE TABLE {}", qualified_name(schema, table_name)))
+            .fetch_one(pool)
+            .await?;
+        let parts = ddl::table_parts(&options.rewrite_ddl(&ddl.1, schema), part);
+        if parts.is_empty() {
+            continue;
+        }
+        count += 1;
+        if options.comments >= Comments::Minimal {
+            writer.println(format!("-- Extract {} for table {}", part.name(), table_name).as_str());
+        }
+        let adds: Vec<String> = parts.iter().map(|p| format!("ADD {}", p)).collect();
+        writer.println(format!("ALTER TABLE {} {};", options.table_name(table_name), adds.join(", ")).as_str());
+    }
+    Ok(count)
 }
 
 //
@@ -55,407 +643,970 @@ pub async fn export_tables(
 pub async fn export_views(
     pool: &Pool<MySql>,
     writer: &mut StdWriter,
-    schema: &String,
-) -> Result<(), sqlx::Error> {
+    schema: &str,
+    options: &ExportOptions,
+) -> Result<usize, sqlx::Error> {
     // Extract views
     let view_names: Vec<(String,)> =
         sqlx::query_as("select table_name from information_schema.tables where table_schema=? and table_type='VIEW'")
-            .bind(&schema)
+            .bind(schema)
             .fetch_all(pool)
             .await?;
 
-    let view_names = order_views(pool, schema, view_names).await?;
+    // The DDL was already fetched to work out the order
+    let (view_names, view_ddl) = order_views(pool, schema, view_names, options.name_case).await?;
 
-    for name in &view_names {
-        writer.println(format!("-- Extract DDL for view {}", name).as_str());
-        let ddl: (String, String) =
-            sqlx::query_as(&format!("SHOW CREATE VIEW {}.{}", &schema, name))
-                .fetch_one(pool)
-                .await?;
-        writer.println(format!("{};", ddl.1).as_str());
+    let mut count = 0;
+    let replaced = |name: &str| options.replaced_views.iter().any(|v| options.name_case.same(v, name));
+    for name in view_names.iter().filter(|v| options.includes(v) && !replaced(v)) {
+        count += 1;
+        if options.comments >= Comments::Minimal {
+            writer.println(format!("-- Extract DDL for view {}", name).as_str());
+        }
+        writer.println(format!("{};", options.rewrite_ddl(&view_ddl[name], schema)).as_str());
     }
-    Ok(())
+    Ok(count)
 }
 
 pub async fn export_stored_procs(
     pool: &Pool<MySql>,
     writer: &mut StdWriter,
-    schema: &String,
-) -> Result<(), sqlx::Error> {
-    // Extract stored procedures - only support body type of SQL
-    let routines: Vec<(String,)> = sqlx::query_as(
-        "select routine_name from information_schema.routines where routine_schema=? and routine_body='SQL' and routine_type='PROCEDURE'",
-    )
-    .bind(&schema)
-    .fetch_all(pool)
-    .await?;
-    for row in &routines {
-        // get the parameters
-        let (procedure, sql_mode, ddl, character_set, collation, db_collation): (
-            String,
-            String,
-            String,
-            String,
-            String,
-            String,
-        ) = sqlx::query_as(format!("show create procedure {}.{}", &schema, &row.0).as_str())
-            .fetch_one(pool)
-            .await?;
+    schema: &str,
+    options: &ExportOptions,
+) -> Result<usize, sqlx::Error> {
+    // Only routines with SQL bodies are supported
+    let routines = routine::schema_routines(pool, schema, "PROCEDURE", options.escape_mode).await?;
+    let tables = body_tables(pool, schema, options).await?;
+    let mut count = 0;
+    for (name, routine) in routines {
+        let Some(routine) = routine else {
+            if options.comments >= Comments::Minimal {
+                writer.println(format!("-- Skipped stored procedure {}, its definition is hidden from the dumping user", name).as_str());
+            }
+            continue;
+        };
+        count += 1;
 
-        writer.println(format!("-- Extract DDL for stored procedure {}", procedure).as_str());
-        writer.println(format!("-- SQL Mode {}", sql_mode).as_str());
-        writer.println(format!("-- Character Set {}", character_set).as_str());
-        writer.println(format!("-- Collation {}", collation).as_str());
-        writer.println(format!("-- Database Collation {}", db_collation).as_str());
+        if options.comments >= Comments::Minimal {
+            writer.println(format!("-- Extract DDL for stored procedure {}", routine.name).as_str());
+        }
+        write_routine_metadata(writer, options, &routine.sql_mode, &routine.character_set, &routine.collation, &routine.db_collation);
+        write_routine_session(writer, &routine.character_set, &routine.collation);
 
         writer.println("DELIMITER ;;");
-        writer.println(format!("{};;", ddl).as_str());
+        let ddl = options.renames.rewrite_body(&routine.ddl, &tables);
+        writer.println(format!("{};;", options.rewrite_ddl(&ddl, schema)).as_str());
         writer.println("DELIMITER ;");
+        write_routine_session_end(writer);
     }
-    Ok(())
+    Ok(count)
 }
 
 pub async fn export_functions(
     pool: &Pool<MySql>,
     writer: &mut StdWriter,
-    schema: &String,
-) -> Result<(), sqlx::Error> {
-    // Extract stored procedures - only support body type of SQL
-    let routines: Vec<(String,)> = sqlx::query_as(
-        "select routine_name from information_schema.routines where routine_schema=? and routine_body='SQL' and routine_type='FUNCTION'",
-    )
-    .bind(&schema)
-    .fetch_all(pool)
-    .await?;
-    for row in &routines {
-        // get the parameters
-        let (procedure, sql_mode, ddl, character_set, collation, db_collation): (
-            String,
-            String,
-            String,
-            String,
-            String,
-            String,
-        ) = sqlx::query_as(format!("show create function {}.{}", &schema, &row.0).as_str())
-            .fetch_one(pool)
-            .await?;
+    schema: &str,
+    options: &ExportOptions,
+) -> Result<usize, sqlx::Error> {
+    // Only routines with SQL bodies are supported
+    let routines = routine::schema_routines(pool, schema, "FUNCTION", options.escape_mode).await?;
+    let tables = body_tables(pool, schema, options).await?;
+    let mut count = 0;
+    for (name, routine) in routines {
+        let Some(routine) = routine else {
+            if options.comments >= Comments::Minimal {
+                writer.println(format!("-- Skipped function {}, its definition is hidden from the dumping user", name).as_str());
+            }
+            continue;
+        };
+        count += 1;
 
-        writer.println(format!("-- Extract DDL for function {}", procedure).as_str());
-        writer.println(format!("-- SQL Mode {}", sql_mode).as_str());
-        writer.println(format!("-- Character Set {}", character_set).as_str());
-        writer.println(format!("-- Collation {}", collation).as_str());
-        writer.println(format!("-- Database Collation {}", db_collation).as_str());
+        if options.comments >= Comments::Minimal {
+            writer.println(format!("-- Extract DDL for function {}", routine.name).as_str());
+        }
+        write_routine_metadata(writer, options, &routine.sql_mode, &routine.character_set, &routine.collation, &routine.db_collation);
+        write_routine_session(writer, &routine.character_set, &routine.collation);
 
         writer.println("DELIMITER ;;");
-        writer.println(format!("{};;", ddl).as_str());
+        let ddl = options.renames.rewrite_body(&routine.ddl, &tables);
+        writer.println(format!("{};;", options.rewrite_ddl(&ddl, schema)).as_str());
         writer.println("DELIMITER ;");
+        write_routine_session_end(writer);
     }
-    Ok(())
+    Ok(count)
 }
 
+// trigger_name, definer, action_timing, event_manipulation, event_object_table,
+//   action_order, action_statement, sql_mode, character_set_client,
+//   collation_connection, database_collation
+type TriggerRow = (String, String, String, String, String, i64, String, String, String, String, String);
+
 pub async fn export_triggers(
     pool: &Pool<MySql>,
     writer: &mut StdWriter,
-    schema: &String,
-) -> Result<(), sqlx::Error> {
-    // Extract stored procedures - only support body type of SQL
-    let triggers: Vec<(String,)> = sqlx::query_as(
-        "select trigger_name from information_schema.triggers where trigger_schema=?",
+    schema: &str,
+    options: &ExportOptions,
+) -> Result<usize, sqlx::Error> {
+    // Everything SHOW CREATE TRIGGER returns is in information_schema, so the
+    //   statements are rebuilt from a single query
+    let triggers: Vec<TriggerRow> = sqlx::query_as(
+        "select trigger_name, definer, action_timing, event_manipulation, event_object_table, cast(action_order as signed), action_statement, sql_mode, character_set_client, collation_connection, database_collation from information_schema.triggers where trigger_schema=? order by event_object_table, action_timing, event_manipulation, action_order",
     )
-    .bind(&schema)
+    .bind(schema)
     .fetch_all(pool)
     .await?;
-    for row in &triggers {
-        // get the parameters
-        let (trigger, sql_mode, ddl, character_set, collation, db_collation): (
-            String,
-            String,
-            String,
-            String,
-            String,
-            String,
-        ) = sqlx::query_as(format!("show create trigger {}.{}", &schema, &row.0).as_str())
-            .fetch_one(pool)
-            .await?;
-
-        writer.println(format!("-- Extract DDL for trigger {}", trigger).as_str());
-        writer.println(format!("-- SQL Mode {}", sql_mode).as_str());
-        writer.println(format!("-- Character Set {}", character_set).as_str());
-        writer.println(format!("-- Collation {}", collation).as_str());
-        writer.println(format!("-- Database Collation {}", db_collation).as_str());
+    let tables = body_tables(pool, schema, options).await?;
+    // The trigger that fires just before, for the same table, timing and
+    //   event, so the firing order is restored with FOLLOWS (MySQL 5.7.2)
+    let mut previous: Option<&TriggerRow> = None;
+    let mut count = 0;
+    // Triggers of tables left out of the dump would fail to restore
+    for row in triggers.iter().filter(|t| options.includes(&t.4)) {
+        let (trigger, definer, timing, event, table, order, statement, sql_mode, character_set, collation, db_collation) = row;
+        count += 1;
+        let follows = match previous {
+            Some(p) if *order > 1 && (&p.2, &p.3, &p.4) == (timing, event, table) => format!(" FOLLOWS {}", quote_identifier(&p.0)),
+            _ => String::new(),
+        };
+        previous = Some(row);
+        if options.comments >= Comments::Minimal {
+            writer.println(format!("-- Extract DDL for trigger {}", trigger).as_str());
+        }
+        write_routine_metadata(writer, options, sql_mode, character_set, collation, db_collation);
+        write_routine_session(writer, character_set, collation);
 
         writer.println("DELIMITER ;;");
-        writer.println(format!("{};;", ddl).as_str());
+        let ddl = format!(
+            "CREATE DEFINER={} TRIGGER {} {} {} ON {} FOR EACH ROW{} {}",
+            quote_definer(definer),
+            quote_identifier(trigger),
+            timing,
+            event,
+            quote_identifier(table),
+            follows,
+            statement
+        );
+        let ddl = options.renames.rewrite_body(&ddl, &tables);
+        writer.println(format!("{};;", options.rewrite_ddl(&ddl, schema)).as_str());
         writer.println("DELIMITER ;");
+        write_routine_session_end(writer);
+    }
+    Ok(count)
+}
+
+// The session settings a routine or trigger was created with
+fn write_routine_metadata(
+    writer: &mut StdWriter,
+    options: &ExportOptions,
+    sql_mode: &str,
+    character_set: &str,
+    collation: &str,
+    db_collation: &str,
+) {
+    if options.comments < Comments::Normal {
+        return;
+    }
+    writer.println(format!("-- SQL Mode {}", sql_mode).as_str());
+    writer.println(format!("-- Character Set {}", character_set).as_str());
+    writer.println(format!("-- Collation {}", collation).as_str());
+    writer.println(format!("-- Database Collation {}", db_collation).as_str());
+}
+
+//
+// A routine or trigger keeps the character set and collation of the session
+//   that created it, string literals and comparisons in its body use them.
+//   As mysqldump does, the session is switched to them around the CREATE and
+//   switched back after, so the restored routine behaves the same whatever
+//   the restoring client's defaults are.
+//
+fn write_routine_session(writer: &mut StdWriter, character_set: &str, collation: &str) {
+    writer.println("/*!50003 SET @saved_cs_client      = @@character_set_client */ ;");
+    writer.println("/*!50003 SET @saved_cs_results     = @@character_set_results */ ;");
+    writer.println("/*!50003 SET @saved_col_connection = @@collation_connection */ ;");
+    writer.println(format!("/*!50003 SET character_set_client  = {} */ ;", character_set).as_str());
+    writer.println(format!("/*!50003 SET character_set_results = {} */ ;", character_set).as_str());
+    writer.println(format!("/*!50003 SET collation_connection  = {} */ ;", collation).as_str());
+}
+
+fn write_routine_session_end(writer: &mut StdWriter) {
+    writer.println("/*!50003 SET character_set_client  = @saved_cs_client */ ;");
+    writer.println("/*!50003 SET character_set_results = @saved_cs_results */ ;");
+    writer.println("/*!50003 SET collation_connection  = @saved_col_connection */ ;");
+}
+
+// engine, row_format, table_collation, create_options
+type TableDetails = (Option<String>, Option<String>, Option<String>, Option<String>);
+
+// Storage details of a table for verbose dumps
+async fn write_table_details(pool: &Pool<MySql>, writer: &mut StdWriter, schema: &str, table_name: &str) -> Result<(), sqlx::Error> {
+    let details: Option<TableDetails> = sqlx::query_as(
+        "select engine, row_format, table_collation, create_options from information_schema.tables where table_schema=? and table_name=?",
+    )
+    .bind(schema)
+    .bind(table_name)
+    .fetch_optional(pool)
+    .await?;
+    if let Some((engine, row_format, collation, create_options)) = details {
+        writer.println(format!("-- Engine: {}", engine.unwrap_or_default()).as_str());
+        writer.println(format!("-- Row format: {}", row_format.unwrap_or_default()).as_str());
+        writer.println(format!("-- Collation: {}", collation.unwrap_or_default()).as_str());
+        if let Some(create_options) = create_options.filter(|o| !o.is_empty()) {
+            writer.println(format!("-- Create options: {}", create_options).as_str());
+        }
     }
     Ok(())
 }
 
+// user@host as `user`@`host`, the way SHOW CREATE writes it
+pub fn quote_definer(definer: &str) -> String {
+    match definer.rsplit_once('@') {
+        Some((user, host)) => format!("{}@{}", quote_identifier(user), quote_identifier(host)),
+        None => quote_identifier(definer),
+    }
+}
+
 pub async fn export_data_single_core(
     pool: &Pool<MySql>,
     writer: &mut StdWriter,
-    schema: &String,
-    single_row_inserts: bool,
-    skip_unknown_datatypes: bool,
-) -> Result<(), sqlx::Error> {
-    let max_insert_count = if single_row_inserts { 1 } else { 100 };
+    schema: &str,
+    options: &ExportOptions,
+) -> Result<HashMap<String, u64>, sqlx::Error> {
+    let max_insert_count = if options.single_row_inserts { 1 } else { 100 };
 
     // Grab all of the tables from the selected schema
-    let table_names: Vec<(String,)> =
-        sqlx::query_as("select table_name from information_schema.tables where table_schema=? and table_type='BASE TABLE'")
-            .bind(&schema)
-            .fetch_all(pool)
-            .await?;
+    let table_names = base_tables(pool, schema, options).await?;
 
-    'tables: for row in &table_names {
-        writer.println(format!("-- Extracting data for {}", row.0).as_str());
-        let mut count = 0;
-        // query table
-        let data_rows = sqlx::query::<_>(&format!("select * from {}.{}", &schema, &row.0))
-            .fetch_all(pool)
-            .await?;
-        if data_rows.len() == 0 {
-            continue 'tables;
-        }
-        let column_names = compute_column_name(data_rows.get(0).unwrap().columns());
-        for i in 0..data_rows.len() {
-            let data = data_rows.get(i);
-            if data.is_none() {
-                continue;
-            }
-            let data = data.unwrap();
-            if data.is_empty() {
-                continue;
-            }
-            if count % max_insert_count == 0 {
-                writer
-                    .print(format!("insert into `{}` ({}) values(", row.0, column_names).as_str());
-            }
-
-            let cols = data.columns().len();
-            for i in 0..cols - 1 {
-                let value = cast_data(&data, i, skip_unknown_datatypes);
-                if let Some(value) = value {
-                    writer.print(format!("{},", value).as_str());
-                } else {
-                    writer.print("NULL,");
-                }
-            }
+    let mut rows = HashMap::new();
+    for row in table_names.iter().filter(|t| options.includes_data(&t.0)) {
+        let count = export_single_table(schema, &row.0, pool, max_insert_count, options, options.batch_bytes(1), &mut |batch| {
+            writer.print(batch.as_str())
+        })
+        .await?;
+        rows.insert(row.0.clone(), count);
+    }
 
-            let value = cast_data(&data, cols - 1, skip_unknown_datatypes);
-            if let Some(value) = value {
-                writer.print(format!("{}", value).as_str());
-            } else {
-                writer.print("NULL");
-            }
+    Ok(rows)
+}
 
-            count = count + 1;
-            if count % max_insert_count == 0 {
-                writer.print(");\n");
-            } else {
-                if i >= data_rows.len() - 1 {
-                    writer.println(");");
-                } else {
-                    writer.print("),\n\t(");
-                }
+//
+// Export the queries dumped as tables, each as a CREATE TABLE for its
+//   result's columns followed by its rows. Returns the number of rows.
+//
+pub async fn export_queries(
+    pool: &Pool<MySql>,
+    writer: &mut StdWriter,
+    schema: &str,
+    options: &ExportOptions,
+    include_ddl: bool,
+    include_data: bool,
+) -> Result<u64, sqlx::Error> {
+    let max_insert_count = if options.single_row_inserts { 1 } else { 100 };
+    let mut rows = 0;
+    for (name, query) in &options.queries {
+        if include_ddl {
+            if options.comments >= Comments::Minimal {
+                writer.println(format!("-- Extract DDL for query {}", name).as_str());
             }
+            writer.println(format!("{};", query.create_table(&options.table_name(name))).as_str());
+        }
+        if include_data {
+            rows += export_single_table(schema, name, pool, max_insert_count, options, options.batch_bytes(1), &mut |batch| {
+                writer.print(batch.as_str())
+            })
+            .await?;
         }
     }
-
-    Ok(())
+    Ok(rows)
 }
 
+//
+// Export the rows of one table as insert statements. Rows are streamed from
+//   the server and the statements passed to `emit` in batches of about
+//   `batch_bytes`, always ending on a complete statement. Returns the number
+//   of rows exported.
+//
 pub async fn export_single_table(
-    schema: &String,
-    table_name: &String,
+    schema: &str,
+    table_name: &str,
     pool: &Pool<MySql>,
     max_insert_count: i32,
-    skip_unknown_datatypes: bool,
-) -> Result<String, sqlx::Error> {
-    let mut output = String::with_capacity(MAX_BUFFER_SIZE);
-    output.push_str(format!("-- Extracting data for {}", table_name).as_str());
+    options: &ExportOptions,
+    batch_bytes: usize,
+    emit: &mut dyn FnMut(String),
+) -> Result<u64, sqlx::Error> {
+    let mut output = String::with_capacity(batch_bytes.min(MAX_BUFFER_SIZE));
+    if options.comments >= Comments::Minimal {
+        output.push_str(format!("-- Extracting data for {}\n", table_name).as_str());
+    }
     let mut count = 0;
+
+    let table_hooks = options.hooks(table_name);
+    for hook in table_hooks.iter().flat_map(|h| &h.before) {
+        hooks::run_table_hook(pool, schema, table_name, "before", hook).await?;
+    }
+    let source = table_hooks.and_then(|h| h.source.as_deref()).unwrap_or(table_name);
+    let (table_columns, renders) = column_renders(pool, schema, table_name, source, options).await?;
+
+    // A query has no table to checksum or take indexes from
+    let is_query = options.queries.contains_key(table_name);
+    // CHECKSUM TABLE covers every row, it is only taken when they are all
+    //   written as read
+    let partial = options.row_filters.contains_key(table_name) || options.transformer.is_some();
+    let take_checksum = options.checksum && !is_query && !partial;
+
+    if !is_query {
+        options.definitions.remember(pool, schema, source).await?;
+    }
+
+    // The rows are read in a transaction, rolled back if the export fails,
+    //   with a consistent snapshot when they are checksummed or fingerprinted
+    //   so those are of the rows written
+    let fingerprints = options.fingerprints.as_ref().filter(|_| !is_query);
+    let mut transaction = pool.begin().await?;
+    if fingerprints.is_some() || take_checksum {
+        sqlx::query("START TRANSACTION WITH CONSISTENT SNAPSHOT").execute(&mut *transaction).await?;
+    }
+    let checksum = if take_checksum {
+        Some(stats::table_checksum(&mut transaction, schema, source).await?)
+    } else {
+        None
+    };
+    if let Some(fingerprints) = fingerprints {
+        let columns: Vec<String> = table_columns.iter().map(|c| c.0.clone()).collect();
+        let target_columns = columns.iter().map(|c| options.renames.column(table_name, c)).collect();
+        let from = match options.row_selection(table_name) {
+            clause if clause.is_empty() => qualified_name(schema, source),
+            clause => format!("(select * from {}{}) as dbdump_rows", qualified_name(schema, source), clause),
+        };
+        fingerprints
+            .record(&mut transaction, &from, &columns, &options.renames.table(table_name), target_columns)
+            .await?;
+    }
+
     // query table
-    let data_rows = sqlx::query::<>(&format!("select * from {}.{}", &schema, &table_name))
-        .fetch_all(pool).await?;
-    if data_rows.len() == 0 {
-        return Ok(output);
-    }
-    
-    let column_names = compute_column_name(data_rows.get(0).unwrap().columns());
-    for i in 0..data_rows.len() {
-        let data = data_rows.get(i);
-        if data.is_none() {
-            continue;
-        }
-        let data = data.unwrap();
+    let query = select_rows(schema, table_name, source, &table_columns, &renders, options);
+    let mut data_rows = sqlx::query::<>(&query).fetch(&mut *transaction);
+    let mut progress = match options.progress {
+        Some(interval) => Some(TableProgress::new(pool, schema, table_name, interval).await?),
+        None => None,
+    };
+
+    let mut column_names = None;
+    let mut column_list: Option<Vec<String>> = None;
+    let mut in_statement = false;
+    // Where the statement being built starts in output
+    let mut statement_start = 0;
+    let commit_rows = if options.transactional_load == TransactionalLoad::Off { 0 } else { options.commit_rows };
+    while let Some(data) = data_rows.next().await {
+        let data = data?;
         if data.is_empty() {
             continue;
         }
-        if count % max_insert_count == 0 {
-            output.push_str(format!("insert into `{}` ({}) values(", table_name, column_names).as_str());
+        let values: Vec<Option<String>> = match &options.transformer {
+            None => renders.iter().enumerate().map(|(i, render)| render_value(&data, i, render, options)).collect::<Result<_, _>>()?,
+            Some(transformer) => {
+                let mut values: Vec<Value> = renders
+                    .iter()
+                    .enumerate()
+                    .map(|(i, render)| typed_value(&data, i, render, options))
+                    .collect::<Result<_, _>>()?;
+                let columns = column_list.get_or_insert_with(|| table_columns.iter().map(|c| c.0.clone()).collect());
+                if !transformer.0.transform(table_name, columns, &mut values) {
+                    continue;
+                }
+                values.iter().map(|v| v.to_sql(options.escape_mode)).collect()
+            }
+        };
+
+        if count == 0 && options.transactional_load == TransactionalLoad::Table {
+            output.push_str("START TRANSACTION;\n");
+        }
+        let column_names = column_names.get_or_insert_with(|| compute_column_name(table_name, data.columns(), options));
+        if in_statement {
+            output.push_str("),\n\t(");
+        } else {
+            statement_start = output.len();
+            output.push_str(format!("insert into {} ({}) values(", options.table_name(table_name), column_names).as_str());
         }
 
-        let cols = data.columns().len();
-        for i in 0..cols - 1 {
-            let value = cast_data(&data, i, skip_unknown_datatypes);
+        let cols = values.len();
+        for (i, value) in values.into_iter().enumerate() {
             if let Some(value) = value {
-                output.push_str(format!("{},", value).as_str());
+                output.push_str(value.as_str());
             } else {
-                output.push_str("NULL,");
+                output.push_str("NULL");
+            }
+            if i < cols - 1 {
+                output.push(',');
             }
         }
 
-        let value = cast_data(&data, cols - 1, skip_unknown_datatypes);
-        if let Some(value) = value {
-            output.push_str(format!("{}", value).as_str());
-        } else {
-            output.push_str("NULL");
+        count += 1;
+        if let Some(progress) = progress.as_mut() {
+            progress.row(table_name, count as u64);
         }
-
-        count = count + 1;
-        if count % max_insert_count == 0 {
+        in_statement = count % max_insert_count != 0;
+        // Statements never span a commit
+        let commit = commit_rows > 0 && (count as u64).is_multiple_of(commit_rows);
+        in_statement = in_statement && !commit;
+        if !in_statement {
             output.push_str(");\n");
-        } else {
-            if i >= data_rows.len() - 1 {
-                output.push_str(");\n");
-            } else {
-                output.push_str("),\n\t(");
+            if options.check_sql {
+                sqlcheck::check_statement(table_name, &output[statement_start..], options.escape_mode);
+            }
+            if commit {
+                output.push_str("COMMIT;\nSTART TRANSACTION;\n");
+            }
+            if output.len() >= batch_bytes {
+                emit(std::mem::take(&mut output));
             }
         }
     }
-    Ok(output)
+    drop(data_rows);
+    transaction.commit().await?;
+    if !is_query {
+        options.definitions.check(pool, schema, source).await?;
+    }
+    if in_statement {
+        output.push_str(");\n");
+        if options.check_sql {
+            sqlcheck::check_statement(table_name, &output[statement_start..], options.escape_mode);
+        }
+    }
+    if count > 0 && options.transactional_load == TransactionalLoad::Table {
+        output.push_str("COMMIT;\n");
+    }
+    if options.comments >= Comments::Verbose {
+        output.push_str(format!("-- {} rows exported for {}\n", count, table_name).as_str());
+    }
+    if options.defer_indexes && options.compat != Compat::ClickHouse && !is_query {
+        output.push_str(&deferred_indexes(pool, schema, table_name, options).await?);
+    }
+    if options.auto_increment && options.compat != Compat::ClickHouse && !is_query {
+        output.push_str(&auto_increment(pool, schema, table_name, options).await?);
+    }
+    if let Some(checksum) = checksum {
+        output.push_str(&stats::checksum_comment(table_name, checksum));
+    } else if options.checksum && !is_query {
+        output.push_str(&stats::partial_checksum_comment(table_name));
+    }
+    if !output.is_empty() {
+        emit(output);
+    }
+    for hook in table_hooks.iter().flat_map(|h| &h.after) {
+        hooks::run_table_hook(pool, schema, table_name, "after", hook).await?;
+    }
+    Ok(count as u64)
+}
+
+// The ALTER TABLE adding the indexes export_tables left out of a table
+async fn deferred_indexes(pool: &Pool<MySql>, schema: &str, table_name: &str, options: &ExportOptions) -> Result<String, sqlx::Error> {
+    let ddl: (String, String) = sqlx::query_as(&format!("SHOW CREATE TABLE {}", qualified_name(schema, table_name)))
+        .fetch_one(pool)
+        .await?;
+    let (_, indexes) = ddl::defer_indexes(&options.rewrite_ddl(&ddl.1, schema));
+    if indexes.is_empty() {
+        return Ok(String::new());
+    }
+    let adds: Vec<String> = indexes.iter().map(|i| format!("ADD {}", i)).collect();
+    Ok(format!("ALTER TABLE {} {};\n", options.table_name(table_name), adds.join(", ")))
+}
+
+//
+// The ALTER TABLE moving a table's AUTO_INCREMENT just past the largest value
+//   of its column in the source, read when the table's rows are. A target
+//   loaded with only some of the rows then never hands out an id the source
+//   already used, whatever the CREATE TABLE said.
+//
+async fn auto_increment(pool: &Pool<MySql>, schema: &str, table_name: &str, options: &ExportOptions) -> Result<String, sqlx::Error> {
+    let column: Option<(String,)> = sqlx::query_as(
+        "select column_name from information_schema.columns where table_schema=? and table_name=? and extra like '%auto_increment%'",
+    )
+    .bind(schema)
+    .bind(table_name)
+    .fetch_optional(pool)
+    .await?;
+    let Some((column,)) = column else {
+        return Ok(String::new());
+    };
+    let (next,): (String,) = sqlx::query_as(&format!(
+        "select cast(coalesce(max({}), 0) + 1 as char) from {}",
+        quote_identifier(&column),
+        qualified_name(schema, table_name)
+    ))
+    .fetch_one(pool)
+    .await?;
+    Ok(format!("ALTER TABLE {} AUTO_INCREMENT = {};\n", options.table_name(table_name), next))
+}
+
+//
+// Progress through a single table, logged every so many rows or seconds so a
+//   long export can be told apart from a hung one. The estimate is the
+//   engine's row count for the whole table.
+//
+struct TableProgress {
+    interval: ProgressInterval,
+    estimate: u64,
+    started: Instant,
+    last_logged: Instant,
+}
+
+impl TableProgress {
+    async fn new(pool: &Pool<MySql>, schema: &str, table_name: &str, interval: ProgressInterval) -> Result<Self, sqlx::Error> {
+        let estimate: Option<(Option<u64>,)> = sqlx::query_as(
+            "select cast(table_rows as unsigned) from information_schema.tables where table_schema=? and table_name=?",
+        )
+        .bind(schema)
+        .bind(table_name)
+        .fetch_optional(pool)
+        .await?;
+        let now = Instant::now();
+        Ok(TableProgress {
+            interval,
+            estimate: estimate.and_then(|e| e.0).unwrap_or(0),
+            started: now,
+            last_logged: now,
+        })
+    }
+
+    fn row(&mut self, table_name: &str, rows: u64) {
+        let due = match self.interval {
+            ProgressInterval::Rows(every) => rows.is_multiple_of(every),
+            // Checking the clock on every row is wasted effort
+            ProgressInterval::Seconds(every) => rows.is_multiple_of(1000) && self.last_logged.elapsed().as_secs() >= every,
+        };
+        if !due {
+            return;
+        }
+        self.last_logged = Instant::now();
+        let elapsed = self.started.elapsed().as_secs_f64().max(0.001);
+        let percent = match self.estimate {
+            0 => "?".to_string(),
+            estimate => (rows * 100 / estimate).min(100).to_string(),
+        };
+        Logger::info(format!(
+            "Exporting {}: {} rows (~{}% of ~{}), {:.0} rows/s",
+            table_name,
+            rows,
+            percent,
+            self.estimate,
+            rows as f64 / elapsed
+        ));
+    }
+}
+
+// column_name, data_type, character_set_name
+type TableColumn = (String, String, Option<String>);
+
+// Work out how each column is selected and rendered before querying, some
+//   columns need to be fetched through a conversion function
+async fn column_renders(
+    pool: &Pool<MySql>,
+    schema: &str,
+    table_name: &str,
+    source: &str,
+    options: &ExportOptions,
+) -> Result<(Vec<TableColumn>, Vec<ColumnRender>), sqlx::Error> {
+    if let Some(query) = options.queries.get(table_name) {
+        let table_columns = query.columns.clone();
+        let renders = table_columns
+            .iter()
+            .map(|(name, data_type, charset)| ColumnRender::for_column(table_name, name, data_type, charset.as_deref(), options))
+            .collect();
+        return Ok((table_columns, renders));
+    }
+    let cached = options
+        .cached_catalog(schema)
+        .and_then(|c| c.tables.iter().find(|t| options.name_case.same(&t.name, source)));
+    let table_columns: Vec<TableColumn> = match cached {
+        Some(table) => table
+            .columns
+            .iter()
+            .map(|c| (c.name.clone(), c.data_type.clone(), c.character_set.clone()))
+            .collect(),
+        None => sqlx::query_as("select column_name, data_type, character_set_name from information_schema.columns where table_schema=? and table_name=? order by ordinal_position")
+            .bind(schema)
+            .bind(source)
+            .fetch_all(pool)
+            .await?,
+    };
+    let renders: Vec<ColumnRender> = table_columns
+        .iter()
+        .map(|(name, data_type, charset)| ColumnRender::for_column(table_name, name, data_type, charset.as_deref(), options))
+        .collect();
+    Ok((table_columns, renders))
+}
+
+fn select_rows(
+    schema: &str,
+    table_name: &str,
+    source: &str,
+    table_columns: &[TableColumn],
+    renders: &[ColumnRender],
+    options: &ExportOptions,
+) -> String {
+    let select_list = table_columns
+        .iter()
+        .zip(renders)
+        .map(|(c, render)| render.select_expression(&c.0))
+        .collect::<Vec<String>>()
+        .join(",");
+    let tag = options.query_tag.as_ref().map(|t| format!("/* {} */ ", t)).unwrap_or_default();
+    let from = match options.queries.get(table_name) {
+        Some(query) => format!("({}) as dbdump_query", query.sql),
+        None => qualified_name(schema, source),
+    };
+    format!("{}select {} from {}{}", tag, select_list, from, options.row_clause(table_name))
+}
+
+// The statement export_single_table reads a table's rows with
+pub async fn data_query(pool: &Pool<MySql>, schema: &str, table_name: &str, options: &ExportOptions) -> Result<String, sqlx::Error> {
+    let source = options.hooks(table_name).and_then(|h| h.source.as_deref()).unwrap_or(table_name);
+    let (table_columns, renders) = column_renders(pool, schema, table_name, source, options).await?;
+    Ok(select_rows(schema, table_name, source, &table_columns, &renders, options))
+}
 
+//
+// The rows of a table the `clause` after its name picks, e.g. a where and
+//   order by, as the SQL literals export_single_table would write for them.
+//   Returns the column names along with the rows.
+//
+pub async fn row_literals(
+    pool: &Pool<MySql>,
+    schema: &str,
+    table_name: &str,
+    clause: &str,
+    options: &ExportOptions,
+) -> Result<(Vec<String>, Vec<Vec<Option<String>>>), sqlx::Error> {
+    let (table_columns, renders) = column_renders(pool, schema, table_name, table_name, options).await?;
+    let select_list = table_columns
+        .iter()
+        .zip(&renders)
+        .map(|(c, render)| render.select_expression(&c.0))
+        .collect::<Vec<String>>()
+        .join(",");
+    let query = format!("select {} from {} {}", select_list, qualified_name(schema, table_name), clause);
+    let rows = sqlx::query(&query)
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|row| renders.iter().enumerate().map(|(i, render)| render_value(row, i, render, options)).collect())
+        .collect::<Result<_, _>>()?;
+    Ok((table_columns.into_iter().map(|c| c.0).collect(), rows))
 }
 
 pub async fn export_data(
     pool: &Pool<MySql>,
     writer: &mut StdWriter,
     thread_count: usize,
-    schema: &String,
-    single_row_inserts: bool,
-    skip_unknown_datatypes: bool,
-) -> Result<(), sqlx::Error> {
-    if thread_count == 1 {
-        return export_data_single_core(pool, writer, schema, single_row_inserts, skip_unknown_datatypes).await;
+    schema: &str,
+    options: &ExportOptions,
+) -> Result<u64, sqlx::Error> {
+    if options.transactional_load == TransactionalLoad::Dump {
+        writer.println("START TRANSACTION;");
     }
-    let max_insert_count = if single_row_inserts { 1 } else { 100 };
+    let rows = if thread_count == 1 {
+        export_data_single_core(pool, writer, schema, options).await?
+    } else {
+        export_data_parallel(pool, writer, thread_count, schema, options).await?
+    };
+    if options.transactional_load == TransactionalLoad::Dump {
+        writer.println("COMMIT;");
+    }
+    drift::check_row_drift(pool, schema, &rows, options).await?;
+    Ok(rows.values().sum())
+}
 
-    // Grab all of the tables from the selected schema
-    let mut table_names: Vec<(String,)> =
-        sqlx::query_as("select table_name from information_schema.tables where table_schema=? and table_type='BASE TABLE'")
-            .bind(&schema)
-            .fetch_all(pool)
-            .await?;
+// Shared by the threads of export_data_parallel
+#[derive(Default)]
+struct ParallelProgress {
+    // Work items done and their estimated bytes
+    items: usize,
+    bytes: u64,
+    rows: HashMap<String, u64>,
+    // The first failure, the dump is abandoned after it
+    error: Option<sqlx::Error>,
+}
+
+async fn export_data_parallel(
+    pool: &Pool<MySql>,
+    writer: &mut StdWriter,
+    thread_count: usize,
+    schema: &str,
+    options: &ExportOptions,
+) -> Result<HashMap<String, u64>, sqlx::Error> {
+    let max_insert_count = if options.single_row_inserts { 1 } else { 100 };
 
-    table_names.reverse();
-    let work_queue= Arc::new(Mutex::new(table_names));
-    let vec = Vec::<String>::new();
-    let result_queue = Arc::new(Mutex::new(vec));
+    // Largest work first, the queue is popped from the end
+    let mut plan = plan::plan_export(pool, schema, options).await?;
+    let total_bytes: u64 = plan.iter().map(|w| w.bytes).sum::<u64>().max(1);
+    let total_items = plan.len();
+    plan.reverse();
+    let work_queue = Arc::new(Mutex::new(plan));
+    let progress = Arc::new(Mutex::new(ParallelProgress::default()));
+    // Batches of statements go to this thread for writing, the budget holds
+    //   the export threads back when the writer can't keep up
+    let budget = Arc::new(MemoryBudget::new(options.buffer_bytes()));
+    let batch_bytes = options.batch_bytes(thread_count);
+    let (sender, receiver) = mpsc::channel::<String>();
     let mut handles = vec![];
 
-    for i in 0..thread_count {
+    for _ in 0..thread_count {
         let thread_work_queue = work_queue.clone();
-        let result_queue = result_queue.clone();
+        let sender = sender.clone();
+        let budget = budget.clone();
+        let progress = progress.clone();
         let pool_own = pool.clone();
-        let schema = schema.clone();
+        let schema = schema.to_string();
+        let options = options.clone();
         let handle = thread::spawn(move || {
+            let mut emit = |batch: String| {
+                budget.acquire(batch.len());
+                let _ = sender.send(batch);
+            };
             loop {
-                let mut work: Option<(String, )> =None;
-                {
-                    let mut work_queue = thread_work_queue.lock().unwrap();
-                    work = work_queue.pop();
-                }
+                let work: Option<plan::WorkItem> = thread_work_queue.lock().unwrap().pop();
                 if work.is_none() {
                     return;
                 }
-                let table_name = work.unwrap().0;
-                let result = block_on(export_single_table(&schema, &table_name, &pool_own, max_insert_count, skip_unknown_datatypes));
-                if result.is_ok() {
-                    let mut result_queue = result_queue.lock().unwrap();
-                    result_queue.push(result.ok().unwrap());
+                let work = work.unwrap();
+                let result = match &work.chunk {
+                    Some(chunk) => {
+                        // The checksum comment, which can't be taken for a
+                        //   part, and the deferred indexes and AUTO_INCREMENT
+                        //   are only written once, after the last part
+                        let mut options = options.clone();
+                        options.row_filters.insert(work.table.clone(), chunk.clone());
+                        options.checksum = options.checksum && work.part == work.parts;
+                        options.defer_indexes = options.defer_indexes && work.part == work.parts;
+                        options.auto_increment = options.auto_increment && work.part == work.parts;
+                        block_on(export_single_table(&schema, &work.table, &pool_own, max_insert_count, &options, batch_bytes, &mut emit))
+                    }
+                    None => block_on(export_single_table(&schema, &work.table, &pool_own, max_insert_count, &options, batch_bytes, &mut emit)),
+                };
+                let mut progress = progress.lock().unwrap();
+                match result {
+                    Ok(rows) => *progress.rows.entry(work.table.clone()).or_default() += rows,
+                    Err(e) => {
+                        // The dump is incomplete, the other threads finish
+                        //   what they are on and take no more work
+                        Logger::error(format!("Unable to export {}: {}", work.describe(), e));
+                        thread_work_queue.lock().unwrap().clear();
+                        progress.error.get_or_insert(e);
+                        continue;
+                    }
                 }
+                progress.items += 1;
+                progress.bytes += work.bytes;
+                Logger::info(format!(
+                    "Exported {} ({}/{}, ~{}%)",
+                    work.describe(),
+                    progress.items,
+                    total_items,
+                    progress.bytes * 100 / total_bytes
+                ));
             }
         });
         handles.push(handle);
     }
-    println!("Waiting for threads to finish. {}", handles.len());
+    drop(sender);
 
+    // Ends once every thread has finished and dropped its sender
+    for batch in receiver {
+        writer.print(batch.as_str());
+        budget.release(batch.len());
+    }
     for handle in handles {
         handle.join().unwrap();
     }
-    let result_queue = result_queue.lock().unwrap();
-    println!("Read {} arrays", result_queue.len());
-    for str in result_queue.iter() {
-        writer.println(str.as_str());
+
+    let mut progress = progress.lock().unwrap();
+    match progress.error.take() {
+        Some(e) => Err(e),
+        None => Ok(std::mem::take(&mut progress.rows)),
     }
-    
-    Ok(())
 }
 
-pub fn cast_data(row: &MySqlRow, index: usize, skip_unknown_datatypes: bool) -> Option<String> {
+fn render_value(row: &MySqlRow, index: usize, render: &ColumnRender, options: &ExportOptions) -> Result<Option<String>, sqlx::Error> {
+    Ok(match render {
+        ColumnRender::Default => cast_data(row, index, options)?,
+        ColumnRender::Transcode(encoding) => transcode_data(row, index, *encoding, options.escape_mode),
+        ColumnRender::Uuid(format) => row.try_get::<Vec<u8>, usize>(index).ok().map(|b| uuid::render(&b, *format)),
+        ColumnRender::Spatial => spatial_data(row, index, options),
+        ColumnRender::Vector(format) => row.try_get_unchecked::<String, usize>(index).ok().map(|v| format.render(&v)),
+        ColumnRender::Time => row.try_get_unchecked::<String, usize>(index).ok().map(|v| format!("'{}'", v)),
+    })
+}
+
+// The value handed to a RowTransformer, decoded where it is a plain number
+//   or string and otherwise the rendered literal
+fn typed_value(row: &MySqlRow, index: usize, render: &ColumnRender, options: &ExportOptions) -> Result<Value, sqlx::Error> {
+    let decoded = match render {
+        ColumnRender::Default => match row.column(index).type_info().to_string().as_str() {
+            "TINYINT" | "SMALLINT" | "INT" | "BIGINT" => row.try_get::<i64, usize>(index).ok().map(Value::Int),
+            "TINYINT UNSIGNED" | "SMALLINT UNSIGNED" | "INT UNSIGNED" | "BIGINT UNSIGNED" => {
+                row.try_get::<u64, usize>(index).ok().map(Value::UInt)
+            }
+            "FLOAT" => row.try_get::<f32, usize>(index).ok().map(|n| Value::Float(n as f64)),
+            "DOUBLE" => row.try_get::<f64, usize>(index).ok().map(Value::Float),
+            "CHAR" | "VARCHAR" | "TEXT" | "ENUM" => row.try_get::<String, usize>(index).ok().map(Value::Text),
+            _ => None,
+        },
+        ColumnRender::Transcode(encoding) => row
+            .try_get::<Vec<u8>, usize>(index)
+            .ok()
+            .map(|bytes| Value::Text(transcode::decode(&bytes, *encoding))),
+        _ => None,
+    };
+    if let Some(value) = decoded {
+        return Ok(value);
+    }
+    Ok(match render_value(row, index, render, options)? {
+        Some(literal) => Value::Sql(literal),
+        None => Value::Null,
+    })
+}
+
+// Decode the raw bytes of a latin1 column and quote them as a utf8 string
+fn transcode_data(row: &MySqlRow, index: usize, encoding: SourceEncoding, mode: EscapeMode) -> Option<String> {
+    let bytes = row.try_get::<Vec<u8>, usize>(index).ok()?;
+    Some(quote_string(&transcode::decode(&bytes, encoding), mode))
+}
+
+// Turn a SRID:WKT value into ST_GeomFromText('WKT', SRID). MySQL 8 rejects
+//   geometry without the SRID when the column is SRID restricted. ClickHouse
+//   columns hold the WKT as a string.
+fn spatial_data(row: &MySqlRow, index: usize, options: &ExportOptions) -> Option<String> {
+    let value = row.try_get_unchecked::<String, usize>(index).ok()?;
+    let (srid, wkt) = value.split_once(':')?;
+    if options.compat == Compat::ClickHouse {
+        return Some(quote_string(wkt, options.escape_mode));
+    }
+    Some(format!("ST_GeomFromText({}, {})", quote_string(wkt, options.escape_mode), srid))
+}
+
+pub fn cast_data(row: &MySqlRow, index: usize, options: &ExportOptions) -> Result<Option<String>, sqlx::Error> {
     let col = row.column(index);
     let type_name = col.type_info().to_string();
+    let mode = options.escape_mode;
+
+    if let Some(handler) = options.type_handlers.get(&type_name) {
+        return Ok(handler(row, index, mode));
+    }
+    if let Some(rule) = options.type_overrides.iter().find(|r| r.type_name == type_name) {
+        return Ok(rule.mode.render(row, index, mode));
+    }
 
-    match type_name.as_str() {
-        "BOOLEAN" => to_string(row.try_get::<bool, usize>(index), false),
-        "TINYINT" => to_string(row.try_get::<i8, usize>(index), false),
-        "BIT" => to_string(row.try_get::<bool, usize>(index), false),
-        "SMALLINT" => to_string(row.try_get::<i16, usize>(index), false),
-        "INT" => to_string(row.try_get::<i32, usize>(index), false),
-        "BIGINT" => to_string(row.try_get::<i64, usize>(index), false),
-        "TINYINT UNSIGNED" => to_string(row.try_get::<u8, usize>(index), false),
-        "SMALLINT UNSIGNED" => to_string(row.try_get::<u16, usize>(index), false),
-        "INT UNSIGNED" => to_string(row.try_get::<u32, usize>(index), false),
-        "BIGINT UNSIGNED" => to_string(row.try_get::<u64, usize>(index), false),
-        "FLOAT" => to_string(row.try_get::<f32, usize>(index), false),
-        "DOUBLE" => to_string(row.try_get::<f64, usize>(index), false),
-        "CHAR" => to_string(row.try_get::<String, usize>(index), true),
-        "VARCHAR" => to_string(row.try_get::<String, usize>(index), true),
-        "TEXT" => to_string(row.try_get::<String, usize>(index), true),
+    let value = match type_name.as_str() {
+        "BOOLEAN" => to_string(row.try_get::<bool, usize>(index)),
+        "TINYINT" => to_string(row.try_get::<i8, usize>(index)),
+        "BIT" => to_string(row.try_get::<bool, usize>(index)),
+        "SMALLINT" => to_string(row.try_get::<i16, usize>(index)),
+        "INT" => to_string(row.try_get::<i32, usize>(index)),
+        "BIGINT" => to_string(row.try_get::<i64, usize>(index)),
+        "TINYINT UNSIGNED" => to_string(row.try_get::<u8, usize>(index)),
+        "SMALLINT UNSIGNED" => to_string(row.try_get::<u16, usize>(index)),
+        "INT UNSIGNED" => to_string(row.try_get::<u32, usize>(index)),
+        "BIGINT UNSIGNED" => to_string(row.try_get::<u64, usize>(index)),
+        "FLOAT" => to_string(row.try_get::<f32, usize>(index)),
+        "DOUBLE" => to_string(row.try_get::<f64, usize>(index)),
+        "CHAR" => to_quoted_string(row.try_get::<String, usize>(index), mode),
+        "VARCHAR" => to_quoted_string(row.try_get::<String, usize>(index), mode),
+        "TEXT" => to_quoted_string(row.try_get::<String, usize>(index), mode),
         "TIMESTAMP" => to_date_string(row.try_get::<DateTime<Utc>, usize>(index)),
         "DATETIME" => to_date_string(row.try_get::<NaiveDateTime, usize>(index)),
         "DATE" => to_date_string(row.try_get::<NaiveDate, usize>(index)),
         "TIME" => to_date_string(row.try_get::<NaiveTime, usize>(index)),
-        "DECIMAL" => to_string(row.try_get::<BigDecimal, usize>(index), false),
-        "ENUM" => to_string(row.try_get::<String, usize>(index), true),
-        // "AddOtherTypesHere" => to_string(row.try_get::<i64, usize>(index), false),
+        "DECIMAL" => to_string(row.try_get::<BigDecimal, usize>(index)),
+        "ENUM" => to_quoted_string(row.try_get::<String, usize>(index), mode),
+        "JSON" => to_json_string(row.try_get_unchecked::<String, usize>(index), options.json_format, mode),
+        // "AddOtherTypesHere" => to_string(row.try_get::<i64, usize>(index)),
         // Add support for Binary data
-        "VARBINARY" => None,
-        "BINARY" => None,
-        "BLOB" => None,
+        "VARBINARY" => return Ok(None),
+        "BINARY" => return Ok(None),
+        "BLOB" => return Ok(None),
 
         _ => {
-            if skip_unknown_datatypes {
-                None
+            if options.skip_unknown_datatypes {
+                return Ok(None)
             } else {
                 panic!("The database type {} is not implemented in this version of dbdump. Please try to download a more recent version or report a bug if you are on the most recent version", type_name)
             }
         }
+    };
+    if value.is_none() {
+        return undecodable_data(row, index, &type_name, options);
     }
+    Ok(value)
 }
 
-fn quote(str: String) -> String {
-    format!(
-        "'{}'",
-        str.replace("'", "''")
-            .replace("\\", "\\\\")
-            .replace("\n", "\\n")
-            .replace("\r", "\\r")
-    )
+// Columns already warned about, so a bad column is only reported once
+static UNDECODABLE_COLUMNS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+//
+// A value that is there but doesn't decode as its type, e.g. a zero date or
+//   an ENUM value outside the list, both of which lax sql_modes let in. The
+//   raw value is written instead, or with --strict-data the table's export
+//   fails with an error.
+//
+fn undecodable_data(row: &MySqlRow, index: usize, type_name: &str, options: &ExportOptions) -> Result<Option<String>, sqlx::Error> {
+    if row.try_get_raw(index).map_or(true, |v| v.is_null()) {
+        return Ok(None);
+    }
+    let name = row.column(index).name();
+    if options.strict_data {
+        return Err(sqlx::Error::Decode(
+            format!("Column {} holds a value that isn't a valid {}, stopping because of --strict-data", name, type_name).into(),
+        ));
+    }
+    let Ok(raw) = row.try_get_unchecked::<Vec<u8>, usize>(index) else {
+        return Ok(None);
+    };
+    let value = match type_name {
+        "DATE" | "DATETIME" | "TIMESTAMP" => binary_datetime(&raw, type_name == "DATE"),
+        _ => Some(quote_string(&String::from_utf8_lossy(&raw), options.escape_mode)),
+    };
+    let mut warned = UNDECODABLE_COLUMNS.lock().unwrap();
+    if !warned.iter().any(|w| w == name) {
+        warned.push(name.to_string());
+        Logger::warn(format!(
+            "Column {} holds a value that isn't a valid {}, writing it as {}",
+            name,
+            type_name,
+            value.as_deref().unwrap_or("NULL")
+        ));
+    }
+    Ok(value)
+}
+
+// A date or datetime in the binary protocol: a length byte, then year (2
+//   bytes), month and day, then hour, minute and second, then microseconds
+//   (4 bytes). Zero dates have a length of 0.
+fn binary_datetime(raw: &[u8], date_only: bool) -> Option<String> {
+    let len = *raw.first()? as usize;
+    let b = raw.get(1..=len)?;
+    let (year, month, day) = if len >= 4 { (u16::from_le_bytes([b[0], b[1]]), b[2], b[3]) } else { (0, 0, 0) };
+    let date = format!("{:04}-{:02}-{:02}", year, month, day);
+    if date_only {
+        return Some(format!("'{}'", date));
+    }
+    let (hour, minute, second) = if len >= 7 { (b[4], b[5], b[6]) } else { (0, 0, 0) };
+    let micros = if len >= 11 { u32::from_le_bytes([b[7], b[8], b[9], b[10]]) } else { 0 };
+    let fraction = if micros > 0 { format!(".{:06}", micros) } else { String::new() };
+    Some(format!("'{} {:02}:{:02}:{:02}{}'", date, hour, minute, second, fraction))
 }
 
-fn compute_column_name(columns: &[MySqlColumn]) -> String {
+fn compute_column_name(table_name: &str, columns: &[MySqlColumn], options: &ExportOptions) -> String {
     columns
-        .into_iter()
-        .map(|x| format!("`{}`", x.name()))
+        .iter()
+        .map(|x| options.compat.quote_identifier(&options.renames.column(table_name, x.name())))
         .collect::<Vec<String>>()
         .join(",")
 }
 
-pub fn write_header(writer: &mut StdWriter, schema: &String, url: &String) {
+pub fn write_header(writer: &mut StdWriter, schema: &str, url: &str) {
     writer.println("-- -----------------------------------------------------------------------------------------");
-    writer.println("-- Database Dump Tool v0.3.1");
+    writer.println(format!("-- Database Dump Tool v{}", env!("CARGO_PKG_VERSION")).as_str());
     writer.println("-- https://github.com/wcherry/dbdump");
     writer.println("-- ");
     writer.println(format!("-- Created at {}", Local::now()).as_str());
@@ -464,19 +1615,65 @@ pub fn write_header(writer: &mut StdWriter, schema: &String, url: &String) {
     writer.println("-- -----------------------------------------------------------------------------------------");
 }
 
+//
+// The default character set and collation of a schema, so the schema is
+//   recreated with the same defaults and tables without an explicit charset
+//   come out the same.
+//
+#[derive(Clone, Debug)]
+pub struct SchemaCharset {
+    pub character_set: String,
+    pub collation: String,
+}
+
+pub async fn schema_charset(pool: &Pool<MySql>, schema: &str) -> Result<Option<SchemaCharset>, sqlx::Error> {
+    let row: Option<(String, String)> = sqlx::query_as(
+        "select default_character_set_name, default_collation_name from information_schema.schemata where schema_name=?",
+    )
+    .bind(schema)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|(character_set, collation)| SchemaCharset { character_set, collation }))
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn write_prefix(
     writer: &mut StdWriter,
-    source_schema: &String,
+    source_schema: &str,
     target_schema: Option<String>,
     create_schema: bool,
+    use_schema: bool,
     disable_check: bool,
+    compat: Compat,
+    escape_mode: EscapeMode,
+    charset: Option<&SchemaCharset>,
 ) {
-    let schema = target_schema.unwrap_or(source_schema.clone());
+    let schema = target_schema.unwrap_or(source_schema.to_string());
 
-    if create_schema {
-        writer.println(format!("create schema if not EXISTS {};", &schema).as_str());
+    if let Some(sql_mode) = compat.dump_sql_mode(escape_mode) {
+        writer.println(&sql_mode);
+    }
+    if create_schema && use_schema {
+        match charset {
+            // ClickHouse has no character sets, strings are bytes
+            _ if compat == Compat::ClickHouse => {
+                writer.println(format!("CREATE DATABASE IF NOT EXISTS {};", compat.quote_identifier(&schema)).as_str())
+            }
+            Some(charset) => writer.println(
+                format!(
+                    "CREATE DATABASE IF NOT EXISTS {} DEFAULT CHARACTER SET {} COLLATE {};",
+                    compat.quote_identifier(&schema),
+                    charset.character_set,
+                    charset.collation
+                )
+                .as_str(),
+            ),
+            None => writer.println(format!("create schema if not EXISTS {};", compat.quote_identifier(&schema)).as_str()),
+        }
+    }
+    if use_schema {
+        writer.println(format!("use {};", compat.quote_identifier(&schema)).as_str());
     }
-    writer.println(format!("use {};", &schema).as_str());
     if disable_check {
         writer.println("SET FOREIGN_KEY_CHECKS=0;");
     }
@@ -488,22 +1685,97 @@ pub fn write_postfix(writer: &mut StdWriter, disable_check: bool) {
     }
 }
 
+//
+// What a dump contained, written as the last line of a complete SQL dump.
+//   A dump without the trailer was cut short.
+//
+#[derive(Debug, Default)]
+pub struct DumpSummary {
+    pub tables: usize,
+    pub views: usize,
+    pub routines: usize,
+    pub triggers: usize,
+    pub rows: u64,
+}
+
+pub const TRAILER_PREFIX: &str = "-- Dump completed at ";
+pub const SECTION_BEGIN: &str = "-- dbdump section begin: ";
+pub const SECTION_END: &str = "-- dbdump section end: ";
+
+//
+// Named sections let several runs be appended to one file, e.g. the DDL now
+//   and the data later, or one section per schema. Each section holds a
+//   complete dump with its own header and trailer.
+//
+pub fn write_section_begin(writer: &mut StdWriter, name: &str) {
+    writer.println(format!("{}{}", SECTION_BEGIN, name).as_str());
+}
+
+pub fn write_section_end(writer: &mut StdWriter, name: &str) {
+    writer.println(format!("{}{}", SECTION_END, name).as_str());
+}
+
+pub fn write_trailer(writer: &mut StdWriter, summary: &DumpSummary) {
+    writer.println(
+        format!(
+            "{}{} ({} tables, {} views, {} routines, {} triggers, {} rows)",
+            TRAILER_PREFIX,
+            Local::now(),
+            summary.tables,
+            summary.views,
+            summary.routines,
+            summary.triggers,
+            summary.rows
+        )
+        .as_str(),
+    );
+}
+
 pub fn write_footer(writer: &mut StdWriter) {
     writer.flush();
 }
 
-fn to_string<T: Display>(n: Result<T, sqlx::Error>, q: bool) -> Option<String> {
+fn to_string<T: Display>(n: Result<T, sqlx::Error>) -> Option<String> {
     if let Ok(v) = n {
-        Some(if q {
-            quote(v.to_string())
-        } else {
-            v.to_string()
-        })
+        Some(v.to_string())
+    } else {
+        None
+    }
+}
+
+fn to_quoted_string<T: Display>(n: Result<T, sqlx::Error>, mode: EscapeMode) -> Option<String> {
+    if let Ok(v) = n {
+        Some(quote_string(&v.to_string(), mode))
     } else {
         None
     }
 }
 
+fn to_json_string(n: Result<String, sqlx::Error>, format: JsonFormat, mode: EscapeMode) -> Option<String> {
+    let raw = n.ok()?;
+    match json::format_json(&raw, format) {
+        Ok(formatted) if format == JsonFormat::Pretty => {
+            // The lines are escaped one by one and joined with real line
+            //   breaks, which a string literal may hold, so the value reads
+            //   across lines in the dump
+            let lines: Vec<String> = formatted
+                .lines()
+                .map(|line| {
+                    let quoted = quote_string(line, mode);
+                    quoted[1..quoted.len() - 1].to_string()
+                })
+                .collect();
+            Some(format!("'{}'", lines.join("\n")))
+        }
+        Ok(formatted) => Some(quote_string(&formatted, mode)),
+        Err(e) => {
+            // Write what the server gave us rather than silently changing it
+            Logger::warn(format!("Found malformed JSON value ({e}), writing it unchanged"));
+            Some(quote_string(&raw, mode))
+        }
+    }
+}
+
 fn to_date_string<T: Display>(n: Result<T, sqlx::Error>) -> Option<String> {
     if let Ok(v) = n {
         // Strip off the UTC that is added to Timestamps
@@ -519,26 +1791,59 @@ fn to_date_string<T: Display>(n: Result<T, sqlx::Error>) -> Option<String> {
     }
 }
 
+// The base tables of a schema, from the cached catalog when there is one
+pub async fn base_tables(pool: &Pool<MySql>, schema: &str, options: &ExportOptions) -> Result<Vec<(String,)>, sqlx::Error> {
+    if let Some(catalog) = options.cached_catalog(schema) {
+        return Ok(catalog.tables.iter().map(|t| (t.name.clone(),)).collect());
+    }
+    sqlx::query_as("select table_name from information_schema.tables where table_schema=? and table_type='BASE TABLE'")
+        .bind(schema)
+        .fetch_all(pool)
+        .await
+}
+
+// The tables and views routine and trigger bodies may name unqualified, only
+//   needed when they are renamed
+async fn body_tables(pool: &Pool<MySql>, schema: &str, options: &ExportOptions) -> Result<Vec<String>, sqlx::Error> {
+    if options.renames.is_empty() {
+        return Ok(vec![]);
+    }
+    let tables: Vec<(String,)> = sqlx::query_as("select table_name from information_schema.tables where table_schema=?")
+        .bind(schema)
+        .fetch_all(pool)
+        .await?;
+    Ok(tables.into_iter().map(|t| t.0).collect())
+}
+
 async fn order_tables(
     pool: &Pool<MySql>,
-    schema: &String,
+    schema: &str,
     tables: Vec<(String,)>,
+    options: &ExportOptions,
 ) -> Result<Vec<String>, sqlx::Error> {
+    let name_case = options.name_case;
     let mut sorted_tables: Vec<String> = tables.iter().map(|t| t.0.to_string()).collect();
 
-    let rows : Vec<(String, String)>= sqlx::query_as("select TABLE_NAME,REFERENCED_TABLE_NAME from information_schema.REFERENTIAL_CONSTRAINTS where CONSTRAINT_SCHEMA=?")
-    .bind(&schema)
-    .fetch_all(pool)
-    .await?;
+    let rows: Vec<(String, String)> = match options.cached_catalog(schema) {
+        Some(catalog) => catalog
+            .tables
+            .iter()
+            .flat_map(|t| t.foreign_keys.iter().map(|fk| (t.name.clone(), fk.referenced_table.clone())))
+            .collect(),
+        None => sqlx::query_as("select TABLE_NAME,REFERENCED_TABLE_NAME from information_schema.REFERENTIAL_CONSTRAINTS where CONSTRAINT_SCHEMA=?")
+            .bind(schema)
+            .fetch_all(pool)
+            .await?,
+    };
 
     for row in rows {
         //let mut it = sorted_tables.iter();
         let tab_index = sorted_tables
             .iter()
-            .position(|s| s.eq_ignore_ascii_case(&row.0));
+            .position(|s| name_case.same(s, &row.0));
         let ref_index = sorted_tables
             .iter()
-            .position(|s| s.eq_ignore_ascii_case(&row.1));
+            .position(|s| name_case.same(s, &row.1));
         if tab_index.is_none() {
             Logger::info(format!(
                 "Found a reference to a table {} that doesn't exists",
@@ -562,38 +1867,42 @@ async fn order_tables(
             sorted_tables.insert(tab_index, el);
         }
     }
-    return Ok(sorted_tables);
+    Ok(sorted_tables)
 }
 
+// Returns the views in dependency order along with the DDL of each view
 async fn order_views(
     pool: &Pool<MySql>,
-    schema: &String,
+    schema: &str,
     views: Vec<(String,)>,
-) -> Result<Vec<String>, sqlx::Error> {
-    let from_regex = Regex::new(r"from\s+(\()?`[^`]+`\.`([^`]+)`").unwrap();
-    let join_regex = Regex::new(r"join\s+(\()?`[^`]+`\.`([^`]+)`").unwrap();
+    name_case: NameCase,
+) -> Result<(Vec<String>, HashMap<String, String>), sqlx::Error> {
+    let from_regex = Regex::new(r#"from\s+(\()?[`"][^`"]+[`"]\.[`"]([^`"]+)[`"]"#).unwrap();
+    let join_regex = Regex::new(r#"join\s+(\()?[`"][^`"]+[`"]\.[`"]([^`"]+)[`"]"#).unwrap();
 
     let mut sorted_views = views.iter().map(|t| t.0.to_string()).collect();
+    let mut view_ddl = HashMap::new();
     for view in views {
         let ddl: (String, String) =
-            sqlx::query_as(&format!("SHOW CREATE VIEW {}.{}", &schema, &view.0))
+            sqlx::query_as(&format!("SHOW CREATE VIEW {}", qualified_name(schema, &view.0)))
                 .fetch_one(pool)
                 .await?;
         for grp in from_regex.captures_iter(&ddl.1) {
-            sorted_views = reorder_vec(sorted_views, &view.0, &grp[0].to_string());
+            sorted_views = reorder_vec(sorted_views, &view.0, &grp[0], name_case);
         }
         for grp in join_regex.captures_iter(&ddl.1) {
-            sorted_views = reorder_vec(sorted_views, &view.0, &grp[0].to_string());
+            sorted_views = reorder_vec(sorted_views, &view.0, &grp[0], name_case);
         }
+        view_ddl.insert(view.0, ddl.1);
     }
 
-    return Ok(sorted_views);
+    Ok((sorted_views, view_ddl))
 }
 
-fn reorder_vec(mut vec: Vec<String>, table_name: &String, ref_name: &String) -> Vec<String> {
+fn reorder_vec(mut vec: Vec<String>, table_name: &str, ref_name: &str, name_case: NameCase) -> Vec<String> {
     let mut it = vec.iter();
-    let tab_index = it.position(|s| s.eq_ignore_ascii_case(table_name));
-    let ref_index = it.position(|s| s.eq_ignore_ascii_case(ref_name));
+    let tab_index = it.position(|s| name_case.same(s, table_name));
+    let ref_index = it.position(|s| name_case.same(s, ref_name));
     if tab_index.is_none() {
         Logger::info(format!(
             "Found a reference to a table/view {table_name} that doesn't exists"
@@ -614,5 +1923,5 @@ fn reorder_vec(mut vec: Vec<String>, table_name: &String, ref_name: &String) ->
         vec.insert(tab_index, org_ref);
     }
 
-    return vec;
+    vec
 }
@@ -0,0 +1,223 @@
+use crate::catalog::{Catalog, Table};
+use crate::docs::cell;
+use crate::profile::ReportFormat;
+use crate::std_writer::StdWriter;
+use crate::SchemaCharset;
+use regex::Regex;
+use serde::Serialize;
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use std::collections::HashMap;
+
+// table_name, column_name, character_set_name, collation_name
+type CharsetRow = (String, String, Option<String>, Option<String>);
+
+//
+// How much a finding matters
+//   Error   - will cause problems, e.g. foreign keys that can't use an index
+//             on both sides
+//   Warning - likely to cause problems or going away in a future server
+//   Info    - worth knowing, often deliberate
+//
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    pub rule: &'static str,
+    // table or table.column
+    pub object: String,
+    pub message: String,
+}
+
+//
+// Check a schema for common design problems:
+//   no-primary-key         tables without a primary key
+//   unindexed-foreign-key  foreign key columns no index starts with
+//   deprecated-type        column types and options newer servers deprecate
+//   charset-mismatch       columns and tables whose character set differs
+//                          from their table's or schema's, and foreign keys
+//                          joining columns with different collations
+// Findings are sorted by severity, then object.
+//
+pub async fn lint_schema(
+    pool: &Pool<MySql>,
+    catalog: &Catalog,
+    schema_charset: Option<&SchemaCharset>,
+) -> Result<Vec<Finding>, sqlx::Error> {
+    let charsets: Vec<CharsetRow> = sqlx::query_as(
+        "select table_name, column_name, character_set_name, collation_name from information_schema.columns where table_schema=?",
+    )
+    .bind(&catalog.schema)
+    .fetch_all(pool)
+    .await?;
+    let table_charsets: HashMap<String, String> = sqlx::query_as::<_, (String, String)>(
+        "select t.table_name, c.character_set_name from information_schema.tables t join information_schema.collation_character_set_applicability c on c.collation_name = t.table_collation where t.table_schema=? and t.table_type='BASE TABLE'",
+    )
+    .bind(&catalog.schema)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .collect();
+    let collations: HashMap<(&str, &str), &str> = charsets
+        .iter()
+        .filter_map(|(table, column, _, collation)| collation.as_deref().map(|c| ((table.as_str(), column.as_str()), c)))
+        .collect();
+
+    let mut findings = vec![];
+    for table in &catalog.tables {
+        check_keys(table, &collations, &mut findings);
+        check_types(table, &mut findings);
+    }
+
+    for (table, column, charset, _) in &charsets {
+        let (Some(charset), Some(table_charset)) = (charset, table_charsets.get(table)) else {
+            continue;
+        };
+        if is_utf8mb3(charset) {
+            findings.push(Finding {
+                severity: Severity::Warning,
+                rule: "deprecated-type",
+                object: format!("{}.{}", table, column),
+                message: format!("{} is deprecated and can't hold every character, use utf8mb4", charset),
+            });
+        }
+        if charset != table_charset {
+            findings.push(Finding {
+                severity: Severity::Warning,
+                rule: "charset-mismatch",
+                object: format!("{}.{}", table, column),
+                message: format!("character set {} differs from the table's {}", charset, table_charset),
+            });
+        }
+    }
+    for (table, charset) in &table_charsets {
+        if let Some(schema_charset) = schema_charset.filter(|s| &s.character_set != charset) {
+            findings.push(Finding {
+                severity: Severity::Info,
+                rule: "charset-mismatch",
+                object: table.clone(),
+                message: format!("character set {} differs from the schema's {}", charset, schema_charset.character_set),
+            });
+        }
+    }
+
+    findings.sort_by(|a, b| a.severity.cmp(&b.severity).then(a.object.cmp(&b.object)).then(a.rule.cmp(b.rule)));
+    Ok(findings)
+}
+
+fn check_keys(table: &Table, collations: &HashMap<(&str, &str), &str>, findings: &mut Vec<Finding>) {
+    if table.primary_key().is_none() {
+        findings.push(Finding {
+            severity: Severity::Warning,
+            rule: "no-primary-key",
+            object: table.name.clone(),
+            message: "has no primary key, rows can't be told apart by replication or chunked exports".to_string(),
+        });
+    }
+    for fk in &table.foreign_keys {
+        let indexed = table
+            .indexes
+            .iter()
+            .any(|i| i.columns.len() >= fk.columns.len() && i.columns[..fk.columns.len()] == fk.columns[..]);
+        if !indexed {
+            findings.push(Finding {
+                severity: Severity::Warning,
+                rule: "unindexed-foreign-key",
+                object: format!("{}.{}", table.name, fk.columns.join(",")),
+                message: format!("foreign key {} has no index starting with its columns", fk.name),
+            });
+        }
+        for (column, referenced) in fk.columns.iter().zip(&fk.referenced_columns) {
+            let here = collations.get(&(table.name.as_str(), column.as_str()));
+            let there = collations.get(&(fk.referenced_table.as_str(), referenced.as_str()));
+            if let (Some(here), Some(there)) = (here, there) {
+                if here != there {
+                    findings.push(Finding {
+                        severity: Severity::Error,
+                        rule: "charset-mismatch",
+                        object: format!("{}.{}", table.name, column),
+                        message: format!(
+                            "collation {} differs from {} on {}.{} it references, lookups can't use the index",
+                            here, there, fk.referenced_table, referenced
+                        ),
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn check_types(table: &Table, findings: &mut Vec<Finding>) {
+    let float_precision = Regex::new(r"^(float|double|real)\(\d+,\d+\)").unwrap();
+    for column in &table.columns {
+        let column_type = column.column_type.to_lowercase();
+        let problem = if float_precision.is_match(&column_type) {
+            Some("floating point precision (M,D) is deprecated, use decimal for exact values")
+        } else if column_type.contains("zerofill") {
+            Some("ZEROFILL is deprecated, pad the values where they are displayed")
+        } else if column_type == "year(2)" {
+            Some("YEAR(2) is no longer supported, use YEAR")
+        } else if column_type.contains("unsigned") && column_type.starts_with(['f', 'd', 'r']) {
+            Some("UNSIGNED is deprecated for floating point and decimal types")
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            findings.push(Finding {
+                severity: Severity::Warning,
+                rule: "deprecated-type",
+                object: format!("{}.{}", table.name, column.name),
+                message: format!("{}: {}", column.column_type, problem),
+            });
+        }
+    }
+}
+
+fn is_utf8mb3(charset: &str) -> bool {
+    charset == "utf8" || charset == "utf8mb3"
+}
+
+pub fn write_findings(writer: &mut StdWriter, schema: &str, findings: &[Finding], format: ReportFormat) {
+    match format {
+        ReportFormat::Json => writer.println(
+            serde_json::to_string_pretty(findings)
+                .expect("Unable to serialize the lint findings")
+                .as_str(),
+        ),
+        ReportFormat::Markdown => {
+            writer.println(format!("# Lint of `{}`", schema).as_str());
+            writer.println("");
+            writer.println("| Severity | Rule | Object | Finding |");
+            writer.println("|----------|------|--------|---------|");
+            for finding in findings {
+                writer.println(
+                    format!(
+                        "| {} | {} | {} | {} |",
+                        finding.severity.name(),
+                        finding.rule,
+                        cell(&finding.object),
+                        cell(&finding.message)
+                    )
+                    .as_str(),
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use crate::stats::format_bytes;
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+
+//
+// Listing of what is on the server, for the list and estimate commands.
+//   Sizes are information_schema estimates.
+//
+pub async fn list_schemas(pool: &Pool<MySql>) -> Result<Vec<(String, u64, u64)>, sqlx::Error> {
+    sqlx::query_as(
+        "select s.schema_name, cast(count(t.table_name) as unsigned), cast(coalesce(sum(t.data_length + t.index_length), 0) as unsigned) from information_schema.schemata s left join information_schema.tables t on t.table_schema = s.schema_name where s.schema_name not in ('information_schema', 'performance_schema', 'mysql', 'sys') group by s.schema_name order by s.schema_name",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// name, type, estimated rows, data size, index size
+pub type TableListing = (String, String, u64, u64, u64);
+
+pub async fn list_tables(pool: &Pool<MySql>, schema: &str) -> Result<Vec<TableListing>, sqlx::Error> {
+    sqlx::query_as(
+        "select table_name, table_type, cast(coalesce(table_rows, 0) as unsigned), cast(coalesce(data_length, 0) as unsigned), cast(coalesce(index_length, 0) as unsigned) from information_schema.tables where table_schema=? order by table_name",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await
+}
+
+pub fn print_schemas(schemas: &[(String, u64, u64)]) {
+    println!("{:<40} {:>8} {:>12}", "SCHEMA", "TABLES", "SIZE");
+    for (name, tables, size) in schemas {
+        println!("{:<40} {:>8} {:>12}", name, tables, format_bytes(*size));
+    }
+}
+
+pub fn print_tables(tables: &[TableListing]) {
+    println!("{:<40} {:<10} {:>12} {:>12} {:>12}", "NAME", "TYPE", "ROWS", "DATA", "INDEX");
+    for (name, table_type, rows, data, index) in tables {
+        let table_type = if table_type == "VIEW" { "view" } else { "table" };
+        println!(
+            "{:<40} {:<10} {:>12} {:>12} {:>12}",
+            name,
+            table_type,
+            rows,
+            format_bytes(*data),
+            format_bytes(*index)
+        );
+    }
+}
+
+//
+// Rough size of a dump of the schema. INSERT text runs somewhat larger than
+//   the InnoDB data pages it comes from, and indexes aren't dumped at all.
+//
+pub fn print_estimate(schema: &str, tables: &[TableListing]) {
+    let rows: u64 = tables.iter().map(|t| t.2).sum();
+    let data: u64 = tables.iter().map(|t| t.3).sum();
+    let estimate = data + data / 5;
+    println!("Schema:          {}", schema);
+    println!("Tables:          {}", tables.iter().filter(|t| t.1 != "VIEW").count());
+    println!("Views:           {}", tables.iter().filter(|t| t.1 == "VIEW").count());
+    println!("Rows (estimate): {}", rows);
+    println!("Data size:       {}", format_bytes(data));
+    println!("Dump size:       ~{}", format_bytes(estimate));
+}
@@ -40,4 +40,35 @@ impl Logger {
         }
         eprintln!("{msg}");
     }
+
+    // Log every statement run against the server, see QueryTrace
+    pub fn trace_queries() {
+        // Only the first call installs it, there is one logger per process
+        if log::set_logger(&QUERY_TRACE).is_ok() {
+            log::set_max_level(log::LevelFilter::Debug);
+        }
+    }
+}
+
+//
+// Every statement sqlx runs is handed to the log crate with how long it took
+//   and how many rows it returned, slow ones at warn and the rest at debug.
+//   Tracing passes them on to stderr next to dbdump's own messages.
+//
+struct QueryTrace;
+
+static QUERY_TRACE: QueryTrace = QueryTrace;
+
+impl log::Log for QueryTrace {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.target().starts_with("sqlx::query")
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[query] {}", record.args());
+        }
+    }
+
+    fn flush(&self) {}
 }
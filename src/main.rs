@@ -1,6 +1,19 @@
-use clap::Parser;
-use dbdump::std_writer::StdWriter;
-use sqlx::mysql::MySqlPoolOptions;
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use dbdump::escape::EscapeMode;
+use dbdump::json::JsonFormat;
+use dbdump::logger::Logger;
+use dbdump::sql::{quote_identifier, Compat};
+use dbdump::std_writer::{LineEnding, StdWriter};
+use dbdump::transcode::{OutputEncoding, SourceEncoding};
+use dbdump::uuid::UuidFormat;
+use dbdump::vector::VectorFormat;
+use sqlx::mysql::{MySql, MySqlPoolOptions};
+use sqlx::pool::Pool;
+use sqlx::types::chrono::Local;
+use sqlx::Executor;
+use std::path::Path;
+use std::sync::Arc;
 use url::Url;
 
 use dbdump::*;
@@ -8,11 +21,76 @@ use dbdump::*;
 /// Standalone database dump tool
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None, arg_required_else_help(true))]
-struct Args {
-    /// Schema to extract
-    #[arg(short, long, required = false)]
-    schema: Option<String>,
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Dump a schema as SQL (or documentation)
+    Dump(DumpArgs),
+    /// Load a dump file into a database
+    Restore(RestoreArgs),
+    /// Compare the structure of two schemas
+    Diff(CompareArgs),
+    /// List the schemas on a server, or the tables in a schema
+    List(ListArgs),
+    /// Check a restored schema against its source using row counts and checksums
+    Verify(CompareArgs),
+    /// Compare a schema with a copy restored from an earlier dump chunk by
+    /// chunk and write the statements that bring the copy up to date
+    Sync(SyncArgs),
+    /// Estimate the size of a dump before running it
+    Estimate(ConnectionArgs),
+    /// Check a schema for missing keys, deprecated types and charset mismatches
+    Lint(LintArgs),
+    /// Report null counts, min/max and distinct values for every column
+    Profile(ProfileArgs),
+    /// Look for columns holding personal data and suggest masking rules for
+    /// the config file
+    ScanPii(ScanPiiArgs),
+    /// Run a dump repeatedly on a schedule, keeping the last few files
+    Watch(WatchArgs),
+    /// Dump the same schema from several servers, e.g. the shards of one
+    /// database, into one file
+    Federate(FederateArgs),
+    /// Measure catalog latency and scan throughput with 1..N connections and
+    /// suggest a thread count
+    Bench(BenchArgs),
+    /// Dump a schema with the given dump flags, restore it into a scratch
+    /// schema on the same server and compare the two
+    Selftest(SelftestArgs),
+    /// Generate fake rows for every table of a schema from its structure
+    Seed(SeedArgs),
+    /// Check a dump file is complete and structurally sound before restoring it
+    Validate {
+        /// Dump file to check
+        input_file: String,
+    },
+    /// Re-emit a mysqldump file in dbdump's layout, with optional rewrites
+    Normalize(NormalizeArgs),
+    /// Write the rows of a dump file out as CSV or JSONL, one file per table
+    Convert(ConvertArgs),
+    /// Split a dump file into a file per table, view and routine set
+    Split {
+        /// Dump file to split
+        input_file: String,
+        /// Directory the files are written to
+        output_dir: String,
+    },
+    /// Print a shell completion script, e.g. `dbdump completions zsh > _dbdump`
+    Completions {
+        /// Shell to generate completions for
+        shell: Shell,
+    },
+    /// Print the man page in roff format, e.g. `dbdump man > dbdump.1`
+    Man,
+}
+
+/// Options shared by every command that talks to a database
+#[derive(clap::Args, Clone, Debug)]
+struct ConnectionArgs {
     /// Database url to connect to
     #[arg(short, long)]
     url: String,
@@ -23,6 +101,37 @@ struct Args {
     #[arg(long = "pass", required = false)]
     password: Option<String>,
 
+    /// Schema to use, defaults to the database in the url
+    #[arg(short, long, required = false)]
+    schema: Option<String>,
+
+    /// SQL run on every connection when it is opened, e.g. "SET SESSION
+    /// group_concat_max_len = 1000000" (may be repeated)
+    #[arg(long = "init-command", required = false)]
+    init_commands: Vec<String>,
+
+    /// Name for this run, set with the program name and version as the
+    /// @dbdump_job, @dbdump_program and @dbdump_version session variables of
+    /// every connection and in a comment on the data queries
+    #[arg(long = "job-id", required = false)]
+    job_id: Option<String>,
+
+    /// Log every statement run against the server with its timing and row
+    /// count
+    #[arg(long = "trace-queries", required = false, default_value_t = false)]
+    trace_queries: bool,
+
+    /// Directory schema metadata is cached in between runs, reused while the
+    /// schema's structure is unchanged
+    #[arg(long = "metadata-cache", required = false)]
+    metadata_cache: Option<String>,
+}
+
+#[derive(clap::Args, Clone, Debug)]
+struct DumpArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
     /// Extract schema only
     #[arg(short = 'd', long = "no-data", default_value_t = false)]
     exclude_data: bool,
@@ -35,12 +144,45 @@ struct Args {
     #[arg(short, long = "output-file", required = false)]
     output_file: Option<String>,
 
+    /// Compress the output: none, gzip, zstd or xz. By default it follows
+    /// the output file's extension (.gz, .zst, .xz)
+    #[arg(long = "compress", required = false)]
+    compress: Option<compress::Compression>,
+
+    /// End lines with lf or crlf, for tools on Windows
+    #[arg(long = "line-endings", required = false, default_value = "lf")]
+    line_endings: LineEnding,
+
+    /// Character encoding of the output: utf8, utf8-bom, utf16le, latin1 or
+    /// cp1252. A latin1 or cp1252 SQL dump sets its client character set
+    #[arg(long = "output-encoding", required = false, default_value = "utf8")]
+    output_encoding: OutputEncoding,
+
+    /// Add to the end of the output file instead of replacing it
+    #[arg(long = "append", required = false, default_value_t = false)]
+    append: bool,
+
+    /// Wrap the dump in named section markers, so several runs can be
+    /// appended to one file with clear boundaries
+    #[arg(long = "section", required = false)]
+    section: Option<String>,
+
+    /// Threads used for compression
+    /// 0 means use the number of cores
+    #[arg(long = "compress-threads", required = false, default_value_t = 0)]
+    compress_threads: usize,
+
     /// Thread count for data extraction
     /// Default is 1
     /// 0 means use the number of cores
     #[arg(long = "thread-count", required = false, default_value_t = 1)]
     thread_count: usize,
 
+    /// Memory budget in MB for rows fetched but not yet written, export
+    /// threads wait when it is used up
+    #[arg(long = "buffer-mb", required = false, default_value_t = 256)]
+    buffer_mb: usize,
+
     /// Rename the schema
     #[arg(long = "new-schema-name", required = false)]
     renamed_schema_name: Option<String>,
@@ -49,6 +191,87 @@ struct Args {
     #[arg(long = "no-create-schema", required = false, default_value_t = true)]
     create_schema: bool,
 
+    /// Leave out the create schema and use statements and the schema name in
+    /// references, so the dump loads into the database chosen at restore time
+    #[arg(long = "no-use-db", required = false, default_value_t = false, conflicts_with = "renamed_schema_name")]
+    no_use_db: bool,
+
+    /// Qualify every table, view, routine and trigger name with the schema
+    /// instead of relying on the use statement
+    #[arg(long = "qualified-names", required = false, default_value_t = false, conflicts_with = "no_use_db")]
+    qualified_names: bool,
+
+    /// Leave foreign key checks on while loading, the data must then be in
+    /// dependency order
+    #[arg(long = "keep-foreign-key-checks", required = false, default_value_t = false)]
+    keep_foreign_key_checks: bool,
+
+    /// Load the data inside explicit transactions, one per table or one for
+    /// the whole dump. One per table reads the tables with a single thread
+    #[arg(long = "transactional-load", required = false, default_value = "off", default_missing_value = "table", num_args = 0..=1)]
+    transactional_load: TransactionalLoad,
+
+    /// With --transactional-load, commit every N rows
+    #[arg(long = "commit-every", required = false, default_value_t = 0)]
+    commit_every: u64,
+
+    /// Seconds a data query waits for a locked row before it fails, sets
+    /// lock_wait_timeout and innodb_lock_wait_timeout for the dump's sessions
+    #[arg(long = "lock-wait-timeout", required = false)]
+    lock_wait_timeout: Option<u64>,
+
+    /// What to do about rows other sessions have locked: none (consistent
+    /// reads, no row locks), wait (locking reads, bounded by
+    /// --lock-wait-timeout), nowait (fail the table) or skip (leave locked
+    /// rows out)
+    #[arg(long = "locked-rows", required = false, default_value = "none")]
+    locked_rows: LockedRows,
+
+    /// Write the dump even when it looks bigger than the free space where it
+    /// is going
+    #[arg(long = "skip-space-check", required = false, default_value_t = false)]
+    skip_space_check: bool,
+
+    /// How much commentary to write: none, minimal (object banners only),
+    /// normal or verbose (adds table storage details and row counts)
+    #[arg(long = "comments", required = false, default_value = "normal")]
+    comments: Comments,
+
+    /// Write no comments, the same as --comments none
+    #[arg(long = "skip-comments", required = false, default_value_t = false)]
+    skip_comments: bool,
+
+    /// Leave TABLESPACE, DATA DIRECTORY and INDEX DIRECTORY out of the table
+    /// DDL, restoring them needs privileges and paths the target rarely has
+    #[arg(long = "no-tablespaces", required = false, default_value_t = false)]
+    no_tablespaces: bool,
+
+    /// Create plain secondary indexes with an ALTER TABLE after each table's
+    /// data instead of in CREATE TABLE, which restores big tables faster
+    #[arg(long = "defer-indexes", required = false, default_value_t = false, conflicts_with = "data_shards")]
+    defer_indexes: bool,
+
+    /// Set each table's AUTO_INCREMENT just past the largest value in the
+    /// source after its rows, so a target loaded with a subset of them
+    /// never reuses an id
+    #[arg(long = "set-auto-increment", required = false, default_value_t = false, conflicts_with = "data_shards")]
+    set_auto_increment: bool,
+
+    /// Only write each table's secondary indexes, as ALTER TABLE ... ADD
+    /// statements for tables created with another tool
+    #[arg(long = "indexes-only", required = false, default_value_t = false, conflicts_with_all = ["constraints_only", "data_shards", "explain", "interactive", "queries", "materialize_views"])]
+    indexes_only: bool,
+
+    /// Only write each table's foreign keys and CHECK constraints, as ALTER
+    /// TABLE ... ADD statements for tables created with another tool
+    #[arg(long = "constraints-only", required = false, default_value_t = false, conflicts_with_all = ["data_shards", "explain", "interactive", "queries", "materialize_views"])]
+    constraints_only: bool,
+
+    /// Minimal output as mysqldump --compact writes it: no header, comments,
+    /// FOREIGN_KEY_CHECKS wrapper or trailer, only the statements
+    #[arg(long = "compact", required = false, default_value_t = false)]
+    compact: bool,
+
     /// Use single row inserts
     #[arg(long = "single-row-inserts", required = false, default_value_t = false)]
     single_row_inserts: bool,
@@ -60,40 +283,814 @@ struct Args {
         default_value_t = false
     )]
     skip_unknown_datatypes: bool,
+
+    /// Log progress through each table every N rows, or every N seconds
+    /// with an s suffix (e.g. 1000000 or 30s)
+    #[arg(long = "progress-every", required = false)]
+    progress_every: Option<ProgressInterval>,
+
+    /// Parse every generated insert statement before writing it and report
+    /// any that fail, a self check for escaping bugs
+    #[arg(long = "check-sql", required = false, default_value_t = false)]
+    check_sql: bool,
+
+    /// Compare the table and column comments of every CREATE TABLE written
+    /// with the server's and report any the DDL rewrites dropped or changed
+    #[arg(long = "audit-comments", required = false, default_value_t = false)]
+    audit_comments: bool,
+
+    /// Stop the dump on values that aren't valid for their type, like zero
+    /// dates or unknown ENUM values, rather than writing them as found
+    #[arg(long = "strict-data", required = false, default_value_t = false)]
+    strict_data: bool,
+
+    /// How to fetch column types dbdump doesn't handle, e.g.
+    /// 'GEOMETRY=string,INET6=string' (string, bytes or null)
+    #[arg(long = "type-override", required = false, value_delimiter = ',')]
+    type_overrides: Vec<types::TypeOverrideRule>,
+
+    /// How string values are escaped: backslash (MySQL default) or standard
+    /// (for servers running with NO_BACKSLASH_ESCAPES)
+    #[arg(long = "escape-mode", required = false, default_value = "backslash")]
+    escape_mode: EscapeMode,
+
+    /// Output dialect: mysql (default), ansi (double quoted identifiers and
+    /// standard string escaping, for ANSI_QUOTES sessions) or clickhouse
+    /// (MergeTree tables and ClickHouse types, tables and data only)
+    #[arg(long = "compat", alias = "target-dialect", required = false, default_value = "mysql")]
+    compat: Compat,
+
+    /// Transcode latin1 columns to utf8mb4, decoding the stored bytes with the
+    /// given encoding (latin1, cp1252 or utf8)
+    #[arg(long = "transcode-latin1", required = false)]
+    transcode_latin1: Option<SourceEncoding>,
+
+    /// Render a BINARY(16) column as a UUID, given as table.column or column
+    /// (may be repeated)
+    #[arg(long = "uuid-column", required = false)]
+    uuid_columns: Vec<String>,
+
+    /// How UUID columns are written: function (UUID_TO_BIN('...')) or hex
+    /// (X'...' with the UUID in a comment)
+    #[arg(long = "uuid-format", required = false, default_value = "function")]
+    uuid_format: UuidFormat,
+
+    /// How JSON column values are written: compact or pretty
+    #[arg(long = "json-format", required = false, default_value = "compact")]
+    json_format: JsonFormat,
+
+    /// How VECTOR column values are written: string (STRING_TO_VECTOR) or hex
+    #[arg(long = "vector-format", required = false, default_value = "string")]
+    vector_format: VectorFormat,
+
+    /// Recreate optimizer histograms after the data is loaded
+    #[arg(long = "histograms", required = false, default_value_t = false)]
+    histograms: bool,
+
+    /// End the dump with an ANALYZE TABLE for every table, so the restored
+    /// tables start with fresh statistics
+    #[arg(long = "analyze-tables", required = false, default_value_t = false)]
+    analyze_tables: bool,
+
+    /// End the dump with an OPTIMIZE TABLE for every table, rebuilding it
+    /// once the data is in
+    #[arg(long = "optimize-tables", required = false, default_value_t = false)]
+    optimize_tables: bool,
+
+    /// Annotate each table with its row count, data size, index size and
+    /// last update time
+    #[arg(long = "table-stats", required = false, default_value_t = false)]
+    table_stats: bool,
+
+    /// Run CHECKSUM TABLE and record the value after each table's data
+    #[arg(long = "checksum", required = false, default_value_t = false)]
+    checksum: bool,
+
+    /// Also write a SQL script that checks row counts and checksums after
+    /// the dump has been restored
+    #[arg(long = "verify-script", required = false)]
+    verify_script: Option<String>,
+
+    /// What to produce: sql (a restorable dump), markdown (a data
+    /// dictionary of the schema), dot (a GraphViz ER diagram), schema-json
+    /// (a machine-readable catalog of the schema), mysqlsh (a directory
+    /// MySQL Shell's util.loadDump() can load, named by --output-file), xml
+    /// (the layout of mysqldump --xml), arrow (a directory of Arrow IPC
+    /// files, one per table, named by --output-file), bigquery (a directory
+    /// of JSON rows, BigQuery schemas and a bq load script), kafka (every
+    /// row published to --kafka-brokers) or anonymized-schema (the tables'
+    /// DDL with every name replaced, to share without business terms)
+    #[arg(long = "format", required = false, default_value = "sql")]
+    format: OutputFormat,
+
+    /// Kafka bootstrap servers for --format kafka, host:port,...
+    #[arg(long = "kafka-brokers", required = false)]
+    kafka_brokers: Option<String>,
+
+    /// Topic each table's rows go to, {schema} and {table} are filled in
+    #[arg(long = "kafka-topic", required = false, default_value = "{schema}.{table}")]
+    kafka_topic: String,
+
+    /// librdkafka producer setting as key=value, e.g. security.protocol=SSL
+    /// (may be repeated)
+    #[arg(long = "kafka-option", required = false)]
+    kafka_options: Vec<String>,
+
+    /// Include views in the dot diagram
+    #[arg(long = "dot-include-views", required = false, default_value_t = false)]
+    dot_include_views: bool,
+
+    /// Rename tables in the DDL and data: old=new, prefix:<text>,
+    /// suffix:<text> or regex:<pattern>=<replacement> (may be repeated, rules
+    /// apply in order)
+    #[arg(long = "rename-table", required = false)]
+    rename_tables: Vec<rename::TableRename>,
+
+    /// Rename a column in the DDL and data, given as table.old=new with the
+    /// source table name, or *.old=new for every table (may be repeated)
+    #[arg(long = "rename-column", required = false)]
+    rename_columns: Vec<rename::ColumnRename>,
+
+    /// Dump this schema into the --new-schema-name schema, prefixing its
+    /// object names (may be repeated to merge several schemas)
+    #[arg(long = "merge-schema", required = false)]
+    merge_schemas: Vec<String>,
+
+    /// Prefix for the objects of merged schemas, {schema} is replaced by the
+    /// source schema name
+    #[arg(long = "merge-prefix", required = false, default_value = "{schema}_")]
+    merge_prefix: String,
+
+    /// Pick the tables and views to dump, and whether to include DDL and
+    /// data, interactively before the dump starts
+    #[arg(short = 'i', long = "interactive", required = false, default_value_t = false)]
+    interactive: bool,
+
+    /// File listing the tables to dump, one per line, each with optional
+    /// flags (no-data, limit=N) and a WHERE clause for its rows
+    #[arg(long = "tables-file", required = false, conflicts_with_all = ["interactive", "fixture", "slice_column"])]
+    tables_file: Option<String>,
+
+    /// Dump the full schema but only about N rows per table, plus the parent
+    /// rows their foreign keys need, for development databases
+    #[arg(long = "fixture", required = false)]
+    fixture: Option<u64>,
+
+    /// Only dump the rows of tables with this column that fall between
+    /// --from and --to, tables without it are dumped in full
+    #[arg(long = "slice-column", required = false, conflicts_with = "fixture")]
+    slice_column: Option<String>,
+
+    /// With --slice-column, the first value included, e.g. 2024-01-01
+    #[arg(long = "from", required = false, requires = "slice_column")]
+    slice_from: Option<String>,
+
+    /// With --slice-column, the first value no longer included
+    #[arg(long = "to", required = false, requires = "slice_column")]
+    slice_to: Option<String>,
+
+    /// Only dump a share of each table's rows, e.g. 1%, spread evenly along
+    /// the primary key (needs MySQL 8.0 or MariaDB 10.2)
+    #[arg(long = "sample", required = false, conflicts_with_all = ["fixture", "sample_every"])]
+    sample: Option<sample::SampleRate>,
+
+    /// Only dump every Nth row of each table in primary key order, starting
+    /// with the first
+    #[arg(long = "sample-every", required = false, conflicts_with = "fixture")]
+    sample_every: Option<u64>,
+
+    /// Spread each table's rows over N files by primary key hash, written to
+    /// <output-file>.shards with an index.json, so a table can be loaded by
+    /// several connections at once. The output file gets the DDL
+    #[arg(long = "data-shards", required = false, conflicts_with = "fixture")]
+    data_shards: Option<usize>,
+
+    /// Write the tables and their rows to N files in <output-file>.buckets
+    /// with an index.json, each table always in the same bucket, so the
+    /// buckets can be restored in parallel. The output file gets the views,
+    /// routines and triggers, to load after the buckets
+    #[arg(long = "table-buckets", required = false, conflicts_with_all = ["data_shards", "indexes_only", "constraints_only"])]
+    table_buckets: Option<usize>,
+
+    /// Write the SELECT statements the data export would run, with the
+    /// server's EXPLAIN of each, instead of dumping
+    #[arg(long = "explain", required = false, default_value_t = false, conflicts_with = "data_shards")]
+    explain: bool,
+
+    /// SQL run against the source before the dump starts, inline or @file
+    /// (may be repeated)
+    #[arg(long = "pre-sql", required = false)]
+    pre_sql: Vec<String>,
+
+    /// SQL run against the source once the dump is written, inline or @file
+    /// (may be repeated)
+    #[arg(long = "post-sql", required = false)]
+    post_sql: Vec<String>,
+
+    /// URL the run's report is POSTed to as JSON when it finishes or fails
+    #[arg(long = "notify-url", required = false)]
+    notify_url: Option<String>,
+
+    /// Command run with sh -c when the dump finishes or fails, with the JSON
+    /// report on stdin and DBDUMP_STATUS set to success or failure
+    #[arg(long = "notify-command", required = false)]
+    notify_command: Option<String>,
+
+    /// TOML config file with per table filter and transform scripts
+    #[arg(long = "config", required = false)]
+    config: Option<String>,
+
+    /// Dump the result of a query as a table, e.g. "report_users=SELECT ...
+    /// JOIN ...", with a CREATE TABLE for its columns (may be repeated)
+    #[arg(long = "query", required = false)]
+    queries: Vec<query::NamedQuery>,
+
+    /// Also dump the rows of these views, each into a table named after the
+    /// view with --materialize-suffix, for consumers without the underlying
+    /// tables
+    #[arg(long = "materialize-views", required = false, value_delimiter = ',')]
+    materialize_views: Vec<String>,
+
+    /// Added to a materialized view's name for its table. Empty replaces the
+    /// view with the table, its DDL is left out
+    #[arg(long = "materialize-suffix", required = false, default_value = "_data")]
+    materialize_suffix: String,
+}
+
+#[derive(clap::Args, Debug)]
+struct WatchArgs {
+    #[command(flatten)]
+    dump: DumpArgs,
+
+    /// How often to dump: an interval (30m, 6h, 1d) or a cron expression
+    /// such as "0 2 * * *"
+    #[arg(long = "every")]
+    every: schedule::Schedule,
+
+    /// Number of dump files to keep, older ones are deleted after each
+    /// successful dump. 0 keeps everything
+    #[arg(long = "keep-last", alias = "keep", required = false, default_value_t = 0)]
+    keep_last: usize,
+
+    /// Keep the dump files of the last D days, older ones are deleted after
+    /// each successful dump. With --keep-last a file either one keeps stays
+    #[arg(long = "keep-days", required = false)]
+    keep_days: Option<u32>,
+}
+
+#[derive(clap::Args, Debug)]
+struct FederateArgs {
+    #[command(flatten)]
+    dump: DumpArgs,
+
+    /// A server to dump from, as name=url, or only the name to put it in
+    /// place of {shard} in the url (repeat for each shard)
+    #[arg(long = "shard", required = true)]
+    shards: Vec<federate::Shard>,
+
+    /// schemas restores each shard into its own schema, merged loads the rows
+    /// of every shard into the tables of the first
+    #[arg(long = "shard-layout", required = false, default_value = "schemas")]
+    shard_layout: federate::ShardLayout,
+
+    /// Suffix of each shard's schema with --shard-layout schemas, {shard} is
+    /// replaced by the shard's name
+    #[arg(long = "shard-suffix", required = false, default_value = "_{shard}")]
+    shard_suffix: String,
+}
+
+#[derive(clap::Args, Debug)]
+struct SeedArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    /// Rows to generate per table
+    #[arg(long = "rows", required = false, default_value_t = 100)]
+    rows: u64,
+
+    /// Seed for the random values, the same seed gives the same data
+    #[arg(long = "random-seed", required = false, default_value_t = 1)]
+    random_seed: u64,
+
+    /// Filename to write output to
+    #[arg(short, long = "output-file", required = false)]
+    output_file: Option<String>,
+
+    /// Output dialect: mysql (default), ansi or clickhouse
+    #[arg(long = "compat", required = false, default_value = "mysql")]
+    compat: Compat,
+}
+
+#[derive(clap::Args, Debug)]
+struct NormalizeArgs {
+    /// mysqldump file to read
+    input_file: String,
+
+    /// File to write the normalized dump to
+    output_file: String,
+
+    /// Remove DEFINER clauses from views, routines and triggers
+    #[arg(long = "strip-definer", required = false, default_value_t = false)]
+    strip_definer: bool,
+
+    /// Rename the schema
+    #[arg(long = "new-schema-name", required = false)]
+    renamed_schema_name: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct ConvertArgs {
+    /// Dump file to read
+    input_file: String,
+
+    /// Output format: csv (default) or jsonl
+    #[arg(long = "format", required = false, default_value = "csv")]
+    format: convert::ConvertFormat,
+
+    /// Directory the table files are written to
+    #[arg(short, long = "output-dir", required = false, default_value = ".")]
+    output_dir: String,
+}
+
+#[derive(clap::Args, Debug)]
+struct RestoreArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    /// Dump file to load
+    input_file: String,
+
+    /// Load the dump into this schema instead of the one it was written for
+    #[arg(long = "as", required = false)]
+    restore_as: Option<String>,
+
+    /// Only restore these tables and views, with their triggers
+    #[arg(long = "tables", required = false, value_delimiter = ',')]
+    tables: Vec<String>,
+
+    /// Restore the schema only
+    #[arg(short = 'd', long = "no-data", default_value_t = false)]
+    exclude_data: bool,
+
+    /// Restore the data only
+    #[arg(short = 'n', long = "no-ddl", default_value_t = false)]
+    exclude_ddl: bool,
+
+    /// Only restore the named section of a dump built with --append
+    #[arg(long = "section", required = false)]
+    section: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct CompareArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    /// Url of the database to compare against
+    #[arg(long = "target-url")]
+    target_url: String,
+
+    /// Schema to compare against, defaults to the database in the target url
+    /// or the source schema
+    #[arg(long = "target-schema", required = false)]
+    target_schema: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct SyncArgs {
+    #[command(flatten)]
+    compare: CompareArgs,
+
+    /// Only sync these tables
+    #[arg(long = "tables", required = false, value_delimiter = ',')]
+    tables: Vec<String>,
+
+    /// Rows compared at a time, only chunks that differ are read in full
+    #[arg(long = "chunk-rows", required = false, default_value_t = 1000)]
+    chunk_rows: u64,
+
+    /// Filename to write the statements to
+    #[arg(short, long = "output-file", required = false)]
+    output_file: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct LintArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    /// Report format: markdown (default) or json
+    #[arg(long = "format", required = false, default_value = "markdown")]
+    format: profile::ReportFormat,
+
+    /// Filename to write the report to
+    #[arg(short, long = "output-file", required = false)]
+    output_file: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct ProfileArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    /// Only profile these tables
+    #[arg(long = "tables", required = false, value_delimiter = ',')]
+    tables: Vec<String>,
+
+    /// Profile only the first N rows of each table, for large tables
+    #[arg(long = "sample-rows", required = false)]
+    sample_rows: Option<u64>,
+
+    /// Report format: json (default) or markdown
+    #[arg(long = "format", required = false, default_value = "json")]
+    format: profile::ReportFormat,
+
+    /// Filename to write the report to
+    #[arg(short, long = "output-file", required = false)]
+    output_file: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct BenchArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    /// Table to scan, defaults to the one with the most data
+    #[arg(long = "table", required = false)]
+    table: Option<String>,
+
+    /// Most connections to scan with, defaults to the number of CPUs
+    #[arg(long = "max-connections", required = false, default_value_t = 0)]
+    max_connections: usize,
+
+    /// Rows read by each scan
+    #[arg(long = "rows", required = false, default_value_t = 1_000_000)]
+    rows: u64,
+
+    /// Filename to write the report to
+    #[arg(short, long = "output-file", required = false)]
+    output_file: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct SelftestArgs {
+    #[command(flatten)]
+    dump: DumpArgs,
+
+    /// Schema the dump is restored into, defaults to <schema>_selftest. It
+    /// must not exist yet
+    #[arg(long = "scratch-schema", required = false)]
+    scratch_schema: Option<String>,
+
+    /// Leave the scratch schema in place afterwards, to look into differences
+    #[arg(long = "keep-scratch", required = false, default_value_t = false)]
+    keep_scratch: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct ScanPiiArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    /// Only scan these tables
+    #[arg(long = "tables", required = false, value_delimiter = ',')]
+    tables: Vec<String>,
+
+    /// Rows of each table whose values are checked, 0 to go by column names
+    /// only
+    #[arg(long = "sample-rows", required = false, default_value_t = 1000)]
+    sample_rows: u64,
+
+    /// Filename to write the suggested config to
+    #[arg(short, long = "output-file", required = false)]
+    output_file: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct ListArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+}
+
+impl ConnectionArgs {
+    fn parsed_url(&self) -> Url {
+        let mut url = Url::parse(&self.url).expect("Invalid url, unable to parse");
+        if let Some(user) = &self.username {
+            url.set_username(user).expect("Cannot set username");
+        }
+        if let Some(pass) = &self.password {
+            url.set_password(Some(pass.as_str()))
+                .expect("Cannot set password");
+        }
+        url
+    }
+
+    fn schema_opt(&self) -> Option<String> {
+        self.schema.clone().or_else(|| schema_from_url(&self.parsed_url()))
+    }
+
+    fn schema(&self) -> String {
+        self.schema_opt().expect("Unable to obtain the schema. Either include the schema name as part of the url or pass it using the --schema argument")
+    }
+
+    // How the sessions and queries of this run are labelled for the DBA
+    fn session_tag(&self) -> String {
+        let tag = match &self.job_id {
+            Some(job) => format!("dbdump v{} job {}", env!("CARGO_PKG_VERSION"), job),
+            None => format!("dbdump v{}", env!("CARGO_PKG_VERSION")),
+        };
+        tag.replace("*/", "* /")
+    }
+
+    // The catalog of a schema, through the metadata cache when there is one
+    async fn load_catalog(&self, pool: &Pool<MySql>, schema: &str) -> Result<catalog::Catalog, sqlx::Error> {
+        match &self.metadata_cache {
+            Some(dir) => {
+                let url = self.parsed_url();
+                let server = format!("{}-{}", url.host_str().unwrap_or("localhost"), url.port().unwrap_or(3306));
+                catalog::load_cached_catalog(pool, schema, Path::new(dir), &server).await
+            }
+            None => catalog::load_catalog(pool, schema).await,
+        }
+    }
+
+    //
+    // Create a pool of connections, applying any session settings each
+    //   connection needs before use. sqlx 0.6 has no way to send connection
+    //   attributes in the handshake, so every session is labelled with user
+    //   variables instead, see performance_schema.user_variables_by_thread.
+    //   The job id is bound rather than quoted, the session's sql_mode
+    //   decides how a literal would be read.
+    //
+    async fn connect(&self, max_connections: u32, session_sql_mode: Option<&'static str>) -> Result<Pool<MySql>, sqlx::Error> {
+        if self.trace_queries {
+            Logger::trace_queries();
+        }
+        let job_id = self.job_id.clone();
+        let init_commands = self.init_commands.clone();
+        MySqlPoolOptions::new()
+            .max_connections(max_connections)
+            .after_connect(move |conn, _meta| {
+                let job_id = job_id.clone();
+                let init_commands = init_commands.clone();
+                Box::pin(async move {
+                    if let Some(sql_mode) = session_sql_mode {
+                        conn.execute(format!("SET SESSION sql_mode = CONCAT(@@sql_mode, ',{}')", sql_mode).as_str())
+                            .await?;
+                    }
+                    sqlx::query("SET @dbdump_program = 'dbdump', @dbdump_version = ?, @dbdump_job = ?")
+                        .bind(env!("CARGO_PKG_VERSION"))
+                        .bind(job_id)
+                        .execute(&mut *conn)
+                        .await?;
+                    for command in &init_commands {
+                        conn.execute(command.as_str()).await?;
+                    }
+                    Ok(())
+                })
+            })
+            .connect(self.parsed_url().as_ref())
+            .await
+    }
+}
+
+fn schema_from_url(url: &Url) -> Option<String> {
+    url.path()
+        .split_once('/')
+        .map(|(_, schema)| schema.to_string())
+        .filter(|schema| !schema.is_empty())
 }
 
 #[async_std::main]
 async fn main() -> Result<(), sqlx::Error> {
     //
-    // Parse the command line arguments and set the writer to write to a file or STDOUT
+    // Parse the command line arguments and hand off to the command
     //
-    let args = Args::parse();
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::Dump(args) => run_dump_notified(args).await,
+        Command::Restore(args) => run_restore(args).await,
+        Command::Diff(args) => run_diff(args).await,
+        Command::List(args) => run_list(args).await,
+        Command::Verify(args) => run_verify(args).await,
+        Command::Sync(args) => run_sync(args).await,
+        Command::Estimate(args) => run_estimate(args).await,
+        Command::Lint(args) => run_lint(args).await,
+        Command::Profile(args) => run_profile(args).await,
+        Command::ScanPii(args) => run_scan_pii(args).await,
+        Command::Watch(args) => run_watch(args).await,
+        Command::Federate(args) => run_federate(args).await,
+        Command::Bench(args) => run_bench(args).await,
+        Command::Selftest(args) => run_selftest(args).await,
+        Command::Seed(args) => run_seed(args).await,
+        Command::Validate { input_file } => {
+            run_validate(&input_file);
+            Ok(())
+        }
+        Command::Normalize(args) => {
+            run_normalize(&args);
+            Ok(())
+        }
+        Command::Convert(args) => {
+            run_convert(&args);
+            Ok(())
+        }
+        Command::Split { input_file, output_dir } => {
+            run_split(&input_file, &output_dir);
+            Ok(())
+        }
+        Command::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "dbdump", &mut std::io::stdout());
+            Ok(())
+        }
+        Command::Man => {
+            clap_mangen::Man::new(Cli::command())
+                .render(&mut std::io::stdout())
+                .expect("Unable to write the man page");
+            Ok(())
+        }
+    }
+}
+
+//
+// Run a dump and tell --notify-url and --notify-command how it went
+//
+async fn run_dump_notified(args: DumpArgs) -> Result<(), sqlx::Error> {
+    let schema = args
+        .connection
+        .schema_opt()
+        .or_else(|| args.merge_schemas.first().cloned())
+        .unwrap_or_default();
+    let notifier = notify::Notifier::new(args.notify_url.clone(), args.notify_command.clone(), schema, args.output_file.clone());
+    if let Some(notifier) = &notifier {
+        notifier.watch_panics();
+    }
+    let definitions = ddl_change::TableDefinitions::default();
+    sqlcheck::reset();
+    comment_audit::reset();
+    let summary = match run_dump(args, definitions.clone()).await {
+        Ok(summary) => summary,
+        Err(e) => {
+            if let Some(notifier) = &notifier {
+                notifier.failure(&e.to_string());
+            }
+            return Err(e);
+        }
+    };
+    // Failures are returned rather than exiting, watch and federate carry on
+    //   after them
+    if sqlcheck::problems() > 0 {
+        let message = format!("{} generated statements failed to parse", sqlcheck::problems());
+        if let Some(notifier) = &notifier {
+            notifier.failure(&message);
+        }
+        return Err(sqlx::Error::Io(std::io::Error::other(message)));
+    }
+    if definitions.changes() > 0 {
+        Logger::error(format!(
+            "{} tables were altered while they were dumped, the dump may not be consistent",
+            definitions.changes()
+        ));
+    }
+    if comment_audit::problems() > 0 {
+        let message = format!("{} table or column comments were lost from the dump", comment_audit::problems());
+        if let Some(notifier) = &notifier {
+            notifier.failure(&message);
+        }
+        return Err(sqlx::Error::Io(std::io::Error::other(message)));
+    }
+    if let Some(notifier) = &notifier {
+        notifier.success(&summary);
+    }
+    Ok(())
+}
 
-    let mut url = Url::parse(&args.url).expect("Invalid url, unable to parse");
-    if let Some(user) = args.username {
-        url.set_username(&user).expect("Cannot set username");
+//
+// Where the files of --data-shards and --table-buckets go, and the
+//   statements each starts and ends with so it loads on its own
+//
+fn split_output(args: &DumpArgs, schema: &str, dir: String, compression: compress::Compression, disable_check: bool) -> shard::ShardOutput {
+    let mut prefix = vec![];
+    if let Some(sql_mode) = args.compat.dump_sql_mode(args.compat.escape_mode(args.escape_mode)) {
+        prefix.push(sql_mode);
+    }
+    if !args.no_use_db {
+        let target = args.renamed_schema_name.clone().unwrap_or_else(|| schema.to_string());
+        prefix.push(format!("use {};", args.compat.quote_identifier(&target)));
     }
-    if let Some(pass) = args.password {
-        url.set_password(Some(pass.as_str()))
-            .expect("Cannot set password");
+    let mut postfix = vec![];
+    if disable_check {
+        prefix.push("SET FOREIGN_KEY_CHECKS=0;".to_string());
+        postfix.push("SET FOREIGN_KEY_CHECKS=1;".to_string());
     }
+    shard::ShardOutput {
+        dir,
+        compression,
+        prefix,
+        postfix,
+    }
+}
 
-    let mut writer = StdWriter::new(args.output_file);
-    let schema = if let Some(schema) = args.schema {
-        schema
+//
+// The options a schema is dumped with, its rows cut down to a fixture or
+//   sliced and sampled. The first schema was sliced and sampled with the base
+//   options.
+//
+async fn source_options(
+    pool: &Pool<MySql>,
+    args: &DumpArgs,
+    base_options: &ExportOptions,
+    source: &str,
+    renames: &rename::Renames,
+    first: bool,
+    sample_rate: Option<sample::SampleRate>,
+) -> Result<ExportOptions, sqlx::Error> {
+    let mut options = base_options.clone();
+    options.renames = renames.clone();
+    let cached = args.connection.metadata_cache.is_some();
+    let catalog = if cached || args.fixture.is_some() || (sample_rate.is_some() && !first) {
+        Some(args.connection.load_catalog(pool, source).await?)
     } else {
-        url.path()
-            .split_once('/')
-            .expect("Unable to obtain the schema. Either include the schema name as part of the url or pass it using the --schema argument")
-            .1
-            .to_string()
+        None
+    };
+    if let (Some(rows), Some(catalog)) = (args.fixture, &catalog) {
+        options.row_filters = fixture::select_rows(pool, catalog, rows).await?;
+    }
+    if let (Some(column), false) = (&args.slice_column, first) {
+        options.row_filters = slice::slice_rows(pool, source, column, args.slice_from.as_deref(), args.slice_to.as_deref()).await?;
+    }
+    if let (Some(rate), false, Some(catalog)) = (sample_rate, first, &catalog) {
+        options.row_filters = sample::sample_rows(catalog, rate, &options.row_filters);
+    }
+    if cached {
+        options.catalog = catalog.map(Arc::new);
+    }
+    Ok(options)
+}
+
+async fn run_dump(args: DumpArgs, definitions: ddl_change::TableDefinitions) -> Result<DumpSummary, sqlx::Error> {
+    let compress_threads = if args.compress_threads == 0 { num_cpus::get() } else { args.compress_threads };
+    let compression = args
+        .compress
+        .unwrap_or_else(|| args.output_file.as_deref().map(compress::Compression::from_path).unwrap_or_default());
+    if let (true, Some(section), Some(output_file)) = (args.append, &args.section, &args.output_file) {
+        if section_exists(output_file, section) {
+            panic!("{} already contains a section named {}", output_file, section);
+        }
+    }
+
+    //
+    // A tar archive is built from the finished dump, which is staged first
+    //
+    let archive = args.output_file.clone().filter(|f| archive::is_archive(f));
+    if archive.is_some() && (args.append || args.format != OutputFormat::Sql) {
+        panic!("Tar output is for SQL dumps and can't be appended to");
+    }
+    let mut writer = match &archive {
+        Some(path) => StdWriter::open(Some(archive::staging_file(path)), compress::Compression::None, 1, false),
+        // The output file names a directory the format writes into
+        None if args.format.is_directory() => StdWriter::new(None),
+        None => StdWriter::open(args.output_file.clone(), compression, compress_threads, args.append),
     };
+    let text_format = (args.line_endings, args.output_encoding);
+    if text_format != Default::default() {
+        // The archive is split from the staged dump, which has to stay UTF-8
+        //   with plain line feeds
+        if archive.is_some() || args.format.is_directory() {
+            panic!("--line-endings and --output-encoding can't be used with tar archives or directory formats");
+        }
+        if args.format == OutputFormat::Xml && args.output_encoding.mysql_charset().is_some() {
+            panic!("--format xml is written in UTF-8 or UTF-16");
+        }
+        writer.set_text_format(args.line_endings, args.output_encoding, !args.append);
+    }
+    let schema = args
+        .connection
+        .schema_opt()
+        .or_else(|| args.merge_schemas.first().cloned())
+        .unwrap_or_else(|| args.connection.schema());
+    if args.interactive && !args.merge_schemas.is_empty() {
+        panic!("--interactive can't be used with --merge-schema");
+    }
+    let config = match &args.config {
+        Some(path) => config::Config::load(path).unwrap_or_else(|e| panic!("{}", e)),
+        None => config::Config::default(),
+    };
+    let transformer = script_transformer(&config);
 
     //
-    // Compute number of threads to use
+    // Compute number of threads to use.
+    //   A transaction per table needs each table's statements written
+    //   together, which only one thread does
     //
     let thread_count = 
-    if args.thread_count == 0 {
+    if args.transactional_load == TransactionalLoad::Table {
+        if args.thread_count > 1 {
+            panic!("--transactional-load table writes one table at a time and can't be used with --thread-count above 1");
+        }
+        1
+    } else if args.thread_count == 0 {
         let num_cpus = num_cpus::get();
         if num_cpus > 1 { num_cpus - 1 } else { num_cpus }
     } else {
@@ -104,57 +1101,1076 @@ async fn main() -> Result<(), sqlx::Error> {
     // Create a pool of connections.
     // Probably overkill as we currently only use one connection
     //
-    let pool = MySqlPoolOptions::new()
-        .max_connections(thread_count as u32 +1)
-        .connect(&url.to_string())
+    let mut connection = args.connection.clone();
+    if let Some(timeout) = args.lock_wait_timeout {
+        connection.init_commands.push(format!("SET SESSION lock_wait_timeout = {}", timeout));
+        connection.init_commands.push(format!("SET SESSION innodb_lock_wait_timeout = {}", timeout));
+    }
+    let pool = connection
+        .connect(thread_count as u32 + 1, args.compat.session_sql_mode())
         .await?;
+    let name_case = sql::NameCase::of_server(&pool).await?;
+    let locking_read = if args.locked_rows == LockedRows::None {
+        None
+    } else {
+        let (server_version,): (String,) = sqlx::query_as("select version()").fetch_one(&pool).await?;
+        args.locked_rows.clause(&server_version).unwrap_or_else(|e| panic!("{}", e))
+    };
+    for hook in &args.pre_sql {
+        hooks::run_sql_hook(&pool, "pre-sql", hook).await?;
+    }
+
+    //
+    // Documentation formats are generated from the catalog and contain no SQL
+    //
+    if args.format.is_documentation() {
+        let catalog = args.connection.load_catalog(&pool, &schema).await?;
+        match args.format {
+            OutputFormat::Markdown => docs::write_markdown(&mut writer, &catalog),
+            OutputFormat::Dot => dot::write_dot(&mut writer, &catalog, args.dot_include_views),
+            OutputFormat::AnonymizedSchema => shape::write_anonymized_schema(&mut writer, &catalog),
+            OutputFormat::SchemaJson => writer.println(
+                serde_json::to_string_pretty(&catalog)
+                    .expect("Unable to serialize the schema catalog")
+                    .as_str(),
+            ),
+            _ => unreachable!(),
+        }
+        write_footer(&mut writer);
+        for hook in &args.post_sql {
+            hooks::run_sql_hook(&pool, "post-sql", hook).await?;
+        }
+        return Ok(DumpSummary::default());
+    }
+
+    //
+    // Let the user choose what to dump, or read it from the tables file
+    //
+    let spec = args
+        .tables_file
+        .as_deref()
+        .map(|path| tablespec::TableSpec::load(path).unwrap_or_else(|e| panic!("{}", e)))
+        .unwrap_or_default();
+    let mut objects = args.tables_file.as_ref().map(|_| spec.tables.clone());
+    let mut exclude_ddl = args.exclude_ddl;
+    let mut exclude_data = args.exclude_data;
+    let table_part = match (args.indexes_only, args.constraints_only) {
+        (true, _) => Some(ddl::TablePart::Indexes),
+        (_, true) => Some(ddl::TablePart::Constraints),
+        _ => None,
+    };
+    if table_part.is_some() {
+        if args.format != OutputFormat::Sql || args.compat == Compat::ClickHouse {
+            panic!("--indexes-only and --constraints-only are for an SQL dump for MySQL");
+        }
+        exclude_data = true;
+    }
+    if args.interactive {
+        let items = picker::load_items(&pool, &schema).await?;
+        match picker::pick(&schema, items, !exclude_ddl, !exclude_data).expect("Unable to run the interactive picker") {
+            Some(selection) => {
+                objects = Some(selection.objects);
+                exclude_ddl = !selection.include_ddl;
+                exclude_data = !selection.include_data;
+            }
+            None => {
+                eprintln!("Dump cancelled");
+                return Ok(DumpSummary::default());
+            }
+        }
+    }
+
+    //
+    // ClickHouse gets the tables and rows, it has nothing MySQL's views,
+    //   routines and triggers would translate to
+    //
+    if args.compat == Compat::ClickHouse {
+        if args.transactional_load != TransactionalLoad::Off || args.histograms || args.analyze_tables || args.audit_comments {
+            panic!("--transactional-load, --histograms, --analyze-tables and --audit-comments can't be used with --compat clickhouse");
+        }
+        if !exclude_ddl {
+            Logger::warn("Views, routines and triggers are left out of a ClickHouse dump");
+        }
+    }
+
+    // ALTER TABLE commits whatever transaction it is run in
+    if (args.defer_indexes || args.set_auto_increment) && args.transactional_load == TransactionalLoad::Dump {
+        panic!("--defer-indexes and --set-auto-increment can't be used with --transactional-load dump");
+    }
+
+    let mut base_options = ExportOptions {
+        single_row_inserts: args.single_row_inserts,
+        skip_unknown_datatypes: args.skip_unknown_datatypes,
+        escape_mode: args.compat.escape_mode(args.escape_mode),
+        compat: args.compat,
+        transcode_latin1: args.transcode_latin1,
+        uuid_columns: args.uuid_columns.clone(),
+        uuid_format: args.uuid_format,
+        json_format: args.json_format,
+        vector_format: args.vector_format,
+        table_stats: args.table_stats,
+        checksum: args.checksum,
+        objects,
+        schema_only: spec.schema_only,
+        row_filters: spec.row_filters,
+        locking_read,
+        table_hooks: config.table_hooks(),
+        row_orders: config.row_orders(),
+        query_tag: Some(args.connection.session_tag()),
+        buffer_bytes: args.buffer_mb * 1024 * 1024,
+        renames: Default::default(),
+        transformer,
+        comments: if args.skip_comments || args.compact { Comments::None } else { args.comments },
+        keep_foreign_key_checks: args.keep_foreign_key_checks,
+        transactional_load: args.transactional_load,
+        commit_rows: args.commit_every,
+        progress: args.progress_every,
+        check_sql: args.check_sql,
+        audit_comments: args.audit_comments,
+        strict_data: args.strict_data,
+        type_handlers: Default::default(),
+        type_overrides: args.type_overrides.clone(),
+        name_case,
+        strip_tablespaces: args.no_tablespaces,
+        // Without the data the indexes have to stay in the DDL
+        defer_indexes: args.defer_indexes && !exclude_data,
+        auto_increment: args.set_auto_increment,
+        // Filled in from --query and --materialize-views below
+        queries: Default::default(),
+        replaced_views: vec![],
+        // Filled in per schema by source_options
+        catalog: None,
+        definitions,
+        fingerprints: args.verify_script.as_ref().map(|_| verify::Fingerprints::default()),
+        qualify: args
+            .qualified_names
+            .then(|| args.renamed_schema_name.clone().unwrap_or_else(|| schema.clone())),
+    };
+
+    // The fingerprints are of the rows read, a transformer changes them on
+    //   the way out and without the rows there is nothing to check
+    if args.verify_script.is_some() && (exclude_data || base_options.transformer.is_some()) {
+        panic!("--verify-script can't be used with --no-data or a row transformer");
+    }
+
+    //
+    // Queries dumped as tables, described up front so a bad one fails early
+    //
+    let materialized = args
+        .materialize_views
+        .iter()
+        .map(|view| query::NamedQuery::view(&schema, view, &args.materialize_suffix));
+    let queries: Vec<query::NamedQuery> = args.queries.iter().cloned().chain(materialized).collect();
+    if !queries.is_empty() && (args.format != OutputFormat::Sql || args.compat == Compat::ClickHouse || args.data_shards.is_some()) {
+        panic!("--query and --materialize-views can only be used for an SQL dump for MySQL, without --data-shards");
+    }
+    if args.materialize_suffix.is_empty() {
+        base_options.replaced_views = args.materialize_views.clone();
+    }
+    for named in &queries {
+        if base_options.queries.contains_key(&named.name) {
+            panic!("There are two queries named {}", named.name);
+        }
+        let table = query::describe_query(&pool, &schema, named).await?;
+        base_options.queries.insert(named.name.clone(), table);
+    }
+
+    //
+    // The schemas to dump. Merged schemas all go into the new schema with
+    //   their objects prefixed so the names can't clash.
+    //
+    let sources: Vec<(String, rename::Renames)> = if args.merge_schemas.is_empty() {
+        vec![(
+            schema.clone(),
+            rename::Renames {
+                prefix: None,
+                tables: args.rename_tables.clone(),
+                columns: args.rename_columns.clone(),
+                name_case,
+            },
+        )]
+    } else {
+        if args.renamed_schema_name.is_none() {
+            panic!("--merge-schema needs --new-schema-name for the schema everything is merged into");
+        }
+        args.merge_schemas
+            .iter()
+            .map(|source| {
+                let prefix = args.merge_prefix.replace("{schema}", source);
+                (
+                    source.clone(),
+                    rename::Renames {
+                        prefix: Some(prefix),
+                        tables: args.rename_tables.clone(),
+                        columns: args.rename_columns.clone(),
+                        name_case,
+                    },
+                )
+            })
+            .collect()
+    };
+
+    if let Some(shards) = args.data_shards {
+        if shards == 0 || args.output_file.is_none() || args.format != OutputFormat::Sql || archive.is_some() || sources.len() > 1 {
+            panic!("--data-shards needs at least 1 shard and an --output-file for an SQL dump, not an archive or merged schemas");
+        }
+    }
+    if let Some(buckets) = args.table_buckets {
+        if buckets == 0 || args.output_file.is_none() || args.format != OutputFormat::Sql || archive.is_some() || sources.len() > 1 {
+            panic!("--table-buckets needs at least 1 bucket and an --output-file for an SQL dump, not an archive or merged schemas");
+        }
+    }
+
+    //
+    // A date range across every table with the slice column
+    //
+    if let Some(column) = &args.slice_column {
+        if args.slice_from.is_none() && args.slice_to.is_none() {
+            panic!("--slice-column needs --from, --to or both");
+        }
+        base_options.row_filters =
+            slice::slice_rows(&pool, &sources[0].0, column, args.slice_from.as_deref(), args.slice_to.as_deref()).await?;
+        Logger::info(format!("Slicing {} tables on {}", base_options.row_filters.len(), column));
+    }
+
+    //
+    // A representative share of every table, of the slice if there is one
+    //
+    if args.sample_every == Some(0) {
+        panic!("--sample-every needs at least 1");
+    }
+    let sample_rate = args.sample.or(args.sample_every.map(sample::SampleRate::Every));
+    if let Some(rate) = sample_rate {
+        let catalog = args.connection.load_catalog(&pool, &sources[0].0).await?;
+        base_options.row_filters = sample::sample_rows(&catalog, rate, &base_options.row_filters);
+        Logger::info(format!("Sampling {} tables", base_options.row_filters.len()));
+    }
+
+    //
+    // Show the data queries and how the server plans to run them, for the
+    //   export to be vetted before it is run for real
+    //
+    if args.explain {
+        let mut statements = 0;
+        for (source, renames) in &sources {
+            let options = source_options(&pool, &args, &base_options, source, renames, source == &sources[0].0, sample_rate).await?;
+            statements += explain::explain_data(&pool, &mut writer, thread_count, source, &options).await?;
+        }
+        write_footer(&mut writer);
+        eprintln!("Explained {} data queries", statements);
+        for hook in &args.post_sql {
+            hooks::run_sql_hook(&pool, "post-sql", hook).await?;
+        }
+        return Ok(DumpSummary::default());
+    }
+
+    //
+    // Give up now rather than hours in when the dump won't fit. A tar archive
+    //   also needs room for the staged dump and the files split from it.
+    //
+    let check_space = !args.skip_space_check && !exclude_data && args.format != OutputFormat::Kafka;
+    if let (true, Some(output_file)) = (check_space, &args.output_file) {
+        let schemas: Vec<&str> = sources.iter().map(|s| s.0.as_str()).collect();
+        let dumped = space::estimate_dump_bytes(&pool, &schemas, &base_options).await?;
+        let mut needed = if compression != compress::Compression::None && !args.format.is_directory() {
+            dumped / space::COMPRESSION_RATIO
+        } else {
+            dumped
+        };
+        if archive.is_some() {
+            needed += 2 * dumped;
+        }
+        if let Some(free) = space::free_bytes(output_file) {
+            if needed > free {
+                panic!(
+                    "The dump is estimated at {} but only {} is free for {}, use --skip-space-check to write it anyway",
+                    stats::format_bytes(needed),
+                    stats::format_bytes(free),
+                    output_file
+                );
+            }
+            Logger::debug(format!("The dump is estimated at {}, {} is free", stats::format_bytes(needed), stats::format_bytes(free)));
+        }
+    }
+
+    //
+    // A MySQL Shell dump is a directory of its own files
+    //
+    if args.format == OutputFormat::MysqlShell {
+        let dir = args.output_file.as_deref().expect("--format mysqlsh needs --output-file for the dump directory");
+        if sources.len() > 1 {
+            panic!("--format mysqlsh can't be used with --merge-schema");
+        }
+        let mut options = base_options.clone();
+        options.renames = sources[0].1.clone();
+        let summary = shell::write_shell_dump(&pool, dir, &schema, &options, !exclude_ddl, !exclude_data).await?;
+        eprintln!(
+            "Wrote a MySQL Shell dump of {} to {}, {} tables, {} rows",
+            schema, dir, summary.tables, summary.rows
+        );
+        for hook in &args.post_sql {
+            hooks::run_sql_hook(&pool, "post-sql", hook).await?;
+        }
+        return Ok(summary);
+    }
+
+    //
+    // Arrow files and BigQuery bundles hold only rows, files per table
+    //
+    if matches!(args.format, OutputFormat::Arrow | OutputFormat::BigQuery) {
+        let dir = args.output_file.as_deref().expect("--format arrow and bigquery need --output-file for the directory");
+        if sources.len() > 1 {
+            panic!("--format arrow and bigquery can't be used with --merge-schema");
+        }
+        let mut options = base_options.clone();
+        options.renames = sources[0].1.clone();
+        let summary = match args.format {
+            OutputFormat::Arrow => write_arrow(&pool, dir, &schema, &options).await?,
+            _ => bigquery::write_bigquery_dir(&pool, dir, &schema, &options).await?,
+        };
+        eprintln!("Wrote {} rows of {} tables to {}", summary.rows, summary.tables, dir);
+        for hook in &args.post_sql {
+            hooks::run_sql_hook(&pool, "post-sql", hook).await?;
+        }
+        return Ok(summary);
+    }
+
+    //
+    // Rows published to Kafka, nothing is written
+    //
+    if args.format == OutputFormat::Kafka {
+        let brokers = args.kafka_brokers.as_deref().expect("--format kafka needs --kafka-brokers");
+        if sources.len() > 1 {
+            panic!("--format kafka can't be used with --merge-schema");
+        }
+        let settings: Vec<(String, String)> = args
+            .kafka_options
+            .iter()
+            .map(|o| match o.split_once('=') {
+                Some((key, value)) => (key.trim().to_string(), value.trim().to_string()),
+                None => panic!("Invalid --kafka-option {}, expected key=value", o),
+            })
+            .collect();
+        let mut options = base_options.clone();
+        options.renames = sources[0].1.clone();
+        let summary = publish_kafka(&pool, &schema, &options, brokers, &args.kafka_topic, &settings).await?;
+        eprintln!("Published {} rows of {} tables to Kafka", summary.rows, summary.tables);
+        for hook in &args.post_sql {
+            hooks::run_sql_hook(&pool, "post-sql", hook).await?;
+        }
+        return Ok(summary);
+    }
+
+    //
+    // XML has no SQL, header or trailer, only the document mysqldump writes
+    //
+    if args.format == OutputFormat::Xml {
+        if sources.len() > 1 || args.append {
+            panic!("--format xml can't be used with --merge-schema or --append");
+        }
+        let mut options = base_options.clone();
+        options.renames = sources[0].1.clone();
+        let database = args.renamed_schema_name.clone().unwrap_or_else(|| schema.clone());
+        let summary = xml::write_xml(&pool, &mut writer, &schema, &database, &options, !exclude_ddl, !exclude_data).await?;
+        write_footer(&mut writer);
+        eprintln!("Wrote {} as XML, {} tables, {} views, {} rows", schema, summary.tables, summary.views, summary.rows);
+        for hook in &args.post_sql {
+            hooks::run_sql_hook(&pool, "post-sql", hook).await?;
+        }
+        return Ok(summary);
+    }
 
     //
     // Start writing the 'file', header and whatever other statements required
     //
-    write_header(&mut writer, &schema, &args.url);
+    if let Some(section) = &args.section {
+        write_section_begin(&mut writer, section);
+    }
+    let header_schema = sources.iter().map(|s| s.0.as_str()).collect::<Vec<&str>>().join(", ");
+    if base_options.comments >= Comments::Normal {
+        write_header(&mut writer, &header_schema, &args.connection.url);
+    }
+    if base_options.comments > Comments::None {
+        let mut metadata = metadata::server_metadata(&pool, &header_schema).await?;
+        if let Some(target) = &args.renamed_schema_name {
+            metadata.set("target_schema", target);
+        }
+        metadata.set("compat", format!("{:?}", args.compat).to_lowercase());
+        metadata.set("escape_mode", format!("{:?}", base_options.escape_mode).to_lowercase());
+        metadata.set("use_db", !args.no_use_db);
+        metadata.set("qualified_names", args.qualified_names);
+        metadata.set("foreign_key_checks", args.keep_foreign_key_checks);
+        metadata.set("ddl", !exclude_ddl);
+        metadata.set("data", !exclude_data);
+        metadata.set("flags", recorded_flags());
+        metadata.write(&mut writer);
+    }
+    if let Some(client_charset) = args.output_encoding.mysql_charset() {
+        writer.println(format!("/*!40101 SET NAMES {} */;", client_charset).as_str());
+    }
+    let charset = schema_charset(&pool, &schema).await?;
+    let disable_check = !base_options.keep_foreign_key_checks && !args.compact && args.compat.has_foreign_keys();
     write_prefix(
         &mut writer,
         &schema,
-        args.renamed_schema_name,
+        args.renamed_schema_name.clone(),
         args.create_schema,
-        true,
+        !args.no_use_db,
+        disable_check,
+        args.compat,
+        base_options.escape_mode,
+        charset.as_ref(),
     );
 
+    let mut summary = DumpSummary::default();
+    // What went to the --data-shards and --table-buckets files, the trailer
+    //   only counts what is in this file
+    let mut split_summary = DumpSummary::default();
+    for (source, renames) in &sources {
+        let options = source_options(&pool, &args, &base_options, source, renames, source == &sources[0].0, sample_rate).await?;
+
+        // No CREATE TABLE is written, so the trailer counts no tables
+        if let Some(part) = table_part {
+            let count = export_table_parts(&pool, &mut writer, source, &options, part).await?;
+            Logger::info(format!("Wrote the {} of {} tables of {}", part.name(), count, source));
+            continue;
+        }
+
+        //
+        // The order of the writes should be as follows:
+        // 1. Base tables
+        // 2. Views (require tables)
+        // 3. Stored procedures and functions
+        // 4. Triggers
+        //
+        // With --table-buckets the tables and their rows go into the bucket
+        //   files and the output file gets the rest
+        //
+        if let Some(buckets) = args.table_buckets {
+            let output_file = args.output_file.as_deref().unwrap();
+            let output = split_output(&args, &schema, format!("{}.buckets", output_file), compression, disable_check);
+            let after_file = Path::new(output_file).file_name().unwrap().to_string_lossy();
+            let (tables, rows) =
+                bucket::export_table_buckets(&pool, &output, source, &format!("../{}", after_file), buckets, &options, !exclude_ddl, !exclude_data)
+                    .await?;
+            split_summary.tables += tables;
+            split_summary.rows += rows;
+        } else if !exclude_ddl {
+            summary.tables += export_tables(&pool, &mut writer, source, &options).await?;
+        }
+        if !exclude_ddl && args.compat != Compat::ClickHouse {
+            summary.views += export_views(&pool, &mut writer, source, &options).await?;
+            summary.routines += export_stored_procs(&pool, &mut writer, source, &options).await?;
+            summary.routines += export_functions(&pool, &mut writer, source, &options).await?;
+            summary.triggers += export_triggers(&pool, &mut writer, source, &options).await?;
+        }
+
+        //
+        // After the DDL is written write the data.
+        // We turn off constraints until after the data is loaded so that
+        //   we don't run into any constraint violations during the load
+        //
+        if let (false, Some(shards)) = (exclude_data, args.data_shards) {
+            let output_file = args.output_file.as_deref().unwrap();
+            let output = split_output(&args, &schema, format!("{}.shards", output_file), compression, disable_check);
+            let ddl_file = Path::new(output_file).file_name().unwrap().to_string_lossy();
+            split_summary.rows += shard::export_data_shards(&pool, &output, source, &format!("../{}", ddl_file), shards, &options).await?;
+        } else if !exclude_data && args.table_buckets.is_none() {
+            summary.rows += export_data(&pool, &mut writer, thread_count, source, &options).await?;
+        }
+
+        //
+        // Statistics are rebuilt once the data is in place
+        //
+        if args.histograms {
+            stats::export_histograms(&pool, &mut writer, source, &options).await?;
+        }
+    }
+
     //
-    // The order of the writes should be as follows:
-    // 1. Base tables
-    // 2. Views (require tables)
-    // 3. Stored procedures and functions
-    // 4. Triggers
+    // Queries come last, after everything they read from
     //
-    if !args.exclude_ddl {
-        export_tables(&pool, &mut writer, &schema).await?;
-        export_views(&pool, &mut writer, &schema).await?;
-        export_stored_procs(&pool, &mut writer, &schema).await?;
-        export_functions(&pool, &mut writer, &schema).await?;
-        export_triggers(&pool, &mut writer, &schema).await?;
+    if !base_options.queries.is_empty() {
+        summary.rows += export_queries(&pool, &mut writer, &schema, &base_options, !exclude_ddl, !exclude_data).await?;
+        if !exclude_ddl {
+            summary.tables += base_options.queries.len();
+        }
     }
 
     //
-    // After the DDL is written write the data.
-    // We turn off constraints until after the data is loaded so that
-    //   we don't run into any constraint violations during the load
+    // Fresh statistics once all of the data is in
     //
-    if !args.exclude_data {
-        export_data(
-            &pool,
-            &mut writer,
-            thread_count,
-            &schema,
-            args.single_row_inserts,
-            args.skip_unknown_datatypes,
-        )
-        .await?;
+    if (args.analyze_tables || args.optimize_tables) && !exclude_data {
+        for (source, renames) in &sources {
+            let mut options = base_options.clone();
+            options.renames = renames.clone();
+            stats::write_table_maintenance(&pool, &mut writer, source, &options, args.analyze_tables, args.optimize_tables).await?;
+        }
     }
 
-    write_postfix(&mut writer, true);
+    if let (Some(verify_script), Some(fingerprints)) = (&args.verify_script, &base_options.fingerprints) {
+        // Without the use statement the dump is loaded into the current schema
+        let target_schema = (!args.no_use_db).then(|| args.renamed_schema_name.as_ref().unwrap_or(&schema));
+        verify::write_verify_script(fingerprints, &schema, target_schema.map(String::as_str), verify_script);
+    }
+
+    write_postfix(&mut writer, disable_check);
+    // A compact dump can't be checked for completeness, there is no trailer
+    if !args.compact {
+        write_trailer(&mut writer, &summary);
+    }
+    if let Some(section) = &args.section {
+        write_section_end(&mut writer, section);
+    }
     write_footer(&mut writer);
+    if let Some(path) = &archive {
+        drop(writer);
+        archive::write_archive(path, compression, compress_threads).unwrap_or_else(|e| panic!("Unable to write the archive {}: {}", path, e));
+    }
+    for hook in &args.post_sql {
+        hooks::run_sql_hook(&pool, "post-sql", hook).await?;
+    }
+
+    summary.tables += split_summary.tables;
+    summary.rows += split_summary.rows;
+    Ok(summary)
+}
+
+#[cfg(feature = "scripting")]
+fn script_transformer(config: &config::Config) -> Option<transform::Transformer> {
+    if !config.has_scripts() {
+        return None;
+    }
+    let scripts = script::ScriptTransformer::new(config).unwrap_or_else(|e| panic!("{}", e));
+    Some(transform::Transformer::new(scripts))
+}
+
+#[cfg(not(feature = "scripting"))]
+fn script_transformer(config: &config::Config) -> Option<transform::Transformer> {
+    if config.has_scripts() {
+        panic!("The config file has filter or transform scripts but dbdump was built without the scripting feature");
+    }
+    None
+}
+
+#[cfg(feature = "arrow")]
+async fn write_arrow(
+    pool: &Pool<MySql>,
+    dir: &str,
+    schema: &str,
+    options: &ExportOptions,
+) -> Result<DumpSummary, sqlx::Error> {
+    feather::write_arrow_dir(pool, dir, schema, options).await
+}
+
+#[cfg(not(feature = "arrow"))]
+async fn write_arrow(
+    _pool: &Pool<MySql>,
+    _dir: &str,
+    _schema: &str,
+    _options: &ExportOptions,
+) -> Result<DumpSummary, sqlx::Error> {
+    panic!("--format arrow needs dbdump built with the arrow feature");
+}
+
+#[cfg(feature = "kafka")]
+async fn publish_kafka(
+    pool: &Pool<MySql>,
+    schema: &str,
+    options: &ExportOptions,
+    brokers: &str,
+    topic: &str,
+    settings: &[(String, String)],
+) -> Result<DumpSummary, sqlx::Error> {
+    kafka::publish_rows(pool, schema, options, brokers, topic, settings).await
+}
+
+#[cfg(not(feature = "kafka"))]
+async fn publish_kafka(
+    _pool: &Pool<MySql>,
+    _schema: &str,
+    _options: &ExportOptions,
+    _brokers: &str,
+    _topic: &str,
+    _settings: &[(String, String)],
+) -> Result<DumpSummary, sqlx::Error> {
+    panic!("--format kafka needs dbdump built with the kafka feature");
+}
+
+//
+// The command line as run, for the metadata block. Passwords are masked,
+//   whether given with --pass or in a url.
+//
+fn recorded_flags() -> String {
+    let mut flags = vec![];
+    let mut mask_next = false;
+    for arg in std::env::args().skip(1) {
+        let arg = if mask_next {
+            "****".to_string()
+        } else if let Some(mut url) = Url::parse(&arg).ok().filter(|u| u.password().is_some()) {
+            let _ = url.set_password(Some("****"));
+            url.to_string()
+        } else if arg.starts_with("--pass=") {
+            "--pass=****".to_string()
+        } else {
+            arg
+        };
+        mask_next = !mask_next && arg == "--pass";
+        if arg.contains(char::is_whitespace) {
+            flags.push(format!("'{}'", arg));
+        } else {
+            flags.push(arg);
+        }
+    }
+    flags.join(" ")
+}
+
+// Whether an existing dump file already has a section of this name
+fn section_exists(filename: &str, section: &str) -> bool {
+    use std::io::BufRead;
+    let marker = format!("{}{}", SECTION_BEGIN, section);
+    match compress::open_input(filename) {
+        Ok(reader) => reader.lines().map_while(Result::ok).any(|line| line == marker),
+        Err(_) => false,
+    }
+}
 
+async fn run_restore(args: RestoreArgs) -> Result<(), sqlx::Error> {
+    let pool = args.connection.connect(1, None).await?;
+    let reader = compress::open_input(&args.input_file).expect("Unable to open the dump file");
+    let options = restore::RestoreOptions {
+        schema_rename: args.restore_as.as_deref().map(restore::SchemaRename::new),
+        tables: args.tables,
+        skip_ddl: args.exclude_ddl,
+        skip_data: args.exclude_data,
+        section: args.section,
+    };
+    let count = restore::restore(&pool, reader, options).await?;
+    eprintln!("Executed {} statements from {}", count, args.input_file);
     Ok(())
 }
+
+fn run_normalize(args: &NormalizeArgs) {
+    let options = normalize::NormalizeOptions {
+        strip_definer: args.strip_definer,
+        target_schema: args.renamed_schema_name.clone(),
+    };
+    let compression = compress::Compression::from_path(&args.output_file);
+    let mut writer = StdWriter::open(Some(args.output_file.clone()), compression, num_cpus::get(), false);
+    let summary = normalize::normalize(&args.input_file, &mut writer, &options).unwrap_or_else(|e| panic!("Unable to normalize {}: {}", args.input_file, e));
+    write_footer(&mut writer);
+    eprintln!(
+        "Normalized {} into {}, {} tables, {} views, {} routines, {} triggers, {} rows",
+        args.input_file, args.output_file, summary.tables, summary.views, summary.routines, summary.triggers, summary.rows
+    );
+}
+
+fn run_convert(args: &ConvertArgs) {
+    let summary = convert::convert(&args.input_file, &args.output_dir, args.format)
+        .unwrap_or_else(|e| panic!("Unable to convert {}: {}", args.input_file, e));
+    eprintln!("Converted {} rows of {} tables into {}", summary.rows, summary.tables, args.output_dir);
+}
+
+fn run_split(input_file: &str, output_dir: &str) {
+    let summary = split::split(input_file, output_dir).unwrap_or_else(|e| panic!("Unable to split {}: {}", input_file, e));
+    eprintln!(
+        "Split {} into {}, {} tables, {} views, {} routines, {} triggers, {} rows",
+        input_file, output_dir, summary.tables, summary.views, summary.routines, summary.triggers, summary.rows
+    );
+}
+
+fn run_validate(input_file: &str) {
+    let reader = compress::open_input(input_file).expect("Unable to open the dump file");
+    let validation = validate::validate(reader);
+    for problem in &validation.problems {
+        println!("{}", problem);
+    }
+    if !validation.problems.is_empty() {
+        std::process::exit(1);
+    }
+    eprintln!("{} is complete, {} statements", input_file, validation.statements);
+    for trailer in &validation.trailers {
+        eprintln!("{}", trailer);
+    }
+}
+
+// Connect to both sides of a comparison
+async fn connect_pair(args: &CompareArgs) -> Result<(Pool<MySql>, String, Pool<MySql>, String), sqlx::Error> {
+    let schema = args.connection.schema();
+    let target = ConnectionArgs {
+        url: args.target_url.clone(),
+        username: None,
+        password: None,
+        init_commands: args.connection.init_commands.clone(),
+        job_id: args.connection.job_id.clone(),
+        trace_queries: args.connection.trace_queries,
+        metadata_cache: args.connection.metadata_cache.clone(),
+        schema: args.target_schema.clone(),
+    };
+    let target_schema = target.schema_opt().unwrap_or_else(|| schema.clone());
+    let source_pool = args.connection.connect(1, None).await?;
+    let target_pool = target.connect(1, None).await?;
+    Ok((source_pool, schema, target_pool, target_schema))
+}
+
+async fn run_diff(args: CompareArgs) -> Result<(), sqlx::Error> {
+    let (source_pool, schema, target_pool, target_schema) = connect_pair(&args).await?;
+    let source = args.connection.load_catalog(&source_pool, &schema).await?;
+    let target = catalog::load_catalog(&target_pool, &target_schema).await?;
+    let differences = diff::diff_catalogs(&source, &target);
+    for difference in &differences {
+        println!("{}", difference);
+    }
+    if differences.is_empty() {
+        eprintln!("Schemas {} and {} are identical", schema, target_schema);
+    } else {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+async fn run_list(args: ListArgs) -> Result<(), sqlx::Error> {
+    let pool = args.connection.connect(1, None).await?;
+    match args.connection.schema_opt() {
+        Some(schema) => list::print_tables(&list::list_tables(&pool, &schema).await?),
+        None => list::print_schemas(&list::list_schemas(&pool).await?),
+    }
+    Ok(())
+}
+
+async fn run_verify(args: CompareArgs) -> Result<(), sqlx::Error> {
+    let (source_pool, schema, target_pool, target_schema) = connect_pair(&args).await?;
+    let mismatches = verify::verify_target(&source_pool, &schema, &target_pool, &target_schema).await?;
+    for mismatch in &mismatches {
+        println!("{}", mismatch);
+    }
+    if mismatches.is_empty() {
+        eprintln!("{} matches {}", target_schema, schema);
+    } else {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+async fn run_sync(args: SyncArgs) -> Result<(), sqlx::Error> {
+    if args.chunk_rows == 0 {
+        panic!("--chunk-rows needs at least 1");
+    }
+    let (source_pool, schema, target_pool, target_schema) = connect_pair(&args.compare).await?;
+    let catalog = args.compare.connection.load_catalog(&source_pool, &schema).await?;
+    let options = ExportOptions {
+        objects: (!args.tables.is_empty()).then_some(args.tables),
+        name_case: sql::NameCase::of_server(&source_pool).await?,
+        ..Default::default()
+    };
+    let mut writer = StdWriter::new(args.output_file);
+    let summary = sync::sync_schema(&source_pool, &catalog, &target_pool, &target_schema, &mut writer, args.chunk_rows, &options).await?;
+    writer.flush();
+    eprintln!(
+        "{} of {} chunks in {} tables differ: {} inserts, {} updates, {} deletes",
+        summary.differing_chunks, summary.chunks, summary.tables, summary.inserts, summary.updates, summary.deletes
+    );
+    Ok(())
+}
+
+async fn run_estimate(args: ConnectionArgs) -> Result<(), sqlx::Error> {
+    let pool = args.connect(1, None).await?;
+    let schema = args.schema();
+    list::print_estimate(&schema, &list::list_tables(&pool, &schema).await?);
+    Ok(())
+}
+
+// Exits with 1 when there are errors, so it can gate a CI job
+async fn run_lint(args: LintArgs) -> Result<(), sqlx::Error> {
+    let pool = args.connection.connect(1, None).await?;
+    let schema = args.connection.schema();
+    let catalog = args.connection.load_catalog(&pool, &schema).await?;
+    let charset = schema_charset(&pool, &schema).await?;
+    let findings = lint::lint_schema(&pool, &catalog, charset.as_ref()).await?;
+    let mut writer = StdWriter::new(args.output_file);
+    lint::write_findings(&mut writer, &schema, &findings, args.format);
+    writer.flush();
+    let errors = findings.iter().filter(|f| f.severity == lint::Severity::Error).count();
+    eprintln!("{} findings, {} errors", findings.len(), errors);
+    if errors > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+async fn run_profile(args: ProfileArgs) -> Result<(), sqlx::Error> {
+    let pool = args.connection.connect(1, None).await?;
+    let schema = args.connection.schema();
+    let catalog = args.connection.load_catalog(&pool, &schema).await?;
+    if let Some(table) = args.tables.iter().find(|t| !catalog.tables.iter().any(|c| &c.name == *t)) {
+        panic!("{} has no table named {}", schema, table);
+    }
+    let report = profile::profile_schema(&pool, &catalog, &args.tables, args.sample_rows).await?;
+    let mut writer = StdWriter::new(args.output_file);
+    profile::write_profile(&mut writer, &report, args.format);
+    writer.flush();
+    Ok(())
+}
+
+async fn run_bench(args: BenchArgs) -> Result<(), sqlx::Error> {
+    let max_connections = if args.max_connections == 0 { num_cpus::get() } else { args.max_connections };
+    let pool = args.connection.connect(max_connections as u32 + 1, None).await?;
+    let schema = args.connection.schema();
+    let table = match args.table {
+        Some(table) => table,
+        None => bench::largest_table(&pool, &schema)
+            .await?
+            .unwrap_or_else(|| panic!("{} has no tables to benchmark", schema)),
+    };
+    let report = bench::run_bench(&pool, &schema, &table, max_connections, args.rows).await?;
+    let mut writer = StdWriter::new(args.output_file);
+    bench::write_report(&mut writer, &report);
+    writer.flush();
+    Ok(())
+}
+
+//
+// Check a flag combination produces a dump that restores to the schema it
+//   came from: dump it, load the dump into a scratch schema on the same
+//   server and compare structure, row counts and checksums. Flags that leave
+//   things out on purpose, e.g. --no-data or --exclude, show up as
+//   differences. Exits with 1 when there are any, so it can gate a CI job.
+//
+async fn run_selftest(args: SelftestArgs) -> Result<(), sqlx::Error> {
+    let mut dump = args.dump;
+    if dump.interactive || dump.format != OutputFormat::Sql || dump.output_file.as_deref().is_some_and(archive::is_archive) {
+        panic!("selftest needs a plain SQL dump it can restore");
+    }
+    let schema = dump.connection.schema();
+    let scratch = args.scratch_schema.unwrap_or_else(|| format!("{}_selftest", schema));
+    if scratch == schema {
+        panic!("The scratch schema must differ from {}", schema);
+    }
+    let pool = dump.connection.connect(1, None).await?;
+    if schema_charset(&pool, &scratch).await?.is_some() {
+        panic!("{} already exists, drop it or pick another with --scratch-schema", scratch);
+    }
+    // The dump is removed afterwards unless it was asked for
+    let temporary = dump.output_file.is_none();
+    let output_file = dump.output_file.clone().unwrap_or_else(|| {
+        std::env::temp_dir()
+            .join(format!("dbdump-selftest-{}.sql", std::process::id()))
+            .to_string_lossy()
+            .to_string()
+    });
+    dump.output_file = Some(output_file.clone());
+    Logger::info(format!("Dumping {} to {}", schema, output_file));
+    run_dump_notified(dump.clone()).await?;
+
+    //
+    // Restore over a connection whose default schema is the scratch one, so
+    //   a dump without USE statements can't land in the source
+    //
+    let create = match schema_charset(&pool, &schema).await? {
+        Some(charset) => format!(
+            "create database {} default character set {} collate {}",
+            quote_identifier(&scratch),
+            charset.character_set,
+            charset.collation
+        ),
+        None => format!("create database {}", quote_identifier(&scratch)),
+    };
+    pool.execute(create.as_str()).await?;
+    let mut url = dump.connection.parsed_url();
+    url.set_path(&format!("/{}", scratch));
+    let scratch_connection = ConnectionArgs {
+        url: url.to_string(),
+        username: None,
+        password: None,
+        schema: Some(scratch.clone()),
+        ..dump.connection.clone()
+    };
+    let scratch_pool = scratch_connection.connect(1, None).await?;
+    Logger::info(format!("Restoring into {}", scratch));
+    let reader = compress::open_input(&output_file).expect("Unable to open the dump file");
+    let options = restore::RestoreOptions {
+        schema_rename: Some(restore::SchemaRename::new(&scratch)),
+        ..Default::default()
+    };
+    let restored = restore::restore(&scratch_pool, reader, options).await;
+
+    let mut differences = vec![];
+    if restored.is_ok() {
+        let source = dump.connection.load_catalog(&pool, &schema).await?;
+        let target = catalog::load_catalog(&pool, &scratch).await?;
+        differences = diff::diff_catalogs(&source, &target);
+        differences.extend(verify::verify_target(&pool, &schema, &pool, &scratch).await?);
+    }
+    if args.keep_scratch {
+        Logger::info(format!("Kept {} for inspection", scratch));
+    } else {
+        pool.execute(format!("drop database {}", quote_identifier(&scratch)).as_str()).await?;
+    }
+    if temporary {
+        let _ = std::fs::remove_file(&output_file);
+    }
+
+    let count = restored?;
+    for difference in &differences {
+        println!("{}", difference);
+    }
+    if differences.is_empty() {
+        eprintln!("Restored {} statements into {}, it matches {}", count, scratch, schema);
+    } else {
+        eprintln!("{} differences between {} and its restored copy", differences.len(), schema);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+async fn run_scan_pii(args: ScanPiiArgs) -> Result<(), sqlx::Error> {
+    let pool = args.connection.connect(1, None).await?;
+    let schema = args.connection.schema();
+    let catalog = args.connection.load_catalog(&pool, &schema).await?;
+    if let Some(table) = args.tables.iter().find(|t| !catalog.tables.iter().any(|c| &c.name == *t)) {
+        panic!("{} has no table named {}", schema, table);
+    }
+    let findings = pii::scan_schema(&pool, &catalog, &args.tables, args.sample_rows).await?;
+    let mut writer = StdWriter::new(args.output_file);
+    writer.print(&pii::suggested_config(&schema, &findings));
+    writer.flush();
+    eprintln!("Found {} columns that look like personal data", findings.len());
+    Ok(())
+}
+
+async fn run_seed(args: SeedArgs) -> Result<(), sqlx::Error> {
+    let pool = args.connection.connect(1, None).await?;
+    let schema = args.connection.schema();
+    let catalog = args.connection.load_catalog(&pool, &schema).await?;
+    let mut writer = StdWriter::new(args.output_file);
+    write_header(&mut writer, &schema, &args.connection.url);
+    let disable_check = args.compat.has_foreign_keys();
+    write_prefix(
+        &mut writer,
+        &schema,
+        None,
+        false,
+        true,
+        disable_check,
+        args.compat,
+        args.compat.escape_mode(EscapeMode::Backslash),
+        None,
+    );
+    seed::write_seed_data(&mut writer, &catalog, args.rows, args.random_seed, args.compat);
+    write_postfix(&mut writer, disable_check);
+    write_footer(&mut writer);
+    Ok(())
+}
+
+//
+// Dump each shard in turn into the same file, every one as a section named
+//   after the shard. With the schemas layout each goes into its own schema,
+//   created by the dump. With merged the first shard's dump creates the
+//   tables and the others only add their rows.
+//
+async fn run_federate(args: FederateArgs) -> Result<(), sqlx::Error> {
+    let dump = args.dump;
+    if dump.interactive || dump.section.is_some() || dump.data_shards.is_some() || dump.format != OutputFormat::Sql {
+        panic!("federate writes SQL sections named after the shards and can't be used with --interactive, --section, --data-shards or other formats");
+    }
+    match &dump.output_file {
+        Some(output_file) if !archive::is_archive(output_file) => {}
+        _ => panic!("federate appends every shard to one --output-file, which can't be a tar archive"),
+    }
+    if args.shards.len() < 2 {
+        panic!("federate needs at least two --shard");
+    }
+    for (i, shard) in args.shards.iter().enumerate() {
+        if args.shards[..i].iter().any(|s| s.name == shard.name) {
+            panic!("There are two shards named {}", shard.name);
+        }
+    }
+
+    // A failed shard leaves its section incomplete, the others are still
+    //   dumped
+    let mut failed = vec![];
+    for (i, shard) in args.shards.iter().enumerate() {
+        let mut shard_dump = dump.clone();
+        shard_dump.connection.url = shard.url(&dump.connection.url).unwrap_or_else(|e| panic!("{}", e));
+        shard_dump.section = Some(shard.name.clone());
+        shard_dump.append = dump.append || i > 0;
+        let schema = shard_dump.connection.schema();
+        let target = dump.renamed_schema_name.clone().unwrap_or_else(|| schema.clone());
+        match args.shard_layout {
+            federate::ShardLayout::Schemas => {
+                shard_dump.renamed_schema_name = Some(shard.schema(&target, &args.shard_suffix));
+                shard_dump.create_schema = true;
+            }
+            federate::ShardLayout::Merged => {
+                shard_dump.renamed_schema_name = Some(target);
+                if i > 0 {
+                    if dump.exclude_data {
+                        continue;
+                    }
+                    shard_dump.exclude_ddl = true;
+                }
+            }
+        }
+        Logger::info(format!("Dumping {} from shard {}", schema, shard.name));
+        if let Err(e) = run_dump_notified(shard_dump).await {
+            Logger::error(format!("Dump of shard {} failed: {}", shard.name, e));
+            failed.push(shard.name.clone());
+        }
+    }
+    if !failed.is_empty() {
+        let message = format!("The dumps of shards {} failed", failed.join(", "));
+        return Err(sqlx::Error::Io(std::io::Error::other(message)));
+    }
+    Ok(())
+}
+
+//
+// Daemon mode. The output file is a template ({schema} and {timestamp} are
+//   replaced) so every run gets its own file. A failed run is logged and the
+//   schedule carries on.
+//
+async fn run_watch(args: WatchArgs) -> Result<(), sqlx::Error> {
+    if args.dump.interactive {
+        panic!("--interactive can't be used with watch");
+    }
+    let schema = args.dump.connection.schema();
+    let template = args
+        .dump
+        .output_file
+        .clone()
+        .unwrap_or_else(|| "{schema}-{timestamp}.sql".to_string());
+    if !template.contains("{timestamp}") {
+        panic!("The output file for watch must contain {{timestamp}} so each dump gets its own file");
+    }
+
+    loop {
+        let started = Local::now();
+        let filename = schedule::render_filename(&template, &schema, &started);
+        let mut dump = args.dump.clone();
+        dump.output_file = Some(filename.clone());
+        match run_dump_notified(dump).await {
+            Ok(()) => {
+                Logger::info(format!("Dumped {} to {}", schema, filename));
+                let retention = schedule::Retention {
+                    keep_last: args.keep_last,
+                    keep_days: args.keep_days,
+                };
+                if !retention.is_empty() {
+                    match schedule::apply_retention(&template, &schema, retention, Local::now()) {
+                        Ok(removed) => removed
+                            .iter()
+                            .for_each(|file| Logger::info(format!("Removed old dump {}", file))),
+                        Err(e) => Logger::warn(format!("Unable to remove old dumps: {}", e)),
+                    }
+                }
+            }
+            Err(e) => Logger::error(format!("Dump of {} to {} failed: {}", schema, filename, e)),
+        }
+
+        let next = args.every.next_after(started);
+        Logger::info(format!("Next dump at {}", next));
+        if let Ok(wait) = (next - Local::now()).to_std() {
+            async_std::task::sleep(wait).await;
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use crate::escape::EscapeMode;
+use crate::std_writer::StdWriter;
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use sqlx::Row;
+use std::fmt::Display;
+
+pub const METADATA_PREFIX: &str = "-- dbdump-meta: ";
+
+// Bumped when the meaning of a key changes
+pub const METADATA_VERSION: u32 = 1;
+
+//
+// Machine readable facts about a dump, written after the free text header as
+//   one `-- dbdump-meta: key=value` comment per entry. Restore reads them to
+//   load the dump the way it was written, e.g. without backslash escapes.
+//   Unknown keys are ignored so older versions can read newer dumps.
+//
+#[derive(Clone, Debug, Default)]
+pub struct Metadata {
+    entries: Vec<(String, String)>,
+}
+
+impl Metadata {
+    pub fn set<T: Display>(&mut self, key: &str, value: T) {
+        // Values are single line, anything else would end the comment
+        let value = value.to_string().replace(['\r', '\n'], " ");
+        match self.entries.iter_mut().find(|e| e.0 == key) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((key.to_string(), value)),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter().find(|e| e.0 == key).map(|e| e.1.as_str())
+    }
+
+    pub fn entries(&self) -> &[(String, String)] {
+        &self.entries
+    }
+
+    pub fn write(&self, writer: &mut StdWriter) {
+        for (key, value) in &self.entries {
+            writer.println(format!("{}{}={}", METADATA_PREFIX, key, value).as_str());
+        }
+    }
+
+    // Take in a comment line, returns false when it isn't metadata
+    pub fn read_line(&mut self, line: &str) -> bool {
+        match line.trim().strip_prefix(METADATA_PREFIX).and_then(|entry| entry.split_once('=')) {
+            Some((key, value)) => {
+                self.set(key.trim(), value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn escape_mode(&self) -> EscapeMode {
+        self.get("escape_mode").and_then(|m| m.parse().ok()).unwrap_or_default()
+    }
+}
+
+// The server settings that most often explain a failed or odd restore
+const SERVER_VARIABLES: [&str; 5] = ["sql_mode", "character_set_server", "collation_server", "time_zone", "lower_case_table_names"];
+
+//
+// The metadata describing the source server, the rest is added by the
+//   caller from the options used. The GTID set is recorded when the server
+//   has GTIDs enabled so a replica can be started from the dump.
+//
+pub async fn server_metadata(pool: &Pool<MySql>, schema: &str) -> Result<Metadata, sqlx::Error> {
+    let mut metadata = Metadata::default();
+    metadata.set("metadata_version", METADATA_VERSION);
+    metadata.set("tool_version", env!("CARGO_PKG_VERSION"));
+    let (version,): (String,) = sqlx::query_as("select version()").fetch_one(pool).await?;
+    metadata.set("server_version", version);
+    let query = format!(
+        "select {}",
+        SERVER_VARIABLES.map(|v| format!("cast(@@global.{} as char)", v)).join(", ")
+    );
+    let row = sqlx::query(&query).fetch_one(pool).await?;
+    for (i, variable) in SERVER_VARIABLES.iter().enumerate() {
+        let value: Option<String> = row.try_get(i)?;
+        metadata.set(&format!("server.{}", variable), value.unwrap_or_default());
+    }
+    metadata.set("schema", schema);
+
+    // MySQL keeps the executed set in gtid_executed, MariaDB in gtid_current_pos
+    for query in ["select @@global.gtid_executed", "select @@global.gtid_current_pos"] {
+        if let Ok((Some(gtid),)) = sqlx::query_as::<_, (Option<String>,)>(query).fetch_one(pool).await {
+            if !gtid.is_empty() {
+                metadata.set("gtid_executed", gtid);
+            }
+            break;
+        }
+    }
+    Ok(metadata)
+}
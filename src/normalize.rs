@@ -0,0 +1,388 @@
+use crate::compress::open_input;
+use crate::escape::EscapeMode;
+use crate::logger::Logger;
+use crate::metadata::{Metadata, METADATA_VERSION};
+use crate::rename;
+use crate::restore::StatementReader;
+use crate::sql::{quote_identifier, Compat};
+use crate::std_writer::StdWriter;
+use crate::{write_header, write_postfix, write_prefix, write_trailer, DumpSummary, SchemaCharset};
+use regex::Regex;
+use std::io;
+
+//
+// Re-emit a dump written by mysqldump the way dbdump writes one, so dumps
+//   from elsewhere get the same layout, trailer and rewrites as our own.
+//   The versioned /*!NNNNN ... */ wrappers are unwrapped, mysqldump's
+//   session juggling (LOCK TABLES, DISABLE KEYS, @OLD_ variables, the
+//   placeholder tables for views) is dropped and the inserts are rewritten
+//   with a column list.
+//
+// The file is read twice, once for the DDL and once for the data, so the
+//   rows are never held in memory.
+//
+#[derive(Clone, Debug, Default)]
+pub struct NormalizeOptions {
+    // Remove DEFINER=user@host so objects are created by the loading user
+    pub strip_definer: bool,
+    // Schema the dump is loaded into instead of the one it names
+    pub target_schema: Option<String>,
+}
+
+#[derive(Default)]
+struct Ddl {
+    schema: Option<String>,
+    charset: Option<SchemaCharset>,
+    // Session settings from before the first object, e.g. SET TIME_ZONE
+    session: Vec<String>,
+    tables: Vec<(String, String)>,
+    views: Vec<(String, String)>,
+    routines: Vec<(String, String)>,
+    triggers: Vec<(String, String)>,
+    // Statements normalize doesn't recognise, kept as they are
+    other: Vec<String>,
+}
+
+struct Patterns {
+    versioned: Regex,
+    create: Regex,
+    insert: Regex,
+    definer: Regex,
+    database: Regex,
+}
+
+impl Patterns {
+    fn new() -> Self {
+        let name = r"(?:`(?:[^`]|``)+`|\w+)";
+        Patterns {
+            versioned: Regex::new(r"(?s)/\*!\d*\s?(.*?)\s*\*/").unwrap(),
+            create: Regex::new(&format!(
+                r"(?is)^create\s+(?:or\s+replace\s+)?(?:algorithm\s*=\s*\w+\s+)?(?:definer\s*=\s*\S+\s+)?(?:sql\s+security\s+\w+\s+)?(table|view|procedure|function|trigger)\s+(?:if\s+not\s+exists\s+)?((?:{name}\.)?{name})"
+            ))
+            .unwrap(),
+            insert: insert_pattern(),
+            definer: Regex::new(r"(?i)\s*\bDEFINER\s*=\s*(?:`(?:[^`]|``)*`|'(?:[^']|'')*'|[^\s@]+)@(?:`(?:[^`]|``)*`|'(?:[^']|'')*'|[^\s*]+)").unwrap(),
+            database: Regex::new(r"(?is)^create\s+(?:database|schema)\s+(?:if\s+not\s+exists\s+)?((?:`(?:[^`]|``)+`|\w+))").unwrap(),
+        }
+    }
+
+    // mysqldump hides version dependent syntax in /*!50003 ... */ comments
+    fn unwrap_versioned(&self, statement: &str) -> String {
+        if statement.contains("/*!") {
+            self.versioned.replace_all(statement, "$1").trim().to_string()
+        } else {
+            statement.to_string()
+        }
+    }
+}
+
+//
+// Matches the start of an insert up to its rows: the verb, the table name
+//   and the column list when there is one
+//
+pub fn insert_pattern() -> Regex {
+    let name = r"(?:`(?:[^`]|``)+`|\w+)";
+    Regex::new(&format!(
+        r"(?is)^(insert\s+ignore|insert|replace)\s+into\s+((?:{name}\.)?{name})\s*(\([^()]*\))?\s*values\s*"
+    ))
+    .unwrap()
+}
+
+pub fn normalize(input_file: &str, writer: &mut StdWriter, options: &NormalizeOptions) -> io::Result<DumpSummary> {
+    let patterns = Patterns::new();
+    let ddl = read_ddl(input_file, &patterns)?;
+    let source_schema = ddl.schema.clone().unwrap_or_default();
+    let target_schema = options.target_schema.clone().or(ddl.schema.clone());
+    let rewrite = |statement: &str| {
+        let statement = match &ddl.schema {
+            Some(schema) => rename::strip_schema(statement, schema),
+            None => statement.to_string(),
+        };
+        if options.strip_definer {
+            patterns.definer.replace_all(&statement, "").into_owned()
+        } else {
+            statement
+        }
+    };
+
+    write_header(writer, target_schema.as_deref().unwrap_or(""), &format!("normalized from {}", input_file));
+    let mut metadata = Metadata::default();
+    metadata.set("metadata_version", METADATA_VERSION);
+    metadata.set("tool_version", env!("CARGO_PKG_VERSION"));
+    metadata.set("schema", &source_schema);
+    if let Some(target) = &options.target_schema {
+        metadata.set("target_schema", target);
+    }
+    metadata.set("escape_mode", "backslash");
+    metadata.set("normalized_from", input_file);
+    metadata.write(writer);
+    write_prefix(
+        writer,
+        &source_schema,
+        options.target_schema.clone(),
+        true,
+        target_schema.is_some(),
+        true,
+        Compat::Mysql,
+        EscapeMode::Backslash,
+        ddl.charset.as_ref(),
+    );
+    for statement in &ddl.session {
+        writer.println(format!("{};", statement).as_str());
+    }
+
+    let mut summary = DumpSummary::default();
+    for (name, statement) in &ddl.tables {
+        writer.println(format!("-- Extract DDL for table {}", name).as_str());
+        writer.println(format!("{};", rewrite(statement)).as_str());
+        summary.tables += 1;
+    }
+    // The placeholder tables are gone, so views have to come after the
+    //   views they select from
+    for (name, statement) in order_views(&ddl.views) {
+        writer.println(format!("-- Extract DDL for view {}", name).as_str());
+        writer.println(format!("{};", rewrite(statement)).as_str());
+        summary.views += 1;
+    }
+    for (name, statement) in &ddl.routines {
+        writer.println(format!("-- Extract DDL for routine {}", name).as_str());
+        writer.println("DELIMITER ;;");
+        writer.println(format!("{};;", rewrite(statement)).as_str());
+        writer.println("DELIMITER ;");
+        summary.routines += 1;
+    }
+
+    summary.rows = write_data(input_file, writer, &patterns, &ddl)?;
+
+    for statement in &ddl.other {
+        writer.println(format!("{};", rewrite(statement)).as_str());
+    }
+    // Triggers stay after the data as in the source dump, created earlier
+    //   they would fire while the rows are loaded
+    for (name, statement) in &ddl.triggers {
+        writer.println(format!("-- Extract DDL for trigger {}", name).as_str());
+        writer.println("DELIMITER ;;");
+        writer.println(format!("{};;", rewrite(statement)).as_str());
+        writer.println("DELIMITER ;");
+        summary.triggers += 1;
+    }
+
+    write_postfix(writer, true);
+    write_trailer(writer, &summary);
+    Ok(summary)
+}
+
+fn read_ddl(input_file: &str, patterns: &Patterns) -> io::Result<Ddl> {
+    let mut ddl = Ddl::default();
+    let mut seen_object = false;
+    for statement in StatementReader::new(open_input(input_file)?) {
+        let statement = statement?;
+        if patterns.insert.is_match(&statement) {
+            continue;
+        }
+        let statement = patterns.unwrap_versioned(&statement);
+        let lower = statement.to_lowercase();
+
+        if let Some(captures) = patterns.create.captures(&statement) {
+            seen_object = true;
+            let name = object_name(&captures[2]);
+            let entry = (name, statement.clone());
+            match captures[1].to_lowercase().as_str() {
+                "table" => ddl.tables.push(entry),
+                // Newer versions stand in a dummy view, the real one replaces it
+                "view" => {
+                    ddl.views.retain(|v| v.0 != entry.0);
+                    ddl.views.push(entry)
+                }
+                "procedure" | "function" => ddl.routines.push(entry),
+                _ => ddl.triggers.push(entry),
+            }
+        } else if let Some(captures) = patterns.database.captures(&statement) {
+            ddl.charset = schema_charset(&statement);
+            set_schema(&mut ddl, object_name(&captures[1]))?;
+        } else if let Some(schema) = lower.strip_prefix("use ") {
+            let schema = statement[statement.len() - schema.len()..].trim();
+            set_schema(&mut ddl, object_name(schema))?;
+        } else if lower.starts_with("set ") {
+            // The @OLD_ variables only restore the session mysqldump changed,
+            //   and foreign key checks are handled by the prefix
+            let saved = lower.contains("@old_") || lower.contains("@saved_") || lower.contains("foreign_key_checks");
+            if !seen_object && !saved {
+                ddl.session.push(statement);
+            }
+        } else if is_mysqldump_boilerplate(&lower) {
+            seen_object = seen_object || lower.starts_with("drop ");
+        } else {
+            Logger::warn(format!("Keeping a statement normalize doesn't recognise: {}", excerpt(&statement)));
+            ddl.other.push(statement);
+        }
+    }
+
+    // mysqldump creates a stand in table for each view so views can be
+    //   created in any order, the view replaces it
+    let views: Vec<String> = ddl.views.iter().map(|v| v.0.clone()).collect();
+    ddl.tables.retain(|t| !views.contains(&t.0));
+    Ok(ddl)
+}
+
+fn write_data(input_file: &str, writer: &mut StdWriter, patterns: &Patterns, ddl: &Ddl) -> io::Result<u64> {
+    let mut rows = 0;
+    for statement in StatementReader::new(open_input(input_file)?) {
+        let statement = statement?;
+        let Some(captures) = patterns.insert.captures(&statement) else {
+            continue;
+        };
+        let table = object_name(&captures[2]);
+        let verb = captures[1].split_whitespace().collect::<Vec<&str>>().join(" ").to_lowercase();
+        let columns = match captures.get(3) {
+            Some(columns) => columns.as_str().trim_matches(|c| c == '(' || c == ')').replace(' ', ""),
+            None => table_columns(ddl, &table).join(","),
+        };
+        let values = &statement[captures.get(0).unwrap().end()..];
+        let Some(tuples) = split_rows(values, EscapeMode::Backslash) else {
+            Logger::warn(format!("Unable to split the rows of an insert into {}, keeping it as it is", table));
+            writer.println(format!("{};", statement).as_str());
+            continue;
+        };
+
+        let column_list = if columns.is_empty() { String::new() } else { format!(" ({})", columns) };
+        writer.println(
+            format!(
+                "{} into {}{} values({});",
+                verb,
+                quote_identifier(&table),
+                column_list,
+                tuples.join("),\n\t(")
+            )
+            .as_str(),
+        );
+        rows += tuples.len() as u64;
+    }
+    Ok(rows)
+}
+
+fn table_columns(ddl: &Ddl, table: &str) -> Vec<String> {
+    match ddl.tables.iter().find(|t| t.0 == table) {
+        Some((_, create)) => create_table_columns(create).iter().map(|c| quote_identifier(c)).collect(),
+        None => vec![],
+    }
+}
+
+// The names of the columns a CREATE TABLE gives values for, generated
+//   columns are left out as inserts can't set them
+pub fn create_table_columns(create: &str) -> Vec<String> {
+    create
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| line.starts_with('`') && !line.to_uppercase().contains(" GENERATED ALWAYS "))
+        .filter_map(|line| line[1..].find('`').map(|end| object_name(&line[..end + 2])))
+        .collect()
+}
+
+//
+// Split the VALUES part of an insert into the text of each row, without the
+//   parentheses. None when the text isn't a list of rows.
+//
+pub fn split_rows(values: &str, mode: EscapeMode) -> Option<Vec<&str>> {
+    let mut rows = vec![];
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+    let mut chars = values.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if let Some(q) = quote {
+            if c == '\\' && mode == EscapeMode::Backslash {
+                chars.next();
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '\'' | '"' => quote = Some(c),
+            '(' => {
+                if depth == 0 {
+                    start = i + 1;
+                }
+                depth += 1;
+            }
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    rows.push(&values[start..i]);
+                } else if depth < 0 {
+                    return None;
+                }
+            }
+            ',' if depth == 0 => {}
+            c if depth == 0 && !c.is_whitespace() => return None,
+            _ => {}
+        }
+    }
+    (depth == 0 && quote.is_none() && !rows.is_empty()).then_some(rows)
+}
+
+// Views that select from other views come after them, a cycle can't be
+//   created so the file order breaks any tie
+fn order_views(views: &[(String, String)]) -> Vec<&(String, String)> {
+    let mut pending: Vec<&(String, String)> = views.iter().collect();
+    let mut ordered = Vec::with_capacity(views.len());
+    while !pending.is_empty() {
+        let ready = pending
+            .iter()
+            .position(|(name, ddl)| {
+                !pending
+                    .iter()
+                    .any(|(other, _)| other != name && ddl.contains(&quote_identifier(other)))
+            })
+            .unwrap_or(0);
+        ordered.push(pending.remove(ready));
+    }
+    ordered
+}
+
+fn set_schema(ddl: &mut Ddl, schema: String) -> io::Result<()> {
+    match &ddl.schema {
+        Some(current) if *current != schema => Err(io::Error::other(format!(
+            "The dump holds more than one schema ({} and {}), normalize handles one schema per file",
+            current, schema
+        ))),
+        _ => {
+            ddl.schema = Some(schema);
+            Ok(())
+        }
+    }
+}
+
+fn schema_charset(statement: &str) -> Option<SchemaCharset> {
+    let character_set = Regex::new(r"(?i)CHARACTER\s+SET\s*=?\s*(\w+)").unwrap().captures(statement)?;
+    let collation = Regex::new(r"(?i)COLLATE\s*=?\s*(\w+)").unwrap().captures(statement)?;
+    Some(SchemaCharset {
+        character_set: character_set[1].to_string(),
+        collation: collation[1].to_string(),
+    })
+}
+
+// The unquoted name of a possibly qualified identifier
+pub fn object_name(name: &str) -> String {
+    let name = name.trim().trim_end_matches(';');
+    let last = match name.rfind("`.") {
+        Some(dot) => &name[dot + 2..],
+        None => name.rsplit('.').next().unwrap_or(name),
+    };
+    match last.strip_prefix('`').and_then(|n| n.strip_suffix('`')) {
+        Some(quoted) => quoted.replace("``", "`"),
+        None => last.to_string(),
+    }
+}
+
+fn is_mysqldump_boilerplate(lower: &str) -> bool {
+    lower.starts_with("lock tables")
+        || lower.starts_with("unlock tables")
+        || lower.starts_with("drop ")
+        || (lower.starts_with("alter table") && (lower.ends_with("disable keys") || lower.ends_with("enable keys")))
+}
+
+fn excerpt(statement: &str) -> String {
+    statement.chars().take(100).collect()
+}
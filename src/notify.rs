@@ -0,0 +1,179 @@
+use crate::logger::Logger;
+use crate::DumpSummary;
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Once};
+use std::time::Duration;
+
+// How long the notification endpoint has to answer
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(30);
+
+// The run a panic is reported for
+static CURRENT_RUN: Mutex<Option<Arc<Notifier>>> = Mutex::new(None);
+static PANIC_HOOK: Once = Once::new();
+
+//
+// The outcome of a dump run as sent to --notify-url and --notify-command.
+//   The counts are what was written before the run ended, so a failed run
+//   reports zeros.
+//
+#[derive(Clone, Debug, Serialize)]
+pub struct DumpReport {
+    // success or failure
+    pub status: String,
+    pub schema: String,
+    pub output_file: Option<String>,
+    pub started_at: String,
+    pub finished_at: String,
+    pub duration_seconds: f64,
+    pub tables: usize,
+    pub views: usize,
+    pub routines: usize,
+    pub triggers: usize,
+    pub rows: u64,
+    // The size of the output file when it is a file
+    pub output_bytes: Option<u64>,
+    pub error: Option<String>,
+    pub dbdump_version: String,
+}
+
+//
+// Tells orchestration systems how a run went: the report is POSTed as JSON
+//   to the URL and/or piped to the command (run with sh -c, with
+//   DBDUMP_STATUS and DBDUMP_SCHEMA in its environment). Failures include
+//   panics, caught by a panic hook, so each run notifies exactly once.
+//   Problems notifying are logged and don't change the outcome of the run.
+//
+pub struct Notifier {
+    url: Option<String>,
+    command: Option<String>,
+    schema: String,
+    output_file: Option<String>,
+    started: DateTime<Local>,
+    sent: AtomicBool,
+}
+
+impl Notifier {
+    pub fn new(url: Option<String>, command: Option<String>, schema: String, output_file: Option<String>) -> Option<Arc<Notifier>> {
+        if url.is_none() && command.is_none() {
+            return None;
+        }
+        Some(Arc::new(Notifier {
+            url,
+            command,
+            schema,
+            output_file,
+            started: Local::now(),
+            sent: AtomicBool::new(false),
+        }))
+    }
+
+    // Report a panic as a failure of this run, after the usual panic message.
+    //   The hook is installed once and reports for the latest run, as watch
+    //   runs one dump after another.
+    pub fn watch_panics(self: &Arc<Self>) {
+        *CURRENT_RUN.lock().unwrap() = Some(self.clone());
+        PANIC_HOOK.call_once(|| {
+            let default_hook = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                default_hook(info);
+                let message = match info.payload().downcast_ref::<String>() {
+                    Some(message) => message.clone(),
+                    None => info.payload().downcast_ref::<&str>().map(|m| m.to_string()).unwrap_or_default(),
+                };
+                let current = CURRENT_RUN.lock().ok().and_then(|run| run.clone());
+                if let Some(notifier) = current {
+                    notifier.failure(&message);
+                }
+            }));
+        });
+    }
+
+    pub fn success(&self, summary: &DumpSummary) {
+        self.send(self.report("success", summary, None));
+    }
+
+    pub fn failure(&self, error: &str) {
+        self.send(self.report("failure", &DumpSummary::default(), Some(error.to_string())));
+    }
+
+    fn report(&self, status: &str, summary: &DumpSummary, error: Option<String>) -> DumpReport {
+        let finished = Local::now();
+        let output_bytes = self
+            .output_file
+            .as_ref()
+            .and_then(|f| std::fs::metadata(f).ok())
+            .filter(|m| m.is_file())
+            .map(|m| m.len());
+        DumpReport {
+            status: status.to_string(),
+            schema: self.schema.clone(),
+            output_file: self.output_file.clone(),
+            started_at: self.started.to_rfc3339(),
+            finished_at: finished.to_rfc3339(),
+            duration_seconds: (finished - self.started).num_milliseconds() as f64 / 1000.0,
+            tables: summary.tables,
+            views: summary.views,
+            routines: summary.routines,
+            triggers: summary.triggers,
+            rows: summary.rows,
+            output_bytes,
+            error,
+            dbdump_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    fn send(&self, report: DumpReport) {
+        if self.sent.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let body = serde_json::to_string(&report).expect("Unable to serialize the dump report");
+        if let Some(url) = &self.url {
+            match post_json(url, &body) {
+                Ok(()) => Logger::info(format!("Sent the {} report to {}", report.status, url)),
+                Err(e) => Logger::error(format!("Unable to send the dump report: {}", e)),
+            }
+        }
+        if let Some(command) = &self.command {
+            if let Err(e) = run_command(command, &report, &body) {
+                Logger::error(format!("The notify command failed: {}", e));
+            }
+        }
+    }
+}
+
+fn post_json(url: &str, body: &str) -> io::Result<()> {
+    let tls = native_tls::TlsConnector::new().map_err(io::Error::other)?;
+    let agent = ureq::AgentBuilder::new().tls_connector(Arc::new(tls)).timeout(NOTIFY_TIMEOUT).build();
+    agent
+        .post(url)
+        .set("Content-Type", "application/json")
+        .send_string(body)
+        .map(|_| ())
+        .map_err(|e| io::Error::other(e.to_string()))
+}
+
+// The command's output goes to stderr so it can't end up in a dump written
+//   to stdout
+fn run_command(command: &str, report: &DumpReport, body: &str) -> io::Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("DBDUMP_STATUS", &report.status)
+        .env("DBDUMP_SCHEMA", &report.schema)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::from(io::stderr()))
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A command that doesn't read the report closes the pipe early
+        let _ = stdin.write_all(body.as_bytes());
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("exited with {}", status)));
+    }
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use crate::stats::format_bytes;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{read, Event, KeyCode, KeyEventKind};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, size, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use crossterm::{execute, queue};
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use std::io::{stderr, Write};
+
+//
+// Interactive object picker. Lists the tables and views of the schema with
+//   their sizes and lets the user choose what to dump before anything is
+//   written. Drawn on stderr so a dump going to stdout isn't disturbed.
+//
+pub struct PickerItem {
+    pub name: String,
+    pub kind: String,
+    pub size: u64,
+    pub selected: bool,
+}
+
+pub struct Selection {
+    pub objects: Vec<String>,
+    pub include_ddl: bool,
+    pub include_data: bool,
+}
+
+pub async fn load_items(pool: &Pool<MySql>, schema: &str) -> Result<Vec<PickerItem>, sqlx::Error> {
+    let rows: Vec<(String, String, Option<u64>)> = sqlx::query_as(
+        "select table_name, table_type, cast(coalesce(data_length, 0) + coalesce(index_length, 0) as unsigned) from information_schema.tables where table_schema=? order by table_name",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(name, kind, size)| PickerItem {
+            name,
+            kind: if kind == "VIEW" { "view" } else { "table" }.to_string(),
+            size: size.unwrap_or(0),
+            selected: true,
+        })
+        .collect())
+}
+
+// Run the picker, returning None if the user cancels.
+pub fn pick(
+    schema: &str,
+    mut items: Vec<PickerItem>,
+    mut include_ddl: bool,
+    mut include_data: bool,
+) -> std::io::Result<Option<Selection>> {
+    let mut out = stderr();
+    enable_raw_mode()?;
+    let _terminal = TerminalGuard;
+    execute!(out, EnterAlternateScreen, Hide)?;
+
+    let mut cursor = 0;
+    let mut top = 0;
+    let result = loop {
+        let (_, rows) = size()?;
+        let visible = (rows as usize).saturating_sub(4).max(1);
+        if cursor < top {
+            top = cursor;
+        } else if cursor >= top + visible {
+            top = cursor + 1 - visible;
+        }
+        draw(&mut out, schema, &items, cursor, top, visible, include_ddl, include_data)?;
+
+        if let Event::Key(key) = read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Up | KeyCode::Char('k') => cursor = cursor.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') => cursor = (cursor + 1).min(items.len().saturating_sub(1)),
+                KeyCode::Char(' ') => {
+                    if let Some(item) = items.get_mut(cursor) {
+                        item.selected = !item.selected;
+                    }
+                }
+                KeyCode::Char('a') => {
+                    let select = !items.iter().all(|i| i.selected);
+                    items.iter_mut().for_each(|i| i.selected = select);
+                }
+                KeyCode::Char('s') => include_ddl = !include_ddl,
+                KeyCode::Char('d') => include_data = !include_data,
+                KeyCode::Enter => {
+                    break Some(Selection {
+                        objects: items.iter().filter(|i| i.selected).map(|i| i.name.clone()).collect(),
+                        include_ddl,
+                        include_data,
+                    })
+                }
+                KeyCode::Esc | KeyCode::Char('q') => break None,
+                _ => {}
+            }
+        }
+    };
+    Ok(result)
+}
+
+// Puts the terminal back when the picker ends, on an error too
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(stderr(), Show, LeaveAlternateScreen);
+        let _ = disable_raw_mode();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw(
+    out: &mut impl Write,
+    schema: &str,
+    items: &[PickerItem],
+    cursor: usize,
+    top: usize,
+    visible: usize,
+    include_ddl: bool,
+    include_data: bool,
+) -> std::io::Result<()> {
+    let selected = items.iter().filter(|i| i.selected).count();
+    let total: u64 = items.iter().filter(|i| i.selected).map(|i| i.size).sum();
+    queue!(
+        out,
+        Clear(ClearType::All),
+        MoveTo(0, 0),
+        Print(format!(
+            "Schema {} - {} of {} objects selected ({})",
+            schema,
+            selected,
+            items.len(),
+            format_bytes(total)
+        )),
+        MoveTo(0, 1),
+        Print(format!(
+            "[{}] ddl (s)   [{}] data (d)   space: toggle  a: all  enter: dump  q: cancel",
+            if include_ddl { "x" } else { " " },
+            if include_data { "x" } else { " " }
+        ))
+    )?;
+    for (row, (i, item)) in items.iter().enumerate().skip(top).take(visible).enumerate() {
+        queue!(out, MoveTo(0, row as u16 + 3))?;
+        if i == cursor {
+            queue!(out, SetAttribute(Attribute::Reverse))?;
+        }
+        queue!(
+            out,
+            Print(format!(
+                "[{}] {:<5} {:<48} {:>10}",
+                if item.selected { "x" } else { " " },
+                item.kind,
+                item.name,
+                format_bytes(item.size)
+            )),
+            SetAttribute(Attribute::Reset)
+        )?;
+    }
+    out.flush()
+}
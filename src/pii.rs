@@ -0,0 +1,285 @@
+use crate::catalog::{Catalog, Table};
+use crate::logger::Logger;
+use crate::sql::{qualified_name, quote_identifier};
+use regex::Regex;
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use sqlx::Row;
+
+// Share of the sampled values that have to look like PII for a column to be
+//   flagged on its values alone
+const MATCH_SHARE: f64 = 0.5;
+
+// Types whose values are read for the pattern checks
+const TEXT_TYPES: [&str; 6] = ["char", "varchar", "tinytext", "text", "mediumtext", "longtext"];
+
+//
+// The kinds of personal data scan-pii looks for
+//
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PiiKind {
+    Email,
+    Phone,
+    NationalId,
+    CreditCard,
+    PersonName,
+    Address,
+    BirthDate,
+    IpAddress,
+}
+
+impl PiiKind {
+    fn describe(self) -> &'static str {
+        match self {
+            PiiKind::Email => "email addresses",
+            PiiKind::Phone => "phone numbers",
+            PiiKind::NationalId => "national id numbers",
+            PiiKind::CreditCard => "card numbers",
+            PiiKind::PersonName => "person names",
+            PiiKind::Address => "postal addresses",
+            PiiKind::BirthDate => "birth dates",
+            PiiKind::IpAddress => "IP addresses",
+        }
+    }
+
+    // The replacement written in place of a value, a transform script as
+    //   config.rs takes them. NULLs stay NULL. Where the table has a single
+    //   column key the replacement is made unique with it.
+    fn transform(self, key: Option<&str>) -> String {
+        let replacement = match (self, key) {
+            (PiiKind::Email, Some(key)) => format!("`user${{row[\"{}\"]}}@example.com`", key),
+            (PiiKind::Email, None) => "\"user@example.com\"".to_string(),
+            (PiiKind::PersonName, Some(key)) => format!("`Person ${{row[\"{}\"]}}`", key),
+            (PiiKind::PersonName, None) => "\"Person\"".to_string(),
+            (PiiKind::Phone, _) => "\"555-0100\"".to_string(),
+            (PiiKind::NationalId, _) => "\"000-00-0000\"".to_string(),
+            (PiiKind::CreditCard, _) => "\"4111111111111111\"".to_string(),
+            (PiiKind::Address, _) => "\"1 Example Street\"".to_string(),
+            (PiiKind::BirthDate, _) => "\"1970-01-01\"".to_string(),
+            (PiiKind::IpAddress, _) => "\"192.0.2.1\"".to_string(),
+        };
+        format!("if value == () {{ () }} else {{ {} }}", replacement)
+    }
+}
+
+//
+// A column that looks like it holds personal data, and why
+//
+#[derive(Clone, Debug)]
+pub struct Finding {
+    pub table: String,
+    pub column: String,
+    pub kind: PiiKind,
+    pub reason: String,
+    transform: String,
+}
+
+struct Patterns {
+    names: Vec<(PiiKind, Regex)>,
+    values: Vec<(PiiKind, Regex)>,
+}
+
+impl Patterns {
+    fn new() -> Self {
+        let compile = |pattern: &str| Regex::new(pattern).unwrap();
+        Patterns {
+            names: vec![
+                (PiiKind::Email, compile(r"(?i)e_?mail")),
+                (PiiKind::NationalId, compile(r"(?i)(^|_)(ssn|sin|nin|social_security|national_id|tax_id|passport)")),
+                (PiiKind::CreditCard, compile(r"(?i)(credit_?card|card_?num|cc_?num|^pan$)")),
+                (PiiKind::Phone, compile(r"(?i)(phone|mobile|^cell|fax|^tel$|telephone)")),
+                (PiiKind::PersonName, compile(r"(?i)^(first|last|middle|full|given|family|sur|maiden)_?name$|^surname$")),
+                (PiiKind::Address, compile(r"(?i)(address|street|^addr|post_?code|zip_?code|^zip$)")),
+                (PiiKind::BirthDate, compile(r"(?i)(birth|^dob$)")),
+                (PiiKind::IpAddress, compile(r"(?i)(^ip$|ip_?addr|remote_addr|client_ip)")),
+            ],
+            values: vec![
+                (PiiKind::Email, compile(r"^[^@\s]+@[^@\s]+\.[A-Za-z]{2,}$")),
+                (PiiKind::NationalId, compile(r"^\d{3}-\d{2}-\d{4}$")),
+                (PiiKind::CreditCard, compile(r"^\d{4}[ -]?\d{4}[ -]?\d{4}[ -]?\d{1,7}$")),
+                (PiiKind::Phone, compile(r"^\+?\(?\d{1,4}\)?[\s.-]?\d{2,4}[\s.-]?\d{3,4}[\s.-]?\d{0,4}$")),
+                (PiiKind::IpAddress, compile(r"^(\d{1,3}\.){3}\d{1,3}$")),
+            ],
+        }
+    }
+
+    fn name_kind(&self, column: &str) -> Option<PiiKind> {
+        self.names.iter().find(|(_, pattern)| pattern.is_match(column)).map(|(kind, _)| *kind)
+    }
+
+    // The kind most of the values look like, with the share that do
+    fn value_kind(&self, values: &[String]) -> Option<(PiiKind, f64)> {
+        if values.is_empty() {
+            return None;
+        }
+        self.values
+            .iter()
+            .map(|(kind, pattern)| {
+                let matches = values
+                    .iter()
+                    .filter(|v| pattern.is_match(v.trim()) && plausible(*kind, v.trim()))
+                    .count();
+                (*kind, matches as f64 / values.len() as f64)
+            })
+            .filter(|(_, share)| *share >= MATCH_SHARE)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+}
+
+//
+// Look for columns holding personal data, by their names and by what a
+//   sample of their values looks like. A column flagged by its name alone
+//   is reported even when the sample is empty, a column is flagged by its
+//   values when at least half of the sampled values match a pattern.
+//
+pub async fn scan_schema(
+    pool: &Pool<MySql>,
+    catalog: &Catalog,
+    tables: &[String],
+    sample_rows: u64,
+) -> Result<Vec<Finding>, sqlx::Error> {
+    let patterns = Patterns::new();
+    let mut findings = vec![];
+    for table in catalog.tables.iter().filter(|t| tables.is_empty() || tables.contains(&t.name)) {
+        Logger::info(format!("Scanning {}", table.name));
+        findings.extend(scan_table(pool, &catalog.schema, table, sample_rows, &patterns).await?);
+    }
+    Ok(findings)
+}
+
+async fn scan_table(
+    pool: &Pool<MySql>,
+    schema: &str,
+    table: &Table,
+    sample_rows: u64,
+    patterns: &Patterns,
+) -> Result<Vec<Finding>, sqlx::Error> {
+    let text_columns: Vec<&str> = table
+        .columns
+        .iter()
+        .filter(|c| TEXT_TYPES.iter().any(|t| c.column_type.to_lowercase().split('(').next() == Some(*t)))
+        .map(|c| c.name.as_str())
+        .collect();
+    let mut samples: Vec<Vec<String>> = vec![vec![]; text_columns.len()];
+    if !text_columns.is_empty() && sample_rows > 0 {
+        let select_list: Vec<String> = text_columns.iter().map(|c| quote_identifier(c)).collect();
+        let rows = sqlx::query(&format!(
+            "select {} from {} limit {}",
+            select_list.join(", "),
+            qualified_name(schema, &table.name),
+            sample_rows
+        ))
+        .fetch_all(pool)
+        .await?;
+        for row in &rows {
+            for (i, sample) in samples.iter_mut().enumerate() {
+                if let Some(value) = row.try_get_unchecked::<Option<String>, usize>(i)? {
+                    sample.push(value);
+                }
+            }
+        }
+    }
+
+    // Replacements are numbered by a single column primary key, which isn't
+    //   itself masked
+    let key = table
+        .primary_key()
+        .filter(|k| k.columns.len() == 1)
+        .map(|k| k.columns[0].as_str());
+    let mut findings = vec![];
+    for column in &table.columns {
+        let sample = text_columns.iter().position(|c| *c == column.name).map(|i| &samples[i]);
+        let by_value = sample.and_then(|s| patterns.value_kind(s).map(|(kind, share)| (kind, share, s.len())));
+        // The replacements are strings, so numeric columns are left alone
+        //   whatever they are called and only birth dates can be dates
+        let by_name = patterns.name_kind(&column.name).filter(|kind| {
+            sample.is_some() || (*kind == PiiKind::BirthDate && column.column_type.to_lowercase().starts_with("date"))
+        });
+        let (kind, reason) = match (by_name, by_value) {
+            (Some(kind), Some((value_kind, share, sampled))) if kind == value_kind => (
+                kind,
+                format!("column name, {:.0}% of {} sampled values look like {}", share * 100.0, sampled, kind.describe()),
+            ),
+            (Some(kind), _) => (kind, format!("column name looks like {}", kind.describe())),
+            (None, Some((kind, share, sampled))) => (
+                kind,
+                format!("{:.0}% of {} sampled values look like {}", share * 100.0, sampled, kind.describe()),
+            ),
+            (None, None) => continue,
+        };
+        if key == Some(column.name.as_str()) {
+            Logger::warn(format!("{}.{} looks like {} but is the primary key, it isn't masked", table.name, column.name, kind.describe()));
+            continue;
+        }
+        findings.push(Finding {
+            table: table.name.clone(),
+            column: column.name.clone(),
+            kind,
+            reason,
+            transform: kind.transform(key),
+        });
+    }
+    Ok(findings)
+}
+
+// Card numbers have to pass the Luhn check, and a phone number is written
+//   with a separator or a leading + or it is just a number
+fn plausible(kind: PiiKind, value: &str) -> bool {
+    match kind {
+        PiiKind::CreditCard => luhn(value),
+        PiiKind::Phone => value.chars().any(|c| !c.is_ascii_digit()),
+        _ => true,
+    }
+}
+
+// The Luhn check card numbers pass
+fn luhn(value: &str) -> bool {
+    let digits: Vec<u32> = value.chars().filter_map(|c| c.to_digit(10)).collect();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, d)| if i % 2 == 1 { if d * 2 > 9 { d * 2 - 9 } else { d * 2 } } else { *d })
+        .sum();
+    digits.len() >= 13 && sum.is_multiple_of(10)
+}
+
+//
+// The findings as a config file for dump --config, a transform per flagged
+//   column with the reason above it. Transforms need dbdump built with the
+//   scripting feature.
+//
+pub fn suggested_config(schema: &str, findings: &[Finding]) -> String {
+    let mut config = format!(
+        "# Masking rules suggested by dbdump scan-pii for {}\n# Review them, then use with dbdump dump --config (needs the scripting feature)\n",
+        schema
+    );
+    let mut current_table = None;
+    for finding in findings {
+        if current_table != Some(&finding.table) {
+            config.push_str(&format!("\n[tables.{}.transform]\n", toml_key(&finding.table)));
+            current_table = Some(&finding.table);
+        }
+        config.push_str(&format!("# {}\n", finding.reason));
+        config.push_str(&format!("{} = {}\n", toml_key(&finding.column), toml_string(&finding.transform)));
+    }
+    config
+}
+
+// Bare keys can only have letters, digits, dashes and underscores
+fn toml_key(name: &str) -> String {
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+// Scripts read best as literal strings, which can't hold a single quote
+fn toml_string(value: &str) -> String {
+    if value.contains('\'') {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        format!("'{}'", value)
+    }
+}
@@ -0,0 +1,149 @@
+use crate::logger::Logger;
+use crate::sql::{qualified_name, quote_identifier};
+use crate::stats::format_bytes;
+use crate::ExportOptions;
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+
+// Tables estimated to have more rows than this are split into chunks
+pub const CHUNK_ROWS: u64 = 1_000_000;
+
+//
+// One unit of work for the data export threads - a whole table or a range of
+//   one. Estimates come from information_schema and are only used for
+//   ordering and progress.
+//
+#[derive(Clone, Debug)]
+pub struct WorkItem {
+    pub table: String,
+    // Where clause selecting this chunk, None for the whole table
+    pub chunk: Option<String>,
+    pub part: usize,
+    pub parts: usize,
+    pub rows: u64,
+    pub bytes: u64,
+}
+
+// table_name, table_rows, data_length
+type TableEstimate = (String, Option<u64>, Option<u64>);
+
+//
+// Pre-scan the tables and plan the export. The biggest work items come first
+//   so a large table isn't started last while the other threads sit idle, and
+//   tables with a single integer primary key are split into primary key ranges
+//   so several threads can share them.
+//
+pub async fn plan_export(pool: &Pool<MySql>, schema: &str, options: &ExportOptions) -> Result<Vec<WorkItem>, sqlx::Error> {
+    let tables: Vec<TableEstimate> = sqlx::query_as(
+        "select table_name, cast(table_rows as unsigned), cast(data_length as unsigned) from information_schema.tables where table_schema=? and table_type='BASE TABLE'",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await?;
+
+    let mut plan = vec![];
+    for (table, rows, bytes) in tables.into_iter().filter(|t| options.includes_data(&t.0)) {
+        let rows = rows.unwrap_or(0);
+        let bytes = bytes.unwrap_or(0);
+        // Fixtures already restrict the rows with their own clause, table
+        //   hooks have to run once around the whole table and ordered rows
+        //   have to come out of a single query
+        let chunks = if rows > CHUNK_ROWS
+            && !options.row_filters.contains_key(&table)
+            && options.hooks(&table).is_none()
+            && options.row_order(&table).is_none()
+        {
+            chunk_ranges(pool, schema, &table, rows).await?
+        } else {
+            vec![]
+        };
+        if chunks.is_empty() {
+            plan.push(WorkItem {
+                table,
+                chunk: None,
+                part: 1,
+                parts: 1,
+                rows,
+                bytes,
+            });
+            continue;
+        }
+        let parts = chunks.len();
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            plan.push(WorkItem {
+                table: table.clone(),
+                chunk: Some(chunk),
+                part: i + 1,
+                parts,
+                rows: rows / parts as u64,
+                bytes: bytes / parts as u64,
+            });
+        }
+    }
+    plan.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.table.cmp(&b.table)).then(a.part.cmp(&b.part)));
+
+    let total_rows: u64 = plan.iter().map(|w| w.rows).sum();
+    let total_bytes: u64 = plan.iter().map(|w| w.bytes).sum();
+    Logger::info(format!(
+        "Export plan: {} work items, ~{} rows, ~{}",
+        plan.len(),
+        total_rows,
+        format_bytes(total_bytes)
+    ));
+    for item in &plan {
+        Logger::debug(format!("  {} ~{} rows ~{}", item.describe(), item.rows, format_bytes(item.bytes)));
+    }
+    Ok(plan)
+}
+
+impl WorkItem {
+    pub fn describe(&self) -> String {
+        if self.parts > 1 {
+            format!("{} (part {}/{})", self.table, self.part, self.parts)
+        } else {
+            self.table.clone()
+        }
+    }
+}
+
+// Split a table on its primary key when the key is a single integer column
+async fn chunk_ranges(pool: &Pool<MySql>, schema: &str, table: &str, rows: u64) -> Result<Vec<String>, sqlx::Error> {
+    let key: Vec<(String, String)> = sqlx::query_as(
+        "select c.column_name, c.data_type from information_schema.key_column_usage k join information_schema.columns c on c.table_schema=k.table_schema and c.table_name=k.table_name and c.column_name=k.column_name where k.table_schema=? and k.table_name=? and k.constraint_name='PRIMARY'",
+    )
+    .bind(schema)
+    .bind(table)
+    .fetch_all(pool)
+    .await?;
+    if key.len() != 1 || !["tinyint", "smallint", "mediumint", "int", "bigint"].contains(&key[0].1.as_str()) {
+        return Ok(vec![]);
+    }
+    let column = quote_identifier(&key[0].0);
+
+    let (min, max): (Option<i64>, Option<i64>) = sqlx::query_as(&format!(
+        "select cast(min({0}) as signed), cast(max({0}) as signed) from {1}",
+        column,
+        qualified_name(schema, table)
+    ))
+    .fetch_one(pool)
+    .await?;
+    let (min, max) = match (min, max) {
+        (Some(min), Some(max)) if max > min => (min, max),
+        _ => return Ok(vec![]),
+    };
+
+    let parts = rows.div_ceil(CHUNK_ROWS) as i64;
+    let step = ((max - min) / parts).max(1) + 1;
+    let mut chunks = vec![];
+    let mut start = min;
+    while start <= max {
+        let end = start.saturating_add(step);
+        if end > max {
+            chunks.push(format!("where {} >= {}", column, start));
+            break;
+        }
+        chunks.push(format!("where {} >= {} and {} < {}", column, start, column, end));
+        start = end;
+    }
+    Ok(chunks)
+}
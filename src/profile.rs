@@ -0,0 +1,217 @@
+use crate::catalog::{Catalog, ColumnInfo};
+use crate::docs::cell;
+use crate::logger::Logger;
+use crate::shell::BINARY_TYPES;
+use crate::sql::{qualified_name, quote_identifier};
+use crate::std_writer::StdWriter;
+use serde::Serialize;
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use sqlx::types::chrono::Local;
+use sqlx::Row;
+use std::str::FromStr;
+
+// Longer min/max values are cut short in the report
+const MAX_VALUE_CHARS: usize = 64;
+
+// Types whose values are too large or have no order worth reporting, only
+//   their NULLs are counted
+const UNORDERED_TYPES: [&str; 14] = [
+    "tinytext", "text", "mediumtext", "longtext", "json", "geometry", "point", "linestring", "polygon",
+    "multipoint", "multilinestring", "multipolygon", "geometrycollection", "geomcollection",
+];
+
+//
+// How a profile is written
+//
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    Markdown,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(ReportFormat::Json),
+            "markdown" | "md" => Ok(ReportFormat::Markdown),
+            _ => Err(format!("Unknown report format {s}, expected one of json, markdown")),
+        }
+    }
+}
+
+//
+// A data quality snapshot of a schema: for each column of each table how
+//   many values are NULL, the smallest and largest value and how many
+//   distinct values there are. With a sample the figures cover only the
+//   first rows of each table and the distinct counts are a lower bound.
+//
+#[derive(Clone, Debug, Serialize)]
+pub struct SchemaProfile {
+    pub schema: String,
+    pub generated_at: String,
+    pub sample_rows: Option<u64>,
+    pub tables: Vec<TableProfile>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TableProfile {
+    pub name: String,
+    pub rows: u64,
+    pub columns: Vec<ColumnProfile>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ColumnProfile {
+    pub name: String,
+    pub column_type: String,
+    pub nulls: u64,
+    // None for text, blob, JSON and spatial columns
+    pub min: Option<String>,
+    pub max: Option<String>,
+    pub distinct: Option<u64>,
+}
+
+pub async fn profile_schema(
+    pool: &Pool<MySql>,
+    catalog: &Catalog,
+    tables: &[String],
+    sample_rows: Option<u64>,
+) -> Result<SchemaProfile, sqlx::Error> {
+    let mut profiles = vec![];
+    for table in catalog.tables.iter().filter(|t| tables.is_empty() || tables.contains(&t.name)) {
+        Logger::info(format!("Profiling {}", table.name));
+        profiles.push(profile_table(pool, &catalog.schema, &table.name, &table.columns, sample_rows).await?);
+    }
+    Ok(SchemaProfile {
+        schema: catalog.schema.clone(),
+        generated_at: Local::now().to_rfc3339(),
+        sample_rows,
+        tables: profiles,
+    })
+}
+
+//
+// Everything about a table comes from one aggregate query, so each table is
+//   read once
+//
+async fn profile_table(
+    pool: &Pool<MySql>,
+    schema: &str,
+    table: &str,
+    columns: &[ColumnInfo],
+    sample_rows: Option<u64>,
+) -> Result<TableProfile, sqlx::Error> {
+    let ordered: Vec<bool> = columns.iter().map(|c| is_ordered(&c.column_type)).collect();
+    let mut select_list = vec!["count(*)".to_string()];
+    for (column, ordered) in columns.iter().zip(&ordered) {
+        let name = quote_identifier(&column.name);
+        select_list.push(format!("count(*) - count({})", name));
+        if *ordered {
+            select_list.push(format!("cast(min({}) as char)", name));
+            select_list.push(format!("cast(max({}) as char)", name));
+            select_list.push(format!("count(distinct {})", name));
+        }
+    }
+    let source = match sample_rows {
+        Some(rows) => format!("(select * from {} limit {}) sample", qualified_name(schema, table), rows),
+        None => qualified_name(schema, table),
+    };
+    let row = sqlx::query(&format!("select {} from {}", select_list.join(", "), source))
+        .fetch_one(pool)
+        .await?;
+
+    let rows: i64 = row.try_get(0)?;
+    let mut index = 1;
+    let mut profiles = vec![];
+    for (column, ordered) in columns.iter().zip(&ordered) {
+        let nulls: i64 = row.try_get(index)?;
+        index += 1;
+        let mut profile = ColumnProfile {
+            name: column.name.clone(),
+            column_type: column.column_type.clone(),
+            nulls: nulls as u64,
+            min: None,
+            max: None,
+            distinct: None,
+        };
+        if *ordered {
+            profile.min = row.try_get_unchecked::<Option<String>, usize>(index)?.map(shorten);
+            profile.max = row.try_get_unchecked::<Option<String>, usize>(index + 1)?.map(shorten);
+            profile.distinct = Some(row.try_get::<i64, usize>(index + 2)? as u64);
+            index += 3;
+        }
+        profiles.push(profile);
+    }
+    Ok(TableProfile {
+        name: table.to_string(),
+        rows: rows as u64,
+        columns: profiles,
+    })
+}
+
+fn is_ordered(column_type: &str) -> bool {
+    let data_type = column_type
+        .split(['(', ' '])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    !UNORDERED_TYPES.contains(&data_type.as_str()) && !BINARY_TYPES.contains(&data_type.as_str())
+}
+
+fn shorten(value: String) -> String {
+    if value.chars().count() <= MAX_VALUE_CHARS {
+        return value;
+    }
+    let mut short: String = value.chars().take(MAX_VALUE_CHARS).collect();
+    short.push_str("...");
+    short
+}
+
+pub fn write_profile(writer: &mut StdWriter, profile: &SchemaProfile, format: ReportFormat) {
+    match format {
+        ReportFormat::Json => writer.println(
+            serde_json::to_string_pretty(profile)
+                .expect("Unable to serialize the profile")
+                .as_str(),
+        ),
+        ReportFormat::Markdown => write_markdown(writer, profile),
+    }
+}
+
+fn write_markdown(writer: &mut StdWriter, profile: &SchemaProfile) {
+    writer.println(format!("# Profile of `{}`", profile.schema).as_str());
+    writer.println("");
+    writer.println(format!("Generated by dbdump v{} at {}", env!("CARGO_PKG_VERSION"), profile.generated_at).as_str());
+    if let Some(rows) = profile.sample_rows {
+        writer.println("");
+        writer.println(format!("Sampled the first {} rows of each table, distinct counts are lower bounds", rows).as_str());
+    }
+    writer.println("");
+    for table in &profile.tables {
+        writer.println(format!("## {}", table.name).as_str());
+        writer.println("");
+        writer.println(format!("{} rows", table.rows).as_str());
+        writer.println("");
+        writer.println("| Column | Type | Nulls | Min | Max | Distinct |");
+        writer.println("|--------|------|-------|-----|-----|----------|");
+        for column in &table.columns {
+            writer.println(
+                format!(
+                    "| {} | {} | {} | {} | {} | {} |",
+                    cell(&column.name),
+                    cell(&column.column_type),
+                    column.nulls,
+                    cell(column.min.as_deref().unwrap_or("")),
+                    cell(column.max.as_deref().unwrap_or("")),
+                    column.distinct.map(|d| d.to_string()).unwrap_or_default()
+                )
+                .as_str(),
+            );
+        }
+        writer.println("");
+    }
+}
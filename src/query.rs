@@ -0,0 +1,100 @@
+use crate::sql::qualified_name;
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use sqlx::{Executor, Row};
+use std::str::FromStr;
+
+// The temporary table a query's result is described with
+const DESCRIBE_TABLE: &str = "dbdump_query";
+
+//
+// A query dumped as if it were a table, from --query name=SELECT ...
+//
+#[derive(Clone, Debug)]
+pub struct NamedQuery {
+    pub name: String,
+    pub sql: String,
+}
+
+impl FromStr for NamedQuery {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid query {s}, expected name=SELECT ...");
+        let (name, sql) = s.split_once('=').ok_or_else(invalid)?;
+        let name = name.trim();
+        let sql = sql.trim().trim_end_matches(';').trim();
+        if name.is_empty() || sql.is_empty() {
+            return Err(invalid());
+        }
+        Ok(NamedQuery {
+            name: name.to_string(),
+            sql: sql.to_string(),
+        })
+    }
+}
+
+impl NamedQuery {
+    // The rows of a view as a table named after it, from --materialize-views
+    pub fn view(schema: &str, view: &str, suffix: &str) -> NamedQuery {
+        NamedQuery {
+            name: format!("{}{}", view, suffix),
+            sql: format!("select * from {}", qualified_name(schema, view)),
+        }
+    }
+}
+
+//
+// A query with the table its rows are loaded into: the columns as
+//   information_schema would list them, so they are fetched and rendered like
+//   a table's, and the CREATE TABLE definition following the table's name
+//
+#[derive(Clone, Debug)]
+pub struct QueryTable {
+    pub sql: String,
+    pub columns: Vec<(String, String, Option<String>)>,
+    pub definition: String,
+}
+
+impl QueryTable {
+    pub fn create_table(&self, table_name: &str) -> String {
+        format!("CREATE TABLE {}{}", table_name, self.definition)
+    }
+}
+
+//
+// Work out the table a query's rows go into. The server creates an empty
+//   temporary table from the query's result, on one connection, and its
+//   definition and columns are read back, so the column types are exactly
+//   what the server made of the result. Needs the CREATE TEMPORARY TABLES
+//   privilege on the schema. Unqualified names in the query resolve against
+//   the database in the url.
+//
+pub async fn describe_query(pool: &Pool<MySql>, schema: &str, query: &NamedQuery) -> Result<QueryTable, sqlx::Error> {
+    let table = qualified_name(schema, DESCRIBE_TABLE);
+    let mut conn = pool.acquire().await?;
+    conn.execute(format!("drop temporary table if exists {}", table).as_str()).await?;
+    conn.execute(format!("create temporary table {} as select * from ({}) as dbdump_query limit 0", table, query.sql).as_str())
+        .await?;
+    let (_, ddl): (String, String) = sqlx::query_as(&format!("show create table {}", table)).fetch_one(&mut *conn).await?;
+    // A plain string runs as a text query, so every value reads as text
+    let rows = conn.fetch_all(format!("show full columns from {}", table).as_str()).await?;
+    conn.execute(format!("drop temporary table {}", table).as_str()).await?;
+
+    let columns = rows
+        .iter()
+        .map(|row| {
+            let text = |name: &str| row.try_get_unchecked::<Option<String>, &str>(name).ok().flatten();
+            let column_type = text("Type").unwrap_or_default().to_lowercase();
+            let data_type = column_type.split(['(', ' ']).next().unwrap_or_default().to_string();
+            let charset = text("Collation").and_then(|c| c.split('_').next().map(str::to_string));
+            (text("Field").unwrap_or_default(), data_type, charset)
+        })
+        .collect();
+    let definition = ddl.find(" (").map(|start| ddl[start..].to_string()).unwrap_or(ddl);
+    Ok(QueryTable {
+        sql: query.sql.clone(),
+        columns,
+        definition,
+    })
+}
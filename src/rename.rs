@@ -0,0 +1,320 @@
+use crate::sql::NameCase;
+use regex::{Captures, Regex};
+use std::str::FromStr;
+
+//
+// Renaming of objects on the way out. Table rename rules adapt a dump to
+//   the naming conventions of another environment, and when several schemas
+//   are merged into one every object of a source schema gets the same prefix
+//   so names can't clash in the target.
+//
+// Names are rewritten wherever the DDL refers to them by a quoted
+//   identifier: the object's own name, foreign key references, constraint
+//   names and schema qualified references in view, routine and trigger
+//   bodies. Routine and trigger bodies may also name the schema's tables
+//   without a qualifier, those are renamed by rewrite_body.
+//
+#[derive(Clone, Debug, Default)]
+pub struct Renames {
+    // Prepended to the name of every object
+    pub prefix: Option<String>,
+    // Applied in order to table and view names
+    pub tables: Vec<TableRename>,
+    // Column renames, matched on the source table name
+    pub columns: Vec<ColumnRename>,
+    // How rules are matched to the source table names
+    pub name_case: NameCase,
+}
+
+//
+// A column rename, given as table.old=new. The table is the source name, *
+//   matches every table.
+//
+#[derive(Clone, Debug)]
+pub struct ColumnRename {
+    pub table: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl FromStr for ColumnRename {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid column rename {s}, expected table.old=new");
+        let (column, new) = s.split_once('=').ok_or_else(invalid)?;
+        let (table, old) = column.split_once('.').ok_or_else(invalid)?;
+        if table.is_empty() || old.is_empty() || new.is_empty() {
+            return Err(invalid());
+        }
+        Ok(ColumnRename {
+            table: table.to_string(),
+            old: old.to_string(),
+            new: new.to_string(),
+        })
+    }
+}
+
+//
+// A rule for renaming tables, given on the command line as old=new,
+//   prefix:<text>, suffix:<text> or regex:<pattern>=<replacement>
+//
+#[derive(Clone, Debug)]
+pub enum TableRename {
+    Exact(String, String),
+    Prefix(String),
+    Suffix(String),
+    Regex(Regex, String),
+}
+
+impl FromStr for TableRename {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(prefix) = s.strip_prefix("prefix:") {
+            return Ok(TableRename::Prefix(prefix.to_string()));
+        }
+        if let Some(suffix) = s.strip_prefix("suffix:") {
+            return Ok(TableRename::Suffix(suffix.to_string()));
+        }
+        if let Some(rule) = s.strip_prefix("regex:") {
+            let (pattern, replacement) = rule
+                .rsplit_once('=')
+                .ok_or(format!("Invalid rename rule {s}, expected regex:<pattern>=<replacement>"))?;
+            let pattern = Regex::new(&format!("^(?:{})$", pattern)).map_err(|e| format!("Invalid rename pattern {pattern}: {e}"))?;
+            return Ok(TableRename::Regex(pattern, replacement.to_string()));
+        }
+        match s.split_once('=') {
+            Some((old, new)) if !old.is_empty() && !new.is_empty() => Ok(TableRename::Exact(old.to_string(), new.to_string())),
+            _ => Err(format!(
+                "Invalid rename rule {s}, expected old=new, prefix:<text>, suffix:<text> or regex:<pattern>=<replacement>"
+            )),
+        }
+    }
+}
+
+impl TableRename {
+    fn apply(&self, name: &str, name_case: NameCase) -> String {
+        match self {
+            TableRename::Exact(old, new) if name_case.same(old, name) => new.clone(),
+            TableRename::Exact(_, _) => name.to_string(),
+            TableRename::Prefix(prefix) => format!("{}{}", prefix, name),
+            TableRename::Suffix(suffix) => format!("{}{}", name, suffix),
+            TableRename::Regex(pattern, replacement) => pattern.replace(name, replacement.as_str()).into_owned(),
+        }
+    }
+}
+
+impl Renames {
+    pub fn is_empty(&self) -> bool {
+        self.prefix.is_none() && self.tables.is_empty() && self.columns.is_empty()
+    }
+
+    // The name a column of a source table is written out as
+    pub fn column(&self, table: &str, name: &str) -> String {
+        self.columns
+            .iter()
+            .find(|c| (c.table == "*" || self.name_case.same(&c.table, table)) && c.old.eq_ignore_ascii_case(name))
+            .map(|c| c.new.clone())
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    // The name a table or view is written out as
+    pub fn table(&self, name: &str) -> String {
+        let renamed = self.tables.iter().fold(name.to_string(), |name, rule| rule.apply(&name, self.name_case));
+        self.object(&renamed)
+    }
+
+    // The name of a routine, trigger or constraint
+    pub fn object(&self, name: &str) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{}{}", prefix, name),
+            None => name.to_string(),
+        }
+    }
+
+    //
+    // Rewrite the names in a CREATE statement from the source schema. Schema
+    //   qualified references keep the source qualifier, strip_schema or
+    //   qualify decide what becomes of it.
+    //
+    pub fn rewrite_ddl(&self, ddl: &str, schema: &str) -> String {
+        if self.is_empty() {
+            return ddl.to_string();
+        }
+        let ddl = self.rewrite_columns(ddl, schema);
+
+        // The object's own name and the tables it points at. Identifiers
+        //   followed by a dot are schema qualifiers, handled below.
+        let own_name = Regex::new(
+            r#"(?i)\b(TABLE|VIEW|REFERENCES|(?:BEFORE|AFTER)\s+(?:INSERT|UPDATE|DELETE)\s+ON)(\s+)(`(?:[^`]|``)+`|"(?:[^"]|"")+")(\.?)"#,
+        )
+        .unwrap();
+        let ddl = own_name.replace_all(&ddl, |c: &Captures| {
+            if &c[4] == "." {
+                return c[0].to_string();
+            }
+            format!("{}{}{}", &c[1], &c[2], self.requote(&c[3], |n| self.table(n)))
+        });
+
+        // FOLLOWS and PRECEDES name the trigger another one fires after or
+        //   before
+        let object_name =
+            Regex::new(r#"(?i)\b(CONSTRAINT|TRIGGER|PROCEDURE|FUNCTION|FOLLOWS|PRECEDES)(\s+)(`(?:[^`]|``)+`|"(?:[^"]|"")+")(\.?)"#).unwrap();
+        let ddl = object_name.replace_all(&ddl, |c: &Captures| {
+            if &c[4] == "." {
+                return c[0].to_string();
+            }
+            format!("{}{}{}", &c[1], &c[2], self.requote(&c[3], |n| self.object(n)))
+        });
+
+        // `schema`.`name` - tables, views and routines used in bodies
+        let quoted_schema = format!("((?:`{0}`|\"{0}\")\\.)", regex::escape(schema));
+        let qualified = Regex::new(&format!(r#"{}(`(?:[^`]|``)+`|"(?:[^"]|"")+")"#, quoted_schema)).unwrap();
+        qualified
+            .replace_all(&ddl, |c: &Captures| format!("{}{}", &c[1], self.requote(&c[2], |n| self.table(n))))
+            .into_owned()
+    }
+
+    //
+    // Unqualified references in a routine or trigger body to `tables`, the
+    //   source schema's tables and views, which would otherwise resolve to
+    //   the old names in the schema the dump is restored into. Only a name
+    //   right after FROM, JOIN, INTO, UPDATE or TABLE that is one of `tables`
+    //   is renamed, so variables, cursors and aliases are left alone. Run it
+    //   before rewrite_ddl, which renames quoted names after TABLE itself.
+    //
+    pub fn rewrite_body(&self, ddl: &str, tables: &[String]) -> String {
+        if self.is_empty() || tables.is_empty() {
+            return ddl.to_string();
+        }
+        let reference =
+            Regex::new(r#"(?i)\b(FROM|JOIN|INTO|UPDATE|TABLE)(\s+)(`(?:[^`]|``)+`|"(?:[^"]|"")+"|[0-9A-Za-z_$]+)(\.?)"#).unwrap();
+        reference
+            .replace_all(ddl, |c: &Captures| {
+                let name = &c[3];
+                let quoted = name.starts_with('`') || name.starts_with('"');
+                // Followed by a dot it is a schema qualifier
+                if &c[4] == "." || (quoted && c[1].eq_ignore_ascii_case("TABLE")) {
+                    return c[0].to_string();
+                }
+                let source = if quoted { unquote(name) } else { name.to_string() };
+                if !tables.iter().any(|t| self.name_case.same(t, &source)) {
+                    return c[0].to_string();
+                }
+                let renamed = if quoted {
+                    self.requote(name, |n| self.table(n))
+                } else {
+                    format!("`{}`", self.table(&source).replace('`', "``"))
+                };
+                format!("{}{}{}", &c[1], &c[2], renamed)
+            })
+            .into_owned()
+    }
+
+    //
+    // Column renames, done while the DDL still has the source table names.
+    //   In a CREATE TABLE every identifier that isn't the name of the table,
+    //   an index or a constraint is one of the table's columns, except the
+    //   column list after REFERENCES which belongs to the parent table.
+    //   Elsewhere only schema.table.column references are renamed.
+    //
+    fn rewrite_columns(&self, ddl: &str, schema: &str) -> String {
+        if self.columns.is_empty() {
+            return ddl.to_string();
+        }
+        const IDENTIFIER: &str = r#"(`(?:[^`]|``)+`|"(?:[^"]|"")+")"#;
+
+        let quoted_schema = format!("(?:`{0}`|\"{0}\")", regex::escape(schema));
+        let qualified = Regex::new(&format!(r"({}\.){}\.{}", quoted_schema, IDENTIFIER, IDENTIFIER)).unwrap();
+        let ddl = qualified
+            .replace_all(ddl, |c: &Captures| {
+                let table = unquote(&c[2]);
+                format!("{}{}.{}", &c[1], &c[2], self.requote(&c[3], |n| self.column(&table, n)))
+            })
+            .into_owned();
+
+        let create_table = Regex::new(&format!(r"(?i)^\s*CREATE\s+(?:TEMPORARY\s+)?TABLE\s+(?:IF\s+NOT\s+EXISTS\s+)?{}", IDENTIFIER)).unwrap();
+        let table = match create_table.captures(&ddl) {
+            Some(c) => unquote(&c[1]),
+            None => return ddl,
+        };
+        let named = Regex::new(&format!(r"(?i)(\b(?:KEY|INDEX|CONSTRAINT|TABLE)\s+)?{}", IDENTIFIER)).unwrap();
+        let references = Regex::new(&format!(r"(?i)\bREFERENCES\s+{}", IDENTIFIER)).unwrap();
+
+        let rename_line = |line: &str, table: &str| -> String {
+            named
+                .replace_all(line, |c: &Captures| {
+                    if c.get(1).is_some() {
+                        c[0].to_string()
+                    } else {
+                        self.requote(&c[2], |n| self.column(table, n))
+                    }
+                })
+                .into_owned()
+        };
+        ddl.lines()
+            .map(|line| match references.captures(line) {
+                Some(c) => {
+                    let at = c.get(0).unwrap();
+                    let parent = unquote(&c[1]);
+                    format!(
+                        "{}{}{}",
+                        rename_line(&line[..at.start()], &table),
+                        at.as_str(),
+                        rename_line(&line[at.end()..], &parent)
+                    )
+                }
+                None => rename_line(line, &table),
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    // Apply `rename` to a quoted identifier, keeping the quote style
+    fn requote(&self, quoted: &str, rename: impl Fn(&str) -> String) -> String {
+        let quote = &quoted[..1];
+        let renamed = rename(&unquote(quoted));
+        format!("{0}{1}{0}", quote, renamed.replace(quote, &format!("{0}{0}", quote)))
+    }
+}
+
+//
+// Remove the `schema`. qualifier from references to the source schema, so
+//   the DDL relies on the use statement like the rest of the dump
+//
+pub fn strip_schema(ddl: &str, schema: &str) -> String {
+    let quoted_schema = format!("(?:`{0}`|\"{0}\")\\.", regex::escape(schema));
+    Regex::new(&quoted_schema).unwrap().replace_all(ddl, "").into_owned()
+}
+
+//
+// Qualify the object's own name and the tables it references with the
+//   target schema, `target` being already quoted. References to the source
+//   schema are moved to the target, other schemas are left alone.
+//
+pub fn qualify(ddl: &str, schema: &str, target: &str) -> String {
+    let quoted_schema = format!("(?:`{0}`|\"{0}\")\\.", regex::escape(schema));
+    let ddl = Regex::new(&quoted_schema)
+        .unwrap()
+        .replace_all(ddl, format!("{}.", target).replace('$', "$$"))
+        .into_owned();
+
+    let own_name = Regex::new(
+        r#"(?i)\b(TABLE|VIEW|REFERENCES|PROCEDURE|FUNCTION|TRIGGER|(?:BEFORE|AFTER)\s+(?:INSERT|UPDATE|DELETE)\s+ON)(\s+)(`(?:[^`]|``)+`|"(?:[^"]|"")+")(\.?)"#,
+    )
+    .unwrap();
+    own_name
+        .replace_all(&ddl, |c: &Captures| {
+            if &c[4] == "." {
+                return c[0].to_string();
+            }
+            format!("{}{}{}.{}", &c[1], &c[2], target, &c[3])
+        })
+        .into_owned()
+}
+
+fn unquote(quoted: &str) -> String {
+    let quote = &quoted[..1];
+    quoted[1..quoted.len() - 1].replace(&format!("{0}{0}", quote), quote)
+}
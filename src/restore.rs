@@ -0,0 +1,404 @@
+use crate::escape::EscapeMode;
+use crate::metadata::Metadata;
+use crate::normalize::{insert_pattern, object_name};
+use crate::sql::quote_identifier;
+use crate::{SECTION_BEGIN, SECTION_END};
+use regex::Regex;
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use sqlx::Executor;
+use std::io::BufRead;
+
+//
+// Read a dump file one statement at a time, the way the mysql client does:
+//   statements end with the current delimiter (changed by DELIMITER lines)
+//   unless it appears inside a string, quoted identifier or comment. Comment
+//   only lines are dropped, but versioned /*! ... */ comments are kept as they
+//   are executable. The whole file is never held in memory.
+//
+pub struct StatementReader<R: BufRead> {
+    reader: R,
+    delimiter: String,
+    // Text following a delimiter on the same line, read before the next line
+    pending: Option<String>,
+    // Last non blank line read, comments included
+    last_line: String,
+    // Set when the input ended part way through a statement
+    unterminated: bool,
+    // Comment lines between statements, only kept when asked for
+    keep_comments: bool,
+    comments: Vec<String>,
+    // Off for dumps written for NO_BACKSLASH_ESCAPES sessions
+    backslash_escapes: bool,
+}
+
+impl<R: BufRead> StatementReader<R> {
+    pub fn new(reader: R) -> Self {
+        StatementReader {
+            reader,
+            delimiter: ";".to_string(),
+            pending: None,
+            last_line: String::new(),
+            unterminated: false,
+            keep_comments: false,
+            comments: vec![],
+            backslash_escapes: true,
+        }
+    }
+
+    // Keep the comment lines found between statements for take_comments
+    pub fn keep_comments(mut self) -> Self {
+        self.keep_comments = true;
+        self
+    }
+
+    // The comment lines read since the last call, these come before the
+    //   statement just returned
+    pub fn take_comments(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.comments)
+    }
+
+    // Whether \ escapes the next character inside strings, can be changed
+    //   between statements
+    pub fn set_backslash_escapes(&mut self, backslash_escapes: bool) {
+        self.backslash_escapes = backslash_escapes;
+    }
+
+    pub fn delimiter(&self) -> &str {
+        &self.delimiter
+    }
+
+    pub fn last_line(&self) -> &str {
+        &self.last_line
+    }
+
+    pub fn unterminated(&self) -> bool {
+        self.unterminated
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Normal,
+    Quoted(char),
+    BlockComment,
+}
+
+impl<R: BufRead> Iterator for StatementReader<R> {
+    type Item = std::io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut statement = String::new();
+        let mut state = State::Normal;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if let Some(pending) = self.pending.take() {
+                line = pending;
+            } else {
+                match self.reader.read_line(&mut line) {
+                    Ok(0) => {
+                        let statement = statement.trim();
+                        return if statement.is_empty() && state == State::Normal {
+                            None
+                        } else {
+                            self.unterminated = true;
+                            Some(Ok(statement.to_string()))
+                        };
+                    }
+                    Ok(_) => {
+                        if !line.trim().is_empty() {
+                            self.last_line = line.trim().to_string();
+                        }
+                    }
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+
+            if state == State::Normal && statement.trim().is_empty() {
+                let trimmed = line.trim();
+                if trimmed.is_empty() || trimmed.starts_with("--") || trimmed.starts_with('#') {
+                    if self.keep_comments && !trimmed.is_empty() {
+                        self.comments.push(trimmed.to_string());
+                    }
+                    continue;
+                }
+                if trimmed.get(..10).is_some_and(|d| d.eq_ignore_ascii_case("DELIMITER ")) {
+                    self.delimiter = trimmed[10..].trim().to_string();
+                    continue;
+                }
+            }
+
+            let chars: Vec<char> = line.chars().collect();
+            let delimiter: Vec<char> = self.delimiter.chars().collect();
+            let mut i = 0;
+            while i < chars.len() {
+                let c = chars[i];
+                match state {
+                    State::Quoted(q) => {
+                        if c == '\\' && q != '`' && self.backslash_escapes {
+                            statement.push(c);
+                            i += 1;
+                            if i < chars.len() {
+                                statement.push(chars[i]);
+                            }
+                        } else {
+                            statement.push(c);
+                            if c == q {
+                                state = State::Normal;
+                            }
+                        }
+                    }
+                    State::BlockComment => {
+                        statement.push(c);
+                        if c == '*' && chars.get(i + 1) == Some(&'/') {
+                            statement.push('/');
+                            i += 1;
+                            state = State::Normal;
+                        }
+                    }
+                    State::Normal => {
+                        if chars[i..].starts_with(&delimiter) {
+                            // Anything after the delimiter on this line is the
+                            //   start of the next statement
+                            let rest: String = chars[i + delimiter.len()..].iter().collect();
+                            if !rest.trim().is_empty() {
+                                self.pending = Some(rest);
+                            }
+                            return Some(Ok(statement.trim().to_string()));
+                        }
+                        match c {
+                            '\'' | '"' | '`' => state = State::Quoted(c),
+                            '/' if chars.get(i + 1) == Some(&'*') => state = State::BlockComment,
+                            '-' if chars.get(i + 1) == Some(&'-')
+                                && chars.get(i + 2).is_none_or(|c| c.is_whitespace()) =>
+                            {
+                                // Trailing comment, skip the rest of the line
+                                statement.push('\n');
+                                break;
+                            }
+                            _ => {}
+                        }
+                        statement.push(c);
+                    }
+                }
+                i += 1;
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RestoreOptions {
+    pub schema_rename: Option<SchemaRename>,
+    // Only these tables and views, with their triggers, when not empty
+    pub tables: Vec<String>,
+    pub skip_ddl: bool,
+    pub skip_data: bool,
+    // Only the statements of this --section of an appended dump
+    pub section: Option<String>,
+}
+
+//
+// Execute every statement of a dump against the target. Returns the number
+//   of statements executed. The metadata block in the header says how the
+//   dump was written, it comes before the first statement so the session is
+//   set up before anything that depends on it runs.
+//
+pub async fn restore<R: BufRead>(pool: &Pool<MySql>, reader: R, mut options: RestoreOptions) -> Result<usize, sqlx::Error> {
+    let mut count = 0;
+    // A single connection so session state (USE, SET ...) carries over
+    let mut conn = pool.acquire().await?;
+    let mut statements = StatementReader::new(reader).keep_comments();
+    let mut escape_mode = EscapeMode::Backslash;
+    let mut rename = options.schema_rename.take();
+    let classifier = Classifier::new();
+    let mut section: Option<String> = None;
+    while let Some(statement) = statements.next() {
+        let statement = statement.map_err(sqlx::Error::Io)?;
+
+        let mut metadata = Metadata::default();
+        let comments = statements.take_comments();
+        for comment in &comments {
+            if let Some(name) = comment.strip_prefix(SECTION_BEGIN) {
+                section = Some(name.to_string());
+            } else if comment.starts_with(SECTION_END) {
+                section = None;
+            }
+        }
+        let has_metadata = comments.iter().filter(|c| metadata.read_line(c)).count() > 0;
+        if let Some(rename) = rename.as_mut().filter(|_| has_metadata) {
+            rename.read_metadata(&metadata);
+        }
+        if has_metadata && metadata.escape_mode() != escape_mode {
+            escape_mode = metadata.escape_mode();
+            statements.set_backslash_escapes(escape_mode == EscapeMode::Backslash);
+            let sql_mode = match escape_mode {
+                EscapeMode::Backslash => "SET SESSION sql_mode = REPLACE(@@sql_mode, 'NO_BACKSLASH_ESCAPES', '')",
+                EscapeMode::Standard => "SET SESSION sql_mode = CONCAT(@@sql_mode, ',NO_BACKSLASH_ESCAPES')",
+            };
+            conn.execute(sql_mode).await?;
+        }
+
+        if options.section.as_ref().is_some_and(|wanted| section.as_ref() != Some(wanted))
+            || !classifier.includes(&statement, &options)
+        {
+            continue;
+        }
+        let statement = match rename.as_mut() {
+            Some(rename) => rename.rewrite(&statement),
+            None => statement,
+        };
+        conn.execute(statement.as_str()).await?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+//
+// Which object a statement belongs to, for restoring or splitting part of a
+//   dump. Table DDL, data and statistics belong to their table, triggers to
+//   the table they are on. Anything else (USE, SET, transactions) sets up
+//   the session.
+//
+pub struct Classifier {
+    create: Regex,
+    trigger_table: Regex,
+    table_statement: Regex,
+    insert: Regex,
+}
+
+impl Default for Classifier {
+    fn default() -> Self {
+        Classifier::new()
+    }
+}
+
+pub enum Part {
+    // The kind of object (table, view, procedure, function or trigger) and
+    //   the table or view it belongs to
+    Ddl(String, Option<String>),
+    Data(String),
+    Session,
+}
+
+impl Classifier {
+    pub fn new() -> Self {
+        let name = r"((?:(?:`(?:[^`]|``)+`|\w+)\.)?(?:`(?:[^`]|``)+`|\w+))";
+        Classifier {
+            create: Regex::new(&format!(
+                r"(?is)^create\s+(?:or\s+replace\s+)?(?:algorithm\s*=\s*\w+\s+)?(?:definer\s*=\s*\S+\s+)?(?:sql\s+security\s+\w+\s+)?(table|view|procedure|function|trigger)\s+(?:if\s+not\s+exists\s+)?{name}"
+            ))
+            .unwrap(),
+            trigger_table: Regex::new(&format!(r"(?is)^\s*(?:before|after)\s+(?:insert|update|delete)\s+on\s+{name}")).unwrap(),
+            table_statement: Regex::new(&format!(
+                r"(?is)^(?:alter|analyze|optimize|drop)\s+(?:table|view)\s+(?:if\s+exists\s+)?{name}"
+            ))
+            .unwrap(),
+            insert: insert_pattern(),
+        }
+    }
+
+    pub fn part(&self, statement: &str) -> Part {
+        if let Some(captures) = self.insert.captures(statement) {
+            return Part::Data(object_name(&captures[2]));
+        }
+        if let Some(captures) = self.create.captures(statement) {
+            let name = object_name(&captures[2]);
+            let kind = captures[1].to_lowercase();
+            let table = match kind.as_str() {
+                "table" | "view" => Some(name),
+                "trigger" => {
+                    let rest = &statement[captures.get(0).unwrap().end()..];
+                    self.trigger_table.captures(rest).map(|c| object_name(&c[1]))
+                }
+                // Routines don't belong to a table
+                _ => None,
+            };
+            return Part::Ddl(kind, table);
+        }
+        if let Some(captures) = self.table_statement.captures(statement) {
+            return Part::Ddl("table".to_string(), Some(object_name(&captures[1])));
+        }
+        Part::Session
+    }
+
+    fn includes(&self, statement: &str, options: &RestoreOptions) -> bool {
+        let wanted = |table: &Option<String>| {
+            options.tables.is_empty() || table.as_ref().is_some_and(|t| options.tables.contains(t))
+        };
+        match self.part(statement) {
+            Part::Session => true,
+            Part::Ddl(_, table) => !options.skip_ddl && wanted(&table),
+            Part::Data(table) => !options.skip_data && wanted(&Some(table)),
+        }
+    }
+}
+
+//
+// Loading a dump into a schema other than the one it was written for, so
+//   one backup can seed several environments. USE and CREATE SCHEMA
+//   statements and `schema`. qualified references are moved to the new
+//   name. The schema the dump was written for comes from its metadata, or
+//   failing that the first USE or CREATE SCHEMA. Only the table name of an
+//   insert is rewritten, never its values.
+//
+pub struct SchemaRename {
+    target: String,
+    // The source schema and the pattern for references qualified with it
+    source: Option<(String, Regex)>,
+    schema_statement: Regex,
+    insert: Regex,
+}
+
+impl SchemaRename {
+    pub fn new(target: &str) -> Self {
+        SchemaRename {
+            target: target.to_string(),
+            source: None,
+            schema_statement: Regex::new(
+                r"(?is)^(use\s+|create\s+(?:database|schema)\s+(?:/\*!\d+\s+)?(?:if\s+not\s+exists\s*)?(?:\*/\s*)?)(`(?:[^`]|``)+`|\w+)",
+            )
+            .unwrap(),
+            insert: insert_pattern(),
+        }
+    }
+
+    // Dumps written with --new-schema-name use the new name throughout
+    pub fn read_metadata(&mut self, metadata: &Metadata) {
+        if let Some(schema) = metadata.get("target_schema").or(metadata.get("schema")) {
+            self.set_source(schema);
+        }
+    }
+
+    fn set_source(&mut self, schema: &str) {
+        let qualifier = format!("(?:`{}`|\"{}\")\\.", regex::escape(&schema.replace('`', "``")), regex::escape(schema));
+        self.source = Some((schema.to_string(), Regex::new(&qualifier).unwrap()));
+    }
+
+    pub fn rewrite(&mut self, statement: &str) -> String {
+        if let Some(captures) = self.schema_statement.captures(statement) {
+            let schema = object_name(&captures[2]);
+            if self.source.is_none() {
+                self.set_source(&schema);
+            }
+            if self.source.as_ref().is_some_and(|(source, _)| *source == schema) {
+                let end = captures.get(0).unwrap().end();
+                return format!("{}{}{}", &captures[1], quote_identifier(&self.target), &statement[end..]);
+            }
+            return statement.to_string();
+        }
+        let Some((_, qualifier)) = &self.source else {
+            return statement.to_string();
+        };
+        let replacement = format!("{}.", quote_identifier(&self.target)).replace('$', "$$");
+        match self.insert.find(statement) {
+            Some(prefix) => format!(
+                "{}{}",
+                qualifier.replace_all(prefix.as_str(), replacement.as_str()),
+                &statement[prefix.end()..]
+            ),
+            None => qualifier.replace_all(statement, replacement.as_str()).into_owned(),
+        }
+    }
+}
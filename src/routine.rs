@@ -0,0 +1,174 @@
+use crate::escape::{quote_string, EscapeMode};
+use crate::logger::Logger;
+use crate::quote_definer;
+use crate::sql::{qualified_name, quote_identifier};
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use std::collections::HashMap;
+
+//
+// What SHOW CREATE PROCEDURE / FUNCTION returns for a routine
+//
+#[derive(Clone, Debug)]
+pub struct RoutineDdl {
+    pub name: String,
+    pub sql_mode: String,
+    pub ddl: String,
+    pub character_set: String,
+    pub collation: String,
+    pub db_collation: String,
+}
+
+// name, sql_mode, ddl, character_set_client, collation_connection,
+//   database_collation
+type ShowCreateRow = (String, String, Option<String>, String, String, String);
+
+// routine_name, definer, routine_definition, dtd_identifier,
+//   character_set_name, is_deterministic, sql_data_access, security_type,
+//   routine_comment, sql_mode, character_set_client, collation_connection,
+//   database_collation
+type RoutineRow = (
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    String,
+    String,
+    String,
+    String,
+    String,
+    String,
+    String,
+    String,
+);
+
+// specific_name, parameter_mode, parameter_name, dtd_identifier,
+//   character_set_name
+type ParameterRow = (String, Option<String>, String, String, Option<String>);
+
+//
+// The CREATE statements of the procedures or functions of a schema, with
+//   SQL bodies, by name. They are rebuilt from information_schema.ROUTINES and
+//   PARAMETERS, two queries for the whole schema rather than a SHOW CREATE
+//   per routine. Those only hold the body for the routine's definer or users
+//   who may see it, for the others SHOW CREATE is tried and without it the
+//   routine is skipped with a warning and None returned for it.
+//
+pub async fn schema_routines(
+    pool: &Pool<MySql>,
+    schema: &str,
+    routine_type: &str,
+    escape_mode: EscapeMode,
+) -> Result<Vec<(String, Option<RoutineDdl>)>, sqlx::Error> {
+    let routines: Vec<RoutineRow> = sqlx::query_as(
+        "select routine_name, definer, routine_definition, dtd_identifier, character_set_name, is_deterministic, sql_data_access, security_type, routine_comment, sql_mode, character_set_client, collation_connection, database_collation from information_schema.routines where routine_schema=? and routine_type=? and routine_body='SQL' order by routine_name",
+    )
+    .bind(schema)
+    .bind(routine_type)
+    .fetch_all(pool)
+    .await?;
+
+    // parameter_mode is NULL for the parameters of a function
+    let parameters: Vec<ParameterRow> = sqlx::query_as(
+        "select specific_name, parameter_mode, parameter_name, dtd_identifier, character_set_name from information_schema.parameters where specific_schema=? and routine_type=? and ordinal_position > 0 order by specific_name, ordinal_position",
+    )
+    .bind(schema)
+    .bind(routine_type)
+    .fetch_all(pool)
+    .await?;
+    let mut by_routine: HashMap<&str, Vec<String>> = HashMap::new();
+    for (routine, mode, parameter, data_type, character_set) in &parameters {
+        let data_type = with_charset(data_type, character_set.as_deref());
+        let parameter = match mode {
+            Some(mode) => format!("{} {} {}", mode, quote_identifier(parameter), data_type),
+            None => format!("{} {}", quote_identifier(parameter), data_type),
+        };
+        by_routine.entry(routine.as_str()).or_default().push(parameter);
+    }
+
+    let mut ddls = vec![];
+    for routine in routines {
+        let name = routine.0.clone();
+        let ddl = match &routine.2 {
+            Some(_) => {
+                let parameters = by_routine.get(name.as_str()).map(Vec::as_slice).unwrap_or_default();
+                Some(rebuild(routine, parameters, routine_type, escape_mode))
+            }
+            None => show_create(pool, schema, &name, routine_type).await?,
+        };
+        ddls.push((name, ddl));
+    }
+    Ok(ddls)
+}
+
+fn rebuild(routine: RoutineRow, parameters: &[String], routine_type: &str, escape_mode: EscapeMode) -> RoutineDdl {
+    let (name, definer, body, returns, returns_charset, deterministic, data_access, security, comment, sql_mode, character_set, collation, db_collation) =
+        routine;
+    let mut ddl = format!(
+        "CREATE DEFINER={} {} {}({})",
+        quote_definer(&definer),
+        routine_type,
+        quote_identifier(&name),
+        parameters.join(", ")
+    );
+    if let (Some(returns), "FUNCTION") = (returns, routine_type) {
+        ddl.push_str(&format!(" RETURNS {}", with_charset(&returns, returns_charset.as_deref())));
+    }
+    // Only what differs from the defaults, as SHOW CREATE writes it
+    if deterministic == "YES" {
+        ddl.push_str("\n    DETERMINISTIC");
+    }
+    if data_access != "CONTAINS SQL" {
+        ddl.push_str(&format!("\n    {}", data_access));
+    }
+    if security != "DEFINER" {
+        ddl.push_str(&format!("\n    SQL SECURITY {}", security));
+    }
+    if !comment.is_empty() {
+        ddl.push_str(&format!("\n    COMMENT {}", quote_string(&comment, escape_mode)));
+    }
+    ddl.push('\n');
+    ddl.push_str(&body.unwrap_or_default());
+    RoutineDdl {
+        name,
+        sql_mode,
+        ddl,
+        character_set,
+        collation,
+        db_collation,
+    }
+}
+
+// String types carry their character set, as SHOW CREATE writes them
+fn with_charset(data_type: &str, character_set: Option<&str>) -> String {
+    match character_set {
+        Some(character_set) => format!("{} CHARSET {}", data_type, character_set),
+        None => data_type.to_string(),
+    }
+}
+
+// SHOW CREATE for a routine whose body information_schema hides, it returns
+//   no statement either without SHOW_ROUTINE or SELECT on mysql.proc
+async fn show_create(pool: &Pool<MySql>, schema: &str, name: &str, routine_type: &str) -> Result<Option<RoutineDdl>, sqlx::Error> {
+    let (name, sql_mode, ddl, character_set, collation, db_collation): ShowCreateRow =
+        sqlx::query_as(format!("show create {} {}", routine_type.to_lowercase(), qualified_name(schema, name)).as_str())
+            .fetch_one(pool)
+            .await?;
+    let Some(ddl) = ddl else {
+        Logger::warn(format!(
+            "Skipping {} {}, its definition is hidden from this user, it needs SHOW_ROUTINE or SELECT on mysql.proc",
+            routine_type.to_lowercase(),
+            name
+        ));
+        return Ok(None);
+    };
+    Ok(Some(RoutineDdl {
+        name,
+        sql_mode,
+        ddl,
+        character_set,
+        collation,
+        db_collation,
+    }))
+}
@@ -0,0 +1,103 @@
+use crate::catalog::Catalog;
+use crate::fixture::{column_list, row_key};
+use crate::logger::Logger;
+use crate::sql::qualified_name;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// Seed for tables sampled at random, so a rerun picks the same rows as long
+//   as the table is read in the same order
+const RANDOM_SEED: u32 = 1;
+
+//
+// How much of each table a sampled dump keeps
+//   Percent - e.g. 1%, spread evenly over the key range
+//   Every   - every Nth row in key order, starting with the first
+//
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SampleRate {
+    Percent(f64),
+    Every(u64),
+}
+
+impl FromStr for SampleRate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let percent = s.trim().trim_end_matches('%').trim();
+        match percent.parse::<f64>() {
+            Ok(percent) if percent > 0.0 && percent <= 100.0 => Ok(SampleRate::Percent(percent)),
+            _ => Err(format!("Invalid sample {s}, expected a percentage above 0 and up to 100, e.g. 1%")),
+        }
+    }
+}
+
+impl SampleRate {
+    // Whether the row numbered `rn` in key order, counting from 1, is kept.
+    //   The first row always is, so no table comes out empty.
+    fn keeps(self, rn: &str) -> String {
+        match self {
+            SampleRate::Percent(percent) => {
+                format!("ceil({rn} * {percent} / 100) > ceil(({rn} - 1) * {percent} / 100)")
+            }
+            SampleRate::Every(n) => format!("mod({rn} - 1, {n}) = 0"),
+        }
+    }
+
+    fn fraction(self) -> String {
+        match self {
+            SampleRate::Percent(percent) => format!("{} / 100", percent),
+            SampleRate::Every(n) => format!("1 / {}", n),
+        }
+    }
+}
+
+//
+// A representative subset of every table. Rows are numbered along the
+//   primary key, or the first unique index, by a window function in one pass
+//   over the key and the numbers picked are spread evenly over it, so the
+//   sample covers old and new rows alike and the data query reads the rows
+//   it keeps by key instead of sorting the table with ORDER BY RAND().
+//   Tables without a key keep a seeded random share of their rows.
+//
+// Filters already on a table, e.g. a slice, are sampled from rather than
+//   replaced. Returns the clause to append to each table's data query, as
+//   fixtures do. Numbering rows needs MySQL 8.0 or MariaDB 10.2.
+//
+pub fn sample_rows(catalog: &Catalog, rate: SampleRate, filters: &HashMap<String, String>) -> HashMap<String, String> {
+    let mut sampled = HashMap::new();
+    for table in &catalog.tables {
+        let condition = match filters.get(&table.name) {
+            None => None,
+            Some(filter) => match filter.strip_prefix("where ") {
+                Some(condition) => Some(condition),
+                None => panic!("The rows of {} are already limited by {}, they can't be sampled", table.name, filter),
+            },
+        };
+        let clause = match row_key(table) {
+            Some(key) => {
+                let key = column_list(&key);
+                format!(
+                    "where ({}) in (select {} from (select {}, row_number() over (order by {}) as dbdump_rn from {}{}) dbdump_sample where {})",
+                    key,
+                    key,
+                    key,
+                    key,
+                    qualified_name(&catalog.schema, &table.name),
+                    condition.map(|c| format!(" where {}", c)).unwrap_or_default(),
+                    rate.keeps("dbdump_rn")
+                )
+            }
+            None => {
+                Logger::warn(format!("{} has no primary or unique key, it is sampled at random", table.name));
+                let predicate = format!("rand({}) < {}", RANDOM_SEED, rate.fraction());
+                match condition {
+                    Some(condition) => format!("where ({}) and {}", condition, predicate),
+                    None => format!("where {}", predicate),
+                }
+            }
+        };
+        sampled.insert(table.name.clone(), clause);
+    }
+    sampled
+}
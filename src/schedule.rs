@@ -0,0 +1,248 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDateTime, Timelike};
+use regex::Regex;
+use std::path::Path;
+use std::str::FromStr;
+
+//
+// When a watched dump runs. Either a fixed interval (30m, 6h, 1d) counted
+//   from the start of the previous run, or a standard 5 field cron expression
+//   (minute hour day-of-month month day-of-week) in local time.
+//
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Schedule {
+    Every(Duration),
+    Cron(CronExpression),
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.split_whitespace().count() == 5 {
+            return CronExpression::from_str(s).map(Schedule::Cron);
+        }
+        let digits = s.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+        let count: i64 = digits
+            .parse()
+            .map_err(|_| format!("Invalid schedule {s}, expected an interval like 30m, 6h, 1d or a cron expression"))?;
+        let duration = match &s[digits.len()..] {
+            "s" => Duration::seconds(count),
+            "m" => Duration::minutes(count),
+            "h" => Duration::hours(count),
+            "d" => Duration::days(count),
+            unit => return Err(format!("Unknown interval unit {unit}, expected one of s, m, h, d")),
+        };
+        if count <= 0 {
+            return Err(format!("Invalid schedule {s}, the interval must be positive"));
+        }
+        Ok(Schedule::Every(duration))
+    }
+}
+
+impl Schedule {
+    // The first time after `after` the dump should run
+    pub fn next_after(&self, after: DateTime<Local>) -> DateTime<Local> {
+        match self {
+            Schedule::Every(duration) => after + *duration,
+            Schedule::Cron(cron) => cron.next_after(after),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CronExpression {
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    days: Vec<u32>,
+    months: Vec<u32>,
+    weekdays: Vec<u32>,
+}
+
+impl FromStr for CronExpression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!("Invalid cron expression {s}, expected 5 fields"));
+        }
+        let mut weekdays = parse_field(fields[4], 0, 7)?;
+        // Both 0 and 7 are Sunday
+        if weekdays.contains(&7) {
+            weekdays.retain(|d| *d != 7);
+            if !weekdays.contains(&0) {
+                weekdays.insert(0, 0);
+            }
+        }
+        Ok(CronExpression {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            weekdays,
+        })
+    }
+}
+
+// Parse one cron field: *, n, a-b, lists of those, each with an optional /step
+fn parse_field(field: &str, min: u32, max: u32) -> Result<Vec<u32>, String> {
+    let mut values = vec![];
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or(format!("Invalid step in cron field {field}"))?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (parse_value(a, field)?, parse_value(b, field)?)
+        } else {
+            let value = parse_value(range, field)?;
+            // n/step means from n to the end of the range
+            (value, if part.contains('/') { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            return Err(format!("Cron field {field} is out of range {min}-{max}"));
+        }
+        values.extend((start..=end).step_by(step as usize));
+    }
+    values.sort_unstable();
+    values.dedup();
+    Ok(values)
+}
+
+fn parse_value(value: &str, field: &str) -> Result<u32, String> {
+    value.parse().map_err(|_| format!("Invalid value {value} in cron field {field}"))
+}
+
+impl CronExpression {
+    fn matches(&self, time: &DateTime<Local>) -> bool {
+        self.minutes.contains(&time.minute())
+            && self.hours.contains(&time.hour())
+            && self.months.contains(&time.month())
+            && self.day_matches(time)
+    }
+
+    // Like cron, when both day fields are restricted either one matching is
+    //   enough
+    fn day_matches(&self, time: &DateTime<Local>) -> bool {
+        let day = self.days.contains(&time.day());
+        let weekday = self.weekdays.contains(&time.weekday().num_days_from_sunday());
+        let any_day = self.days.len() == 31;
+        let any_weekday = self.weekdays.len() == 7;
+        match (any_day, any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+
+    fn next_after(&self, after: DateTime<Local>) -> DateTime<Local> {
+        let mut time = after
+            .with_second(0)
+            .and_then(|t| t.with_nanosecond(0))
+            .unwrap_or(after)
+            + Duration::minutes(1);
+        // Minute steps are cheap, and four years covers every valid expression
+        //   including 29th of February
+        for _ in 0..4 * 366 * 24 * 60 {
+            if self.matches(&time) {
+                return time;
+            }
+            time += Duration::minutes(1);
+        }
+        panic!("Cron expression never matches");
+    }
+}
+
+// How {timestamp} is written in output filenames
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+//
+// Output filenames for watched dumps. {schema} and {timestamp} are replaced,
+//   the timestamp sorts in time order so the newest files are easy to find.
+//
+pub fn render_filename(template: &str, schema: &str, time: &DateTime<Local>) -> String {
+    template
+        .replace("{schema}", schema)
+        .replace("{timestamp}", &time.format(TIMESTAMP_FORMAT).to_string())
+}
+
+//
+// Which of the files produced from a template to keep. A file is kept when
+//   any rule keeps it: it is one of the newest `keep_last`, or its timestamp
+//   is less than `keep_days` days old. Without either rule nothing is removed.
+//
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Retention {
+    // 0 for no limit on the number of files
+    pub keep_last: usize,
+    pub keep_days: Option<u32>,
+}
+
+impl Retention {
+    pub fn is_empty(&self) -> bool {
+        self.keep_last == 0 && self.keep_days.is_none()
+    }
+}
+
+//
+// Delete the files produced from the template the retention rules don't
+//   keep, by the timestamp in their names. Only files whose name matches the
+//   template exactly are considered, anything else in the directory is left
+//   alone. Returns the files removed.
+//
+pub fn apply_retention(template: &str, schema: &str, retention: Retention, now: DateTime<Local>) -> std::io::Result<Vec<String>> {
+    if retention.is_empty() {
+        return Ok(vec![]);
+    }
+    let template = template.replace("{schema}", schema);
+    let path = Path::new(&template);
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name_template = path.file_name().and_then(|n| n.to_str()).unwrap_or(&template);
+    let pattern = regex::escape(name_template)
+        .replacen(r"\{timestamp\}", r"(\d{8}-\d{6})", 1)
+        .replace(r"\{timestamp\}", r"\d{8}-\d{6}");
+    let pattern = Regex::new(&format!("^{pattern}$")).expect("Invalid filename template");
+
+    // Names with their timestamps, newest first
+    let mut files: Vec<(String, Option<DateTime<Local>>)> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter_map(|name| {
+            let captures = pattern.captures(&name)?;
+            let time = NaiveDateTime::parse_from_str(&captures[1], TIMESTAMP_FORMAT)
+                .ok()
+                .and_then(|t| t.and_local_timezone(Local).earliest());
+            Some((name, time))
+        })
+        .collect();
+    files.sort_by(|a, b| b.0.cmp(&a.0));
+
+    let cutoff = retention.keep_days.map(|days| now - Duration::days(days as i64));
+    let mut removed = vec![];
+    for (index, (name, time)) in files.iter().enumerate() {
+        let recent = index < retention.keep_last;
+        let young = match (cutoff, time) {
+            (Some(cutoff), Some(time)) => *time >= cutoff,
+            _ => false,
+        };
+        if !recent && !young {
+            let file = dir.join(name);
+            std::fs::remove_file(&file)?;
+            removed.push(file.display().to_string());
+        }
+    }
+    Ok(removed)
+}
@@ -0,0 +1,115 @@
+use crate::config::Config;
+use crate::transform::{RowTransformer, Value};
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+use std::collections::HashMap;
+
+//
+// Row filters and value transforms written in rhai. Every script sees the
+//   row being exported as `row`, a map of column name to value, and a
+//   transform also gets the column's own value as `value`. Numbers and
+//   strings are passed as such, NULL as () and other values (dates, decimals,
+//   binary) as the SQL literal text. A transform returning a literal
+//   unchanged leaves it as is, any other string is written as a string.
+//
+// Transforms run after the filter and see the original row, so one column
+//   can be derived from another that is also being replaced.
+//
+pub struct ScriptTransformer {
+    engine: Engine,
+    tables: HashMap<String, TableScripts>,
+}
+
+struct TableScripts {
+    filter: Option<AST>,
+    transforms: Vec<(String, AST)>,
+}
+
+impl ScriptTransformer {
+    pub fn new(config: &Config) -> Result<Self, String> {
+        let engine = Engine::new();
+        let mut tables = HashMap::new();
+        for (table, scripts) in &config.tables {
+            let compile = |what: &str, script: &str| {
+                engine
+                    .compile(script)
+                    .map_err(|e| format!("Unable to compile the {} script for {}: {}", what, table, e))
+            };
+            let filter = scripts.filter.as_deref().map(|s| compile("filter", s)).transpose()?;
+            let mut transforms = vec![];
+            for (column, script) in &scripts.transform {
+                transforms.push((column.clone(), compile(&format!("{} transform", column), script)?));
+            }
+            tables.insert(table.clone(), TableScripts { filter, transforms });
+        }
+        Ok(ScriptTransformer { engine, tables })
+    }
+}
+
+impl RowTransformer for ScriptTransformer {
+    fn transform(&self, table: &str, columns: &[String], values: &mut [Value]) -> bool {
+        let scripts = match self.tables.get(table) {
+            Some(scripts) => scripts,
+            None => return true,
+        };
+        let row: Map = columns.iter().zip(values.iter()).map(|(c, v)| (c.into(), to_dynamic(v))).collect();
+
+        if let Some(filter) = &scripts.filter {
+            let mut scope = Scope::new();
+            scope.push_constant("row", row.clone());
+            let keep = self
+                .engine
+                .eval_ast_with_scope::<bool>(&mut scope, filter)
+                .unwrap_or_else(|e| panic!("The filter script for {} failed: {}", table, e));
+            if !keep {
+                return false;
+            }
+        }
+
+        for (column, script) in &scripts.transforms {
+            let index = match columns.iter().position(|c| c == column) {
+                Some(index) => index,
+                None => panic!("The transform script for {}.{} names a column the table doesn't have", table, column),
+            };
+            let mut scope = Scope::new();
+            scope.push_constant("row", row.clone());
+            scope.push_constant("value", to_dynamic(&values[index]));
+            let result = self
+                .engine
+                .eval_ast_with_scope::<Dynamic>(&mut scope, script)
+                .unwrap_or_else(|e| panic!("The transform script for {}.{} failed: {}", table, column, e));
+            values[index] = from_dynamic(result, &values[index]);
+        }
+        true
+    }
+}
+
+fn to_dynamic(value: &Value) -> Dynamic {
+    match value {
+        Value::Null => Dynamic::UNIT,
+        Value::Int(n) => Dynamic::from(*n),
+        Value::UInt(n) => match i64::try_from(*n) {
+            Ok(n) => Dynamic::from(n),
+            Err(_) => Dynamic::from(*n as f64),
+        },
+        Value::Float(n) => Dynamic::from(*n),
+        Value::Text(s) | Value::Sql(s) => Dynamic::from(s.clone()),
+    }
+}
+
+fn from_dynamic(result: Dynamic, original: &Value) -> Value {
+    if result.is_unit() {
+        Value::Null
+    } else if let Ok(n) = result.as_int() {
+        Value::Int(n)
+    } else if let Ok(n) = result.as_float() {
+        Value::Float(n)
+    } else if let Ok(b) = result.as_bool() {
+        Value::Int(b as i64)
+    } else {
+        let text = result.to_string();
+        match original {
+            Value::Sql(literal) if *literal == text => Value::Sql(text),
+            _ => Value::Text(text),
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use chrono::{Duration, NaiveDate};
+use crate::catalog::{Catalog, ColumnInfo, Table};
+use crate::escape::{quote_string, EscapeMode};
+use crate::logger::Logger;
+use crate::sql::Compat;
+use crate::std_writer::StdWriter;
+use std::collections::HashMap;
+
+//
+// Synthetic data for an empty schema. Values are generated from the column
+//   types alone, tables are filled parents first so every foreign key points
+//   at a generated row, and columns in unique indexes get distinct values.
+//   The same seed always produces the same data.
+//
+
+const WORDS: [&str; 24] = [
+    "alpha", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel", "india", "juliet", "kilo", "lima",
+    "mike", "november", "oscar", "papa", "quebec", "romeo", "sierra", "tango", "uniform", "victor", "whiskey",
+    "yankee",
+];
+
+// xorshift64*, plenty for fake data and keeps the output reproducible
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        if n == 0 {
+            0
+        } else {
+            self.next() % n
+        }
+    }
+
+    fn word(&mut self) -> &'static str {
+        WORDS[self.below(WORDS.len() as u64) as usize]
+    }
+}
+
+// Order tables so every table comes after the tables it references. Tables
+//   in a reference cycle are appended in name order.
+fn fill_order(catalog: &Catalog) -> Vec<&Table> {
+    let mut ordered: Vec<&Table> = vec![];
+    let mut remaining: Vec<&Table> = catalog.tables.iter().collect();
+    while !remaining.is_empty() {
+        let ready = remaining.iter().position(|t| {
+            t.foreign_keys.iter().all(|fk| {
+                fk.referenced_table == t.name
+                    || ordered.iter().any(|o| o.name == fk.referenced_table)
+                    || !remaining.iter().any(|r| r.name == fk.referenced_table)
+            })
+        });
+        ordered.push(remaining.remove(ready.unwrap_or(0)));
+    }
+    ordered
+}
+
+// The length or precision in a type such as varchar(40) or decimal(10,2)
+fn type_size(column_type: &str) -> Option<(u64, u64)> {
+    let start = column_type.find('(')?;
+    let end = column_type[start..].find(')')? + start;
+    let mut parts = column_type[start + 1..end].split(',');
+    let size = parts.next()?.trim().parse().ok()?;
+    let scale = parts.next().and_then(|s| s.trim().parse().ok()).unwrap_or(0);
+    Some((size, scale))
+}
+
+// The quoted members of an enum(...) or set(...) type, still quoted
+fn members(column_type: &str) -> Vec<String> {
+    let start = column_type.find('(').map(|i| i + 1).unwrap_or(0);
+    let end = column_type.rfind(')').unwrap_or(column_type.len());
+    let mut members = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    let chars: Vec<char> = column_type[start..end].chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\'' {
+            if quoted && chars.get(i + 1) == Some(&'\'') {
+                current.push_str("''");
+                i += 2;
+                continue;
+            }
+            quoted = !quoted;
+            current.push(c);
+        } else if c == ',' && !quoted {
+            members.push(current.trim().to_string());
+            current.clear();
+        } else {
+            current.push(c);
+        }
+        i += 1;
+    }
+    if !current.trim().is_empty() {
+        members.push(current.trim().to_string());
+    }
+    members
+}
+
+//
+// A literal for one column of row `row` (counting from 1). `unique` columns
+//   must not repeat across rows.
+//
+fn generate_value(column: &ColumnInfo, row: u64, unique: bool, random: &mut Random, mode: EscapeMode) -> String {
+    let column_type = column.column_type.to_lowercase();
+    let base = column_type.split(['(', ' ']).next().unwrap_or("");
+    let unsigned = column_type.contains("unsigned");
+    match base {
+        "tinyint" if column_type.starts_with("tinyint(1)") => {
+            if unique { row.min(127).to_string() } else { random.below(2).to_string() }
+        }
+        "tinyint" | "smallint" | "mediumint" | "int" | "integer" | "bigint" => {
+            let max: u64 = match base {
+                "tinyint" => 127,
+                "smallint" => 32_767,
+                "mediumint" => 8_388_607,
+                _ => 2_147_483_647,
+            };
+            if unique || column.extra.contains("auto_increment") {
+                row.min(max).to_string()
+            } else if unsigned {
+                random.below(max.min(100_000)).to_string()
+            } else {
+                (random.below(max.min(100_000)) as i64 - (max.min(100_000) / 2) as i64).to_string()
+            }
+        }
+        "decimal" | "numeric" => {
+            let (precision, scale) = type_size(&column_type).unwrap_or((10, 0));
+            let whole = 10u64.saturating_pow(precision.saturating_sub(scale).min(6) as u32);
+            let integer = if unique { row % whole } else { random.below(whole) };
+            if scale > 0 {
+                format!("{}.{:0width$}", integer, random.below(10u64.pow(scale.min(6) as u32)), width = scale.min(6) as usize)
+            } else {
+                integer.to_string()
+            }
+        }
+        "float" | "double" | "real" => {
+            if unique {
+                format!("{}.5", row)
+            } else {
+                format!("{}.{}", random.below(10_000), random.below(100))
+            }
+        }
+        "bit" => format!("b'{}'", if unique { row % 2 } else { random.below(2) }),
+        "year" => (1990 + if unique { row % 165 } else { random.below(36) }).to_string(),
+        "date" => format!("'{}'", random_date(random, unique.then_some(row)).format("%Y-%m-%d")),
+        "datetime" | "timestamp" => {
+            let time = random_date(random, unique.then_some(row))
+                .and_hms_opt(random.below(24) as u32, random.below(60) as u32, random.below(60) as u32)
+                .unwrap();
+            format!("'{}'", time.format("%Y-%m-%d %H:%M:%S"))
+        }
+        "time" => format!("'{:02}:{:02}:{:02}'", random.below(24), random.below(60), random.below(60)),
+        "enum" | "set" => {
+            let members = members(&column.column_type);
+            if members.is_empty() {
+                "''".to_string()
+            } else if unique {
+                members[(row - 1) as usize % members.len()].clone()
+            } else {
+                members[random.below(members.len() as u64) as usize].clone()
+            }
+        }
+        "json" => format!("'{{\"id\": {}, \"name\": \"{}\"}}'", row, random.word()),
+        "binary" | "varbinary" | "tinyblob" | "blob" | "mediumblob" | "longblob" => {
+            let length = type_size(&column_type).map(|s| s.0).unwrap_or(16).min(16);
+            let mut bytes: String = (0..length).map(|_| format!("{:02X}", random.below(256))).collect();
+            if unique {
+                // Keep the row number in the last bytes so values can't repeat
+                let suffix = format!("{:016X}", row);
+                let keep = bytes.len().saturating_sub(suffix.len());
+                bytes = format!("{}{}", &bytes[..keep], &suffix[suffix.len() - (bytes.len() - keep)..]);
+            }
+            if bytes.is_empty() { "''".to_string() } else { format!("X'{}'", bytes) }
+        }
+        "geometry" | "point" | "linestring" | "polygon" | "multipoint" | "multilinestring" | "multipolygon"
+        | "geometrycollection" | "geomcollection" => {
+            format!("ST_GeomFromText('POINT({} {})')", random.below(180), random.below(90))
+        }
+        _ => {
+            // char, varchar and the text types
+            let limit = match base {
+                "char" | "varchar" => type_size(&column_type).map(|s| s.0).unwrap_or(32),
+                "tinytext" => 255,
+                _ => 200,
+            } as usize;
+            let mut text = if base.ends_with("text") {
+                (0..8).map(|_| random.word()).collect::<Vec<&str>>().join(" ")
+            } else {
+                format!("{} {}", random.word(), random.word())
+            };
+            if unique {
+                let suffix = format!("-{}", row);
+                text.truncate(limit.saturating_sub(suffix.len()));
+                text.push_str(&suffix);
+                text = text[text.len().saturating_sub(limit)..].to_string();
+            } else {
+                text.truncate(limit);
+            }
+            quote_string(&text, mode)
+        }
+    }
+}
+
+// A date between 2000 and 2025, or for unique columns the row'th day from 2000
+fn random_date(random: &mut Random, row: Option<u64>) -> NaiveDate {
+    let start = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+    let days = row.unwrap_or_else(|| random.below(26 * 365));
+    start + Duration::days(days as i64)
+}
+
+//
+// Write insert statements for `rows` rows in every table.
+//
+pub fn write_seed_data(writer: &mut StdWriter, catalog: &Catalog, rows: u64, seed: u64, compat: Compat) {
+    let mode = compat.escape_mode(EscapeMode::Backslash);
+    let mut random = Random(seed.max(1));
+    // Generated literals for each table, by column name
+    let mut generated: HashMap<String, Vec<HashMap<String, String>>> = HashMap::new();
+
+    for table in fill_order(catalog) {
+        // Generated columns can't be given values
+        let columns: Vec<&ColumnInfo> = table
+            .columns
+            .iter()
+            .filter(|c| !c.extra.to_uppercase().contains("GENERATED"))
+            .collect();
+        let unique_columns: Vec<&String> = table
+            .indexes
+            .iter()
+            .filter(|i| i.unique)
+            .flat_map(|i| i.columns.iter())
+            .collect();
+
+        let mut table_rows: Vec<HashMap<String, String>> = vec![];
+        for row in 1..=rows {
+            let mut values: HashMap<String, String> = HashMap::new();
+
+            // Foreign keys take all their columns from one parent row
+            for fk in &table.foreign_keys {
+                let parent_rows = if fk.referenced_table == table.name {
+                    &table_rows
+                } else {
+                    match generated.get(&fk.referenced_table) {
+                        Some(parent_rows) => parent_rows,
+                        None => {
+                            Logger::warn(format!(
+                                "{} references {} which is filled later, {} gets random values",
+                                table.name, fk.referenced_table, fk.name
+                            ));
+                            continue;
+                        }
+                    }
+                };
+                let nullable = fk
+                    .columns
+                    .iter()
+                    .all(|c| table.columns.iter().any(|tc| &tc.name == c && tc.nullable));
+                if parent_rows.is_empty() {
+                    if nullable {
+                        for column in &fk.columns {
+                            values.insert(column.clone(), "NULL".to_string());
+                        }
+                    }
+                    continue;
+                }
+                // One-to-one references walk the parents in order
+                let unique = fk.columns.iter().any(|c| unique_columns.contains(&c));
+                let parent = if unique {
+                    &parent_rows[(row - 1) as usize % parent_rows.len()]
+                } else {
+                    &parent_rows[random.below(parent_rows.len() as u64) as usize]
+                };
+                for (column, referenced) in fk.columns.iter().zip(&fk.referenced_columns) {
+                    if let Some(value) = parent.get(referenced) {
+                        values.insert(column.clone(), value.clone());
+                    }
+                }
+            }
+
+            for column in &columns {
+                if values.contains_key(&column.name) {
+                    continue;
+                }
+                let unique = unique_columns.contains(&&column.name);
+                let value = if column.nullable && !unique && random.below(10) == 0 {
+                    "NULL".to_string()
+                } else {
+                    generate_value(column, row, unique, &mut random, mode)
+                };
+                values.insert(column.name.clone(), value);
+            }
+            table_rows.push(values);
+        }
+
+        if !table_rows.is_empty() {
+            writer.println(format!("-- Seed data for {}", table.name).as_str());
+            let column_names = columns
+                .iter()
+                .map(|c| compat.quote_identifier(&c.name))
+                .collect::<Vec<String>>()
+                .join(",");
+            for chunk in table_rows.chunks(500) {
+                let tuples: Vec<String> = chunk
+                    .iter()
+                    .map(|values| {
+                        format!(
+                            "({})",
+                            columns
+                                .iter()
+                                .map(|c| values[&c.name].as_str())
+                                .collect::<Vec<&str>>()
+                                .join(",")
+                        )
+                    })
+                    .collect();
+                writer.println(
+                    format!(
+                        "insert into {} ({}) values\n{};",
+                        compat.quote_identifier(&table.name),
+                        column_names,
+                        tuples.join(",\n")
+                    )
+                    .as_str(),
+                );
+            }
+        }
+        generated.insert(table.name.clone(), table_rows);
+    }
+}
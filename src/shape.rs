@@ -0,0 +1,149 @@
+use crate::catalog::{Catalog, ColumnInfo, Table};
+use crate::std_writer::StdWriter;
+use std::collections::HashMap;
+
+//
+// The structure of a schema with every name replaced, to share with vendors
+//   and support without giving away what the tables are about. Tables are
+//   t1, t2, ... in name order, columns c1, c2, ... in table order, indexes
+//   k1, k2, ... and foreign keys t<n>_fk1, ... ENUM and SET values become
+//   'v1', 'v2', ... Comments, string defaults, generated column expressions
+//   and views, routines and triggers are left out. The numbering only
+//   depends on the names, so the same schema always comes out the same.
+//
+pub fn write_anonymized_schema(writer: &mut StdWriter, catalog: &Catalog) {
+    let table_names: HashMap<&str, String> = catalog
+        .tables
+        .iter()
+        .enumerate()
+        .map(|(i, t)| (t.name.as_str(), format!("t{}", i + 1)))
+        .collect();
+    writer.println("-- Anonymized schema structure written by dbdump, all names are replaced");
+    writer.println("");
+    // Tables are in name order, not dependency order
+    writer.println("SET FOREIGN_KEY_CHECKS=0;");
+    writer.println("");
+    for table in &catalog.tables {
+        writer.println(format!("{};", create_table(table, &table_names, catalog)).as_str());
+        writer.println("");
+    }
+    writer.println("SET FOREIGN_KEY_CHECKS=1;");
+}
+
+fn create_table(table: &Table, table_names: &HashMap<&str, String>, catalog: &Catalog) -> String {
+    let name = &table_names[table.name.as_str()];
+    let mut definitions: Vec<String> = table.columns.iter().enumerate().map(|(i, c)| column_definition(i, c)).collect();
+
+    let mut keys = 0;
+    for index in &table.indexes {
+        // Functional index parts have no column to name
+        let columns: Vec<String> = index.columns.iter().filter_map(|c| column_name(table, c)).collect();
+        if columns.is_empty() {
+            continue;
+        }
+        if index.name == "PRIMARY" {
+            definitions.push(format!("  PRIMARY KEY ({})", columns.join(", ")));
+            continue;
+        }
+        keys += 1;
+        let unique = if index.unique { "UNIQUE " } else { "" };
+        definitions.push(format!("  {}KEY k{} ({})", unique, keys, columns.join(", ")));
+    }
+
+    for (i, fk) in table.foreign_keys.iter().enumerate() {
+        // A parent in another schema has no number
+        let Some(parent) = catalog.tables.iter().find(|t| t.name == fk.referenced_table) else {
+            continue;
+        };
+        let columns: Vec<String> = fk.columns.iter().filter_map(|c| column_name(table, c)).collect();
+        let referenced: Vec<String> = fk.referenced_columns.iter().filter_map(|c| column_name(parent, c)).collect();
+        definitions.push(format!(
+            "  CONSTRAINT {}_fk{} FOREIGN KEY ({}) REFERENCES {} ({})",
+            name,
+            i + 1,
+            columns.join(", "),
+            table_names[parent.name.as_str()],
+            referenced.join(", ")
+        ));
+    }
+
+    let mut ddl = format!("CREATE TABLE {} (\n{}\n)", name, definitions.join(",\n"));
+    if let Some(engine) = &table.engine {
+        ddl.push_str(&format!(" ENGINE={}", engine));
+    }
+    ddl
+}
+
+// The generic name of one of a table's columns
+fn column_name(table: &Table, name: &str) -> Option<String> {
+    table.columns.iter().position(|c| c.name == name).map(|i| format!("c{}", i + 1))
+}
+
+fn column_definition(index: usize, column: &ColumnInfo) -> String {
+    let (column_type, values) = anonymized_type(&column.column_type);
+    let mut definition = format!("  c{} {}", index + 1, column_type);
+    if !column.nullable {
+        definition.push_str(" NOT NULL");
+    }
+    let extra = column.extra.to_lowercase();
+    if let Some(default) = &column.default {
+        // Numbers and the current time say nothing about the business
+        let upper = default.to_uppercase();
+        if default.parse::<f64>().is_ok() || upper.starts_with("CURRENT_TIMESTAMP") || upper.starts_with("NOW(") {
+            definition.push_str(&format!(" DEFAULT {}", default));
+        } else if let Some(position) = values.iter().position(|v| v == default) {
+            definition.push_str(&format!(" DEFAULT 'v{}'", position + 1));
+        }
+    }
+    if extra.contains("auto_increment") {
+        definition.push_str(" AUTO_INCREMENT");
+    }
+    if extra.contains("on update current_timestamp") {
+        definition.push_str(" ON UPDATE CURRENT_TIMESTAMP");
+    }
+    definition
+}
+
+// The column type with ENUM and SET values replaced, and the values that
+//   were replaced so a default can be matched to its new name
+fn anonymized_type(column_type: &str) -> (String, Vec<String>) {
+    let lower = column_type.to_lowercase();
+    let kind = if lower.starts_with("enum(") {
+        "enum"
+    } else if lower.starts_with("set(") {
+        "set"
+    } else {
+        return (column_type.to_string(), vec![]);
+    };
+    let values = quoted_values(&column_type[kind.len() + 1..]);
+    let replaced: Vec<String> = (1..=values.len()).map(|i| format!("'v{}'", i)).collect();
+    (format!("{}({})", kind, replaced.join(",")), values)
+}
+
+// The values of a 'a','b''c' list, up to the closing parenthesis
+fn quoted_values(list: &str) -> Vec<String> {
+    let mut values = vec![];
+    let mut chars = list.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                let mut value = String::new();
+                while let Some(c) = chars.next() {
+                    if c == '\'' {
+                        if chars.peek() == Some(&'\'') {
+                            chars.next();
+                            value.push('\'');
+                            continue;
+                        }
+                        break;
+                    }
+                    value.push(c);
+                }
+                values.push(value);
+            }
+            ')' => break,
+            _ => {}
+        }
+    }
+    values
+}
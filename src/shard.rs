@@ -0,0 +1,137 @@
+use crate::compress::Compression;
+use crate::logger::Logger;
+use crate::sql::quote_identifier;
+use crate::std_writer::StdWriter;
+use crate::{export_single_table, ExportOptions};
+use serde::Serialize;
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use std::fs;
+use std::path::Path;
+
+//
+// The loader index written next to the shard files. Load the DDL file first,
+//   then the files of each table in any order or all at once.
+//
+#[derive(Debug, Serialize)]
+pub struct ShardIndex {
+    pub ddl: String,
+    pub shards: usize,
+    pub tables: Vec<TableShards>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TableShards {
+    pub table: String,
+    // Relative to the index
+    pub files: Vec<String>,
+    pub rows: Vec<u64>,
+}
+
+//
+// Where and how the shard files are written
+//
+pub struct ShardOutput {
+    pub dir: String,
+    pub compression: Compression,
+    // The statements each file starts with so it loads on its own, e.g.
+    //   use and SET FOREIGN_KEY_CHECKS=0, and those it ends with
+    pub prefix: Vec<String>,
+    pub postfix: Vec<String>,
+}
+
+//
+// The rows of every table spread over `shards` files, so a table can be
+//   loaded by several connections at once. A row goes to the shard its
+//   primary key hashes to (CRC32 of the key columns, modulo the shard
+//   count), so the split is even whatever the key values look like. Tables
+//   without a primary key or with table hooks go into a single file.
+//
+pub async fn export_data_shards(
+    pool: &Pool<MySql>,
+    output: &ShardOutput,
+    schema: &str,
+    ddl_file: &str,
+    shards: usize,
+    options: &ExportOptions,
+) -> Result<u64, sqlx::Error> {
+    fs::create_dir_all(&output.dir)?;
+    let max_insert_count = if options.single_row_inserts { 1 } else { 100 };
+    let tables: Vec<(String,)> = sqlx::query_as(
+        "select table_name from information_schema.tables where table_schema=? and table_type='BASE TABLE' order by table_name",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await?;
+
+    let mut index = ShardIndex {
+        ddl: ddl_file.to_string(),
+        shards,
+        tables: vec![],
+    };
+    let mut total = 0;
+    for (table,) in tables.iter().filter(|t| options.includes_data(&t.0)) {
+        let key: Vec<(String,)> = sqlx::query_as(
+            "select column_name from information_schema.statistics where table_schema=? and table_name=? and index_name='PRIMARY' order by seq_in_index",
+        )
+        .bind(schema)
+        .bind(table)
+        .fetch_all(pool)
+        .await?;
+        let parts = if key.is_empty() || options.hooks(table).is_some() { 1 } else { shards };
+        let hash = format!(
+            "crc32(concat_ws(char(31), {}))",
+            key.iter().map(|c| quote_identifier(&c.0)).collect::<Vec<String>>().join(", ")
+        );
+
+        let base = options.renames.table(table).replace(['/', '\\'], "_");
+        let mut table_shards = TableShards {
+            table: options.renames.table(table),
+            files: vec![],
+            rows: vec![],
+        };
+        for part in 0..parts {
+            let file = format!("{}.{}.sql{}", base, part + 1, output.compression.extension());
+            let mut writer = StdWriter::open(
+                Some(Path::new(&output.dir).join(&file).to_string_lossy().to_string()),
+                output.compression,
+                1,
+                false,
+            );
+            for line in &output.prefix {
+                writer.println(line);
+            }
+            // The checksum covers the whole table so it goes in the last file
+            let mut options = options.clone();
+            options.checksum = options.checksum && part + 1 == parts;
+            if parts > 1 {
+                let predicate = format!("mod({}, {}) = {}", hash, parts, part);
+                let filter = match options.row_filters.get(table) {
+                    None => format!("where {}", predicate),
+                    Some(filter) => match filter.strip_prefix("where ") {
+                        Some(condition) => format!("where ({}) and {}", condition, predicate),
+                        None => panic!("The rows of {} are already limited by {}, they can't be sharded", table, filter),
+                    },
+                };
+                options.row_filters.insert(table.clone(), filter);
+            }
+            let rows = export_single_table(schema, table, pool, max_insert_count, &options, options.batch_bytes(1), &mut |batch| {
+                writer.print(batch.as_str())
+            })
+            .await?;
+            for line in &output.postfix {
+                writer.println(line);
+            }
+            writer.flush();
+            table_shards.files.push(file);
+            table_shards.rows.push(rows);
+            total += rows;
+        }
+        Logger::info(format!("Wrote {} rows of {} to {} files", table_shards.rows.iter().sum::<u64>(), table, parts));
+        index.tables.push(table_shards);
+    }
+
+    let index_json = serde_json::to_string_pretty(&index).expect("Unable to serialize the shard index");
+    fs::write(Path::new(&output.dir).join("index.json"), format!("{}\n", index_json))?;
+    Ok(total)
+}
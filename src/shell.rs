@@ -0,0 +1,433 @@
+use crate::restore::{Classifier, Part, StatementReader};
+use crate::sql::{qualified_name, quote_identifier};
+use crate::std_writer::StdWriter;
+use crate::{
+    export_functions, export_stored_procs, export_tables, export_triggers, export_views, schema_charset, Comments,
+    DumpSummary, ExportOptions,
+};
+use async_std::stream::StreamExt;
+use serde_json::{json, Map, Value};
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use sqlx::Row;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// The dump format version written to @.json, one every loader understands
+const DUMP_VERSION: &str = "1.0.2";
+
+// Data files are cut at about this many bytes so the loader can run chunks
+//   in parallel
+const BYTES_PER_CHUNK: u64 = 64_000_000;
+
+// Types exported as base64 and decoded by the loader with FROM_BASE64
+pub const BINARY_TYPES: [&str; 14] = [
+    "binary", "varbinary", "tinyblob", "blob", "mediumblob", "longblob", "bit", "geometry", "point", "linestring",
+    "polygon", "multipoint", "multilinestring", "multipolygon",
+];
+
+//
+// A dump laid out the way MySQL Shell's dump utilities write one, so it can
+//   be loaded with util.loadDump() and its parallel loader. The output is a
+//   directory:
+//     @.json, @.sql, @.post.sql     what was dumped and session scripts
+//     <schema>.json, <schema>.sql   the schema's objects, CREATE DATABASE
+//                                   and routines
+//     <schema>@<table>.json/.sql    column list and load options, CREATE TABLE
+//     <schema>@<table>@N.tsv        the rows in chunks, the last is @@N
+//     <schema>@<view>.pre.sql/.sql  a placeholder and the view
+//     <schema>@<table>.triggers.sql
+//     @.done.json                   written last, the loader waits for it
+//   The DDL is written with the usual exporters, so renames and the other
+//   DDL options apply. Rows are written tab separated with \ escapes and
+//   binary values in base64.
+//
+pub async fn write_shell_dump(
+    pool: &Pool<MySql>,
+    dir: &str,
+    schema: &str,
+    options: &ExportOptions,
+    include_ddl: bool,
+    include_data: bool,
+) -> Result<DumpSummary, sqlx::Error> {
+    fs::create_dir_all(dir)?;
+    let dir = Path::new(dir);
+    let schema_base = basename(schema);
+    let begin = chrono::Utc::now();
+    let mut summary = DumpSummary::default();
+
+    let tables: Vec<String> = sqlx::query_as::<_, (String,)>(
+        "select table_name from information_schema.tables where table_schema=? and table_type='BASE TABLE' order by table_name",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|t| t.0)
+    .filter(|t| options.includes(t))
+    .collect();
+
+    //
+    // The DDL is staged as a normal dump and sorted into the object scripts
+    //
+    let mut objects = Objects::default();
+    if include_ddl {
+        let staging = dir.join("@.ddl.partial.sql");
+        let mut ddl_options = options.clone();
+        ddl_options.comments = Comments::None;
+        let mut writer = StdWriter::new(Some(staging.to_string_lossy().to_string()));
+        summary.tables += export_tables(pool, &mut writer, schema, &ddl_options).await?;
+        summary.views += export_views(pool, &mut writer, schema, &ddl_options).await?;
+        summary.routines += export_stored_procs(pool, &mut writer, schema, &ddl_options).await?;
+        summary.routines += export_functions(pool, &mut writer, schema, &ddl_options).await?;
+        summary.triggers += export_triggers(pool, &mut writer, schema, &ddl_options).await?;
+        writer.flush();
+        drop(writer);
+        objects = Objects::read(&staging)?;
+        fs::remove_file(&staging)?;
+    }
+
+    let charset = schema_charset(pool, schema).await?;
+    let mut schema_sql = format!("CREATE DATABASE IF NOT EXISTS {}", quote_identifier(schema));
+    if let Some(charset) = &charset {
+        schema_sql.push_str(&format!(" DEFAULT CHARACTER SET {} COLLATE {}", charset.character_set, charset.collation));
+    }
+    schema_sql.push_str(";\n");
+    for (_, statement) in &objects.routines {
+        schema_sql.push_str(&format!("DELIMITER ;;\n{};;\nDELIMITER ;\n", statement));
+    }
+    write_file(dir, &format!("{}.sql", schema_base), &schema_sql)?;
+
+    for (table, statement) in &objects.tables {
+        write_file(dir, &format!("{}@{}.sql", schema_base, basename(table)), &format!("{};\n", statement))?;
+    }
+    for (table, statements) in &objects.triggers {
+        let script: String = statements.iter().map(|s| format!("DELIMITER ;;\n{};;\nDELIMITER ;\n", s)).collect();
+        write_file(dir, &format!("{}@{}.triggers.sql", schema_base, basename(table)), &script)?;
+    }
+    for (view, statement) in &objects.views {
+        let columns: Vec<(String,)> =
+            sqlx::query_as("select column_name from information_schema.columns where table_schema=? and table_name=? order by ordinal_position")
+                .bind(schema)
+                .bind(view)
+                .fetch_all(pool)
+                .await?;
+        // Lets views that select from each other be created in any order
+        let placeholder = columns.iter().map(|c| format!("1 AS {}", quote_identifier(&c.0))).collect::<Vec<String>>().join(", ");
+        let view_base = basename(view);
+        write_file(
+            dir,
+            &format!("{}@{}.pre.sql", schema_base, view_base),
+            &format!("CREATE OR REPLACE VIEW {} AS SELECT {};\n", quote_identifier(view), placeholder),
+        )?;
+        write_file(
+            dir,
+            &format!("{}@{}.sql", schema_base, view_base),
+            &format!("DROP VIEW IF EXISTS {};\n{};\n", quote_identifier(view), statement),
+        )?;
+    }
+
+    //
+    // The rows, with the table metadata the loader needs to load them
+    //
+    let mut table_bytes = Map::new();
+    let mut chunk_bytes = Map::new();
+    let mut data_bytes = 0;
+    for table in &tables {
+        // The name the table has in the dump, which the DDL uses
+        let name = options.renames.table(table);
+        let table_base = basename(&name);
+        let columns: Vec<(String, String)> = sqlx::query_as(
+            "select column_name, data_type from information_schema.columns where table_schema=? and table_name=? and extra not like '%GENERATED%' order by ordinal_position",
+        )
+        .bind(schema)
+        .bind(table)
+        .fetch_all(pool)
+        .await?;
+        let (primary_index,): (Option<String>,) = sqlx::query_as(
+            "select max(index_name) from information_schema.statistics where table_schema=? and table_name=? and index_name='PRIMARY'",
+        )
+        .bind(schema)
+        .bind(table)
+        .fetch_one(pool)
+        .await?;
+
+        let decode: Map<String, Value> = columns
+            .iter()
+            .filter(|c| BINARY_TYPES.contains(&c.1.to_lowercase().as_str()))
+            .map(|c| (options.renames.column(table, &c.0), json!("FROM_BASE64")))
+            .collect();
+        let table_json = json!({
+            "options": {
+                "schema": schema,
+                "table": name,
+                "columns": columns.iter().map(|c| options.renames.column(table, &c.0)).collect::<Vec<String>>(),
+                "fieldsTerminatedBy": "\t",
+                "fieldsEnclosedBy": "",
+                "fieldsOptionallyEnclosed": false,
+                "fieldsEscapedBy": "\\",
+                "linesTerminatedBy": "\n",
+                "decodeColumns": decode,
+                "primaryIndex": primary_index.unwrap_or_default(),
+                "compression": "none",
+            },
+            "triggers": objects.triggers.get(&name).map(|t| t.iter().map(|s| created_name(s)).collect::<Vec<String>>()).unwrap_or_default(),
+            "histograms": [],
+            "includesData": include_data,
+            "includesDdl": include_ddl,
+            "extension": "tsv",
+            "chunking": true,
+        });
+        write_file(dir, &format!("{}@{}.json", schema_base, table_base), &pretty(&table_json))?;
+
+        if include_data {
+            let base = format!("{}@{}", schema_base, table_base);
+            let (rows, chunks) = write_table_data(pool, dir, &base, schema, table, &columns, options).await?;
+            summary.rows += rows;
+            let bytes: u64 = chunks.iter().map(|c| c.1).sum();
+            data_bytes += bytes;
+            table_bytes.insert(name.clone(), json!(bytes));
+            for (file, size) in chunks {
+                chunk_bytes.insert(file, json!(size));
+            }
+        }
+    }
+
+    let schema_json = json!({
+        "schema": schema,
+        "includesDdl": include_ddl,
+        "includesViewsDdl": include_ddl,
+        "includesData": include_data,
+        "tables": tables.iter().map(|t| options.renames.table(t)).collect::<Vec<String>>(),
+        "views": objects.views.iter().map(|v| v.0.clone()).collect::<Vec<String>>(),
+        "functions": objects.routines.iter().filter(|r| r.0 == "function").map(|r| created_name(&r.1)).collect::<Vec<String>>(),
+        "procedures": objects.routines.iter().filter(|r| r.0 == "procedure").map(|r| created_name(&r.1)).collect::<Vec<String>>(),
+        "events": [],
+        "basenames": tables
+            .iter()
+            .map(|t| options.renames.table(t))
+            .chain(objects.views.iter().map(|v| v.0.clone()))
+            .map(|t| (t.clone(), json!(basename(&t))))
+            .collect::<Map<String, Value>>(),
+    });
+    write_file(dir, &format!("{}.json", schema_base), &pretty(&schema_json))?;
+
+    let (server_version,): (String,) = sqlx::query_as("select version()").fetch_one(pool).await?;
+    let (user, hostname): (String, String) = sqlx::query_as("select current_user(), @@hostname").fetch_one(pool).await?;
+    let instance_json = json!({
+        "dumper": format!("dbdump {}", env!("CARGO_PKG_VERSION")),
+        "version": DUMP_VERSION,
+        "origin": "dumpSchemas",
+        "schemas": [schema],
+        "basenames": { schema: schema_base },
+        "users": [],
+        "defaultCharacterSet": charset.as_ref().map(|c| c.character_set.clone()).unwrap_or("utf8mb4".to_string()),
+        "tzUtc": false,
+        "bytesPerChunk": BYTES_PER_CHUNK,
+        "user": user,
+        "hostname": hostname,
+        "server": hostname,
+        "serverVersion": server_version,
+        "gtidExecuted": "",
+        "gtidExecutedInconsistent": false,
+        "consistent": false,
+        "mdsCompatibility": false,
+        "begin": begin.format("%Y-%m-%d %H:%M:%S").to_string(),
+    });
+    write_file(dir, "@.json", &pretty(&instance_json))?;
+    write_file(dir, "@.sql", "-- Written by dbdump\n")?;
+    write_file(dir, "@.post.sql", "-- Written by dbdump\n")?;
+
+    let done_json = json!({
+        "end": chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        "dataBytes": data_bytes,
+        "schemaDataBytes": { schema: data_bytes },
+        "tableDataBytes": { schema: table_bytes },
+        "chunkFileBytes": chunk_bytes,
+    });
+    write_file(dir, "@.done.json", &pretty(&done_json))?;
+    Ok(summary)
+}
+
+// The objects of the staged DDL, by name
+#[derive(Default)]
+struct Objects {
+    tables: Vec<(String, String)>,
+    views: Vec<(String, String)>,
+    // procedure or function, and the statement
+    routines: Vec<(String, String)>,
+    triggers: BTreeMap<String, Vec<String>>,
+}
+
+impl Objects {
+    fn read(path: &Path) -> io::Result<Objects> {
+        let classifier = Classifier::new();
+        let mut objects = Objects::default();
+        for statement in StatementReader::new(BufReader::new(File::open(path)?)) {
+            let statement = statement?;
+            if let Part::Ddl(kind, table) = classifier.part(&statement) {
+                match (kind.as_str(), table) {
+                    ("table", Some(table)) => objects.tables.push((table, statement)),
+                    ("view", Some(view)) => objects.views.push((view, statement)),
+                    ("trigger", Some(table)) => objects.triggers.entry(table).or_default().push(statement),
+                    ("procedure" | "function", _) => objects.routines.push((kind, statement)),
+                    _ => {}
+                }
+            }
+        }
+        Ok(objects)
+    }
+}
+
+//
+// Write the rows of a table as tab separated chunks. Returns the row count
+//   and the name and size of every chunk file.
+//
+async fn write_table_data(
+    pool: &Pool<MySql>,
+    dir: &Path,
+    base: &str,
+    schema: &str,
+    table: &str,
+    columns: &[(String, String)],
+    options: &ExportOptions,
+) -> Result<(u64, Vec<(String, u64)>), sqlx::Error> {
+    let binary: Vec<bool> = columns.iter().map(|c| BINARY_TYPES.contains(&c.1.to_lowercase().as_str())).collect();
+    let select_list = columns
+        .iter()
+        .zip(&binary)
+        .map(|((name, _), binary)| {
+            if *binary {
+                format!("to_base64({})", quote_identifier(name))
+            } else {
+                format!("cast({} as char)", quote_identifier(name))
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",");
+    let row_filter = options.row_clause(table);
+    let query = format!("select {} from {}{}", select_list, qualified_name(schema, table), row_filter);
+    let mut data_rows = sqlx::query(&query).fetch(pool);
+
+    let mut rows = 0;
+    let mut chunks: Vec<(String, u64)> = vec![];
+    let mut chunk = Chunk::create(dir, base, 0)?;
+    let mut line = String::new();
+    while let Some(row) = data_rows.next().await {
+        let row = row?;
+        line.clear();
+        for (i, binary) in binary.iter().enumerate() {
+            if i > 0 {
+                line.push('\t');
+            }
+            match row.try_get::<Option<String>, usize>(i)? {
+                // base64 output is wrapped every 76 characters
+                Some(value) if *binary => line.push_str(&value.replace('\n', "")),
+                Some(value) => escape_tsv(&value, &mut line),
+                None => line.push_str("\\N"),
+            }
+        }
+        line.push('\n');
+        chunk.write(line.as_bytes())?;
+        rows += 1;
+        if chunk.bytes >= BYTES_PER_CHUNK {
+            chunks.push(chunk.finish(dir, base, false)?);
+            chunk = Chunk::create(dir, base, chunks.len())?;
+        }
+    }
+    chunks.push(chunk.finish(dir, base, true)?);
+    Ok((rows, chunks))
+}
+
+struct Chunk {
+    writer: BufWriter<File>,
+    path: PathBuf,
+    index: usize,
+    bytes: u64,
+}
+
+impl Chunk {
+    fn create(dir: &Path, base: &str, index: usize) -> io::Result<Chunk> {
+        let path = dir.join(format!("{}@{}.tsv", base, index));
+        Ok(Chunk {
+            writer: BufWriter::new(File::create(&path)?),
+            path,
+            index,
+            bytes: 0,
+        })
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.bytes += bytes.len() as u64;
+        self.writer.write_all(bytes)
+    }
+
+    // The last chunk of a table is named with @@ so the loader knows the
+    //   table is complete. Each chunk has an index file holding its size.
+    fn finish(mut self, dir: &Path, base: &str, last: bool) -> io::Result<(String, u64)> {
+        self.writer.flush()?;
+        let name = if last {
+            let name = format!("{}@@{}.tsv", base, self.index);
+            fs::rename(&self.path, dir.join(&name))?;
+            name
+        } else {
+            format!("{}@{}.tsv", base, self.index)
+        };
+        fs::write(dir.join(format!("{}.idx", name)), self.bytes.to_be_bytes())?;
+        Ok((name, self.bytes))
+    }
+}
+
+// The escapes LOAD DATA understands with ESCAPED BY '\'
+fn escape_tsv(value: &str, out: &mut String) {
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\0' => out.push_str("\\0"),
+            '\x1a' => out.push_str("\\Z"),
+            _ => out.push(c),
+        }
+    }
+}
+
+//
+// File names for objects the way MySQL Shell encodes them: characters that
+//   aren't safe in a file name, and @ which separates the parts of the name,
+//   are percent encoded. The loader reads the names back from the basenames
+//   in the JSON files.
+//
+fn basename(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_alphanumeric() || c == '_' || c == '-' || c == ' ' || c == '$' {
+            out.push(c);
+        } else {
+            let mut buf = [0; 4];
+            for b in c.encode_utf8(&mut buf).bytes() {
+                out.push_str(&format!("%{:02X}", b));
+            }
+        }
+    }
+    out
+}
+
+// The name of a routine or trigger from its CREATE statement
+fn created_name(statement: &str) -> String {
+    let re = regex::Regex::new(r"(?i)\b(?:PROCEDURE|FUNCTION|TRIGGER)\s+(`(?:[^`]|``)+`|\w+)").unwrap();
+    re.captures(statement)
+        .map(|c| crate::normalize::object_name(&c[1]))
+        .unwrap_or_default()
+}
+
+fn pretty(value: &Value) -> String {
+    serde_json::to_string_pretty(value).unwrap()
+}
+
+fn write_file(dir: &Path, name: &str, contents: &str) -> io::Result<()> {
+    fs::write(dir.join(name), contents)
+}
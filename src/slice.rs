@@ -0,0 +1,39 @@
+use crate::escape::{quote_string, EscapeMode};
+use crate::sql::quote_identifier;
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use std::collections::HashMap;
+
+//
+// A date range cut across the whole schema, e.g. the last 30 days: every
+//   table with the column gets `where column >= from and column < to`, the
+//   tables without it are dumped in full. Returns the clause to append to
+//   each sliced table's data query, as fixtures do.
+//
+pub async fn slice_rows(
+    pool: &Pool<MySql>,
+    schema: &str,
+    column: &str,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<HashMap<String, String>, sqlx::Error> {
+    let tables: Vec<(String,)> = sqlx::query_as(
+        "select c.table_name from information_schema.columns c join information_schema.tables t on t.table_schema=c.table_schema and t.table_name=c.table_name where c.table_schema=? and c.column_name=? and t.table_type='BASE TABLE'",
+    )
+    .bind(schema)
+    .bind(column)
+    .fetch_all(pool)
+    .await?;
+
+    // Doubled quotes work whether or not the session takes backslash escapes
+    let column = quote_identifier(column);
+    let mut predicates = vec![];
+    if let Some(from) = from {
+        predicates.push(format!("{} >= {}", column, quote_string(from, EscapeMode::Standard)));
+    }
+    if let Some(to) = to {
+        predicates.push(format!("{} < {}", column, quote_string(to, EscapeMode::Standard)));
+    }
+    let clause = format!("where {}", predicates.join(" and "));
+    Ok(tables.into_iter().map(|t| (t.0, clause.clone())).collect())
+}
@@ -0,0 +1,66 @@
+use crate::ExportOptions;
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use std::path::{Path, PathBuf};
+
+// How much smaller compressed output is assumed to be, dumps are mostly text
+//   and usually compress better than this
+pub const COMPRESSION_RATIO: u64 = 4;
+
+//
+// Roughly how many bytes the rows of the schemas take when dumped, from the
+//   data length information_schema keeps for each table. SQL text of a row is
+//   usually about as long as InnoDB's copy of it, so this is an estimate, not
+//   a bound.
+//
+pub async fn estimate_dump_bytes(pool: &Pool<MySql>, schemas: &[&str], options: &ExportOptions) -> Result<u64, sqlx::Error> {
+    let mut bytes = 0;
+    for schema in schemas {
+        let tables: Vec<(String, Option<u64>)> = sqlx::query_as(
+            "select table_name, cast(data_length as unsigned) from information_schema.tables where table_schema=? and table_type='BASE TABLE'",
+        )
+        .bind(schema)
+        .fetch_all(pool)
+        .await?;
+        bytes += tables
+            .iter()
+            .filter(|t| options.includes_data(&t.0))
+            .map(|t| t.1.unwrap_or(0))
+            .sum::<u64>();
+    }
+    Ok(bytes)
+}
+
+//
+// Free space for an unprivileged user on the filesystem a file or directory
+//   is going to be written to. Paths that don't exist yet are looked up
+//   through their nearest existing parent. None where it can't be found out.
+//
+pub fn free_bytes(path: &str) -> Option<u64> {
+    let mut dir = PathBuf::from(path);
+    while !dir.exists() {
+        dir = match dir.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+    }
+    filesystem_free_bytes(&dir)
+}
+
+#[cfg(unix)]
+fn filesystem_free_bytes(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn filesystem_free_bytes(_: &Path) -> Option<u64> {
+    None
+}
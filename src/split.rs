@@ -0,0 +1,134 @@
+use crate::compress::{open_input, Compression};
+use crate::logger::Logger;
+use crate::restore::{Classifier, Part, StatementReader};
+use crate::std_writer::StdWriter;
+use crate::{write_trailer, DumpSummary, SECTION_BEGIN, SECTION_END};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+//
+// Split a dump into a file per object, so part of an old backup can be
+//   restored on its own or the tables loaded in parallel. The files follow
+//   the mydumper naming:
+//     <table>-schema.sql           CREATE TABLE and statistics
+//     <table>.sql                  the rows
+//     <table>-schema-triggers.sql  the table's triggers
+//     <view>-schema-view.sql       CREATE VIEW
+//     routines.sql                 procedures and functions
+//   The session setup at the top of the dump (header comments, metadata,
+//   USE, SET ...) is repeated at the top of every file and the statements
+//   closing the dump at the end of every file, each file gets its own
+//   completion trailer so each one restores and validates alone. Session
+//   statements in between, such as transaction control, are left out.
+//
+// Only the file being written is kept open, a dump keeps the statements of
+//   each object together so a file is seldom opened again. The closing
+//   statements are only known at the end, they are appended to every file
+//   then.
+//
+pub fn split(input_file: &str, output_dir: &str) -> io::Result<DumpSummary> {
+    std::fs::create_dir_all(output_dir)?;
+    let classifier = Classifier::new();
+    let mut prelude: Vec<String> = vec![];
+    let mut epilogue: Vec<String> = vec![];
+    let mut files: BTreeMap<String, DumpSummary> = BTreeMap::new();
+    let mut current: Option<(String, StdWriter)> = None;
+    let mut total = DumpSummary::default();
+
+    let mut statements = StatementReader::new(open_input(input_file)?).keep_comments();
+    while let Some(statement) = statements.next() {
+        let statement = statement?;
+        let comments = statements.take_comments();
+        let delimiter = statements.delimiter().to_string();
+
+        let (file_name, kind) = match classifier.part(&statement) {
+            Part::Session => {
+                if files.is_empty() {
+                    let markers = |c: &String| c.starts_with(SECTION_BEGIN) || c.starts_with(SECTION_END);
+                    prelude.extend(comments.into_iter().filter(|c| !markers(c)));
+                    prelude.push(format!("{};", statement));
+                } else {
+                    epilogue.push(format!("{};", statement));
+                }
+                continue;
+            }
+            Part::Data(table) => (table, "data".to_string()),
+            Part::Ddl(kind, table) => match (kind.as_str(), table) {
+                ("table", Some(table)) => (format!("{}-schema", table), kind),
+                ("view", Some(view)) => (format!("{}-schema-view", view), kind),
+                ("trigger", Some(table)) => (format!("{}-schema-triggers", table), kind),
+                ("procedure" | "function", _) => ("routines".to_string(), kind),
+                (_, None) => {
+                    Logger::warn(format!("Unable to tell which table a {} belongs to, it goes in other.sql", kind));
+                    ("other".to_string(), kind)
+                }
+                _ => unreachable!(),
+            },
+        };
+
+        epilogue.clear();
+        if current.as_ref().is_none_or(|(name, _)| *name != file_name) {
+            if let Some((_, mut writer)) = current.take() {
+                writer.flush();
+            }
+            let path = file_path(output_dir, &file_name);
+            let writer = if files.contains_key(&file_name) {
+                StdWriter::open(Some(path), Compression::None, 1, true)
+            } else {
+                let mut writer = StdWriter::new(Some(path));
+                for line in &prelude {
+                    writer.println(line);
+                }
+                files.insert(file_name.clone(), DumpSummary::default());
+                writer
+            };
+            current = Some((file_name.clone(), writer));
+        }
+        let writer = &mut current.as_mut().unwrap().1;
+        let summary = files.get_mut(&file_name).unwrap();
+        if delimiter != ";" {
+            writer.println(format!("DELIMITER {}", delimiter).as_str());
+            writer.println(format!("{}{}", statement, delimiter).as_str());
+            writer.println("DELIMITER ;");
+        } else {
+            writer.println(format!("{};", statement).as_str());
+        }
+        match kind.as_str() {
+            "table" if statement.to_lowercase().starts_with("create") => summary.tables += 1,
+            "view" => summary.views += 1,
+            "procedure" | "function" => summary.routines += 1,
+            "trigger" => summary.triggers += 1,
+            "data" => summary.rows += count_rows(&statement),
+            _ => {}
+        }
+    }
+
+    if let Some((_, mut writer)) = current.take() {
+        writer.flush();
+    }
+    for (file_name, summary) in &files {
+        let mut writer = StdWriter::open(Some(file_path(output_dir, file_name)), Compression::None, 1, true);
+        for statement in &epilogue {
+            writer.println(statement);
+        }
+        write_trailer(&mut writer, summary);
+        writer.flush();
+        total.tables += summary.tables;
+        total.views += summary.views;
+        total.routines += summary.routines;
+        total.triggers += summary.triggers;
+        total.rows += summary.rows;
+    }
+    Ok(total)
+}
+
+fn file_path(output_dir: &str, file_name: &str) -> String {
+    let file_name = file_name.replace(['/', '\\'], "_");
+    Path::new(output_dir).join(format!("{}.sql", file_name)).to_string_lossy().to_string()
+}
+
+// Rows in an insert as dbdump writes them, one per line
+fn count_rows(statement: &str) -> u64 {
+    statement.matches("),\n\t(").count() as u64 + 1
+}
@@ -0,0 +1,127 @@
+use crate::escape::EscapeMode;
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use std::str::FromStr;
+
+//
+// Helpers for building the SQL statements dbdump sends to the server and
+//   writes to the dump. Every schema, table, view, routine and column name
+//   must go through these so that reserved words, dashes and spaces survive.
+//
+
+// Quote a single identifier with backticks, doubling any embedded backtick.
+pub fn quote_identifier(name: &str) -> String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+// Quote a schema qualified object name, e.g. `my-schema`.`order`
+pub fn qualified_name(schema: &str, name: &str) -> String {
+    format!("{}.{}", quote_identifier(schema), quote_identifier(name))
+}
+
+//
+// The SQL dialect the dump is written for. Queries sent to the server always
+//   use backticks; this only controls what ends up in the output.
+//   Mysql - backtick quoted identifiers, backslash escaped strings
+//   Ansi  - double quoted identifiers, standard escaped strings, loadable by
+//           sessions running with ANSI_QUOTES and easier to port elsewhere
+//   ClickHouse - tables rewritten for ClickHouse with MergeTree engines and
+//           inserts it accepts, for moving data into it for analytics
+//
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compat {
+    #[default]
+    Mysql,
+    Ansi,
+    ClickHouse,
+}
+
+impl FromStr for Compat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "mysql" => Ok(Compat::Mysql),
+            "ansi" => Ok(Compat::Ansi),
+            "clickhouse" => Ok(Compat::ClickHouse),
+            _ => Err(format!("Unknown compat mode {s}, expected one of mysql, ansi, clickhouse")),
+        }
+    }
+}
+
+impl Compat {
+    // Quote an identifier for the output file.
+    pub fn quote_identifier(&self, name: &str) -> String {
+        match self {
+            Compat::Mysql | Compat::ClickHouse => quote_identifier(name),
+            Compat::Ansi => format!("\"{}\"", name.replace('"', "\"\"")),
+        }
+    }
+
+    // The escaping the target session expects, given the user's choice.
+    pub fn escape_mode(&self, requested: EscapeMode) -> EscapeMode {
+        match self {
+            Compat::Mysql => requested,
+            Compat::Ansi => EscapeMode::Standard,
+            // A backslash always starts an escape in ClickHouse literals
+            Compat::ClickHouse => EscapeMode::Backslash,
+        }
+    }
+
+    // Whether the target has foreign keys to switch off during the load
+    pub fn has_foreign_keys(&self) -> bool {
+        *self != Compat::ClickHouse
+    }
+
+    // sql_mode flags to add to every source connection so the DDL returned by
+    //   SHOW CREATE ... matches the output dialect.
+    pub fn session_sql_mode(&self) -> Option<&'static str> {
+        match self {
+            Compat::Mysql | Compat::ClickHouse => None,
+            Compat::Ansi => Some("ANSI_QUOTES"),
+        }
+    }
+
+    // The SET SQL_MODE starting a dump, so the target reads its identifiers
+    //   and string literals the way they were written. Standard escaping
+    //   needs NO_BACKSLASH_ESCAPES whatever the compat, a server in its
+    //   default mode would take every backslash for an escape.
+    pub fn dump_sql_mode(&self, escape_mode: EscapeMode) -> Option<String> {
+        let mut flags: Vec<&str> = self.session_sql_mode().into_iter().collect();
+        if escape_mode == EscapeMode::Standard {
+            flags.push("NO_BACKSLASH_ESCAPES");
+        }
+        if flags.is_empty() {
+            return None;
+        }
+        Some(format!("/*!40101 SET SQL_MODE=CONCAT(@@SQL_MODE, ',{}') */;", flags.join(",")))
+    }
+}
+
+//
+// How the source server compares table names, from lower_case_table_names.
+//   With 0 `Foo` and `foo` are different tables, with 1 names are stored in
+//   lower case and with 2 they are stored as given but compared in lower
+//   case. Column names are never case sensitive.
+//
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NameCase {
+    Sensitive,
+    #[default]
+    Insensitive,
+}
+
+impl NameCase {
+    pub async fn of_server(pool: &Pool<MySql>) -> Result<Self, sqlx::Error> {
+        let (setting,): (i64,) = sqlx::query_as("select cast(@@lower_case_table_names as signed)").fetch_one(pool).await?;
+        Ok(if setting == 0 { NameCase::Sensitive } else { NameCase::Insensitive })
+    }
+
+    // Whether two table, view or schema names are the same object
+    pub fn same(self, a: &str, b: &str) -> bool {
+        match self {
+            NameCase::Sensitive => a == b,
+            NameCase::Insensitive => a.eq_ignore_ascii_case(b),
+        }
+    }
+}
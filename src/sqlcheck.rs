@@ -0,0 +1,40 @@
+use crate::escape::EscapeMode;
+use crate::logger::Logger;
+use sqlparser::dialect::{Dialect, GenericDialect, MySqlDialect};
+use sqlparser::parser::Parser;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Statements that failed to parse in this run
+static PROBLEMS: AtomicUsize = AtomicUsize::new(0);
+
+//
+// A self check for --check-sql. The insert statements dbdump builds are
+//   parsed before they are written, so an escaping or quoting bug in dbdump
+//   shows up in the dump run rather than in the restore. DDL is left alone as
+//   it comes from the server and uses table options the parser doesn't know.
+//
+pub fn check_statement(table: &str, statement: &str, mode: EscapeMode) -> bool {
+    // Without backslash escapes the only special character is the quote
+    let dialect: &dyn Dialect = match mode {
+        EscapeMode::Backslash => &MySqlDialect {},
+        EscapeMode::Standard => &GenericDialect {},
+    };
+    match Parser::parse_sql(dialect, statement) {
+        Ok(_) => true,
+        Err(e) => {
+            PROBLEMS.fetch_add(1, Ordering::Relaxed);
+            let excerpt: String = statement.chars().take(200).collect();
+            Logger::error(format!("Generated SQL for {} doesn't parse: {}\n{}", table, e, excerpt));
+            false
+        }
+    }
+}
+
+// Start over for another dump in the same process, as watch runs them
+pub fn reset() {
+    PROBLEMS.store(0, Ordering::Relaxed);
+}
+
+pub fn problems() -> usize {
+    PROBLEMS.load(Ordering::Relaxed)
+}
@@ -0,0 +1,173 @@
+use crate::logger::Logger;
+use crate::sql::qualified_name;
+use crate::std_writer::StdWriter;
+use crate::{Comments, ExportOptions};
+use sqlx::mysql::{MySql, MySqlConnection};
+use sqlx::pool::Pool;
+use sqlx::types::chrono::NaiveDateTime;
+use sqlx::Row;
+
+//
+// Recreate the optimizer histograms (MySQL 8.0+). Histograms aren't part of
+//   the table DDL so without this a restored database plans queries without
+//   them until someone runs ANALYZE TABLE by hand.
+//
+pub async fn export_histograms(
+    pool: &Pool<MySql>,
+    writer: &mut StdWriter,
+    schema: &str,
+    options: &ExportOptions,
+) -> Result<(), sqlx::Error> {
+    let histograms: Result<Vec<(String, String, u64)>, sqlx::Error> = sqlx::query_as(
+        "select table_name, column_name, cast(json_extract(histogram, '$.\"number-of-buckets-specified\"') as unsigned) from information_schema.column_statistics where schema_name=? order by table_name, column_name",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await;
+
+    // Servers without COLUMN_STATISTICS (MySQL 5.7, MariaDB) have nothing to export
+    let histograms = match histograms {
+        Ok(histograms) => histograms,
+        Err(e) => {
+            Logger::warn(format!("Unable to read column statistics, skipping histograms: {e}"));
+            return Ok(());
+        }
+    };
+
+    // Only the tables in the dump, under the names they are written with
+    let mut current_table = "";
+    for (table_name, column_name, buckets) in histograms.iter().filter(|h| options.includes(&h.0)) {
+        if current_table != table_name {
+            if options.comments >= Comments::Minimal {
+                writer.println(format!("-- Recreate histograms for table {}", table_name).as_str());
+            }
+            current_table = table_name;
+        }
+        writer.println(
+            format!(
+                "ANALYZE TABLE {} UPDATE HISTOGRAM ON {} WITH {} BUCKETS;",
+                options.table_name(table_name),
+                options.compat.quote_identifier(&options.renames.column(table_name, column_name)),
+                buckets
+            )
+            .as_str(),
+        );
+    }
+    Ok(())
+}
+
+//
+// Statements refreshing the statistics of every dumped table, written at the
+//   end of the dump so they run once all the rows are in. Otherwise the
+//   restored tables keep the estimates taken while they were empty until
+//   InnoDB gets around to sampling them. OPTIMIZE TABLE also rebuilds each
+//   table, which takes as long as copying it.
+//
+pub async fn write_table_maintenance(
+    pool: &Pool<MySql>,
+    writer: &mut StdWriter,
+    schema: &str,
+    options: &ExportOptions,
+    analyze: bool,
+    optimize: bool,
+) -> Result<(), sqlx::Error> {
+    let tables: Vec<(String,)> = sqlx::query_as(
+        "select table_name from information_schema.tables where table_schema=? and table_type='BASE TABLE' order by table_name",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await?;
+    if options.comments >= Comments::Minimal {
+        writer.println(format!("-- Refresh the statistics of the tables of {}", schema).as_str());
+    }
+    for (table_name,) in tables.iter().filter(|t| options.includes(&t.0)) {
+        if optimize {
+            writer.println(format!("OPTIMIZE TABLE {};", options.table_name(table_name)).as_str());
+        }
+        if analyze {
+            writer.println(format!("ANALYZE TABLE {};", options.table_name(table_name)).as_str());
+        }
+    }
+    Ok(())
+}
+
+//
+// Annotate a table's section with its size as reported by information_schema,
+//   so the dump doubles as a point-in-time capacity report. Row counts are the
+//   storage engine's estimate.
+//
+pub async fn write_table_stats(
+    pool: &Pool<MySql>,
+    writer: &mut StdWriter,
+    schema: &str,
+    table_name: &str,
+) -> Result<(), sqlx::Error> {
+    let (rows, data_length, index_length, update_time): (
+        Option<u64>,
+        Option<u64>,
+        Option<u64>,
+        Option<NaiveDateTime>,
+    ) = sqlx::query_as(
+        "select table_rows, data_length, index_length, update_time from information_schema.tables where table_schema=? and table_name=?",
+    )
+    .bind(schema)
+    .bind(table_name)
+    .fetch_one(pool)
+    .await?;
+
+    writer.println(format!("-- Rows (estimated): {}", rows.unwrap_or(0)).as_str());
+    writer.println(format!("-- Data size: {}", format_bytes(data_length.unwrap_or(0))).as_str());
+    writer.println(format!("-- Index size: {}", format_bytes(index_length.unwrap_or(0))).as_str());
+    if let Some(update_time) = update_time {
+        writer.println(format!("-- Last updated: {}", update_time).as_str());
+    } else {
+        writer.println("-- Last updated: unknown");
+    }
+    Ok(())
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+//
+// CHECKSUM TABLE for a single table, None when the engine can't compute one
+//   (or the table is gone). Taken on the connection the rows are read with,
+//   in the same snapshot, and written as a trailing comment per table so a
+//   restore can later be checked against the source.
+//
+pub async fn table_checksum(
+    connection: &mut MySqlConnection,
+    schema: &str,
+    table_name: &str,
+) -> Result<Option<u64>, sqlx::Error> {
+    let row = sqlx::query(format!("CHECKSUM TABLE {}", qualified_name(schema, table_name)).as_str())
+        .fetch_one(connection)
+        .await?;
+    // The column is signed on some servers and unsigned on others
+    Ok(row.try_get_unchecked::<Option<u64>, usize>(1).unwrap_or(None))
+}
+
+pub fn checksum_comment(table_name: &str, checksum: Option<u64>) -> String {
+    match checksum {
+        Some(checksum) => format!("-- Checksum for table {}: {}\n", table_name, checksum),
+        None => format!("-- Checksum for table {}: unavailable\n", table_name),
+    }
+}
+
+// The rows written are a part of the table or were changed on the way out,
+//   its checksum wouldn't match them
+pub fn partial_checksum_comment(table_name: &str) -> String {
+    format!("-- Checksum for table {}: not applicable, its rows are filtered or transformed\n", table_name)
+}
@@ -1,36 +1,121 @@
-use std::fs::File;
+use crate::compress::{Compression, ParallelCompressor};
+use crate::logger::Logger;
+use crate::transcode::{self, OutputEncoding};
+use std::fs::{File, OpenOptions};
 use std::io::{BufWriter, Write};
+use std::str::FromStr;
+
+//
+// How lines end in the output
+//
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl FromStr for LineEnding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "lf" | "unix" => Ok(LineEnding::Lf),
+            "crlf" | "windows" => Ok(LineEnding::Crlf),
+            _ => Err(format!("Unknown line ending {s}, expected one of lf, crlf")),
+        }
+    }
+}
 
 pub struct StdWriter {
-    writer: Option<BufWriter<File>>,
+    writer: Option<Box<dyn Write>>,
+    line_ending: LineEnding,
+    encoding: OutputEncoding,
+    // Set once a character couldn't be encoded, so the warning is given once
+    replaced: bool,
 }
 
 impl StdWriter {
     pub fn new(filename: Option<String>) -> Self {
         if let Some(filename) = filename {
-            let writer = Some(BufWriter::new(
+            let writer: Option<Box<dyn Write>> = Some(Box::new(BufWriter::new(
                 File::create(filename).expect("Unable to create file"),
-            ));
-            StdWriter { writer }
+            )));
+            StdWriter::with_writer(writer)
         } else {
-            StdWriter { writer: None }
+            StdWriter::with_writer(None)
+        }
+    }
+
+    // Compress the output (file or stdout) on `threads` worker threads,
+    //   optionally adding to the end of an existing file. Compressed output
+    //   can be appended too as gzip and zstd streams may be concatenated.
+    pub fn open(filename: Option<String>, compression: Compression, threads: usize, append: bool) -> Self {
+        let file = filename.map(|filename| {
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(append)
+                .truncate(!append)
+                .open(filename)
+                .expect("Unable to create file")
+        });
+        let writer: Option<Box<dyn Write>> = match (file, compression) {
+            (Some(file), Compression::None) => Some(Box::new(BufWriter::new(file))),
+            (Some(file), _) => Some(Box::new(ParallelCompressor::new(file, compression, threads))),
+            (None, Compression::None) => None,
+            (None, _) => Some(Box::new(ParallelCompressor::new(std::io::stdout(), compression, threads))),
+        };
+        StdWriter::with_writer(writer)
+    }
+
+    fn with_writer(writer: Option<Box<dyn Write>>) -> Self {
+        StdWriter {
+            writer,
+            line_ending: LineEnding::default(),
+            encoding: OutputEncoding::default(),
+            replaced: false,
+        }
+    }
+
+    // Write everything from here on with these line endings and in this
+    //   encoding, starting with its byte order mark unless the output is
+    //   being appended to
+    pub fn set_text_format(&mut self, line_ending: LineEnding, encoding: OutputEncoding, byte_order_mark: bool) {
+        self.line_ending = line_ending;
+        self.encoding = encoding;
+        if byte_order_mark {
+            self.write_bytes(encoding.byte_order_mark());
         }
     }
 
     pub fn print(&mut self, buf: &str) {
-        if let Some(writer) = self.writer.as_mut() {
-            writer.write(buf.as_bytes()).unwrap();
-        } else {
-            print!("{}", &buf);
+        if self.line_ending == LineEnding::Lf && self.encoding == OutputEncoding::Utf8 {
+            self.write_bytes(buf.as_bytes());
+            return;
+        }
+        let text = match self.line_ending {
+            LineEnding::Lf => buf.to_string(),
+            LineEnding::Crlf => buf.replace('\n', "\r\n"),
+        };
+        let (bytes, replaced) = transcode::encode(&text, self.encoding);
+        if replaced && !self.replaced {
+            self.replaced = true;
+            Logger::warn(format!("The output has characters {:?} can't represent, they are written as '?'", self.encoding));
         }
+        self.write_bytes(&bytes);
     }
 
     pub fn println(&mut self, buf: &str) {
+        self.print(buf);
+        self.print("\n");
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
         if let Some(writer) = self.writer.as_mut() {
-            writer.write(buf.as_bytes()).unwrap();
-            writer.write("\n".as_bytes()).unwrap();
+            writer.write_all(bytes).unwrap();
         } else {
-            println!("{}", &buf);
+            std::io::stdout().write_all(bytes).unwrap();
         }
     }
 
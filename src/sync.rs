@@ -0,0 +1,217 @@
+use crate::catalog::{Catalog, Table};
+use crate::fixture::column_list;
+use crate::logger::Logger;
+use crate::sql::{qualified_name, quote_identifier};
+use crate::std_writer::StdWriter;
+use crate::verify::fingerprint_select;
+use crate::{row_literals, ExportOptions};
+use sqlx::mysql::MySql;
+use sqlx::pool::Pool;
+use std::collections::HashMap;
+
+type Literals = Vec<Option<String>>;
+
+#[derive(Clone, Debug, Default)]
+pub struct SyncSummary {
+    pub tables: usize,
+    pub chunks: u64,
+    pub differing_chunks: u64,
+    pub inserts: u64,
+    pub updates: u64,
+    pub deletes: u64,
+}
+
+//
+// Write the statements that bring a target restored from an earlier dump
+//   back in line with its source, instead of dumping everything again. Each
+//   table is walked along its primary key in chunks of `chunk_rows` source
+//   rows and the row count and fingerprint of every chunk compared on both
+//   sides. Only the chunks that differ are read and compared row by row:
+//   rows only in the source are inserted, rows only in the target deleted
+//   and the changed columns of the rest updated.
+//
+// Both sides are expected to have the same structure, see diff. Tables
+//   without a primary key, or missing from the target, are skipped with a
+//   warning. Nothing is changed on the target, the statements are loaded like
+//   a dump.
+//
+pub async fn sync_schema(
+    source: &Pool<MySql>,
+    catalog: &Catalog,
+    target: &Pool<MySql>,
+    target_schema: &str,
+    writer: &mut StdWriter,
+    chunk_rows: u64,
+    options: &ExportOptions,
+) -> Result<SyncSummary, sqlx::Error> {
+    let target_tables: Vec<(String,)> =
+        sqlx::query_as("select table_name from information_schema.tables where table_schema=? and table_type='BASE TABLE'")
+            .bind(target_schema)
+            .fetch_all(target)
+            .await?;
+
+    writer.println(format!("-- dbdump sync of {} into {}", catalog.schema, target_schema).as_str());
+    writer.println(format!("use {};", quote_identifier(target_schema)).as_str());
+    // Rows are reconciled table by table, not in dependency order
+    writer.println("SET FOREIGN_KEY_CHECKS=0;");
+    let mut summary = SyncSummary::default();
+    for table in catalog.tables.iter().filter(|t| options.includes(&t.name)) {
+        let Some(key) = table.primary_key().map(|k| k.columns.clone()) else {
+            Logger::warn(format!("{} has no primary key, it can't be synced", table.name));
+            continue;
+        };
+        if !target_tables.iter().any(|t| t.0 == table.name) {
+            Logger::warn(format!("{} is missing from {}, it can't be synced", table.name, target_schema));
+            continue;
+        }
+        summary.tables += 1;
+        sync_table(source, &catalog.schema, target, target_schema, table, &key, chunk_rows, writer, options, &mut summary).await?;
+    }
+    writer.println("SET FOREIGN_KEY_CHECKS=1;");
+    Ok(summary)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn sync_table(
+    source: &Pool<MySql>,
+    schema: &str,
+    target: &Pool<MySql>,
+    target_schema: &str,
+    table: &Table,
+    key: &[String],
+    chunk_rows: u64,
+    writer: &mut StdWriter,
+    options: &ExportOptions,
+    summary: &mut SyncSummary,
+) -> Result<(), sqlx::Error> {
+    let key_list = column_list(key);
+    let columns: Vec<String> = table.columns.iter().map(|c| c.name.clone()).collect();
+    let fingerprint = fingerprint_select(&columns);
+    let mut differing = 0;
+
+    // The key of the last row of the previous chunk, e.g. (1,'a')
+    let mut lower: Option<String> = None;
+    loop {
+        let start = lower.as_ref().map(|lower| format!("({}) > {}", key_list, lower));
+        let last_row = format!(
+            "{}order by {} limit 1 offset {}",
+            start.as_ref().map(|s| format!("where {} ", s)).unwrap_or_default(),
+            key_list,
+            chunk_rows - 1
+        );
+        let (names, rows) = row_literals(source, schema, &table.name, &last_row, options).await?;
+        let upper = rows.first().map(|row| key_tuple(&names, row, key));
+        // The last chunk is open ended, it takes in rows added to the target
+        //   past the end of the source
+        let condition = match (&start, &upper) {
+            (Some(start), Some(upper)) => format!("{} and ({}) <= {}", start, key_list, upper),
+            (Some(start), None) => start.clone(),
+            (None, Some(upper)) => format!("({}) <= {}", key_list, upper),
+            (None, None) => "true".to_string(),
+        };
+
+        summary.chunks += 1;
+        let query = format!("{} from {} where {}", fingerprint, qualified_name(schema, &table.name), condition);
+        let here: (u64, u64) = sqlx::query_as(&query).fetch_one(source).await?;
+        let query = format!("{} from {} where {}", fingerprint, qualified_name(target_schema, &table.name), condition);
+        let there: (u64, u64) = sqlx::query_as(&query).fetch_one(target).await?;
+        if here != there {
+            differing += 1;
+            sync_chunk(source, schema, target, target_schema, &table.name, key, &condition, writer, options, summary).await?;
+        }
+
+        match upper {
+            Some(upper) => lower = Some(upper),
+            None => break,
+        }
+    }
+    summary.differing_chunks += differing;
+    if differing > 0 {
+        Logger::info(format!("{} chunks of {} differ", differing, table.name));
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn sync_chunk(
+    source: &Pool<MySql>,
+    schema: &str,
+    target: &Pool<MySql>,
+    target_schema: &str,
+    table_name: &str,
+    key: &[String],
+    condition: &str,
+    writer: &mut StdWriter,
+    options: &ExportOptions,
+    summary: &mut SyncSummary,
+) -> Result<(), sqlx::Error> {
+    let clause = format!("where {} order by {}", condition, column_list(key));
+    let (names, source_rows) = row_literals(source, schema, table_name, &clause, options).await?;
+    let (_, target_rows) = row_literals(target, target_schema, table_name, &clause, options).await?;
+    let mut remaining: HashMap<String, &Literals> = target_rows.iter().map(|row| (key_tuple(&names, row, key), row)).collect();
+
+    let table = quote_identifier(table_name);
+    for row in &source_rows {
+        let row_key = key_tuple(&names, row, key);
+        match remaining.remove(&row_key) {
+            None => {
+                summary.inserts += 1;
+                writer.println(
+                    format!(
+                        "insert into {} ({}) values({});",
+                        table,
+                        column_list(&names),
+                        row.iter().map(literal).collect::<Vec<&str>>().join(",")
+                    )
+                    .as_str(),
+                );
+            }
+            Some(existing) if existing != row => {
+                summary.updates += 1;
+                let changes: Vec<String> = names
+                    .iter()
+                    .zip(row.iter().zip(existing.iter()))
+                    .filter(|(_, (new, old))| new != old)
+                    .map(|(name, (new, _))| format!("{}={}", quote_identifier(name), literal(new)))
+                    .collect();
+                writer.println(format!("update {} set {} where {};", table, changes.join(","), key_match(&names, row, key)).as_str());
+            }
+            Some(_) => {}
+        }
+    }
+    for row in &target_rows {
+        if remaining.contains_key(&key_tuple(&names, row, key)) {
+            summary.deletes += 1;
+            writer.println(format!("delete from {} where {};", table, key_match(&names, row, key)).as_str());
+        }
+    }
+    Ok(())
+}
+
+fn literal(value: &Option<String>) -> &str {
+    value.as_deref().unwrap_or("NULL")
+}
+
+// A row's key as a row constructor, e.g. (1,'a')
+fn key_tuple(names: &[String], row: &Literals, key: &[String]) -> String {
+    let values: Vec<&str> = key_values(names, row, key).map(|(_, value)| value).collect();
+    format!("({})", values.join(","))
+}
+
+// A row's key as a where condition, e.g. `id`=1 and `code`='a'
+fn key_match(names: &[String], row: &Literals, key: &[String]) -> String {
+    key_values(names, row, key)
+        .map(|(name, value)| format!("{}={}", quote_identifier(name), value))
+        .collect::<Vec<String>>()
+        .join(" and ")
+}
+
+fn key_values<'a>(names: &'a [String], row: &'a Literals, key: &'a [String]) -> impl Iterator<Item = (&'a String, &'a str)> {
+    key.iter().map(move |column| {
+        let index = names
+            .iter()
+            .position(|n| n == column)
+            .unwrap_or_else(|| panic!("Key column {} is not in the table", column));
+        (column, literal(&row[index]))
+    })
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+//
+// A --tables-file: the tables to dump, one per line, each optionally
+//   followed by flags and a WHERE clause restricting its rows:
+//
+//   # Reference data in full
+//   countries
+//   currencies
+//   audit_log      no-data
+//   events         limit=1000
+//   orders         WHERE created_at >= '2024-01-01'
+//   `order lines`  WHERE order_id in (select id from orders where created_at >= '2024-01-01')
+//
+// Flags are
+//   no-data   only the table's structure is dumped
+//   limit=N   only the first N rows, can't be combined with WHERE
+// Everything after WHERE, up to the end of the line, is the condition.
+//   Blank lines and lines starting with # are skipped. Tables not in the
+//   file aren't dumped, views included.
+//
+#[derive(Debug, Default)]
+pub struct TableSpec {
+    // In the order of the file
+    pub tables: Vec<String>,
+    // Clause appended to a table's data query, as ExportOptions.row_filters
+    pub row_filters: HashMap<String, String>,
+    // Tables dumped without their rows
+    pub schema_only: Vec<String>,
+}
+
+impl TableSpec {
+    pub fn load(path: &str) -> Result<TableSpec, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Unable to read tables file {}: {}", path, e))?;
+        TableSpec::parse(&text).map_err(|e| format!("Invalid tables file {}: {}", path, e))
+    }
+
+    pub fn parse(text: &str) -> Result<TableSpec, String> {
+        let mut spec = TableSpec::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fail = |message: String| format!("line {}: {}", number + 1, message);
+            let (table, mut rest) = table_name(line).map_err(fail)?;
+            if spec.tables.contains(&table) {
+                return Err(fail(format!("{} is listed twice", table)));
+            }
+
+            let mut condition = None;
+            let mut limit = None;
+            loop {
+                rest = rest.trim_start();
+                let word_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                let word = &rest[..word_end];
+                if word.is_empty() {
+                    break;
+                }
+                if word.eq_ignore_ascii_case("where") {
+                    let where_condition = rest[word_end..].trim();
+                    if where_condition.is_empty() {
+                        return Err(fail(format!("WHERE without a condition for {}", table)));
+                    }
+                    condition = Some(where_condition);
+                    break;
+                }
+                match word.split_once('=') {
+                    None if word == "no-data" => spec.schema_only.push(table.clone()),
+                    Some(("limit", rows)) => match rows.parse::<u64>() {
+                        Ok(rows) => limit = Some(rows),
+                        Err(_) => return Err(fail(format!("Invalid limit {} for {}", rows, table))),
+                    },
+                    _ => return Err(fail(format!("Unknown flag {} for {}, expected no-data, limit=N or WHERE", word, table))),
+                }
+                rest = &rest[word_end..];
+            }
+
+            match (condition, limit) {
+                (Some(_), Some(_)) => return Err(fail(format!("limit can't be combined with WHERE for {}", table))),
+                (Some(condition), None) => {
+                    spec.row_filters.insert(table.clone(), format!("where {}", condition));
+                }
+                (None, Some(rows)) => {
+                    spec.row_filters.insert(table.clone(), format!("limit {}", rows));
+                }
+                (None, None) => {}
+            }
+            spec.tables.push(table);
+        }
+        if spec.tables.is_empty() {
+            return Err("no tables listed".to_string());
+        }
+        Ok(spec)
+    }
+}
+
+// The table name a line starts with, bare or in backquotes, and the rest
+fn table_name(line: &str) -> Result<(String, &str), String> {
+    let Some(quoted) = line.strip_prefix('`') else {
+        let end = line.find(char::is_whitespace).unwrap_or(line.len());
+        return Ok((line[..end].to_string(), &line[end..]));
+    };
+    let mut name = String::new();
+    let mut chars = quoted.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '`' {
+            if chars.peek().map(|(_, c)| *c) == Some('`') {
+                chars.next();
+                name.push('`');
+                continue;
+            }
+            return Ok((name, &quoted[i + 1..]));
+        }
+        name.push(c);
+    }
+    Err(format!("Unterminated table name {}", line))
+}
@@ -0,0 +1,152 @@
+use regex::Regex;
+use std::str::FromStr;
+
+//
+// Transcoding of legacy latin1 columns into utf8mb4.
+//
+// Columns declared as latin1 frequently hold bytes in some other encoding -
+//   Windows cp1252 from old desktop clients, or utf8 written through a latin1
+//   connection. The values are fetched as raw bytes and decoded using the
+//   encoding the user says is really stored, and the DDL is rewritten so the
+//   restored columns are utf8mb4.
+//
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SourceEncoding {
+    Latin1,
+    Cp1252,
+    Utf8,
+}
+
+impl FromStr for SourceEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "latin1" | "iso-8859-1" => Ok(SourceEncoding::Latin1),
+            "cp1252" | "windows-1252" => Ok(SourceEncoding::Cp1252),
+            "utf8" | "utf-8" | "utf8mb4" => Ok(SourceEncoding::Utf8),
+            _ => Err(format!(
+                "Unknown source encoding {s}, expected one of latin1, cp1252, utf8"
+            )),
+        }
+    }
+}
+
+// cp1252 differs from iso-8859-1 only in 0x80..=0x9F. Undefined positions
+//   map to the C1 control character, as the WHATWG decoder does.
+const CP1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+// Decode raw column bytes into a UTF-8 string.
+pub fn decode(bytes: &[u8], encoding: SourceEncoding) -> String {
+    match encoding {
+        SourceEncoding::Latin1 => bytes.iter().map(|b| *b as char).collect(),
+        SourceEncoding::Cp1252 => bytes
+            .iter()
+            .map(|b| match b {
+                0x80..=0x9F => CP1252_HIGH[(b - 0x80) as usize],
+                _ => *b as char,
+            })
+            .collect(),
+        SourceEncoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+// Rewrite the latin1 charset and collation clauses in a CREATE TABLE
+//   statement to utf8mb4. latin1 collations have no direct equivalent so they
+//   are dropped and the utf8mb4 default collation applies.
+pub fn rewrite_ddl(ddl: &str) -> String {
+    let collate = Regex::new(r"(?i)\s+COLLATE[ =]latin1_\w+").unwrap();
+    let charset = Regex::new(r"(?i)(CHARACTER SET|CHARSET)([ =])latin1\b").unwrap();
+    let ddl = collate.replace_all(ddl, "");
+    charset.replace_all(&ddl, "${1}${2}utf8mb4").into_owned()
+}
+
+//
+// The character encoding a dump is written in, for tools that can't read
+//   UTF-8 without a byte order mark or at all. Characters latin1 and cp1252
+//   can't represent are written as '?'.
+//
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputEncoding {
+    #[default]
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Latin1,
+    Cp1252,
+}
+
+impl FromStr for OutputEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf8" | "utf-8" => Ok(OutputEncoding::Utf8),
+            "utf8-bom" | "utf-8-bom" => Ok(OutputEncoding::Utf8Bom),
+            "utf16le" | "utf-16le" => Ok(OutputEncoding::Utf16Le),
+            "latin1" | "iso-8859-1" => Ok(OutputEncoding::Latin1),
+            "cp1252" | "windows-1252" => Ok(OutputEncoding::Cp1252),
+            _ => Err(format!(
+                "Unknown output encoding {s}, expected one of utf8, utf8-bom, utf16le, latin1, cp1252"
+            )),
+        }
+    }
+}
+
+impl OutputEncoding {
+    // What starts a new file, so readers can tell the encoding
+    pub fn byte_order_mark(self) -> &'static [u8] {
+        match self {
+            OutputEncoding::Utf8Bom => &[0xEF, 0xBB, 0xBF],
+            OutputEncoding::Utf16Le => &[0xFF, 0xFE],
+            _ => &[],
+        }
+    }
+
+    // The MySQL character set a client has to use to load the dump, None
+    //   where the client reads UTF-8 as it does by default. MySQL's latin1 is
+    //   cp1252.
+    pub fn mysql_charset(self) -> Option<&'static str> {
+        match self {
+            OutputEncoding::Latin1 | OutputEncoding::Cp1252 => Some("latin1"),
+            _ => None,
+        }
+    }
+}
+
+// Encode text for the output, also returning whether any character had to be
+//   replaced
+pub fn encode(text: &str, encoding: OutputEncoding) -> (Vec<u8>, bool) {
+    let mut replaced = false;
+    let mut single_byte = |c: char, high: &dyn Fn(char) -> Option<u8>| match c as u32 {
+        0..=0x7F => c as u8,
+        _ => high(c).unwrap_or_else(|| {
+            replaced = true;
+            b'?'
+        }),
+    };
+    let bytes = match encoding {
+        OutputEncoding::Utf8 | OutputEncoding::Utf8Bom => text.as_bytes().to_vec(),
+        OutputEncoding::Utf16Le => text.encode_utf16().flat_map(|u| u.to_le_bytes()).collect(),
+        OutputEncoding::Latin1 => text
+            .chars()
+            .map(|c| single_byte(c, &|c| u8::try_from(c as u32).ok()))
+            .collect(),
+        OutputEncoding::Cp1252 => text
+            .chars()
+            .map(|c| {
+                single_byte(c, &|c| match CP1252_HIGH.iter().position(|h| *h == c) {
+                    Some(i) => Some(0x80 + i as u8),
+                    None if (0xA0..=0xFF).contains(&(c as u32)) => Some(c as u8),
+                    None => None,
+                })
+            })
+            .collect(),
+    };
+    (bytes, replaced)
+}
@@ -0,0 +1,58 @@
+use crate::escape::{quote_string, EscapeMode};
+use std::fmt;
+use std::sync::Arc;
+
+//
+// A column value handed to a RowTransformer. Numbers and strings arrive
+//   decoded, anything else (dates, decimals, JSON, binary, geometry) arrives
+//   as the SQL literal dbdump would have written.
+//
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Text(String),
+    // A literal written to the dump as is
+    Sql(String),
+}
+
+impl Value {
+    // The literal written to the insert statement, None for NULL
+    pub fn to_sql(&self, mode: EscapeMode) -> Option<String> {
+        match self {
+            Value::Null => None,
+            Value::Int(n) => Some(n.to_string()),
+            Value::UInt(n) => Some(n.to_string()),
+            Value::Float(n) => Some(n.to_string()),
+            Value::Text(s) => Some(quote_string(s, mode)),
+            Value::Sql(s) => Some(s.clone()),
+        }
+    }
+}
+
+//
+// Called for every row exported, before it is serialized. The values can be
+//   changed in place and returning false drops the row from the dump. Columns
+//   are the source column names in the same order as the values.
+//
+pub trait RowTransformer: Send + Sync {
+    fn transform(&self, table: &str, columns: &[String], values: &mut [Value]) -> bool;
+}
+
+// A shared transformer in ExportOptions, cloned into every export thread
+#[derive(Clone)]
+pub struct Transformer(pub Arc<dyn RowTransformer>);
+
+impl Transformer {
+    pub fn new<T: RowTransformer + 'static>(transformer: T) -> Self {
+        Transformer(Arc::new(transformer))
+    }
+}
+
+impl fmt::Debug for Transformer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Transformer")
+    }
+}
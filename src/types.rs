@@ -0,0 +1,106 @@
+use crate::escape::{quote_string, EscapeMode};
+use sqlx::mysql::MySqlRow;
+use sqlx::Row;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+// Renders the value at an index of a row as a SQL literal, None for NULL
+pub type TypeHandler = dyn Fn(&MySqlRow, usize, EscapeMode) -> Option<String> + Send + Sync;
+
+//
+// Handlers installed by library users for types dbdump doesn't support, or
+//   supports differently. They are looked up by the type name the driver
+//   reports (e.g. GEOMETRY, INT UNSIGNED) before any built in handling.
+//
+#[derive(Clone, Default)]
+pub struct TypeHandlers {
+    handlers: HashMap<String, Arc<TypeHandler>>,
+}
+
+impl TypeHandlers {
+    pub fn register<F>(&mut self, type_name: &str, handler: F)
+    where
+        F: Fn(&MySqlRow, usize, EscapeMode) -> Option<String> + Send + Sync + 'static,
+    {
+        self.handlers.insert(type_name.to_ascii_uppercase(), Arc::new(handler));
+    }
+
+    pub fn get(&self, type_name: &str) -> Option<&TypeHandler> {
+        self.handlers.get(type_name).map(|h| h.as_ref())
+    }
+}
+
+impl fmt::Debug for TypeHandlers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.handlers.keys()).finish()
+    }
+}
+
+//
+// How to fetch a column type dbdump doesn't know, or wants handled
+//   differently, given as TYPE=string|bytes|null
+//   String - the value as text, quoted
+//   Bytes  - the raw value as a hex literal
+//   Null   - write NULL, like --beta-skip-unknown-datatypes for one type
+//
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TypeOverride {
+    String,
+    Bytes,
+    Null,
+}
+
+#[derive(Clone, Debug)]
+pub struct TypeOverrideRule {
+    // The type name as the driver reports it, upper case, e.g. GEOMETRY
+    pub type_name: String,
+    pub mode: TypeOverride,
+}
+
+impl FromStr for TypeOverrideRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (type_name, mode) = s
+            .split_once('=')
+            .ok_or(format!("Invalid type override {s}, expected TYPE=string|bytes|null"))?;
+        let mode = match mode.trim().to_ascii_lowercase().as_str() {
+            "string" => TypeOverride::String,
+            "bytes" => TypeOverride::Bytes,
+            "null" => TypeOverride::Null,
+            _ => return Err(format!("Unknown type override {mode}, expected one of string, bytes, null")),
+        };
+        Ok(TypeOverrideRule {
+            type_name: type_name.trim().to_ascii_uppercase(),
+            mode,
+        })
+    }
+}
+
+impl TypeOverride {
+    pub fn render(self, row: &MySqlRow, index: usize, mode: EscapeMode) -> Option<String> {
+        match self {
+            TypeOverride::String => row
+                .try_get_unchecked::<Vec<u8>, usize>(index)
+                .ok()
+                .map(|bytes| quote_string(&String::from_utf8_lossy(&bytes), mode)),
+            TypeOverride::Bytes => row.try_get_unchecked::<Vec<u8>, usize>(index).ok().map(|bytes| hex_literal(&bytes)),
+            TypeOverride::Null => None,
+        }
+    }
+}
+
+// 0x... for binary values, '' for an empty one as 0x on its own isn't valid
+pub fn hex_literal(bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        return "''".to_string();
+    }
+    let mut out = String::with_capacity(bytes.len() * 2 + 2);
+    out.push_str("0x");
+    for b in bytes {
+        out.push_str(&format!("{:02X}", b));
+    }
+    out
+}
@@ -0,0 +1,65 @@
+use crate::sql::NameCase;
+use std::str::FromStr;
+
+//
+// Rendering of UUIDs stored as BINARY(16).
+//   Function - UUID_TO_BIN('xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx'), readable and
+//              restored to the identical bytes (MySQL 8.0+)
+//   Hex      - X'...' followed by the UUID in a comment, loadable anywhere
+//
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UuidFormat {
+    #[default]
+    Function,
+    Hex,
+}
+
+impl FromStr for UuidFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "function" => Ok(UuidFormat::Function),
+            "hex" => Ok(UuidFormat::Hex),
+            _ => Err(format!("Unknown uuid format {s}, expected one of function, hex")),
+        }
+    }
+}
+
+// Format 16 bytes in the canonical 8-4-4-4-12 form.
+pub fn to_uuid_string(bytes: &[u8]) -> String {
+    let hex = to_hex(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Render a BINARY(16) value as a SQL literal. Values that are not 16 bytes
+//   long can't be UUIDs and are written as plain hex.
+pub fn render(bytes: &[u8], format: UuidFormat) -> String {
+    if bytes.len() != 16 {
+        return format!("X'{}'", to_hex(bytes));
+    }
+    match format {
+        UuidFormat::Function => format!("UUID_TO_BIN('{}')", to_uuid_string(bytes)),
+        UuidFormat::Hex => format!("X'{}' /* {} */", to_hex(bytes), to_uuid_string(bytes)),
+    }
+}
+
+// Does the --uuid-column pattern (`table.column` or just `column`) match?
+//   Column names are never case sensitive, table names can be.
+pub fn matches_column(pattern: &str, table: &str, column: &str, name_case: NameCase) -> bool {
+    match pattern.split_once('.') {
+        Some((t, c)) => name_case.same(t, table) && c.eq_ignore_ascii_case(column),
+        None => pattern.eq_ignore_ascii_case(column),
+    }
+}
@@ -0,0 +1,172 @@
+use crate::escape::EscapeMode;
+use crate::metadata::Metadata;
+use crate::restore::StatementReader;
+use crate::{SECTION_BEGIN, SECTION_END, TRAILER_PREFIX};
+use regex::Regex;
+use std::io::BufRead;
+
+//
+// Structural checks on a SQL dump before it is trusted for a restore. Every
+//   statement is read the way restore reads them, and the counts in each
+//   completion trailer are compared with what the file actually contains.
+//   Files built up with --append hold several dumps, each is checked against
+//   its own trailer and section markers must pair up.
+//
+#[derive(Debug, Default)]
+pub struct Validation {
+    pub statements: usize,
+    pub trailers: Vec<String>,
+    pub problems: Vec<String>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Counts {
+    tables: usize,
+    views: usize,
+    routines: usize,
+    triggers: usize,
+    rows: u64,
+}
+
+struct Checker {
+    validation: Validation,
+    counts: Counts,
+    // Statements read since the last trailer
+    unfinished: usize,
+    section: Option<String>,
+    section_trailer: bool,
+    create: Regex,
+    trailer: Regex,
+}
+
+pub fn validate<R: BufRead>(reader: R) -> Validation {
+    let mut checker = Checker {
+        validation: Validation::default(),
+        counts: Counts::default(),
+        unfinished: 0,
+        section: None,
+        section_trailer: false,
+        create: Regex::new(r"(?is)^create\s+(?:or\s+replace\s+)?(?:algorithm\s*=\s*\w+\s+)?(?:definer\s*=\s*\S+\s+)?(?:sql\s+security\s+\w+\s+)?(table|view|procedure|function|trigger)\b").unwrap(),
+        // -- Dump completed at <time> (1 tables, 2 views, 3 routines, 4 triggers, 5 rows)
+        trailer: Regex::new(r"\((\d+) tables, (\d+) views, (\d+) routines, (\d+) triggers, (\d+) rows\)$").unwrap(),
+    };
+    let mut foreign_key_checks_off = false;
+
+    let mut statements = StatementReader::new(reader).keep_comments();
+    while let Some(statement) = statements.next() {
+        let mut metadata = Metadata::default();
+        for comment in statements.take_comments() {
+            metadata.read_line(&comment);
+            checker.comment(&comment);
+        }
+        if !metadata.entries().is_empty() {
+            statements.set_backslash_escapes(metadata.escape_mode() == EscapeMode::Backslash);
+        }
+        let statement = match statement {
+            Ok(statement) => statement,
+            Err(e) => {
+                checker.validation.problems.push(format!("Unable to read the dump: {}", e));
+                return checker.validation;
+            }
+        };
+        checker.validation.statements += 1;
+        checker.unfinished += 1;
+
+        let lower = statement.trim_start().to_lowercase();
+        if let Some(kind) = checker.create.captures(&statement).map(|c| c[1].to_lowercase()) {
+            match kind.as_str() {
+                "table" => checker.counts.tables += 1,
+                "view" => checker.counts.views += 1,
+                "procedure" | "function" => checker.counts.routines += 1,
+                _ => checker.counts.triggers += 1,
+            }
+        } else if lower.starts_with("insert ") {
+            checker.counts.rows += statement.matches("),\n\t(").count() as u64 + 1;
+        } else if lower.replace(' ', "").starts_with("setforeign_key_checks=") {
+            foreign_key_checks_off = lower.ends_with('0');
+        }
+    }
+    for comment in statements.take_comments() {
+        checker.comment(&comment);
+    }
+
+    let mut problems = vec![];
+    if statements.unterminated() {
+        problems.push("The last statement is not terminated, the dump is truncated".to_string());
+    }
+    if statements.delimiter() != ";" {
+        problems.push(format!("The delimiter is left as {}, a routine or trigger is incomplete", statements.delimiter()));
+    }
+    if foreign_key_checks_off {
+        problems.push("Foreign key checks are never turned back on".to_string());
+    }
+    if let Some(section) = &checker.section {
+        problems.push(format!("Section {} is never closed", section));
+    }
+    if checker.validation.trailers.is_empty() || checker.unfinished > 0 {
+        problems.push("No completion trailer, the dump did not finish".to_string());
+    }
+    checker.validation.problems.extend(problems);
+    checker.validation
+}
+
+impl Checker {
+    fn comment(&mut self, comment: &str) {
+        if let Some(name) = comment.strip_prefix(SECTION_BEGIN) {
+            if let Some(open) = &self.section {
+                self.validation
+                    .problems
+                    .push(format!("Section {} starts before section {} is closed", name, open));
+            }
+            self.section = Some(name.to_string());
+            self.section_trailer = false;
+        } else if let Some(name) = comment.strip_prefix(SECTION_END) {
+            match &self.section {
+                Some(open) if open == name => {
+                    if !self.section_trailer {
+                        self.validation
+                            .problems
+                            .push(format!("Section {} has no completion trailer, it did not finish", name));
+                    }
+                }
+                _ => self.validation.problems.push(format!("Section {} ends without starting", name)),
+            }
+            self.section = None;
+        } else if comment.starts_with(TRAILER_PREFIX) {
+            self.check_trailer(comment);
+            self.validation.trailers.push(comment.to_string());
+            self.counts = Counts::default();
+            self.unfinished = 0;
+            self.section_trailer = true;
+        }
+    }
+
+    fn check_trailer(&mut self, trailer: &str) {
+        let c = match self.trailer.captures(trailer) {
+            Some(c) => c,
+            None => return,
+        };
+        let expected = Counts {
+            tables: c[1].parse().unwrap_or(0),
+            views: c[2].parse().unwrap_or(0),
+            routines: c[3].parse().unwrap_or(0),
+            triggers: c[4].parse().unwrap_or(0),
+            rows: c[5].parse().unwrap_or(0),
+        };
+        let found = &self.counts;
+        let mismatch = |name: &str, expected: u64, found: u64| {
+            (expected != found).then(|| format!("The trailer records {} {} but the dump contains {}", expected, name, found))
+        };
+        let problems: Vec<String> = [
+            mismatch("tables", expected.tables as u64, found.tables as u64),
+            mismatch("views", expected.views as u64, found.views as u64),
+            mismatch("routines", expected.routines as u64, found.routines as u64),
+            mismatch("triggers", expected.triggers as u64, found.triggers as u64),
+            mismatch("rows", expected.rows, found.rows),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        self.validation.problems.extend(problems);
+    }
+}
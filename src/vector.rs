@@ -0,0 +1,45 @@
+use std::str::FromStr;
+
+//
+// Rendering of the MySQL 9 VECTOR type. The driver can't decode VECTOR
+//   columns, so they are converted on the server as part of the select.
+//   String - STRING_TO_VECTOR('[1.5,2.25,...]'), readable and diffable
+//   Hex    - X'...' of the raw float32 data, exact and compact
+//
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VectorFormat {
+    #[default]
+    String,
+    Hex,
+}
+
+impl FromStr for VectorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "string" => Ok(VectorFormat::String),
+            "hex" => Ok(VectorFormat::Hex),
+            _ => Err(format!("Unknown vector format {s}, expected one of string, hex")),
+        }
+    }
+}
+
+impl VectorFormat {
+    // The select list expression used to fetch an already quoted column
+    pub fn select_expression(&self, name: &str) -> String {
+        match self {
+            VectorFormat::String => format!("VECTOR_TO_STRING({name}) as {name}"),
+            VectorFormat::Hex => format!("hex({name}) as {name}"),
+        }
+    }
+
+    // Turn the fetched value into a literal. The values only ever contain
+    //   digits, signs, exponents, brackets and commas so need no escaping.
+    pub fn render(&self, value: &str) -> String {
+        match self {
+            VectorFormat::String => format!("STRING_TO_VECTOR('{}')", value),
+            VectorFormat::Hex => format!("X'{}'", value),
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use crate::escape::{quote_string, EscapeMode};
+use crate::sql::{qualified_name, quote_identifier};
+use crate::std_writer::StdWriter;
+use sqlx::mysql::{MySql, MySqlConnection};
+use sqlx::pool::Pool;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+//
+// The fingerprints of the rows a dump writes, for the verification script.
+//   export_single_table takes each table's in the snapshot its rows are read
+//   in, from the same rows, and keeps it under the table's name in the dump.
+//   The fingerprints of the chunks of a table exported in parts add up, the
+//   XOR of the row checksums doesn't depend on how the rows are grouped.
+//
+#[derive(Clone, Debug, Default)]
+pub struct Fingerprints(Arc<Mutex<BTreeMap<String, TableFingerprint>>>);
+
+#[derive(Debug)]
+struct TableFingerprint {
+    // The columns as they are named in the dump
+    columns: Vec<String>,
+    rows: u64,
+    checksum: u64,
+}
+
+impl Fingerprints {
+    // Fingerprint the rows `from` picks, on the connection the rows are read
+    //   with. `columns` are read and restored as `target_columns`.
+    pub async fn record(
+        &self,
+        connection: &mut MySqlConnection,
+        from: &str,
+        columns: &[String],
+        table: &str,
+        target_columns: Vec<String>,
+    ) -> Result<(), sqlx::Error> {
+        let (rows, checksum): (u64, u64) = sqlx::query_as(format!("{} from {}", fingerprint_select(columns), from).as_str())
+            .fetch_one(connection)
+            .await?;
+        let mut fingerprints = self.0.lock().unwrap();
+        let fingerprint = fingerprints.entry(table.to_string()).or_insert_with(|| TableFingerprint {
+            columns: target_columns,
+            rows: 0,
+            checksum: 0,
+        });
+        fingerprint.rows += rows;
+        fingerprint.checksum ^= checksum;
+        Ok(())
+    }
+}
+
+//
+// Post-restore verification script. For every table the dump wrote rows for
+//   the exact row count and a content fingerprint of those rows are known,
+//   and a script is written that recomputes both on the target inside a
+//   throwaway procedure and SIGNALs on the first mismatch. Running it needs
+//   only the mysql client. Without a target schema it runs in the current
+//   one, as a dump without a use statement is loaded.
+//
+pub fn write_verify_script(fingerprints: &Fingerprints, schema: &str, target_schema: Option<&str>, filename: &str) {
+    let fingerprints = fingerprints.0.lock().unwrap();
+    let mut writer = StdWriter::new(Some(filename.to_string()));
+    writer.println(format!("-- dbdump post-restore verification for schema {}", schema).as_str());
+    if let Some(target_schema) = target_schema {
+        writer.println(format!("use {};", quote_identifier(target_schema)).as_str());
+    }
+    writer.println("DROP PROCEDURE IF EXISTS `dbdump_verify`;");
+    writer.println("DELIMITER ;;");
+    writer.println("CREATE PROCEDURE `dbdump_verify`()");
+    writer.println("BEGIN");
+    writer.println("  DECLARE actual_rows BIGINT UNSIGNED;");
+    writer.println("  DECLARE actual_checksum BIGINT UNSIGNED;");
+
+    for (table_name, fingerprint) in fingerprints.iter() {
+        writer.println(format!("  -- Table {}", table_name).as_str());
+        writer.println(
+            format!(
+                "  {} into actual_rows, actual_checksum from {};",
+                fingerprint_select(&fingerprint.columns),
+                quote_identifier(table_name)
+            )
+            .as_str(),
+        );
+        writer.println(
+            format!(
+                "  IF actual_rows <> {} THEN SIGNAL SQLSTATE '45000' SET MESSAGE_TEXT = {}; END IF;",
+                fingerprint.rows,
+                mismatch_message("row count", table_name)
+            )
+            .as_str(),
+        );
+        writer.println(
+            format!(
+                "  IF actual_checksum <> {} THEN SIGNAL SQLSTATE '45000' SET MESSAGE_TEXT = {}; END IF;",
+                fingerprint.checksum,
+                mismatch_message("checksum", table_name)
+            )
+            .as_str(),
+        );
+    }
+
+    writer.println("END;;");
+    writer.println("DELIMITER ;");
+    writer.println("CALL `dbdump_verify`();");
+    writer.println("DROP PROCEDURE `dbdump_verify`;");
+    writer.println(
+        format!(
+            "SELECT 'dbdump verify: all {} tables match' AS result;",
+            fingerprints.len()
+        )
+        .as_str(),
+    );
+    writer.flush();
+}
+
+// select list producing (row count, fingerprint) for a table. NULLs are
+//   folded in through isnull() since concat_ws skips them.
+async fn fingerprint_query(
+    pool: &Pool<MySql>,
+    schema: &str,
+    table_name: &str,
+) -> Result<String, sqlx::Error> {
+    let columns: Vec<(String,)> = sqlx::query_as("select column_name from information_schema.columns where table_schema=? and table_name=? order by ordinal_position")
+        .bind(schema)
+        .bind(table_name)
+        .fetch_all(pool)
+        .await?;
+    let columns: Vec<String> = columns.into_iter().map(|c| c.0).collect();
+    Ok(fingerprint_select(&columns))
+}
+
+pub fn fingerprint_select(columns: &[String]) -> String {
+    let values = columns
+        .iter()
+        .map(|c| {
+            let name = quote_identifier(c);
+            format!("{name},isnull({name})")
+        })
+        .collect::<Vec<String>>()
+        .join(",");
+    format!(
+        "select cast(count(*) as unsigned), cast(coalesce(bit_xor(crc32(concat_ws('#',{}))),0) as unsigned)",
+        values
+    )
+}
+
+// MESSAGE_TEXT is limited to 128 characters
+fn mismatch_message(check: &str, table_name: &str) -> String {
+    let message: String = format!("dbdump verify: {} mismatch for table {}", check, table_name)
+        .chars()
+        .take(128)
+        .collect();
+    quote_string(&message, EscapeMode::Backslash)
+}
+
+//
+// Compare a restored target with its source directly, table by table, using
+//   the same row count and fingerprint as the verification script. Returns
+//   the list of mismatches, empty when the target matches.
+//
+pub async fn verify_target(
+    source: &Pool<MySql>,
+    schema: &str,
+    target: &Pool<MySql>,
+    target_schema: &str,
+) -> Result<Vec<String>, sqlx::Error> {
+    let table_names: Vec<(String,)> =
+        sqlx::query_as("select table_name from information_schema.tables where table_schema=? and table_type='BASE TABLE' order by table_name")
+            .bind(schema)
+            .fetch_all(source)
+            .await?;
+
+    let mut mismatches = vec![];
+    for (table_name,) in &table_names {
+        let query = fingerprint_query(source, schema, table_name).await?;
+        let expected: (u64, u64) = sqlx::query_as(
+            format!("{} from {}", query, qualified_name(schema, table_name)).as_str(),
+        )
+        .fetch_one(source)
+        .await?;
+        let actual: Result<(u64, u64), sqlx::Error> = sqlx::query_as(
+            format!("{} from {}", query, qualified_name(target_schema, table_name)).as_str(),
+        )
+        .fetch_one(target)
+        .await;
+
+        match actual {
+            Ok(actual) if actual.0 != expected.0 => mismatches.push(format!(
+                "{}: row count {} expected {}",
+                table_name, actual.0, expected.0
+            )),
+            Ok(actual) if actual.1 != expected.1 => {
+                mismatches.push(format!("{}: checksum mismatch", table_name))
+            }
+            Ok(_) => {}
+            Err(e) => mismatches.push(format!("{}: {}", table_name, e)),
+        }
+    }
+    Ok(mismatches)
+}
@@ -0,0 +1,199 @@
+use crate::shell::BINARY_TYPES;
+use crate::sql::{qualified_name, quote_identifier};
+use crate::std_writer::StdWriter;
+use crate::{DumpSummary, ExportOptions};
+use async_std::stream::StreamExt;
+use sqlx::mysql::{MySql, MySqlRow};
+use sqlx::pool::Pool;
+use sqlx::{Column, Row};
+
+// The attributes of a <field> in a table structure, as SHOW FULL COLUMNS
+//   names them
+const FIELD_QUERY: &str = "select column_name as Field, column_type as Type, is_nullable as `Null`, column_key as `Key`, \
+    column_default as `Default`, extra as Extra, column_comment as Comment \
+    from information_schema.columns where table_schema=? and table_name=? order by ordinal_position";
+
+// The attributes of a <key>, as SHOW KEYS names them
+const KEY_QUERY: &str = "select table_name as `Table`, cast(non_unique as char) as Non_unique, index_name as Key_name, \
+    cast(seq_in_index as char) as Seq_in_index, column_name as Column_name, collation as Collation, \
+    cast(cardinality as char) as Cardinality, cast(sub_part as char) as Sub_part, packed as Packed, nullable as `Null`, \
+    index_type as Index_type, comment as Comment, index_comment as Index_comment \
+    from information_schema.statistics where table_schema=? and table_name=? order by index_name='PRIMARY' desc, index_name, seq_in_index";
+
+// The attributes of <options>, as SHOW TABLE STATUS names them
+const OPTIONS_QUERY: &str = "select table_name as Name, engine as Engine, cast(version as char) as Version, \
+    row_format as Row_format, cast(table_rows as char) as `Rows`, cast(avg_row_length as char) as Avg_row_length, \
+    cast(data_length as char) as Data_length, cast(max_data_length as char) as Max_data_length, \
+    cast(index_length as char) as Index_length, cast(data_free as char) as Data_free, \
+    cast(auto_increment as char) as Auto_increment, cast(create_time as char) as Create_time, \
+    cast(update_time as char) as Update_time, cast(check_time as char) as Check_time, table_collation as Collation, \
+    cast(checksum as char) as Checksum, create_options as Create_options, table_comment as Comment \
+    from information_schema.tables where table_schema=? and table_name=?";
+
+//
+// The schema and rows as mysqldump --xml writes them, for tools that only
+//   read that format:
+//     <mysqldump>
+//       <database name="...">
+//         <table_structure name="...">  a <field> per column, a <key> per
+//                                       index column and the table <options>
+//         <table_data name="...">       a <row> per row with a <field> per
+//                                       value, NULL is xsi:nil="true"
+//   Views get a table structure and no data. Binary values are written in
+//   hex with xsi:type="xs:hexBinary" as mysqldump --hex-blob does, raw bytes
+//   can't go in an XML document. Routines and triggers aren't written.
+//
+pub async fn write_xml(
+    pool: &Pool<MySql>,
+    writer: &mut StdWriter,
+    schema: &str,
+    database: &str,
+    options: &ExportOptions,
+    include_ddl: bool,
+    include_data: bool,
+) -> Result<DumpSummary, sqlx::Error> {
+    let mut summary = DumpSummary::default();
+    let objects: Vec<(String, String)> = sqlx::query_as(
+        "select table_name, table_type from information_schema.tables where table_schema=? order by table_name",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await?;
+
+    writer.println("<?xml version=\"1.0\"?>");
+    writer.println("<mysqldump xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">");
+    writer.println(format!("<database name=\"{}\">", escape_xml(database)).as_str());
+    for (table, table_type) in objects.iter().filter(|o| options.includes(&o.0)) {
+        let is_view = table_type == "VIEW";
+        let name = options.renames.table(table);
+        if include_ddl {
+            writer.println(format!("\t<table_structure name=\"{}\">", escape_xml(&name)).as_str());
+            for row in sqlx::query(FIELD_QUERY).bind(schema).bind(table).fetch_all(pool).await? {
+                writer.println(format!("\t\t<field {} />", attributes(&row, |c, v| rename_field(options, table, c, v))?).as_str());
+            }
+            for row in sqlx::query(KEY_QUERY).bind(schema).bind(table).fetch_all(pool).await? {
+                writer.println(format!("\t\t<key {} />", attributes(&row, |c, v| rename_key(options, table, c, v))?).as_str());
+            }
+            for row in sqlx::query(OPTIONS_QUERY).bind(schema).bind(table).fetch_all(pool).await? {
+                let renamed = |c: &str, v: String| if c == "Name" { name.clone() } else { v };
+                writer.println(format!("\t\t<options {} />", attributes(&row, renamed)?).as_str());
+            }
+            writer.println("\t</table_structure>");
+            if is_view {
+                summary.views += 1;
+            } else {
+                summary.tables += 1;
+            }
+        }
+        if include_data && !is_view {
+            summary.rows += write_table_data(pool, writer, schema, table, &name, options).await?;
+        }
+    }
+    writer.println("</database>");
+    writer.println("</mysqldump>");
+    Ok(summary)
+}
+
+async fn write_table_data(
+    pool: &Pool<MySql>,
+    writer: &mut StdWriter,
+    schema: &str,
+    table: &str,
+    name: &str,
+    options: &ExportOptions,
+) -> Result<u64, sqlx::Error> {
+    let columns: Vec<(String, String)> = sqlx::query_as(
+        "select column_name, data_type from information_schema.columns where table_schema=? and table_name=? order by ordinal_position",
+    )
+    .bind(schema)
+    .bind(table)
+    .fetch_all(pool)
+    .await?;
+    let binary: Vec<bool> = columns.iter().map(|c| BINARY_TYPES.contains(&c.1.to_lowercase().as_str())).collect();
+    let select_list = columns
+        .iter()
+        .zip(&binary)
+        .map(|((column, _), binary)| {
+            if *binary {
+                format!("hex({})", quote_identifier(column))
+            } else {
+                format!("cast({} as char)", quote_identifier(column))
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",");
+    let field_names: Vec<String> = columns.iter().map(|c| escape_xml(&options.renames.column(table, &c.0))).collect();
+    let row_filter = options.row_clause(table);
+    let query = format!("select {} from {}{}", select_list, qualified_name(schema, table), row_filter);
+
+    writer.println(format!("\t<table_data name=\"{}\">", escape_xml(name)).as_str());
+    let mut rows = 0;
+    let mut data_rows = sqlx::query(&query).fetch(pool);
+    let mut text = String::new();
+    while let Some(row) = data_rows.next().await {
+        let row = row?;
+        text.clear();
+        text.push_str("\t<row>\n");
+        for (i, binary) in binary.iter().enumerate() {
+            match row.try_get::<Option<String>, usize>(i)? {
+                Some(value) if *binary => text.push_str(&format!(
+                    "\t\t<field name=\"{}\" xsi:type=\"xs:hexBinary\">{}</field>\n",
+                    field_names[i], value
+                )),
+                Some(value) => text.push_str(&format!("\t\t<field name=\"{}\">{}</field>\n", field_names[i], escape_xml(&value))),
+                None => text.push_str(&format!("\t\t<field name=\"{}\" xsi:nil=\"true\" />\n", field_names[i])),
+            }
+        }
+        text.push_str("\t</row>");
+        writer.println(&text);
+        rows += 1;
+    }
+    writer.println("\t</table_data>");
+    Ok(rows)
+}
+
+//
+// A row of one of the structure queries as XML attributes, named after the
+//   query's columns. NULLs are left out as mysqldump leaves them out.
+//
+fn attributes(row: &MySqlRow, rename: impl Fn(&str, String) -> String) -> Result<String, sqlx::Error> {
+    let mut attributes = vec![];
+    for (i, column) in row.columns().iter().enumerate() {
+        if let Some(value) = row.try_get::<Option<String>, usize>(i)? {
+            let value = rename(column.name(), value);
+            attributes.push(format!("{}=\"{}\"", column.name(), escape_xml(&value)));
+        }
+    }
+    Ok(attributes.join(" "))
+}
+
+// The structure uses the names the table and columns have in the dump
+fn rename_field(options: &ExportOptions, table: &str, attribute: &str, value: String) -> String {
+    match attribute {
+        "Field" => options.renames.column(table, &value),
+        _ => value,
+    }
+}
+
+fn rename_key(options: &ExportOptions, table: &str, attribute: &str, value: String) -> String {
+    match attribute {
+        "Table" => options.renames.table(table),
+        "Column_name" => options.renames.column(table, &value),
+        _ => value,
+    }
+}
+
+// The characters mysqldump escapes in element text and attribute values
+fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}